@@ -0,0 +1,96 @@
+use bintable2::*;
+use std::fs::File;
+use std::io::BufWriter;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+#[structopt(
+    name = "bintable-diff",
+    about = "Writes the rows added to and removed from a bintable between two crawls as two bintables. \
+             Both inputs have to be sorted by key."
+)]
+struct Config {
+    /// Bintable of the earlier crawl
+    #[structopt()]
+    old: String,
+
+    /// Bintable of the later crawl
+    #[structopt()]
+    new: String,
+
+    /// Output of the rows only in the new bintable
+    #[structopt()]
+    added: String,
+
+    /// Output of the rows only in the old bintable
+    #[structopt()]
+    removed: String,
+}
+
+fn create(path: &str, header: &Header) -> BinTableWriter<BufWriter<File>> {
+    let mut out = BinTableWriter::create(path)
+        .expect("open output file")
+        .sorted();
+    out.write_header(header).expect("write header");
+    out
+}
+
+/// Exits before any output is written, if the rows of `path` are not sorted by key
+fn require_sorted(path: &str) {
+    if let Err(e) = check_sorted(path) {
+        let hint = if e.is::<Unsorted>() {
+            ", sort it using sort-bintable first"
+        } else {
+            ""
+        };
+        eprintln!("{path}: {e}{hint}");
+        std::process::exit(1);
+    }
+}
+
+fn main() {
+    let Config {
+        old,
+        new,
+        added,
+        removed,
+    } = Config::from_args();
+
+    require_sorted(&old);
+    require_sorted(&new);
+
+    let old = BinTable::open(&old).expect("open old bintable");
+    let new = BinTable::open(&new).expect("open new bintable");
+
+    if old.header() != new.header() {
+        eprintln!(
+            "warning: the bintables were tokenized differently, {:?} and {:?}",
+            old.header(),
+            new.header()
+        );
+    }
+
+    // the diff is applied to the later crawl's tokenization
+    let header = new.header().clone();
+    let mut added_out = create(&added, &header);
+    let mut removed_out = create(&removed, &header);
+
+    for change in diff(old, new) {
+        let change = change.unwrap_or_else(|e| {
+            // the inputs changed since they were checked
+            eprintln!("{e}");
+            let _ = std::fs::remove_file(&added);
+            let _ = std::fs::remove_file(&removed);
+            std::process::exit(1);
+        });
+        match change {
+            Change::Added(row) => added_out.write(&row).expect("write added row"),
+            Change::Removed(row) => removed_out.write(&row).expect("write removed row"),
+        }
+    }
+
+    let added = added_out.finish().expect("write added rows");
+    let removed = removed_out.finish().expect("write removed rows");
+
+    eprintln!("{} rows added, {} rows removed", added.rows, removed.rows);
+}
@@ -0,0 +1,112 @@
+use bintable2::*;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+#[structopt(
+    name = "bintable-fsck",
+    about = "Verifies the checksums of a bintable and reports its damaged regions. \
+             With --salvage, the intact rows are written to a new bintable."
+)]
+struct Config {
+    /// Bintable to check
+    #[structopt()]
+    table: String,
+
+    /// Output of all rows outside of the damaged regions
+    #[structopt(long)]
+    salvage: Option<String>,
+}
+
+/// Bytes of the file, whose rows were lost
+struct Damage {
+    start: u64,
+    end: u64,
+    error: BinTableError,
+}
+
+fn main() {
+    let Config { table, salvage } = Config::from_args();
+
+    let length = std::fs::metadata(&table)
+        .expect("read size of bintable")
+        .len();
+    let mut bintable = BinTable::open(&table).expect("open bintable file");
+
+    let mut out = salvage.map(|path| {
+        let mut out = BinTableWriter::create(&path).expect("open output file");
+        out.write_header(bintable.header()).expect("write header");
+        out
+    });
+
+    let mut rows = 0;
+    let mut damaged = Vec::new();
+    // damage without an intact block after it yet
+    let mut open: Option<(u64, BinTableError)> = None;
+
+    while let Some(row) = bintable.try_next() {
+        let row = match row {
+            Ok(row) => row,
+            Err(BinTableError::Io(e)) => panic!("failed to read {table}: {e}"),
+            Err(error) => {
+                let start = error.offset().expect("offset of the damage");
+                open.get_or_insert((start, error));
+                continue;
+            }
+        };
+
+        if let Some((start, error)) = open.take() {
+            let end = bintable.block_start();
+            damaged.push(Damage { start, end, error });
+        }
+
+        rows += 1;
+        if let Some(out) = &mut out {
+            out.write(&row).expect("write to output");
+        }
+    }
+    if let Some((start, error)) = open {
+        damaged.push(Damage {
+            start,
+            end: length,
+            error,
+        });
+    }
+
+    if let Some(out) = out {
+        out.finish().expect("write to output");
+    }
+
+    for Damage { start, end, error } in &damaged {
+        println!("damaged bytes {start}..{end}: {error}");
+    }
+    let lost = damaged.iter().map(|d| d.end - d.start).sum::<u64>();
+    println!(
+        "{rows} intact rows, {} damaged regions ({lost} of {length} bytes)",
+        damaged.len()
+    );
+    if !bintable.is_checksummed() {
+        println!("the bintable has no checksums, only its structure was checked");
+    }
+
+    // whole blocks missing between intact ones are only noticed by the statistics behind the last row
+    let complete = match bintable.stats() {
+        Some(stats) if damaged.is_empty() && stats.rows != rows => {
+            println!(
+                "the statistics count {} rows, blocks are missing",
+                stats.rows
+            );
+            false
+        }
+        Some(_) => true,
+        None => {
+            println!(
+                "the bintable has no statistics, a file cut between two blocks looks complete"
+            );
+            true
+        }
+    };
+
+    if !damaged.is_empty() || !complete {
+        std::process::exit(1);
+    }
+}
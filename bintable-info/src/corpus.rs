@@ -0,0 +1,504 @@
+use crate::distribution::{Distribution, QUANTILES};
+use bintable2::TableRow;
+use serde_json::{json, Value};
+
+/// Integers per block of the bit packing model, like the blocks of FastPFor
+const PACKING_BLOCK: usize = 128;
+
+/// Posting lists with at most this many entries are stored in the dictionary slot by `adaptive+dedup`
+const INLINE_LIMIT: usize = 2;
+
+/// Size models of posting list encodings. They estimate the bytes of the encoded locations,
+/// without the lengths and offsets every index stores along with them.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Codec {
+    Uncompressed,
+    VarInt,
+    GroupVarInt,
+    /// FastPFor like: every block of 128 d-gaps is packed with the bit width leaving the fewest bytes,
+    /// larger gaps are stored as exceptions
+    BitPacking,
+    /// rowids of every (tableid, colid) as roaring bitmaps, like `roaring+dedup`
+    Roaring,
+    /// smallest of the above for every posting list, very short ones inline, like `adaptive+dedup`
+    Adaptive,
+}
+
+impl Codec {
+    pub const ALL: [Codec; 6] = [
+        Codec::Uncompressed,
+        Codec::VarInt,
+        Codec::GroupVarInt,
+        Codec::BitPacking,
+        Codec::Roaring,
+        Codec::Adaptive,
+    ];
+
+    pub fn str(self) -> &'static str {
+        match self {
+            Codec::Uncompressed => "uncompressed",
+            Codec::VarInt => "varint",
+            Codec::GroupVarInt => "group varint",
+            Codec::BitPacking => "bit packing",
+            Codec::Roaring => "roaring",
+            Codec::Adaptive => "adaptive",
+        }
+    }
+}
+
+/// Power law fitted to the posting lengths
+#[derive(Debug, Clone, Copy)]
+pub struct Zipf {
+    /// exponent of the distribution of the lengths, P(length) ~ length^-alpha
+    pub alpha: f64,
+    /// exponent of Zipf's law, length ~ rank^-s
+    pub s: f64,
+}
+
+/// Statistics of the posting lists of a bintable sorted by key.
+/// The locations of every posting list are sorted and deduplicated first, like the indices do.
+#[derive(Default)]
+pub struct Corpus {
+    /// locations per key
+    pub postings: Distribution,
+    /// bytes per key
+    pub key_bytes: Distribution,
+    /// distinct tables per key
+    pub tables: Distribution,
+    /// locations per run of equal (tableid, colid) inside a posting list
+    pub runs: Distribution,
+    /// bit widths of the d-gaps of tableid, colid and rowid
+    pub gap_bits: [Distribution; 3],
+    /// locations occurring more than once in a posting list
+    pub duplicates: u64,
+    /// whether a key was smaller than the one before
+    pub unsorted: bool,
+    /// estimated bytes of the posting lists, by codec
+    pub bytes: [u64; Codec::ALL.len()],
+
+    key: Option<String>,
+    list: Vec<[u32; 3]>,
+    gaps: Vec<[u32; 3]>,
+}
+
+impl Corpus {
+    pub fn add(&mut self, row: &TableRow) {
+        match &self.key {
+            Some(key) if *key == row.tokenized => {}
+            key => {
+                if key.as_ref().is_some_and(|key| row.tokenized < *key) {
+                    self.unsorted = true;
+                }
+                self.finish();
+                self.key = Some(row.tokenized.clone());
+            }
+        }
+        self.list.push(row.integers());
+    }
+
+    /// Adds the last posting list, has to be called after the last row.
+    pub fn finish(&mut self) {
+        if self.list.is_empty() {
+            return;
+        }
+
+        let mut list = std::mem::take(&mut self.list);
+        list.sort_unstable();
+        let all = list.len();
+        list.dedup();
+        self.duplicates += (all - list.len()) as u64;
+
+        self.postings.add(list.len() as u64);
+        self.key_bytes
+            .add(self.key.as_ref().map_or(0, |k| k.len()) as u64);
+
+        let mut tables = 0;
+        let mut run = 0;
+        for (i, location) in list.iter().enumerate() {
+            let previous = i.checked_sub(1).map(|i| list[i]);
+            if previous.map(|p| p[0]) != Some(location[0]) {
+                tables += 1;
+            }
+            if previous.map(|p| [p[0], p[1]]) != Some([location[0], location[1]]) {
+                if run > 0 {
+                    self.runs.add(run);
+                }
+                run = 0;
+            }
+            run += 1;
+        }
+        self.runs.add(run);
+        self.tables.add(tables);
+
+        self.gaps.clear();
+        self.gaps.extend(d_gaps(&list));
+        for gap in &self.gaps {
+            for (bits, g) in self.gap_bits.iter_mut().zip(gap) {
+                bits.add(bit_width(*g) as u64);
+            }
+        }
+
+        let sizes = sizes(&list, &self.gaps);
+        for (total, size) in self.bytes.iter_mut().zip(sizes) {
+            *total += size;
+        }
+
+        self.list = list;
+        self.list.clear();
+    }
+
+    pub fn locations(&self) -> u64 {
+        self.postings.sum() as u64
+    }
+
+    /// Maximum likelihood fit of a discrete power law P(length) = length^-alpha / zeta(alpha) to the posting lengths
+    /// (Clauset, Shalizi and Newman 2009, eq. 3.5 with a minimum length of 1). If the lengths follow Zipf's law
+    /// by their rank with exponent s, their distribution follows a power law with alpha = 1 + 1/s.
+    pub fn zipf(&self) -> Option<Zipf> {
+        let n = self.postings.count() as f64;
+        let logs = self
+            .postings
+            .values()
+            .map(|(length, count)| count as f64 * (length as f64).ln())
+            .sum::<f64>();
+        if n == 0.0 || logs <= 0.0 {
+            return None;
+        }
+
+        // the log likelihood has a single maximum, found by golden section search
+        let likelihood = |alpha: f64| -alpha * logs - n * zeta(alpha).ln();
+        let ratio = (5f64.sqrt() - 1.0) / 2.0;
+        let (mut low, mut high) = (1.0 + 1e-6, 20.0);
+        while high - low > 1e-9 {
+            let a = high - ratio * (high - low);
+            let b = low + ratio * (high - low);
+            if likelihood(a) > likelihood(b) {
+                high = b;
+            } else {
+                low = a;
+            }
+        }
+
+        let alpha = (low + high) / 2.0;
+        Some(Zipf {
+            alpha,
+            s: 1.0 / (alpha - 1.0),
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        let locations = self.locations();
+        let codecs = Codec::ALL
+            .iter()
+            .zip(self.bytes)
+            .map(|(codec, bytes)| {
+                (
+                    codec.str().replace(' ', "_"),
+                    json!({
+                        "bytes": bytes,
+                        "bytes_per_location": bytes as f64 / locations.max(1) as f64,
+                        "bits_per_integer": (bytes * 8) as f64 / (locations.max(1) * 3) as f64,
+                    }),
+                )
+            })
+            .collect::<serde_json::Map<_, _>>();
+
+        let [tableid, colid, rowid] = &self.gap_bits;
+        json!({
+            "keys": self.postings.count(),
+            "locations": locations,
+            "duplicates": self.duplicates,
+            "sorted": !self.unsorted,
+            "posting_length": self.postings.to_json(),
+            "key_length": self.key_bytes.to_json(),
+            "tables_per_key": self.tables.to_json(),
+            "run_length": self.runs.to_json(),
+            "gap_bits": {
+                "tableid": exact_json(tableid),
+                "colid": exact_json(colid),
+                "rowid": exact_json(rowid),
+            },
+            "zipf": self.zipf().map(|z| json!({"alpha": z.alpha, "s": z.s})),
+            "codecs": codecs,
+        })
+    }
+
+    /// Quantiles of all distributions and the estimated size per codec
+    pub fn print(&self) {
+        eprintln!(
+            "{} keys, {} locations, {} duplicates",
+            self.postings.count(),
+            self.locations(),
+            self.duplicates
+        );
+        if self.unsorted {
+            eprintln!("warning: the keys are not sorted, posting lists are split wherever the key changes");
+        }
+
+        eprint!("{:<20}{:>10}", "", "mean");
+        for (name, _) in QUANTILES {
+            eprint!("{name:>10}");
+        }
+        eprintln!("{:>10}", "max");
+        let [tableid, colid, rowid] = &self.gap_bits;
+        for (name, d) in [
+            ("posting length", &self.postings),
+            ("key bytes", &self.key_bytes),
+            ("tables per key", &self.tables),
+            ("(tableid, colid) run", &self.runs),
+            ("tableid gap bits", tableid),
+            ("colid gap bits", colid),
+            ("rowid gap bits", rowid),
+        ] {
+            eprint!("{name:<20}{:>10.2}", d.mean());
+            for (_, q) in QUANTILES {
+                eprint!("{:>10}", d.quantile(q));
+            }
+            eprintln!("{:>10}", d.max());
+        }
+
+        if let Some(Zipf { alpha, s }) = self.zipf() {
+            eprintln!(
+                "posting lengths fit a power law with alpha {alpha:.3}, zipf exponent {s:.3}"
+            );
+        }
+
+        eprintln!(
+            "{:<20}{:>14}{:>20}{:>18}",
+            "codec", "bytes", "bytes per location", "bits per integer"
+        );
+        let locations = self.locations().max(1);
+        for (codec, bytes) in Codec::ALL.iter().zip(self.bytes) {
+            eprintln!(
+                "{:<20}{bytes:>14}{:>20.2}{:>18.2}",
+                codec.str(),
+                bytes as f64 / locations as f64,
+                (bytes * 8) as f64 / (locations * 3) as f64
+            );
+        }
+    }
+}
+
+/// Bit widths occurring exactly, they are few
+fn exact_json(d: &Distribution) -> Value {
+    let mut json = d.to_json();
+    json["histogram"] = d
+        .values()
+        .map(|(bits, count)| json!({"bits": bits, "count": count}))
+        .collect();
+    json
+}
+
+/// Riemann zeta function for `s > 1`, the sum of the first terms and the Euler-Maclaurin estimate of the rest
+fn zeta(s: f64) -> f64 {
+    const TERMS: u32 = 100;
+    let k = TERMS as f64;
+    (1..TERMS).map(|i| (i as f64).powf(-s)).sum::<f64>()
+        + k.powf(1.0 - s) / (s - 1.0)
+        + k.powf(-s) / 2.0
+        + s * k.powf(-s - 1.0) / 12.0
+}
+
+/// Bits needed to store `v`, 0 for 0
+fn bit_width(v: u32) -> u32 {
+    32 - v.leading_zeros()
+}
+
+/// D-gaps of a sorted posting list: tableids to the previous tableid, colids to the previous colid
+/// of the same table and rowids to the previous rowid of the same column.
+fn d_gaps(list: &[[u32; 3]]) -> impl Iterator<Item = [u32; 3]> + '_ {
+    let mut previous: Option<[u32; 3]> = None;
+    list.iter().map(move |&[tableid, colid, rowid]| {
+        let gap = match previous {
+            Some([t, c, r]) if t == tableid && c == colid => [0, 0, rowid - r],
+            Some([t, c, _]) if t == tableid => [0, colid - c, rowid],
+            Some([t, _, _]) => [tableid - t, colid, rowid],
+            None => [tableid, colid, rowid],
+        };
+        previous = Some([tableid, colid, rowid]);
+        gap
+    })
+}
+
+/// Estimated bytes of a posting list, in the order of `Codec::ALL`
+fn sizes(list: &[[u32; 3]], gaps: &[[u32; 3]]) -> [u64; Codec::ALL.len()] {
+    let integers = gaps.iter().flatten().copied();
+
+    let uncompressed = list.len() as u64 * 12;
+    let varint = integers
+        .clone()
+        .map(|g| bit_width(g).div_ceil(7).max(1) as u64)
+        .sum::<u64>();
+    let group_varint = (gaps.len() as u64 * 3).div_ceil(4)
+        + integers
+            .map(|g| bit_width(g).div_ceil(8).max(1) as u64)
+            .sum::<u64>();
+    let bit_packing = (0..3)
+        .map(|i| {
+            let column = gaps.iter().map(|g| g[i]).collect::<Vec<_>>();
+            column.chunks(PACKING_BLOCK).map(packed_bytes).sum::<u64>()
+        })
+        .sum::<u64>();
+    let roaring = roaring_bytes(list);
+
+    let adaptive = if list.len() <= INLINE_LIMIT {
+        0
+    } else {
+        // the tag of the encoding
+        1 + varint.min(group_varint).min(bit_packing).min(roaring)
+    };
+
+    [
+        uncompressed,
+        varint,
+        group_varint,
+        bit_packing,
+        roaring,
+        adaptive,
+    ]
+}
+
+/// Bytes of a block packed with the best bit width, larger integers are exceptions of 32 bits and their position
+fn packed_bytes(block: &[u32]) -> u64 {
+    let mut widths = [0u64; 33];
+    for &v in block {
+        widths[bit_width(v) as usize] += 1;
+    }
+
+    let mut exceptions = block.len() as u64;
+    let mut best = u64::MAX;
+    for (b, count) in widths.iter().enumerate() {
+        exceptions -= count;
+        // the bit width and number of exceptions take a byte each
+        let bits = 16 + block.len() as u64 * b as u64 + exceptions * (32 + 8);
+        best = best.min(bits.div_ceil(8));
+    }
+    best
+}
+
+/// Roaring bitmaps of the rowids of every (tableid, colid): containers of 2^16 rowids are arrays of 2 bytes per rowid,
+/// or bitmaps of 8KB if that is smaller, each with a header of 8 bytes.
+fn roaring_bytes(list: &[[u32; 3]]) -> u64 {
+    let mut bytes = 0;
+    let mut container = None;
+    let mut rows = 0u64;
+    for &[tableid, colid, rowid] in list {
+        let key = (tableid, colid, rowid >> 16);
+        if container != Some(key) {
+            if container.is_some() {
+                bytes += 8 + (rows * 2).min(8192);
+            }
+            container = Some(key);
+            rows = 0;
+        }
+        rows += 1;
+    }
+    if container.is_some() {
+        bytes += 8 + (rows * 2).min(8192);
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(key: &str, tableid: u32, colid: u32, rowid: u32) -> TableRow {
+        TableRow {
+            tokenized: key.to_string(),
+            tableid,
+            colid,
+            rowid,
+        }
+    }
+
+    #[test]
+    fn posting_lists() {
+        let mut corpus = Corpus::default();
+        for r in [
+            row("a", 3, 1, 7),
+            row("a", 1, 0, 5),
+            row("a", 1, 0, 2),
+            row("a", 1, 0, 2),
+            row("a", 1, 2, 9),
+            row("bb", 4, 4, 4),
+        ] {
+            corpus.add(&r);
+        }
+        corpus.finish();
+
+        assert_eq!(
+            corpus.postings.values().collect::<Vec<_>>(),
+            [(1, 1), (4, 1)]
+        );
+        assert_eq!(corpus.duplicates, 1);
+        assert_eq!(corpus.key_bytes.sum(), 3);
+        assert_eq!(corpus.tables.values().collect::<Vec<_>>(), [(1, 1), (2, 1)]);
+        // runs (1, 0) (1, 2) (3, 1) and (4, 4)
+        assert_eq!(corpus.runs.values().collect::<Vec<_>>(), [(1, 3), (2, 1)]);
+        assert!(!corpus.unsorted);
+
+        corpus.add(&row("a", 0, 0, 0));
+        assert!(corpus.unsorted);
+    }
+
+    #[test]
+    fn gaps_within_tables_and_columns() {
+        let list = [[1, 0, 2], [1, 0, 5], [1, 2, 9], [3, 1, 7]];
+        assert_eq!(
+            d_gaps(&list).collect::<Vec<_>>(),
+            [[1, 0, 2], [0, 0, 3], [0, 2, 9], [2, 1, 7]]
+        );
+    }
+
+    #[test]
+    fn codec_sizes() {
+        // 200 rows of the same column
+        let list = (0..200).map(|r| [5, 1, r * 2]).collect::<Vec<_>>();
+        let gaps = d_gaps(&list).collect::<Vec<_>>();
+        let [uncompressed, varint, group_varint, bit_packing, roaring, adaptive] =
+            sizes(&list, &gaps);
+
+        assert_eq!(uncompressed, 2400);
+        assert_eq!(varint, 600);
+        assert_eq!(group_varint, 600 + 150);
+        // gaps of 0 take no bits, the rowid gaps of 2 two bits, the first rowid 0 is an exception of none
+        assert!(bit_packing < 150, "{bit_packing}");
+        assert_eq!(roaring, 8 + 400);
+        assert_eq!(adaptive, 1 + bit_packing);
+
+        assert_eq!(packed_bytes(&[0; 128]), 2);
+        assert_eq!(packed_bytes(&[u32::MAX]), 2 + 4);
+    }
+
+    #[test]
+    fn empty_table() {
+        let json = Corpus::default().to_json();
+        assert_eq!(json["locations"], 0);
+        for codec in json["codecs"].as_object().unwrap().values() {
+            assert_eq!(codec["bytes_per_location"], 0.0);
+            assert_eq!(codec["bits_per_integer"], 0.0);
+        }
+    }
+
+    #[test]
+    fn zipf_exponent() {
+        assert!((zeta(2.0) - std::f64::consts::PI.powi(2) / 6.0).abs() < 1e-9);
+
+        // lengths following zipf's law with s = 2/3 have a power law with alpha = 2.5
+        let mut corpus = Corpus::default();
+        for length in 1..10_000u64 {
+            let p = (length as f64).powf(-2.5) / zeta(2.5);
+            corpus.postings.add_n(length, (p * 1e9).round() as u64);
+        }
+        let zipf = corpus.zipf().unwrap();
+        assert!((zipf.alpha - 2.5).abs() < 0.01, "{zipf:?}");
+        assert!((zipf.s - 2.0 / 3.0).abs() < 0.01, "{zipf:?}");
+
+        // no fit for lengths of 1 only
+        corpus = Corpus::default();
+        corpus.postings.add(1);
+        assert!(corpus.zipf().is_none());
+        assert!(Corpus::default().zipf().is_none());
+    }
+}
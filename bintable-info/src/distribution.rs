@@ -0,0 +1,146 @@
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// Quantiles reported for every distribution
+pub const QUANTILES: [(&str, f64); 4] =
+    [("p50", 0.5), ("p90", 0.9), ("p99", 0.99), ("p999", 0.999)];
+
+/// Exact distribution of non-negative integers, stored as the number of occurrences of every distinct value.
+/// Posting lengths and the like repeat a lot, so this stays small even for large corpora.
+#[derive(Debug, Default, Clone)]
+pub struct Distribution {
+    counts: BTreeMap<u64, u64>,
+    n: u64,
+    sum: u128,
+}
+
+impl Distribution {
+    pub fn add(&mut self, value: u64) {
+        self.add_n(value, 1);
+    }
+
+    pub fn add_n(&mut self, value: u64, n: u64) {
+        if n == 0 {
+            return;
+        }
+        *self.counts.entry(value).or_default() += n;
+        self.n += n;
+        self.sum += value as u128 * n as u128;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.n
+    }
+
+    pub fn sum(&self) -> u128 {
+        self.sum
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.n == 0 {
+            return 0.0;
+        }
+        self.sum as f64 / self.n as f64
+    }
+
+    pub fn max(&self) -> u64 {
+        self.counts.keys().next_back().copied().unwrap_or(0)
+    }
+
+    /// Smallest value, that at least the fraction `q` of all values is less or equal to (nearest rank).
+    pub fn quantile(&self, q: f64) -> u64 {
+        let rank = ((q * self.n as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (&value, &count) in &self.counts {
+            seen += count;
+            if seen >= rank {
+                return value;
+            }
+        }
+        0
+    }
+
+    /// Distinct values along with the number of their occurrences, in ascending order
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = (u64, u64)> + '_ {
+        self.counts.iter().map(|(&v, &c)| (v, c))
+    }
+
+    /// Occurrences in buckets of powers of two: 0, 1, 2-3, 4-7, ...
+    /// Returns the first and last value of every non-empty bucket along with its count.
+    pub fn log2_histogram(&self) -> Vec<(u64, u64, u64)> {
+        let mut buckets: Vec<(u64, u64, u64)> = Vec::new();
+        for (value, count) in self.values() {
+            let (first, last) = match value {
+                0 => (0, 0),
+                v => {
+                    let first = 1 << (63 - v.leading_zeros());
+                    (first, first + (first - 1))
+                }
+            };
+            match buckets.last_mut() {
+                Some(bucket) if bucket.0 == first => bucket.2 += count,
+                _ => buckets.push((first, last, count)),
+            }
+        }
+        buckets
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut json = json!({
+            "count": self.n,
+            "mean": self.mean(),
+            "max": self.max(),
+            "histogram": self
+                .log2_histogram()
+                .into_iter()
+                .map(|(first, last, count)| json!({"from": first, "to": last, "count": count}))
+                .collect::<Vec<_>>(),
+        });
+        for (name, q) in QUANTILES {
+            json[name] = json!(self.quantile(q));
+        }
+        json
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantiles() {
+        let mut d = Distribution::default();
+        for v in 1..=100 {
+            d.add(v);
+        }
+        d.add_n(1000, 0);
+
+        assert_eq!(d.count(), 100);
+        assert_eq!(d.mean(), 50.5);
+        assert_eq!(d.quantile(0.5), 50);
+        assert_eq!(d.quantile(0.9), 90);
+        assert_eq!(d.quantile(0.999), 100);
+        assert_eq!(d.quantile(0.0), 1);
+        assert_eq!(d.max(), 100);
+        assert_eq!(Distribution::default().quantile(0.5), 0);
+    }
+
+    #[test]
+    fn powers_of_two() {
+        let mut d = Distribution::default();
+        for v in [0, 1, 2, 3, 3, 4, 7, 8, 100] {
+            d.add(v);
+        }
+        assert_eq!(
+            d.log2_histogram(),
+            [
+                (0, 0, 1),
+                (1, 1, 1),
+                (2, 3, 3),
+                (4, 7, 2),
+                (8, 15, 1),
+                (64, 127, 1)
+            ]
+        );
+    }
+}
@@ -1,8 +1,13 @@
 use std::fs::File;
 
 use bintable2::*;
+use corpus::Corpus;
+use serde_json::json;
 use structopt::StructOpt;
 
+mod corpus;
+mod distribution;
+
 #[derive(StructOpt)]
 struct Config {
     /// Bintable file
@@ -14,6 +19,16 @@ struct Config {
 
     #[structopt(long)]
     histogram: Option<String>,
+
+    /// Write names and statistics of the tables to <table>.meta.
+    /// Names of an existing one are kept.
+    #[structopt(long)]
+    metadata: bool,
+
+    /// Print the posting list statistics and the estimated size per codec as JSON
+    /// instead of the summary line. The rows have to be sorted by key.
+    #[structopt(long)]
+    json: bool,
 }
 
 fn basefile(s: &str) -> &str {
@@ -29,10 +44,20 @@ fn main() {
         table,
         histogram,
         print_rows,
+        metadata,
+        json,
     } = Config::from_args();
 
     let bintable = BinTable::open(&table).expect("open bintable file");
 
+    let mut metadata =
+        metadata.then(
+            || match Metadata::open(&table).expect("read existing metadata") {
+                Some(names) => MetadataBuilder::with_names(&names),
+                None => MetadataBuilder::default(),
+            },
+        );
+
     let mut values: u64 = 0;
     let mut distinct_values: u64 = 0;
 
@@ -41,7 +66,8 @@ fn main() {
     let mut total_length_colid: u64 = 0;
     let mut total_length_rowid: u64 = 0;
 
-    let mut hist = Stats::new();
+    let mut hist = IdHistograms::new();
+    let mut corpus = Corpus::default();
 
     if print_rows {
         eprintln!("tokenized: [tableid, colid, rowid]")
@@ -49,13 +75,19 @@ fn main() {
 
     {
         let mut last_value = String::from("lick the himalayan saltlamp");
-        for TableRow {
-            tokenized,
-            tableid,
-            colid,
-            rowid,
-        } in bintable
-        {
+        for row in bintable {
+            if let Some(metadata) = &mut metadata {
+                metadata.add(&row);
+            }
+            corpus.add(&row);
+
+            let TableRow {
+                tokenized,
+                tableid,
+                colid,
+                rowid,
+            } = row;
+
             if print_rows {
                 eprintln!("'{tokenized}': [{tableid}, {colid}, {rowid}]")
             }
@@ -78,6 +110,8 @@ fn main() {
         }
     }
 
+    corpus.finish();
+
     let mean_cardinality = values as f64 / distinct_values as f64;
 
     let values = values as f64;
@@ -86,10 +120,41 @@ fn main() {
     let colid = total_length_colid as f64 / values;
     let rowid = total_length_rowid as f64 / values;
 
-    println!("table;values;distinct_values;mean_cardinality;avg_cell_len;avg_tableid;avg_colid;avg_rowid");
-
     let tablename = basefile(&table);
-    println!("{tablename};{values};{distinct_values};{mean_cardinality};{cell_len};{tableid};{colid};{rowid}");
+    let footer = Stats::open(&table).expect("read statistics");
+
+    if json {
+        let mut report = corpus.to_json();
+        report["table"] = json!(tablename);
+        report["bytes"] = footer.map_or(json!(null), |stats| {
+            let b = stats.bytes;
+            json!({
+                "header": b.header,
+                "keys": b.keys,
+                "symbols": b.symbols,
+                "locations": b.locations,
+                "framing": b.framing,
+                "checksums": b.checksums,
+            })
+        });
+        println!("{report:#}");
+    } else {
+        println!("table;values;distinct_values;mean_cardinality;avg_cell_len;avg_tableid;avg_colid;avg_rowid");
+        println!("{tablename};{values};{distinct_values};{mean_cardinality};{cell_len};{tableid};{colid};{rowid}");
+
+        corpus.print();
+        if let Some(Stats { bytes: b, .. }) = footer {
+            eprintln!(
+                "bytes in the bintable: {} keys, {} symbols, {} locations, {} framing, {} checksums, {} header",
+                b.keys, b.symbols, b.locations, b.framing, b.checksums, b.header
+            );
+        }
+    }
+
+    if let Some(metadata) = metadata {
+        let mut f = File::create(Metadata::path(&table)).expect("create metadata file");
+        metadata.finish().write_bin(&mut f).expect("write metadata");
+    }
 
     if histogram.is_none() {
         return;
@@ -128,7 +193,7 @@ const BINS: usize = 256;
 const BIN_SPAN: u64 = 16;
 
 #[derive(Debug)]
-struct Stats {
+struct IdHistograms {
     row_bins: [u64; BINS],
     col_bins: [u64; BINS],
     tableid_bins: [u64; BINS],
@@ -138,9 +203,9 @@ struct Stats {
     tableid_width: [u64; 64],
 }
 
-impl Stats {
+impl IdHistograms {
     pub fn new() -> Self {
-        Stats {
+        IdHistograms {
             row_bins: [0; BINS],
             col_bins: [0; BINS],
             tableid_bins: [0; BINS],
@@ -152,18 +217,18 @@ impl Stats {
     }
 
     pub fn row(&mut self, id: u64) {
-        Stats::linear_bin(id, &mut self.row_bins);
-        Stats::bit_bin(id, &mut self.row_width);
+        Self::linear_bin(id, &mut self.row_bins);
+        Self::bit_bin(id, &mut self.row_width);
     }
 
     pub fn col(&mut self, id: u64) {
-        Stats::linear_bin(id, &mut self.col_bins);
-        Stats::bit_bin(id, &mut self.col_width);
+        Self::linear_bin(id, &mut self.col_bins);
+        Self::bit_bin(id, &mut self.col_width);
     }
 
     pub fn table(&mut self, id: u64) {
-        Stats::linear_bin(id, &mut self.tableid_bins);
-        Stats::bit_bin(id, &mut self.tableid_width);
+        Self::linear_bin(id, &mut self.tableid_bins);
+        Self::bit_bin(id, &mut self.tableid_width);
     }
 
     fn linear_bin(value: u64, bins: &mut [u64]) {
@@ -0,0 +1,93 @@
+use bintable2::*;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+#[structopt(
+    name = "bintable-merge",
+    about = "Applies the added and removed rows written by bintable-diff to a base bintable. \
+             All inputs have to be sorted by key, the output is sorted as well."
+)]
+struct Config {
+    /// Bintable the diff is applied to
+    #[structopt()]
+    base: String,
+
+    /// Rows added to the base
+    #[structopt()]
+    added: String,
+
+    /// Rows removed from the base
+    #[structopt()]
+    removed: String,
+
+    /// Output bintable
+    #[structopt()]
+    output: String,
+}
+
+/// Exits before the output is written, if the rows of `path` are not sorted by key
+fn require_sorted(path: &str) {
+    if let Err(e) = check_sorted(path) {
+        let hint = if e.is::<Unsorted>() {
+            ", sort it using sort-bintable first"
+        } else {
+            ""
+        };
+        eprintln!("{path}: {e}{hint}");
+        std::process::exit(1);
+    }
+}
+
+fn main() {
+    let Config {
+        base,
+        added,
+        removed,
+        output,
+    } = Config::from_args();
+
+    if output == base {
+        panic!("base must not be output");
+    }
+
+    require_sorted(&base);
+    require_sorted(&added);
+    require_sorted(&removed);
+
+    let base = BinTable::open(&base).expect("open base bintable");
+    let added = BinTable::open(&added).expect("open added rows");
+    let removed = BinTable::open(&removed).expect("open removed rows");
+
+    if base.header() != added.header() {
+        eprintln!(
+            "warning: the diff was tokenized differently than the base, {:?} and {:?}",
+            added.header(),
+            base.header()
+        );
+    }
+
+    let mut out = BinTableWriter::create(&output)
+        .expect("open output file")
+        .sorted();
+    out.write_header(base.header()).expect("write header");
+
+    let mut patch = apply(base, added, removed);
+    for row in patch.by_ref() {
+        let row = row.unwrap_or_else(|e| {
+            // the inputs changed since they were checked
+            eprintln!("{e}");
+            let _ = std::fs::remove_file(&output);
+            std::process::exit(1);
+        });
+        out.write(&row).expect("write to output");
+    }
+    let stats = out.finish().expect("write to output");
+
+    eprintln!("{} rows written", stats.rows);
+    if patch.missing() > 0 {
+        eprintln!(
+            "warning: {} removed rows were not in the base bintable",
+            patch.missing()
+        );
+    }
+}
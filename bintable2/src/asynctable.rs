@@ -0,0 +1,187 @@
+use crate::decoder::{Decoder, Step, BUFFER};
+use crate::error::BinTableError;
+use crate::header::Header;
+use crate::stats::Stats;
+use crate::tablerow::TableRow;
+use futures_core::Stream;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+
+/// Reads the rows of a bintable from an asynchronous reader, like `BinTable` does from a file.
+/// The stream returns the rows along with the damage found, see `BinTable::try_next`.
+pub struct AsyncBinTable<R> {
+    reader: R,
+    decoder: Decoder,
+    scratch: Box<[u8]>,
+}
+
+impl AsyncBinTable<tokio::fs::File> {
+    pub async fn open(path: &str) -> std::io::Result<Self> {
+        let reader = tokio::fs::File::open(path).await?;
+        AsyncBinTable::new(reader).await
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncBinTable<R> {
+    /// Reads the header in front of the first row, so it is known before the first row is read.
+    pub async fn new(reader: R) -> std::io::Result<Self> {
+        let mut table = AsyncBinTable {
+            reader,
+            decoder: Decoder::default(),
+            scratch: vec![0; BUFFER].into_boxed_slice(),
+        };
+        while !table.decoder.read_header() {
+            let read = table.reader.read(&mut table.scratch).await?;
+            table.append(read);
+        }
+
+        Ok(table)
+    }
+
+    /// Header of the table, empty if it has none.
+    pub fn header(&self) -> &Header {
+        self.decoder.header()
+    }
+
+    /// Whether the rows read so far were closed by checksum records
+    pub fn is_checksummed(&self) -> bool {
+        self.decoder.is_checksummed()
+    }
+
+    /// Offset of the block the rows returned last belong to
+    pub fn block_start(&self) -> u64 {
+        self.decoder.block_start()
+    }
+
+    /// Statistics written by `BinTableWriter`, known once the last row was read.
+    /// `Stats::open` reads them without reading the rows.
+    pub fn stats(&self) -> Option<&Stats> {
+        self.decoder.stats()
+    }
+
+    fn append(&mut self, read: usize) {
+        if read == 0 {
+            self.decoder.end();
+        } else {
+            self.decoder
+                .buffer()
+                .extend_from_slice(&self.scratch[..read]);
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for AsyncBinTable<R> {
+    type Item = Result<TableRow, BinTableError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            match this.decoder.step() {
+                Step::Row(row) => return Poll::Ready(Some(Ok(row))),
+                Step::Damage(e) => return Poll::Ready(Some(Err(e))),
+                Step::End => return Poll::Ready(None),
+                Step::Read => {
+                    let mut buf = ReadBuf::new(&mut this.scratch);
+                    match Pin::new(&mut this.reader).poll_read(cx, &mut buf) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(Ok(())) => {
+                            let read = buf.filled().len();
+                            this.append(read);
+                        }
+                        Poll::Ready(Err(e)) => {
+                            this.decoder.stop();
+                            return Poll::Ready(Some(Err(e.into())));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bintable::BinTable;
+    use crate::tablerow::ParseAcc;
+    use std::future::poll_fn;
+
+    fn write() -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut acc = ParseAcc::default();
+        for i in 0..20_000u32 {
+            let row = TableRow {
+                tokenized: format!("key-{:06}", i / 3),
+                tableid: i % 7,
+                colid: 1,
+                rowid: i,
+            };
+            row.write_bin(&mut data, &mut acc).unwrap();
+        }
+        acc.finish(&mut data).unwrap();
+        data
+    }
+
+    fn sync(name: &str, data: &[u8]) -> Vec<Result<TableRow, String>> {
+        let path = std::env::temp_dir().join(format!("asynctable-{name}-{}", std::process::id()));
+        std::fs::write(&path, data).unwrap();
+        let mut table = BinTable::open(path.to_str().unwrap()).unwrap();
+        let read = table
+            .checked()
+            .map(|r| r.map_err(|e| e.to_string()))
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+        read
+    }
+
+    /// Reads `data` in pieces of `chunk` bytes
+    fn stream(data: &[u8], chunk: usize) -> Vec<Result<TableRow, String>> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let reader = Chunked(data, chunk);
+            let mut table = AsyncBinTable::new(reader).await.unwrap();
+            let mut read = Vec::new();
+            while let Some(row) = poll_fn(|cx| Pin::new(&mut table).poll_next(cx)).await {
+                read.push(row.map_err(|e| e.to_string()));
+            }
+            read
+        })
+    }
+
+    /// Returns at most `.1` bytes per read
+    struct Chunked<'a>(&'a [u8], usize);
+
+    impl AsyncRead for Chunked<'_> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            let n = self.1.min(self.0.len()).min(buf.remaining());
+            buf.put_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn same_as_sync() {
+        let data = write();
+        let mut damaged = data.clone();
+        damaged[data.len() / 2] ^= 0x10;
+
+        for (name, data) in [
+            ("intact", &data[..]),
+            ("damaged", &damaged[..]),
+            ("truncated", &data[..data.len() - 100]),
+        ] {
+            let expected = sync(name, data);
+            for chunk in [1000, BUFFER] {
+                assert_eq!(stream(data, chunk), expected, "{name} in chunks of {chunk}");
+            }
+        }
+    }
+}
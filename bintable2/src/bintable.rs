@@ -1,35 +1,40 @@
+use crate::header::Header;
+use crate::sampling::Sampling;
+use crate::stats::Stats;
 use crate::util::*;
-use rand::rngs::ThreadRng;
-use rand::Rng;
 
 use super::tablerow::TableRow;
-use crate::tablerow::{ParseAcc, ReadError};
+use crate::decoder::{Decoder, Step, BUFFER};
+use crate::error::BinTableError;
 use std::fs::File;
 use std::io::Read;
 
 pub struct BinTableSampler {
     bintable: BinTable,
-    factor: f32,
-    rng: ThreadRng,
+    sampling: Sampling,
 }
 impl BinTableSampler {
-    pub fn open(path: &str, factor: f32) -> std::io::Result<BinTableSampler> {
-        // first search, if a better bintable exists beside the original one.
-        let (path, factor) = if let Some((path, corpus)) = path.rsplit_once('/') {
-            find_best_input(path, corpus, factor)
-        } else {
-            find_best_input(".", path, factor)
-        }?;
+    pub fn open(path: &str, sampling: Sampling) -> std::io::Result<BinTableSampler> {
+        let path = BinTableSampler::input(path, sampling)?;
 
         eprintln!("reading {path}");
         let bintable = BinTable::open(&path)?;
-        let rng = rand::thread_rng();
 
-        Ok(BinTableSampler {
-            bintable,
-            factor,
-            rng,
-        })
+        Ok(BinTableSampler { bintable, sampling })
+    }
+
+    /// The smallest sample with the same mode and seed beside `path` which still contains the sample,
+    /// or `path` itself, if there is none.
+    pub fn input(path: &str, sampling: Sampling) -> std::io::Result<String> {
+        if let Some((path, corpus)) = path.rsplit_once('/') {
+            find_best_input(path, corpus, sampling)
+        } else {
+            find_best_input(".", path, sampling)
+        }
+    }
+
+    pub fn header(&self) -> &Header {
+        self.bintable.header()
     }
 }
 
@@ -39,85 +44,345 @@ impl Iterator for BinTableSampler {
         loop {
             let item = self.bintable.next()?;
 
-            if self.rng.gen::<f32>() < self.factor {
+            if self
+                .sampling
+                .keeps(&item.tokenized, item.tableid, item.colid, item.rowid)
+            {
                 return Some(item);
             }
         }
     }
 }
 
+/// Reads the rows of a bintable. Rows are only returned once their block matches its checksum record.
+/// Bintables written before checksums were introduced are read without verification.
 pub struct BinTable {
     reader: File,
-    buffer: Vec<u8>,
-    offset: usize,
-    acc: ParseAcc,
+    decoder: Decoder,
 }
 
 impl BinTable {
     pub fn open(path: &str) -> std::io::Result<BinTable> {
         let reader = File::open(path)?;
 
-        Ok(BinTable {
+        let mut table = BinTable {
             reader,
-            buffer: Vec::with_capacity(1024 * 8),
-            offset: 0,
-            acc: ParseAcc::default(),
-        })
+            decoder: Decoder::default(),
+        };
+        // read the header in front of the first row, so it is known before the first row is read
+        while !table.decoder.read_header() {
+            table.read()?;
+        }
+
+        Ok(table)
+    }
+
+    /// Header of the table, empty if it has none.
+    pub fn header(&self) -> &Header {
+        self.decoder.header()
+    }
+
+    /// Whether the rows read so far were closed by checksum records,
+    /// bintables written before checksums were introduced are read without verification.
+    pub fn is_checksummed(&self) -> bool {
+        self.decoder.is_checksummed()
+    }
+
+    /// Offset of the block the rows returned last belong to
+    pub fn block_start(&self) -> u64 {
+        self.decoder.block_start()
+    }
+
+    /// Statistics written by `BinTableWriter`, known once the last row was read.
+    /// `Stats::open` reads them without reading the rows.
+    pub fn stats(&self) -> Option<&Stats> {
+        self.decoder.stats()
+    }
+
+    /// Next row, or the damage found instead of it.
+    /// If the bintable has checksums, reading continues with the next intact block after damage.
+    pub fn try_next(&mut self) -> Option<Result<TableRow, BinTableError>> {
+        loop {
+            match self.decoder.step() {
+                Step::Row(row) => return Some(Ok(row)),
+                Step::Damage(e) => return Some(Err(e)),
+                Step::End => return None,
+                Step::Read => {
+                    if let Err(e) = self.read() {
+                        self.decoder.stop();
+                        return Some(Err(e.into()));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Rows of the bintable along with the damage found, see `try_next`.
+    pub fn checked(&mut self) -> impl Iterator<Item = Result<TableRow, BinTableError>> + '_ {
+        std::iter::from_fn(|| self.try_next())
+    }
+
+    fn read(&mut self) -> std::io::Result<()> {
+        let read = self
+            .reader
+            .by_ref()
+            .take(BUFFER as u64)
+            .read_to_end(self.decoder.buffer())?;
+        if read == 0 {
+            self.decoder.end();
+        }
+        Ok(())
     }
 }
 
 impl Iterator for BinTable {
     type Item = TableRow;
+
+    /// Panics on damage, `try_next` returns it instead.
     fn next(&mut self) -> Option<Self::Item> {
-        if self.buffer.len() == self.offset {
-            self.buffer.clear();
-            self.offset = 0;
-            let space = self.buffer.capacity();
-
-            let n = self
-                .reader
-                .by_ref()
-                .take(space as u64)
-                .read_to_end(&mut self.buffer)
-                .expect("to read file");
-
-            if n == 0 {
-                return None;
+        self.try_next()
+            .map(|row| row.unwrap_or_else(|e| panic!("{e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tablerow::{Kind, ParseAcc};
+    use crate::tokenizer::Tokenizer;
+    use varint_compression::decompress;
+
+    fn rows() -> Vec<TableRow> {
+        (0..20_000u32)
+            .map(|i| TableRow {
+                tokenized: format!("key-{:06}", i / 3),
+                tableid: i % 7,
+                colid: 1,
+                rowid: i,
+            })
+            .collect()
+    }
+
+    fn write(rows: &[TableRow]) -> Vec<u8> {
+        let header = Header {
+            tokenizers: vec![Tokenizer::default()],
+        };
+        let mut data = Vec::new();
+        header.write_bin(&mut data).unwrap();
+        let mut acc = ParseAcc::default();
+        for row in rows {
+            row.write_bin(&mut data, &mut acc).unwrap();
+        }
+        acc.finish(&mut data).unwrap();
+        data
+    }
+
+    /// Reads `data` as a bintable, returns the rows and damage along with the start of their block
+    fn read(name: &str, data: &[u8]) -> Vec<(Result<TableRow, BinTableError>, u64)> {
+        let path = std::env::temp_dir().join(format!("bintable2-{name}-{}", std::process::id()));
+        std::fs::write(&path, data).unwrap();
+        let mut table = BinTable::open(path.to_str().unwrap()).unwrap();
+
+        let mut read = Vec::new();
+        while let Some(row) = table.try_next() {
+            read.push((row, table.block_start()));
+        }
+        std::fs::remove_file(&path).unwrap();
+        read
+    }
+
+    /// Starts of all blocks, along with the number of rows before them
+    fn blocks(data: &[u8]) -> Vec<(u64, usize)> {
+        let mut blocks = Vec::<(u64, usize)>::new();
+        for (i, (_, start)) in read("blocks", data).into_iter().enumerate() {
+            if blocks.last().map(|b| b.0) != Some(start) {
+                blocks.push((start, i));
             }
         }
+        blocks
+    }
+
+    #[test]
+    fn checksummed_blocks() {
+        let rows = rows();
+        let data = write(&rows);
+
+        let read = read("checksummed", &data);
+        assert_eq!(read.len(), rows.len());
+        for ((row, _), expected) in read.into_iter().zip(&rows) {
+            assert_eq!(&row.unwrap(), expected);
+        }
+        assert!(blocks(&data).len() >= 3);
+    }
 
-        let fresh_data = &self.buffer[self.offset..];
+    #[test]
+    fn truncated() {
+        let rows = rows();
+        let data = write(&rows);
+        let blocks = blocks(&data);
 
-        match TableRow::from_bin(fresh_data, &mut self.acc) {
-            Ok((row, rest)) => {
-                self.offset = self.buffer.len() - rest.len();
-                return Some(row);
+        for end in (0..data.len()).step_by(2477) {
+            let read = read("truncated", &data[..end]);
+            let (ok, damage): (Vec<_>, Vec<_>) = read.into_iter().partition(|(r, _)| r.is_ok());
+
+            // all complete blocks are read
+            let complete = blocks
+                .iter()
+                .rfind(|(start, _)| *start <= end as u64)
+                .map_or(0, |(_, rows)| *rows);
+            assert_eq!(ok.len(), complete, "truncated after {end} bytes");
+            for ((row, _), expected) in ok.into_iter().zip(&rows) {
+                assert_eq!(&row.unwrap(), expected);
             }
-            Err(ReadError::InitialNumber) => {
-                // we only have very tiny end of buffer and need to seek more.
-                let tmp = fresh_data.to_vec();
-                self.offset = tmp.len();
-                self.buffer.clear();
-                self.buffer.extend(tmp);
-
-                // now fill the buffer
+
+            // a cut between blocks looks like the end of the file
+            let between = end == 0 || blocks.iter().any(|(start, _)| *start == end as u64);
+            assert_eq!(
+                damage.len(),
+                usize::from(!between),
+                "truncated after {end} bytes"
+            );
+            for (e, _) in damage {
+                assert!(matches!(e, Err(BinTableError::Truncated { .. })));
             }
-            Err(ReadError::Needed(n)) => {
-                while (self.buffer.capacity() - self.offset) < n {
-                    self.buffer.reserve(1024);
-                }
+        }
+    }
+
+    #[test]
+    fn corrupted_block() {
+        let rows = rows();
+        let data = write(&rows);
+        let blocks = blocks(&data);
+        let (start, first) = blocks[1];
+        let (end, last) = blocks[2];
+
+        for position in [start + 1, (start + end) / 2, end - 3] {
+            let mut damaged = data.clone();
+            damaged[position as usize] ^= 0x10;
+
+            let read = read("corrupted", &damaged);
+            let (ok, damage): (Vec<_>, Vec<_>) = read.into_iter().partition(|(r, _)| r.is_ok());
+
+            // only the rows of the damaged block are missing
+            let mut expected = rows[..first].to_vec();
+            expected.extend_from_slice(&rows[last..]);
+            let ok = ok.into_iter().map(|(r, _)| r.unwrap()).collect::<Vec<_>>();
+            assert_eq!(ok, expected, "damaged byte {position}");
+
+            assert_eq!(damage.len(), 1);
+            let offset = damage[0].0.as_ref().unwrap_err().offset().unwrap();
+            assert!((start..end).contains(&offset));
+        }
+    }
+
+    #[test]
+    fn corrupted_first_fsst_block() {
+        let rows = rows();
+        let sample = rows
+            .iter()
+            .map(|r| r.tokenized.as_str())
+            .collect::<Vec<_>>();
+
+        let mut data = Vec::new();
+        let mut acc = ParseAcc::with_fsst(fsst::SymbolTable::train(&sample));
+        for row in &rows {
+            row.write_bin(&mut data, &mut acc).unwrap();
+        }
+        acc.finish(&mut data).unwrap();
+
+        let blocks = blocks(&data);
+        assert!(blocks.len() >= 3);
+        let (start, _) = blocks[0];
+        let (end, first) = blocks[1];
+
+        // the symbol table can't be read from the first row of the block
+        let (_, record) = decompress(&data[start as usize..]).unwrap();
+        let kind = data.len() - record.len();
+        assert_eq!(data[kind], Kind::FsstTable.byte());
+        let mut damaged = data.clone();
+        damaged[kind] = 0xff;
+
+        let read = read("corrupted-fsst", &damaged);
+        let (ok, damage): (Vec<_>, Vec<_>) = read.into_iter().partition(|(r, _)| r.is_ok());
+
+        // the rows of all other blocks are salvaged
+        let ok = ok.into_iter().map(|(r, _)| r.unwrap()).collect::<Vec<_>>();
+        assert_eq!(ok, rows[first..]);
+        assert_eq!(damage.len(), 1);
+        let offset = damage[0].0.as_ref().unwrap_err().offset().unwrap();
+        assert!((start..end).contains(&offset));
+    }
+
+    /// The bintable as written before checksums were introduced
+    fn legacy(data: &[u8]) -> Vec<u8> {
+        let mut legacy = Vec::new();
+        let mut rest = data;
+        while !rest.is_empty() {
+            let (length, after) = decompress(rest).unwrap();
+            let record = rest.len() - after.len() + length as usize;
+            if after[0] != Kind::Checksum.byte() {
+                legacy.extend(&rest[..record]);
             }
+            rest = &rest[record..];
         }
+        legacy
+    }
 
-        // read at least n bytes
+    #[test]
+    fn records_across_reads() {
+        // records of varying length cross the ends of the reads,
+        // one key is longer than a whole read
+        let mut rows = (0..3000u32)
+            .map(|i| TableRow {
+                tokenized: format!("key-{i:06}-{}", "x".repeat(i as usize % 97)),
+                tableid: i,
+                colid: i % 5,
+                rowid: i * 31,
+            })
+            .collect::<Vec<_>>();
+        rows.insert(
+            1500,
+            TableRow {
+                tokenized: format!("key-001499-{}", "y".repeat(3 * BUFFER)),
+                tableid: 1,
+                colid: 2,
+                rowid: 3,
+            },
+        );
 
-        let space = self.buffer.capacity() - self.buffer.len();
-        self.reader
-            .by_ref()
-            .take(space as u64)
-            .read_to_end(&mut self.buffer)
-            .expect("to read file");
+        let data = write(&rows);
+        for (name, data) in [
+            ("across-reads", data.clone()),
+            ("across-reads-legacy", legacy(&data)),
+        ] {
+            let read = read(name, &data)
+                .into_iter()
+                .map(|(r, _)| r.unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(read, rows, "{name}");
+        }
+    }
+
+    #[test]
+    fn without_checksums() {
+        let rows = rows();
+        let data = write(&rows);
+
+        // bintables written before checksums were introduced
+        let legacy = legacy(&data);
+
+        let read = read("legacy", &legacy);
+        let read = read
+            .into_iter()
+            .map(|(r, _)| r.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(read, rows);
 
-        self.next()
+        let read = self::read("legacy-truncated", &legacy[..legacy.len() - 2]);
+        assert_eq!(read.len(), rows.len());
+        assert!(matches!(
+            read.last().unwrap().0,
+            Err(BinTableError::Truncated { .. })
+        ));
     }
 }
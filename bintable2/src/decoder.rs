@@ -0,0 +1,345 @@
+//! Decodes the records of a bintable independent of how its bytes are read,
+//! so the blocking `BinTable` and the asynchronous `AsyncBinTable` share it.
+
+use crate::error::BinTableError;
+use crate::header::Header;
+use crate::stats::Stats;
+use crate::tablerow::{Block, Kind, ParseAcc, ReadError, TableRow, BLOCK_SIZE};
+use std::collections::VecDeque;
+use varint_compression::decompress;
+
+/// Bytes read from the file at once
+pub(crate) const BUFFER: usize = 64 * 1024;
+
+/// Longest record accepted, longer lengths are taken for damage
+const MAX_RECORD: u64 = 1 << 24;
+
+enum Record {
+    Row(TableRow),
+    Header(Header),
+    Checksum(Block),
+    Stats(Stats),
+}
+
+/// Position of a record in the buffer
+#[derive(Clone, Copy)]
+struct Frame {
+    start: usize,
+    /// first byte after the length, the kind byte
+    body: usize,
+    end: usize,
+}
+
+/// Progress of decoding the next block
+enum Next {
+    /// the rows of a block were queued
+    Block,
+    /// the block is not completely buffered yet
+    Read,
+    End,
+}
+
+/// What the reader has to do next
+pub(crate) enum Step {
+    Row(TableRow),
+    Damage(BinTableError),
+    /// append more bytes to `buffer()`, or call `end()` at the end of the file
+    Read,
+    End,
+}
+
+/// Rows are only returned once their block matches its checksum record.
+/// Bintables written before checksums were introduced are decoded without verification.
+#[derive(Default)]
+pub(crate) struct Decoder {
+    buffer: Vec<u8>,
+    /// start of the next block in `buffer`
+    offset: usize,
+    /// offset of `buffer` in the file
+    position: u64,
+    eof: bool,
+    acc: ParseAcc,
+    /// verified rows of the current block
+    rows: VecDeque<TableRow>,
+    block_start: u64,
+    /// whether the rows are closed by checksum records
+    checksums: bool,
+    /// damage found after the rows of a bintable without checksums
+    damage: Option<BinTableError>,
+    /// statistics behind the last row
+    stats: Option<Stats>,
+    /// whether an intact block is searched after damage
+    resync: bool,
+    done: bool,
+}
+
+impl Decoder {
+    pub(crate) fn header(&self) -> &Header {
+        &self.acc.header
+    }
+
+    pub(crate) fn block_start(&self) -> u64 {
+        self.block_start
+    }
+
+    pub(crate) fn is_checksummed(&self) -> bool {
+        self.checksums
+    }
+
+    pub(crate) fn stats(&self) -> Option<&Stats> {
+        self.stats.as_ref()
+    }
+
+    /// Buffer to append the bytes read to. Bytes not needed anymore are dropped from its front.
+    pub(crate) fn buffer(&mut self) -> &mut Vec<u8> {
+        if self.offset > 0 {
+            self.buffer.drain(..self.offset);
+            self.position += self.offset as u64;
+            self.offset = 0;
+        }
+        &mut self.buffer
+    }
+
+    /// No more bytes follow
+    pub(crate) fn end(&mut self) {
+        self.eof = true;
+    }
+
+    /// Stops decoding, after the bytes could not be read
+    pub(crate) fn stop(&mut self) {
+        self.done = true;
+    }
+
+    /// Decodes the header in front of the first row, so it is known before the first row is read.
+    /// Returns false, if more bytes are needed.
+    pub(crate) fn read_header(&mut self) -> bool {
+        match self.frame(self.offset) {
+            Ok(None) => self.eof,
+            Ok(Some(frame)) if self.buffer[frame.body] == Kind::Header.byte() => {
+                // a damaged header is reported along with the rows
+                if let Ok(Record::Header(header)) = self.decode(frame) {
+                    self.acc.header = header;
+                    self.offset = frame.end;
+                }
+                true
+            }
+            _ => true,
+        }
+    }
+
+    pub(crate) fn step(&mut self) -> Step {
+        loop {
+            if let Some(row) = self.rows.pop_front() {
+                return Step::Row(row);
+            }
+            if let Some(damage) = self.damage.take() {
+                self.done = true;
+                return Step::Damage(damage);
+            }
+            if self.done {
+                return Step::End;
+            }
+
+            let next = match self.resync {
+                true => Ok(self.resync()),
+                false => self.block(),
+            };
+            match next {
+                Ok(Next::Block) => {}
+                Ok(Next::Read) => return Step::Read,
+                Ok(Next::End) => self.done = true,
+                Err(damage) => {
+                    // continue with the next intact block
+                    match damage.offset() {
+                        Some(offset) if self.checksums => {
+                            self.offset = (offset - self.position) as usize + 1;
+                            self.resync = true;
+                        }
+                        _ => self.done = true,
+                    }
+                    return Step::Damage(damage);
+                }
+            }
+        }
+    }
+
+    fn damage(&self, start: usize) -> impl Fn(ReadError) -> BinTableError {
+        let offset = self.position + start as u64;
+        move |e| BinTableError::Malformed {
+            offset,
+            reason: match e {
+                ReadError::Invalid(reason) => reason,
+                _ => "record",
+            },
+        }
+    }
+
+    /// Frame of the record at `start`, `None` if it is not completely buffered yet.
+    fn frame(&self, start: usize) -> Result<Option<Frame>, BinTableError> {
+        let offset = self.position + start as u64;
+        let data = &self.buffer[start..];
+        let incomplete = if self.eof {
+            Err(BinTableError::Truncated { offset })
+        } else {
+            Ok(None)
+        };
+        if data.is_empty() {
+            return Ok(None);
+        }
+
+        let (length, rest) = match decompress(data) {
+            Ok(d) => d,
+            // the length is not completely buffered
+            Err(_) if data.len() < 10 => return incomplete,
+            Err(_) => {
+                return Err(self.damage(start)(ReadError::Invalid(
+                    "length of the record",
+                )))
+            }
+        };
+        if length == 0 || length > MAX_RECORD {
+            return Err(self.damage(start)(ReadError::Invalid(
+                "length of the record",
+            )));
+        }
+
+        let body = start + data.len() - rest.len();
+        let end = body + length as usize;
+        if end > self.buffer.len() {
+            return incomplete;
+        }
+        Ok(Some(Frame { start, body, end }))
+    }
+
+    fn decode(&mut self, frame: Frame) -> Result<Record, BinTableError> {
+        let damage = self.damage(frame.start);
+        let record = &self.buffer[frame.body..frame.end];
+        Ok(match Kind::from(record[0]).map_err(&damage)? {
+            Kind::Header => Record::Header(Header::from_bin_raw(&record[1..]).map_err(&damage)?),
+            Kind::Checksum => Record::Checksum(Block::decode(&record[1..]).map_err(&damage)?),
+            Kind::Stats => Record::Stats(Stats::from_bin_raw(&record[1..]).map_err(&damage)?),
+            _ => Record::Row(TableRow::from_bin_raw(record, &mut self.acc).map_err(&damage)?),
+        })
+    }
+
+    /// Decodes the block at `offset` once it is completely buffered, if it matches its checksum.
+    fn block(&mut self) -> Result<Next, BinTableError> {
+        let start = self.offset;
+
+        // find the end of the block first
+        let mut frames = Vec::new();
+        let mut end = start;
+        let mut bytes = 0;
+        loop {
+            if end == self.buffer.len() && self.eof {
+                break;
+            }
+            let frame = match self.frame(end) {
+                Ok(Some(frame)) => frame,
+                Ok(None) => return Ok(Next::Read),
+                // without checksums, the rows in front of the damage are still returned
+                Err(damage) if !self.checksums && !frames.is_empty() => {
+                    self.damage = Some(damage);
+                    break;
+                }
+                Err(damage) => return Err(damage),
+            };
+            frames.push(frame);
+            end = frame.end;
+
+            let kind = self.buffer[frame.body];
+            if kind == Kind::Checksum.byte() {
+                break;
+            }
+            if kind != Kind::Header.byte() && kind != Kind::Stats.byte() {
+                bytes += frame.end - frame.start;
+            }
+            // bintables without checksums are read in blocks of the same size
+            if !self.checksums && bytes >= BLOCK_SIZE {
+                break;
+            }
+        }
+
+        if frames.is_empty() {
+            return Ok(Next::End);
+        }
+
+        let mut block = Block::default();
+        let mut rows = Vec::new();
+        let mut closed = false;
+        for frame in frames {
+            match self.decode(frame) {
+                Ok(Record::Row(row)) => {
+                    block.add(&self.buffer[frame.start..frame.end]);
+                    rows.push(row);
+                }
+                Ok(Record::Header(header)) => self.acc.header = header,
+                Ok(Record::Stats(stats)) => self.stats = Some(stats),
+                Ok(Record::Checksum(expected)) => {
+                    self.checksums = true;
+                    closed = true;
+                    if expected != block {
+                        return Err(BinTableError::Checksum {
+                            offset: self.position + start as u64,
+                            expected: expected.crc,
+                            actual: block.crc,
+                        });
+                    }
+                }
+                Err(damage) if !self.checksums => {
+                    self.damage = Some(damage);
+                    break;
+                }
+                Err(damage) => return Err(damage),
+            }
+        }
+
+        if self.checksums && !closed && !rows.is_empty() {
+            return Err(BinTableError::Truncated {
+                offset: self.position + start as u64,
+            });
+        }
+
+        self.offset = end;
+        self.block_start = self.position + start as u64;
+        self.rows.extend(rows);
+        Ok(Next::Block)
+    }
+
+    /// Searches the first block after damage, that matches its checksum.
+    fn resync(&mut self) -> Next {
+        loop {
+            if self.buffer.len() - self.offset < 2 {
+                return if self.eof { Next::End } else { Next::Read };
+            }
+
+            // checksum records are shorter than 128 bytes, their length takes a single byte
+            let data = &self.buffer[self.offset..];
+            if data[0] < 0x80 && data[1] == Kind::Checksum.byte() {
+                let candidate = self.offset;
+                match self.frame(candidate) {
+                    Ok(None) => return Next::Read,
+                    Ok(Some(frame)) if matches!(self.decode(frame), Ok(Record::Checksum(_))) => {
+                        self.offset = frame.end;
+                        // blocks start with a full key
+                        self.acc.last_tokenized.clear();
+
+                        match self.block() {
+                            Ok(Next::Read) => {
+                                self.offset = candidate;
+                                return Next::Read;
+                            }
+                            Ok(next) => {
+                                self.resync = false;
+                                return next;
+                            }
+                            Err(_) => self.offset = candidate,
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            self.offset += 1;
+        }
+    }
+}
@@ -0,0 +1,455 @@
+use crate::bintable::BinTable;
+use crate::stats::Stats;
+use crate::tablerow::TableRow;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::fmt;
+use std::iter::Peekable;
+
+/// A key following a greater one in rows that have to be sorted by key (in byte order).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unsorted {
+    pub key: String,
+    pub previous: String,
+}
+
+impl fmt::Display for Unsorted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "rows must be sorted by key, {:?} follows {:?}",
+            self.key, self.previous
+        )
+    }
+}
+
+impl std::error::Error for Unsorted {}
+
+/// The first key following a greater one, `None` if the rows are sorted by key.
+pub fn first_unsorted(rows: impl IntoIterator<Item = TableRow>) -> Option<Unsorted> {
+    let mut rows = rows.into_iter();
+    let mut previous = rows.next()?.tokenized;
+    for row in rows {
+        if row.tokenized < previous {
+            return Some(Unsorted {
+                key: row.tokenized,
+                previous,
+            });
+        }
+        previous = row.tokenized;
+    }
+    None
+}
+
+/// Fails if the rows of the bintable at `path` are not sorted by key. Trusts the statistics written by
+/// `BinTableWriter`, bintables without them are read up to the first key out of order.
+pub fn check_sorted(path: &str) -> anyhow::Result<()> {
+    if Stats::open(path)?.is_some_and(|stats| stats.sorted) {
+        return Ok(());
+    }
+
+    let mut table = BinTable::open(path)?;
+    let mut damage = None;
+    let rows = table
+        .checked()
+        .map_while(|row| row.map_err(|e| damage = Some(e)).ok());
+
+    match (first_unsorted(rows), damage) {
+        (Some(unsorted), _) => Err(unsorted.into()),
+        (None, Some(damage)) => Err(damage.into()),
+        (None, None) => Ok(()),
+    }
+}
+
+/// Rows of consecutive equal keys, sorted by their location.
+/// Only the keys have to be sorted, locations of a key may come in any order.
+struct Keys<I: Iterator<Item = TableRow>> {
+    rows: Peekable<I>,
+    last: Option<String>,
+}
+
+impl<I: Iterator<Item = TableRow>> Keys<I> {
+    fn new(rows: I) -> Peekable<Self> {
+        Keys {
+            rows: rows.peekable(),
+            last: None,
+        }
+        .peekable()
+    }
+}
+
+impl<I: Iterator<Item = TableRow>> Iterator for Keys<I> {
+    type Item = Result<Vec<TableRow>, Unsorted>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = self.rows.next()?;
+        if let Some(last) = &self.last {
+            if first.tokenized <= *last {
+                return Some(Err(Unsorted {
+                    key: first.tokenized,
+                    previous: last.clone(),
+                }));
+            }
+        }
+
+        let mut rows = vec![first];
+        while let Some(row) = self.rows.next_if(|r| r.tokenized == rows[0].tokenized) {
+            rows.push(row);
+        }
+        rows.sort_unstable();
+
+        self.last = Some(rows[0].tokenized.clone());
+        Some(Ok(rows))
+    }
+}
+
+/// Key of the next rows, or the error if the keys are not sorted
+fn key<I: Iterator<Item = TableRow>>(
+    keys: &mut Peekable<Keys<I>>,
+) -> Result<Option<&str>, Unsorted> {
+    if let Some(Err(unsorted)) = keys.next_if(Result::is_err) {
+        return Err(unsorted);
+    }
+    Ok(keys
+        .peek()
+        .and_then(|rows| rows.as_ref().ok())
+        .map(|rows| rows[0].tokenized.as_str()))
+}
+
+/// Rows of `key`, if it is the next key
+fn rows_of<I: Iterator<Item = TableRow>>(keys: &mut Peekable<Keys<I>>, key: &str) -> Vec<TableRow> {
+    keys.next_if(|rows| matches!(rows, Ok(rows) if rows[0].tokenized == key))
+        .and_then(Result::ok)
+        .unwrap_or_default()
+}
+
+/// Splits two sorted lists into the rows only in `a`, the rows in both and the rows only in `b`.
+/// Rows occurring several times are counted, like in a multiset.
+fn split(a: Vec<TableRow>, b: Vec<TableRow>) -> (Vec<TableRow>, Vec<TableRow>, Vec<TableRow>) {
+    let (mut only_a, mut both, mut only_b) = (Vec::new(), Vec::new(), Vec::new());
+
+    let mut b = b.into_iter().peekable();
+    for row in a {
+        while let Some(smaller) = b.next_if(|r| *r < row) {
+            only_b.push(smaller);
+        }
+        match b.next_if_eq(&row) {
+            Some(row) => both.push(row),
+            None => only_a.push(row),
+        }
+    }
+    only_b.extend(b);
+
+    (only_a, both, only_b)
+}
+
+/// A row that differs between two bintables
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// only in the new bintable
+    Added(TableRow),
+    /// only in the old bintable
+    Removed(TableRow),
+}
+
+/// Changes between two bintables sorted by key, in the order of their keys.
+pub struct Diff<O: Iterator<Item = TableRow>, N: Iterator<Item = TableRow>> {
+    old: Peekable<Keys<O>>,
+    new: Peekable<Keys<N>>,
+    /// changes of the current key
+    changes: VecDeque<Change>,
+    failed: bool,
+}
+
+/// Merges the rows of two bintables sorted by key and returns the rows added to and removed from `old`.
+pub fn diff<O, N>(old: O, new: N) -> Diff<O::IntoIter, N::IntoIter>
+where
+    O: IntoIterator<Item = TableRow>,
+    N: IntoIterator<Item = TableRow>,
+{
+    Diff {
+        old: Keys::new(old.into_iter()),
+        new: Keys::new(new.into_iter()),
+        changes: VecDeque::new(),
+        failed: false,
+    }
+}
+
+impl<O, N> Iterator for Diff<O, N>
+where
+    O: Iterator<Item = TableRow>,
+    N: Iterator<Item = TableRow>,
+{
+    type Item = Result<Change, Unsorted>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.changes.is_empty() {
+            if self.failed {
+                return None;
+            }
+
+            // keys missing on one side sort last, so the other side is drained
+            let order = match (key(&mut self.old), key(&mut self.new)) {
+                (Err(unsorted), _) | (_, Err(unsorted)) => {
+                    self.failed = true;
+                    return Some(Err(unsorted));
+                }
+                (Ok(None), Ok(None)) => return None,
+                (Ok(Some(_)), Ok(None)) => Ordering::Less,
+                (Ok(None), Ok(Some(_))) => Ordering::Greater,
+                (Ok(Some(old)), Ok(Some(new))) => old.cmp(new),
+            };
+
+            let (old, new) = match order {
+                Ordering::Less => (self.old.next(), None),
+                Ordering::Greater => (None, self.new.next()),
+                Ordering::Equal => (self.old.next(), self.new.next()),
+            };
+            let rows = |rows: Option<Result<_, _>>| rows.and_then(Result::ok).unwrap_or_default();
+
+            let (removed, _, added) = split(rows(old), rows(new));
+            self.changes
+                .extend(removed.into_iter().map(Change::Removed));
+            self.changes.extend(added.into_iter().map(Change::Added));
+        }
+
+        self.changes.pop_front().map(Ok)
+    }
+}
+
+/// Rows of a base bintable with a diff applied, sorted by key.
+pub struct Patch<B, A, R>
+where
+    B: Iterator<Item = TableRow>,
+    A: Iterator<Item = TableRow>,
+    R: Iterator<Item = TableRow>,
+{
+    base: Peekable<Keys<B>>,
+    added: Peekable<Keys<A>>,
+    removed: Peekable<Keys<R>>,
+    /// rows of the current key
+    rows: VecDeque<TableRow>,
+    missing: usize,
+    failed: bool,
+}
+
+/// Applies the `added` and `removed` rows of a diff to `base`, all sorted by key.
+/// Applying the diff of two bintables to the old one results in the rows of the new one.
+pub fn apply<B, A, R>(base: B, added: A, removed: R) -> Patch<B::IntoIter, A::IntoIter, R::IntoIter>
+where
+    B: IntoIterator<Item = TableRow>,
+    A: IntoIterator<Item = TableRow>,
+    R: IntoIterator<Item = TableRow>,
+{
+    Patch {
+        base: Keys::new(base.into_iter()),
+        added: Keys::new(added.into_iter()),
+        removed: Keys::new(removed.into_iter()),
+        rows: VecDeque::new(),
+        missing: 0,
+        failed: false,
+    }
+}
+
+impl<B, A, R> Patch<B, A, R>
+where
+    B: Iterator<Item = TableRow>,
+    A: Iterator<Item = TableRow>,
+    R: Iterator<Item = TableRow>,
+{
+    /// Number of removed rows, which were not in the base bintable so far
+    pub fn missing(&self) -> usize {
+        self.missing
+    }
+}
+
+impl<B, A, R> Iterator for Patch<B, A, R>
+where
+    B: Iterator<Item = TableRow>,
+    A: Iterator<Item = TableRow>,
+    R: Iterator<Item = TableRow>,
+{
+    type Item = Result<TableRow, Unsorted>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.rows.is_empty() {
+            if self.failed {
+                return None;
+            }
+
+            let keys = [
+                key(&mut self.base),
+                key(&mut self.added),
+                key(&mut self.removed),
+            ];
+            let key = match keys.into_iter().collect::<Result<Vec<_>, _>>() {
+                Ok(keys) => keys.into_iter().flatten().min()?.to_string(),
+                Err(unsorted) => {
+                    self.failed = true;
+                    return Some(Err(unsorted));
+                }
+            };
+
+            let base = rows_of(&mut self.base, &key);
+            let added = rows_of(&mut self.added, &key);
+            let removed = rows_of(&mut self.removed, &key);
+
+            let (kept, _, missing) = split(base, removed);
+            self.missing += missing.len();
+
+            let mut rows = kept;
+            rows.extend(added);
+            rows.sort_unstable();
+
+            self.rows.extend(rows);
+        }
+
+        self.rows.pop_front().map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(key: &str, tableid: u32, rowid: u32) -> TableRow {
+        TableRow {
+            tokenized: key.to_string(),
+            tableid,
+            colid: 0,
+            rowid,
+        }
+    }
+
+    fn old() -> Vec<TableRow> {
+        vec![
+            row("a", 1, 0),
+            row("b", 1, 1),
+            row("b", 2, 0),
+            row("c", 1, 2),
+            row("e", 3, 0),
+        ]
+    }
+
+    fn new() -> Vec<TableRow> {
+        // locations of a key do not need to be sorted
+        vec![
+            row("a", 1, 0),
+            row("b", 4, 0),
+            row("b", 2, 0),
+            row("d", 4, 1),
+            row("e", 3, 0),
+            row("f", 4, 2),
+        ]
+    }
+
+    #[test]
+    fn changes() {
+        let changes = diff(old(), new()).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            changes,
+            [
+                Change::Removed(row("b", 1, 1)),
+                Change::Added(row("b", 4, 0)),
+                Change::Removed(row("c", 1, 2)),
+                Change::Added(row("d", 4, 1)),
+                Change::Added(row("f", 4, 2)),
+            ]
+        );
+
+        assert_eq!(diff(old(), old()).count(), 0);
+        assert_eq!(diff(Vec::new(), old()).count(), old().len());
+    }
+
+    fn split_changes(
+        changes: impl Iterator<Item = Result<Change, Unsorted>>,
+    ) -> (Vec<TableRow>, Vec<TableRow>) {
+        let (mut added, mut removed) = (Vec::new(), Vec::new());
+        for change in changes {
+            match change.unwrap() {
+                Change::Added(row) => added.push(row),
+                Change::Removed(row) => removed.push(row),
+            }
+        }
+        (added, removed)
+    }
+
+    #[test]
+    fn apply_diff() {
+        let (added, removed) = split_changes(diff(old(), new()));
+
+        let mut patch = apply(old(), added.clone(), removed.clone());
+        let patched = patch.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+        let mut expected = new();
+        expected.sort();
+        assert_eq!(patched, expected);
+        assert_eq!(patch.missing(), 0);
+
+        // the removed rows are not in the new bintable
+        let mut patch = apply(new(), Vec::new(), removed);
+        assert_eq!(patch.by_ref().count(), new().len());
+        assert_eq!(patch.missing(), 2);
+    }
+
+    #[test]
+    fn duplicate_rows() {
+        let old = vec![row("a", 1, 0), row("a", 1, 0), row("b", 1, 0)];
+        let new = vec![row("a", 1, 0), row("b", 1, 0), row("b", 1, 0)];
+
+        let (added, removed) = split_changes(diff(old.clone(), new.clone()));
+        assert_eq!(added, [row("b", 1, 0)]);
+        assert_eq!(removed, [row("a", 1, 0)]);
+        let patched = apply(old, added, removed).collect::<Result<Vec<_>, _>>();
+        assert_eq!(patched.unwrap(), new);
+    }
+
+    #[test]
+    fn unsorted_keys() {
+        let unsorted = vec![
+            row("b", 0, 0),
+            row("c", 0, 0),
+            row("a", 0, 0),
+            row("d", 0, 0),
+        ];
+        let error = Unsorted {
+            key: "a".to_string(),
+            previous: "c".to_string(),
+        };
+
+        // the changes of the keys before are returned, nothing after the error
+        let changes = diff(unsorted.clone(), Vec::new()).collect::<Vec<_>>();
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[2], Err(error.clone()));
+
+        let rows = apply(old(), unsorted.clone(), Vec::new()).collect::<Vec<_>>();
+        assert_eq!(rows.last(), Some(&Err(error.clone())));
+        assert_eq!(rows.iter().filter(|r| r.is_err()).count(), 1);
+
+        assert_eq!(first_unsorted(unsorted), Some(error));
+        assert_eq!(first_unsorted(old()), None);
+    }
+
+    #[test]
+    fn sorted_bintables() {
+        let path = std::env::temp_dir().join(format!("diff-sorted-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        for (rows, sorted) in [
+            (old(), true),
+            (new(), true),
+            (vec![row("b", 0, 0), row("a", 0, 0)], false),
+        ] {
+            let mut out = crate::writer::BinTableWriter::create(path).unwrap();
+            for row in &rows {
+                out.write(row).unwrap();
+            }
+            out.finish().unwrap();
+
+            match check_sorted(path) {
+                Ok(()) => assert!(sorted),
+                Err(e) => assert_eq!(e.downcast::<Unsorted>().unwrap().key, "a"),
+            }
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,71 @@
+use std::fmt;
+
+/// Damage found while reading a bintable, at a byte offset of the file.
+#[derive(Debug)]
+pub enum BinTableError {
+    Io(std::io::Error),
+    /// the file ends inside of a record, or after rows that were not closed by a checksum record
+    Truncated {
+        offset: u64,
+    },
+    /// a record at `offset` can not be decoded
+    Malformed {
+        offset: u64,
+        reason: &'static str,
+    },
+    /// the rows of the block starting at `offset` do not match the checksum record closing it
+    Checksum {
+        offset: u64,
+        expected: u32,
+        actual: u32,
+    },
+}
+
+impl BinTableError {
+    /// Offset of the damaged record or block
+    pub fn offset(&self) -> Option<u64> {
+        match self {
+            BinTableError::Io(_) => None,
+            BinTableError::Truncated { offset }
+            | BinTableError::Malformed { offset, .. }
+            | BinTableError::Checksum { offset, .. } => Some(*offset),
+        }
+    }
+}
+
+impl fmt::Display for BinTableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BinTableError::Io(e) => write!(f, "failed to read bintable: {e}"),
+            BinTableError::Truncated { offset } => {
+                write!(f, "bintable truncated at byte {offset}")
+            }
+            BinTableError::Malformed { offset, reason } => {
+                write!(f, "malformed record at byte {offset}: {reason}")
+            }
+            BinTableError::Checksum {
+                offset,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "checksum mismatch of the block at byte {offset}: expected {expected:08x}, got {actual:08x}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BinTableError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BinTableError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for BinTableError {
+    fn from(e: std::io::Error) -> Self {
+        BinTableError::Io(e)
+    }
+}
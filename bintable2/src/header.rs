@@ -0,0 +1,152 @@
+use crate::tablerow::{Kind, ReadError};
+use crate::tokenizer::Tokenizer;
+use anyhow::{bail, Result};
+use std::io::Write;
+use varint_compression::*;
+
+/// Describes how a bintable was created. It is written in front of the first row.
+/// Bintables without a header are still read, their header is empty.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Header {
+    /// tokenizers applied to the keys, in the order they were applied in
+    pub tokenizers: Vec<Tokenizer>,
+}
+
+impl Header {
+    pub fn is_empty(&self) -> bool {
+        self.tokenizers.is_empty()
+    }
+
+    fn encode(&self) -> String {
+        /*
+            Scheme: one <name>=<value> line per entry
+            readers skip names they don't know.
+        */
+        self.tokenizers
+            .iter()
+            .map(|t| format!("tokenizer={t}\n"))
+            .collect()
+    }
+
+    fn decode(s: &str) -> Result<Self> {
+        let mut header = Header::default();
+        for line in s.lines() {
+            let Some((name, value)) = line.split_once('=') else {
+                bail!("header entry without value: {line:?}");
+            };
+
+            if name == "tokenizer" {
+                header
+                    .tokenizers
+                    .push(value.parse().map_err(anyhow::Error::msg)?);
+            }
+        }
+
+        Ok(header)
+    }
+
+    /// Writes the header, framed like a row: <total length> <kind> <length> <entries>
+    pub fn write_bin(&self, w: &mut impl Write) -> Result<()> {
+        let entries = self.encode();
+        let len = compress(entries.len() as u64);
+        let total_length = compress((1 + len.len() + entries.len()) as u64);
+
+        w.write_all(&total_length)?;
+        w.write_all(&[Kind::Header.byte()])?;
+        w.write_all(&len)?;
+        w.write_all(entries.as_bytes())?;
+        Ok(())
+    }
+
+    /// Reads a header from the start of `data`, `None` if it starts with a row instead.
+    pub fn from_bin(data: &[u8]) -> Result<Option<(Self, &[u8])>, ReadError> {
+        let (need_length, rest) = decompress(data).map_err(|_| ReadError::InitialNumber)?;
+        let need_length = need_length as usize;
+        if need_length == 0 {
+            return Err(ReadError::Invalid("empty record"));
+        }
+
+        if rest.is_empty() {
+            return Err(ReadError::Needed(need_length));
+        }
+        if rest[0] != Kind::Header.byte() {
+            return Ok(None);
+        }
+        if rest.len() < need_length {
+            return Err(ReadError::Needed(need_length - rest.len()));
+        }
+
+        Ok(Some((
+            Header::from_bin_raw(&rest[1..need_length])?,
+            &rest[need_length..],
+        )))
+    }
+
+    /// Reads the entries following the kind byte
+    pub(crate) fn from_bin_raw(data: &[u8]) -> Result<Self, ReadError> {
+        let (len, data) = decompress(data).map_err(|_| ReadError::Invalid("header length"))?;
+        let entries = data
+            .get(..len as usize)
+            .ok_or(ReadError::Invalid("header longer than its record"))?;
+        let entries =
+            std::str::from_utf8(entries).map_err(|_| ReadError::Invalid("header is not utf-8"))?;
+        Header::decode(entries).map_err(|_| ReadError::Invalid("header entries"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BinTable, ParseAcc, TableRow};
+
+    #[test]
+    fn header_in_front_of_rows() {
+        let header = Header {
+            tokenizers: vec![
+                "lowercase,words".parse().unwrap(),
+                "numbers,max-length=16".parse().unwrap(),
+            ],
+        };
+        let rows = (0..2000u32)
+            .map(|i| TableRow {
+                tokenized: format!("{}", i / 3),
+                tableid: i,
+                colid: 1,
+                rowid: 2,
+            })
+            .collect::<Vec<_>>();
+
+        let mut data = Vec::new();
+        header.write_bin(&mut data).unwrap();
+        let mut acc = ParseAcc::default();
+        for row in &rows {
+            row.write_bin(&mut data, &mut acc).unwrap();
+        }
+        acc.finish(&mut data).unwrap();
+
+        let (read, rest) = Header::from_bin(&data).unwrap().unwrap();
+        assert_eq!(read, header);
+        assert_eq!(Header::from_bin(rest).unwrap(), None);
+
+        let path = std::env::temp_dir().join(format!("bintable2-header-{}", std::process::id()));
+        std::fs::write(&path, &data).unwrap();
+        let table = BinTable::open(path.to_str().unwrap()).unwrap();
+        assert_eq!(table.header(), &header);
+        assert_eq!(table.collect::<Vec<_>>(), rows);
+
+        // tables without a header
+        std::fs::write(&path, &data[data.len() - rest.len()..]).unwrap();
+        let table = BinTable::open(path.to_str().unwrap()).unwrap();
+        assert!(table.header().is_empty());
+        assert_eq!(table.collect::<Vec<_>>(), rows);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn unknown_entries_are_skipped() {
+        let header = Header::decode("tokenizer=nfkc\nwritten_by=someone\n").unwrap();
+        assert_eq!(header.tokenizers, ["nfkc".parse().unwrap()]);
+        assert!(Header::decode("tokenizer").is_err());
+    }
+}
@@ -1,8 +1,31 @@
+mod asynctable;
 mod bintable;
+mod decoder;
+mod diff;
+mod error;
+mod header;
+mod metadata;
+mod sampling;
+mod sorter;
+mod stats;
 mod tablerow;
+mod tokenizer;
 mod util;
+mod writer;
 
+pub use asynctable::AsyncBinTable;
 pub use bintable::BinTable;
 pub use bintable::BinTableSampler;
+pub use diff::{apply, check_sorted, diff, first_unsorted, Change, Diff, Patch, Unsorted};
+pub use error::BinTableError;
+pub use header::Header;
+pub use metadata::{Column, Metadata, MetadataBuilder, Table};
+pub use sampling::{SampleMode, Sampling};
+pub use sorter::{RunSorter, SortedRows};
+pub use stats::{Sections, Stats};
 pub use tablerow::ParseAcc;
 pub use tablerow::TableRow;
+pub use tokenizer::Tokenizer;
+pub use writer::BinTableWriter;
+
+pub use fsst::SymbolTable;
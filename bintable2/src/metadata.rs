@@ -0,0 +1,294 @@
+use crate::tablerow::TableRow;
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashSet;
+use std::io::Write;
+use varint_compression::*;
+
+/// Names and statistics of the tables of a bintable, stored beside it in `<bintable>.meta`.
+/// The position of a table is its table id, the position of a column its column id.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    pub tables: Vec<Table>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Table {
+    pub name: String,
+    /// file or database table the table was read from
+    pub source: String,
+    pub rows: u32,
+    pub columns: Vec<Column>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Column {
+    /// header of the column
+    pub name: String,
+    /// number of keys in the column
+    pub values: u64,
+    /// number of distinct keys in the column
+    pub distinct: u64,
+}
+
+impl Metadata {
+    /// Path of the metadata of a bintable
+    pub fn path(bintable: &str) -> String {
+        format!("{bintable}.meta")
+    }
+
+    /// Reads the metadata of a bintable, `None` if it has none.
+    pub fn open(bintable: &str) -> Result<Option<Metadata>> {
+        let path = Metadata::path(bintable);
+        if !std::path::Path::new(&path).exists() {
+            return Ok(None);
+        }
+
+        let data = std::fs::read(&path).with_context(|| format!("read {path}"))?;
+        Metadata::from_bin(&data)
+            .with_context(|| format!("parse {path}"))
+            .map(Some)
+    }
+
+    pub fn table(&self, tableid: u32) -> Option<&Table> {
+        self.tables.get(tableid as usize)
+    }
+
+    pub fn column(&self, tableid: u32, colid: u32) -> Option<&Column> {
+        self.table(tableid)?.columns.get(colid as usize)
+    }
+
+    /// Human readable location of a cell, like `cities.csv, column "country", row 5`.
+    /// Ids are used instead of unknown names.
+    pub fn describe(&self, tableid: u32, colid: u32, rowid: u32) -> String {
+        let table = match self.table(tableid) {
+            Some(t) if !t.source.is_empty() => t.source.clone(),
+            Some(t) if !t.name.is_empty() => t.name.clone(),
+            _ => format!("table {tableid}"),
+        };
+        let column = match self.column(tableid, colid) {
+            Some(c) if !c.name.is_empty() => format!("column {:?}", c.name),
+            _ => format!("column {colid}"),
+        };
+
+        format!("{table}, {column}, row {rowid}")
+    }
+
+    pub fn write_bin(&self, w: &mut impl Write) -> Result<()> {
+        /*
+            Scheme: <table count> { <name> <source> <rows> <column count> { <name> <values> <distinct values> } }
+            numbers are varints, strings are written as <length> <utf-8 bytes>
+        */
+        let mut out = compress(self.tables.len() as u64);
+        for table in &self.tables {
+            write_str(&mut out, &table.name);
+            write_str(&mut out, &table.source);
+            out.extend(compress(table.rows as u64));
+            out.extend(compress(table.columns.len() as u64));
+            for column in &table.columns {
+                write_str(&mut out, &column.name);
+                out.extend(compress_list(&[column.values, column.distinct]));
+            }
+        }
+
+        w.write_all(&out)?;
+        Ok(())
+    }
+
+    pub fn from_bin(mut data: &[u8]) -> Result<Metadata> {
+        let table_count = read_int(&mut data)?;
+        let mut tables = Vec::new();
+        for _ in 0..table_count {
+            let name = read_str(&mut data)?;
+            let source = read_str(&mut data)?;
+            let rows = read_int(&mut data)? as u32;
+
+            let column_count = read_int(&mut data)?;
+            let mut columns = Vec::new();
+            for _ in 0..column_count {
+                columns.push(Column {
+                    name: read_str(&mut data)?,
+                    values: read_int(&mut data)?,
+                    distinct: read_int(&mut data)?,
+                });
+            }
+
+            tables.push(Table {
+                name,
+                source,
+                rows,
+                columns,
+            });
+        }
+
+        Ok(Metadata { tables })
+    }
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    out.extend(compress(s.len() as u64));
+    out.extend(s.as_bytes());
+}
+
+fn read_int(data: &mut &[u8]) -> Result<u64> {
+    let (v, rest) = decompress(data).map_err(|_| anyhow!("truncated number"))?;
+    *data = rest;
+    Ok(v)
+}
+
+fn read_str(data: &mut &[u8]) -> Result<String> {
+    let len = read_int(data)? as usize;
+    if data.len() < len {
+        return Err(anyhow!("truncated string"));
+    }
+
+    let (s, rest) = data.split_at(len);
+    *data = rest;
+    Ok(String::from_utf8(s.to_vec())?)
+}
+
+/// Collects the statistics of the rows of a bintable, in the order of the bintable.
+#[derive(Debug, Default)]
+pub struct MetadataBuilder {
+    metadata: Metadata,
+    last_tokenized: Option<String>,
+    /// columns the current key was already counted in
+    seen: HashSet<(u32, u32)>,
+}
+
+impl MetadataBuilder {
+    /// Keeps the names of the tables and columns, the statistics are collected again.
+    pub fn with_names(names: &Metadata) -> Self {
+        let mut metadata = names.clone();
+        for table in &mut metadata.tables {
+            table.rows = 0;
+            for column in &mut table.columns {
+                column.values = 0;
+                column.distinct = 0;
+            }
+        }
+
+        MetadataBuilder {
+            metadata,
+            ..Default::default()
+        }
+    }
+
+    /// Names a table and its columns, names of columns behind `columns` are dropped.
+    /// `rows` also counts rows without any key, which are never added.
+    pub fn name(&mut self, tableid: u32, name: &str, source: &str, columns: &[String], rows: u32) {
+        let table = self.table(tableid);
+        table.name = name.to_string();
+        table.source = source.to_string();
+        table.rows = table.rows.max(rows);
+        table.columns.truncate(columns.len());
+
+        for (colid, name) in columns.iter().enumerate() {
+            self.column(tableid, colid as u32).name = name.clone();
+        }
+    }
+
+    /// Rows have to be added sorted by key, like they are stored in a bintable.
+    pub fn add(&mut self, row: &TableRow) {
+        if self.last_tokenized.as_ref() != Some(&row.tokenized) {
+            self.last_tokenized = Some(row.tokenized.clone());
+            self.seen.clear();
+        }
+
+        let table = self.table(row.tableid);
+        table.rows = table.rows.max(row.rowid + 1);
+
+        let first = self.seen.insert((row.tableid, row.colid));
+        let column = self.column(row.tableid, row.colid);
+        column.values += 1;
+        if first {
+            column.distinct += 1;
+        }
+    }
+
+    pub fn finish(self) -> Metadata {
+        self.metadata
+    }
+
+    fn table(&mut self, tableid: u32) -> &mut Table {
+        let tables = &mut self.metadata.tables;
+        if tables.len() <= tableid as usize {
+            tables.resize(tableid as usize + 1, Table::default());
+        }
+
+        &mut tables[tableid as usize]
+    }
+
+    fn column(&mut self, tableid: u32, colid: u32) -> &mut Column {
+        let columns = &mut self.table(tableid).columns;
+        if columns.len() <= colid as usize {
+            columns.resize(colid as usize + 1, Column::default());
+        }
+
+        &mut columns[colid as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(tokenized: &str, tableid: u32, colid: u32, rowid: u32) -> TableRow {
+        TableRow {
+            tokenized: tokenized.to_string(),
+            tableid,
+            colid,
+            rowid,
+        }
+    }
+
+    #[test]
+    fn statistics() {
+        let mut builder = MetadataBuilder::default();
+        builder.name(
+            1,
+            "cities",
+            "lake/cities.csv",
+            &["city".to_string(), "country".to_string()],
+            4,
+        );
+
+        let mut rows = vec![
+            row("berlin", 1, 0, 0),
+            row("hamburg", 1, 0, 1),
+            row("germany", 1, 1, 0),
+            row("germany", 1, 1, 1),
+            row("germany", 0, 2, 7),
+        ];
+        rows.sort();
+        for r in &rows {
+            builder.add(r);
+        }
+        let metadata = builder.finish();
+
+        assert_eq!(metadata.tables.len(), 2);
+        assert_eq!(metadata.tables[0].rows, 8);
+        assert_eq!(metadata.tables[0].columns.len(), 3);
+        assert_eq!(metadata.tables[1].rows, 4);
+
+        let country = metadata.column(1, 1).unwrap();
+        assert_eq!((country.values, country.distinct), (2, 1));
+        let city = metadata.column(1, 0).unwrap();
+        assert_eq!((city.values, city.distinct), (2, 2));
+
+        assert_eq!(
+            metadata.describe(1, 1, 3),
+            "lake/cities.csv, column \"country\", row 3"
+        );
+        assert_eq!(metadata.describe(0, 2, 7), "table 0, column 2, row 7");
+        assert_eq!(metadata.describe(5, 0, 0), "table 5, column 0, row 0");
+
+        let mut data = Vec::new();
+        metadata.write_bin(&mut data).unwrap();
+        assert_eq!(Metadata::from_bin(&data).unwrap(), metadata);
+        assert!(Metadata::from_bin(&data[..data.len() - 1]).is_err());
+
+        let renewed = MetadataBuilder::with_names(&metadata).finish();
+        assert_eq!(renewed.column(1, 1).unwrap().name, "country");
+        assert_eq!(renewed.column(1, 1).unwrap().values, 0);
+    }
+}
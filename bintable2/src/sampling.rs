@@ -0,0 +1,204 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// What a sample is drawn of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleMode {
+    /// every row on its own (Bernoulli sampling)
+    Row,
+    /// keep or drop entire posting lists
+    Key,
+    /// keep or drop entire tables
+    Table,
+}
+
+impl SampleMode {
+    pub fn str(self) -> &'static str {
+        match self {
+            SampleMode::Row => "row",
+            SampleMode::Key => "key",
+            SampleMode::Table => "table",
+        }
+    }
+}
+
+impl FromStr for SampleMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "row" => Ok(SampleMode::Row),
+            "key" => Ok(SampleMode::Key),
+            "table" => Ok(SampleMode::Table),
+            _ => Err(String::from("allowed: row key table")),
+        }
+    }
+}
+
+impl fmt::Display for SampleMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.str())
+    }
+}
+
+/// Deterministic sampling: a row is part of the sample, if its seeded hash is below a threshold.
+///
+/// The same mode, factor and seed always select the same rows, independent of the order they are read in.
+/// Samples of the same mode and seed are nested, a sample of a smaller factor is part of every
+/// sample of a greater factor. So smaller samples can be drawn from greater ones, without changing the factor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sampling {
+    pub mode: SampleMode,
+    pub factor: f32,
+    pub seed: u64,
+}
+
+impl Sampling {
+    pub fn new(mode: SampleMode, factor: f32, seed: u64) -> Self {
+        assert!(
+            factor > 0.0 && factor <= 1.0,
+            "factor must be greater than 0 and at most 1"
+        );
+
+        Sampling { mode, factor, seed }
+    }
+
+    /// Whether a row is part of the sample
+    pub fn keeps(&self, tokenized: &str, tableid: u32, colid: u32, rowid: u32) -> bool {
+        if self.factor >= 1.0 {
+            return true;
+        }
+
+        let hash = match self.mode {
+            SampleMode::Row => {
+                let mut h = Hasher::new(self.seed);
+                h.write(tokenized.as_bytes());
+                // the ids have a fixed length, so rows can't collide by shifting bytes between the fields
+                h.write(&tableid.to_le_bytes());
+                h.write(&colid.to_le_bytes());
+                h.write(&rowid.to_le_bytes());
+                h.finish()
+            }
+            SampleMode::Key => {
+                let mut h = Hasher::new(self.seed);
+                h.write(tokenized.as_bytes());
+                h.finish()
+            }
+            SampleMode::Table => {
+                let mut h = Hasher::new(self.seed);
+                h.write(&tableid.to_le_bytes());
+                h.finish()
+            }
+        };
+
+        hash < self.threshold()
+    }
+
+    fn threshold(&self) -> u64 {
+        (self.factor as f64 * 2f64.powi(64)) as u64
+    }
+
+    /// Name of a bintable containing this sample of `corpus`
+    pub fn subset_name(&self, corpus: &str) -> String {
+        format!("{corpus}-{}-{}-{}", self.mode, self.seed, self.factor)
+    }
+
+    /// Parses a name created by `subset_name`, returns the corpus and the sampling.
+    pub fn parse_subset_name(name: &str) -> Option<(&str, Sampling)> {
+        let mut parts = name.rsplitn(4, '-');
+        let factor = parts.next()?.parse().ok()?;
+        let seed = parts.next()?.parse().ok()?;
+        let mode = parts.next()?.parse().ok()?;
+        let corpus = parts.next()?;
+
+        Some((corpus, Sampling { mode, factor, seed }))
+    }
+}
+
+impl fmt::Display for Sampling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} (seed {})", self.mode, self.factor, self.seed)
+    }
+}
+
+/// FNV-1a, finalized by the splitmix64 mixer.
+/// Unlike the hashers of std, it is stable across platforms and releases.
+struct Hasher(u64);
+
+impl Hasher {
+    fn new(seed: u64) -> Self {
+        Hasher(0xcbf29ce484222325 ^ seed.wrapping_mul(0x9e3779b97f4a7c15))
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kept(sampling: Sampling) -> Vec<(u32, u32)> {
+        (0..100u32)
+            .flat_map(|key| (0..100u32).map(move |table| (key, table)))
+            .filter(|(key, table)| sampling.keeps(&format!("key-{key}"), *table, 0, 0))
+            .collect()
+    }
+
+    #[test]
+    fn deterministic_and_nested() {
+        for mode in [SampleMode::Row, SampleMode::Key, SampleMode::Table] {
+            let small = kept(Sampling::new(mode, 0.1, 7));
+            let large = kept(Sampling::new(mode, 0.5, 7));
+
+            assert_eq!(small, kept(Sampling::new(mode, 0.1, 7)));
+            assert_ne!(small, kept(Sampling::new(mode, 0.1, 8)));
+            assert!(small.iter().all(|row| large.contains(row)));
+
+            // every row has its own key and table, so roughly 10% of them are kept in every mode
+            let sampling = Sampling::new(mode, 0.1, 7);
+            let n = (0..10000u32)
+                .filter(|i| sampling.keeps(&format!("key-{i}"), *i, 0, *i))
+                .count();
+            assert!((900..1100).contains(&n), "{mode}: {n}");
+        }
+    }
+
+    #[test]
+    fn modes_keep_whole_groups() {
+        let by_key = kept(Sampling::new(SampleMode::Key, 0.3, 1));
+        let by_table = kept(Sampling::new(SampleMode::Table, 0.3, 1));
+
+        for key in 0..100 {
+            let n = by_key.iter().filter(|(k, _)| *k == key).count();
+            assert!(n == 0 || n == 100, "posting list of key-{key} is split");
+        }
+        for table in 0..100 {
+            let n = by_table.iter().filter(|(_, t)| *t == table).count();
+            assert!(n == 0 || n == 100, "table {table} is split");
+        }
+    }
+
+    #[test]
+    fn subset_names() {
+        let sampling = Sampling::new(SampleMode::Key, 0.25, 42);
+        let name = sampling.subset_name("main-tokenized");
+        assert_eq!(name, "main-tokenized-key-42-0.25");
+        assert_eq!(
+            Sampling::parse_subset_name(&name),
+            Some(("main-tokenized", sampling))
+        );
+        assert_eq!(Sampling::parse_subset_name("main_tokenized-0.5"), None);
+    }
+}
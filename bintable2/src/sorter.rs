@@ -0,0 +1,275 @@
+//! Sorts more rows than fit into memory. Rows are sorted in runs of bounded size,
+//! full runs are written to temporary bintables, which are merged once all rows were pushed.
+
+use crate::bintable::BinTable;
+use crate::error::BinTableError;
+use crate::tablerow::TableRow;
+use crate::writer::BinTableWriter;
+use anyhow::Result;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+type Run = Box<dyn Iterator<Item = Result<TableRow, BinTableError>>>;
+
+/// Runs merged at once, every open run takes a file descriptor and a read buffer.
+const MAX_MERGE: usize = 64;
+
+/// Sorts rows and removes duplicates, keeping at most `run_rows` rows in memory.
+/// Runs are written beside `prefix` as `<prefix>.run-<n>` and removed again once they are merged or dropped.
+pub struct RunSorter {
+    prefix: String,
+    run_rows: usize,
+    rows: Vec<TableRow>,
+    /// runs not merged yet
+    runs: Vec<String>,
+    written: usize,
+}
+
+impl RunSorter {
+    pub fn new(prefix: &str, run_rows: usize) -> Self {
+        RunSorter {
+            prefix: prefix.to_string(),
+            run_rows: run_rows.max(1),
+            rows: Vec::new(),
+            runs: Vec::new(),
+            written: 0,
+        }
+    }
+
+    pub fn push(&mut self, row: TableRow) -> Result<()> {
+        self.rows.push(row);
+        if self.rows.len() >= self.run_rows {
+            self.rows.sort_unstable();
+            self.rows.dedup();
+            let rows = std::mem::take(&mut self.rows);
+            self.write_run(rows.into_iter().map(Ok))?;
+        }
+        Ok(())
+    }
+
+    /// Number of runs written so far
+    pub fn runs(&self) -> usize {
+        self.written
+    }
+
+    fn write_run(
+        &mut self,
+        rows: impl Iterator<Item = Result<TableRow, BinTableError>>,
+    ) -> Result<()> {
+        let path = format!("{}.run-{}", self.prefix, self.written);
+        self.written += 1;
+        // removed by drop, even if writing fails
+        self.runs.push(path.clone());
+
+        let mut out = BinTableWriter::create(&path)?.sorted();
+        for row in rows {
+            out.write(&row?)?;
+        }
+        out.finish()?;
+        Ok(())
+    }
+
+    /// All rows in order, without duplicates.
+    pub fn finish(mut self) -> Result<SortedRows> {
+        // the oldest runs are merged first, until the rest can be merged at once
+        while self.runs.len() >= MAX_MERGE {
+            let merged = self.runs[..MAX_MERGE].to_vec();
+            let rows = Merge::open(&merged, Vec::new())?;
+            self.write_run(rows)?;
+
+            remove(&merged);
+            self.runs.drain(..MAX_MERGE);
+        }
+
+        let mut rows = std::mem::take(&mut self.rows);
+        rows.sort_unstable();
+        rows.dedup();
+
+        let merge = Merge::open(&self.runs, rows)?;
+        Ok(SortedRows {
+            merge,
+            files: std::mem::take(&mut self.runs),
+        })
+    }
+}
+
+impl Drop for RunSorter {
+    fn drop(&mut self) {
+        remove(&self.runs);
+    }
+}
+
+/// Rows of a `RunSorter` in order. The runs are removed once it is dropped.
+pub struct SortedRows {
+    merge: Merge,
+    files: Vec<String>,
+}
+
+impl Iterator for SortedRows {
+    type Item = Result<TableRow, BinTableError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.merge.next()
+    }
+}
+
+impl Drop for SortedRows {
+    fn drop(&mut self) {
+        remove(&self.files);
+    }
+}
+
+/// Merges sorted runs, rows occurring in several runs are returned once.
+struct Merge {
+    runs: Vec<Run>,
+    /// next row of every run that is not exhausted yet
+    heads: BinaryHeap<Reverse<(TableRow, usize)>>,
+    damage: Option<BinTableError>,
+    last: Option<TableRow>,
+}
+
+impl Merge {
+    fn open(paths: &[String], rows: Vec<TableRow>) -> Result<Self> {
+        let mut runs = Vec::<Run>::with_capacity(paths.len() + 1);
+        for path in paths {
+            let mut table = BinTable::open(path)?;
+            runs.push(Box::new(std::iter::from_fn(move || table.try_next())));
+        }
+        runs.push(Box::new(rows.into_iter().map(Ok)));
+
+        let mut merge = Merge {
+            runs,
+            heads: BinaryHeap::new(),
+            damage: None,
+            last: None,
+        };
+        for run in 0..merge.runs.len() {
+            merge.advance(run);
+        }
+        Ok(merge)
+    }
+
+    fn advance(&mut self, run: usize) {
+        match self.runs[run].next() {
+            Some(Ok(row)) => self.heads.push(Reverse((row, run))),
+            Some(Err(e)) => self.damage = Some(e),
+            None => {}
+        }
+    }
+}
+
+impl Iterator for Merge {
+    type Item = Result<TableRow, BinTableError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(damage) = self.damage.take() {
+                self.heads.clear();
+                return Some(Err(damage));
+            }
+
+            let Reverse((row, run)) = self.heads.pop()?;
+            self.advance(run);
+
+            if self.last.as_ref() != Some(&row) {
+                self.last = Some(row.clone());
+                return Some(Ok(row));
+            }
+        }
+    }
+}
+
+fn remove(files: &[String]) {
+    for path in files {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prefix(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("sorter-{name}-{}", std::process::id()));
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn runs_are_merged() {
+        let prefix = prefix("merged");
+        let mut sorter = RunSorter::new(&prefix, 1000);
+
+        // every row occurs twice, in different runs
+        let mut expected = Vec::new();
+        for i in (0..5000u32).chain(0..5000) {
+            let row = TableRow {
+                tokenized: format!("key-{:05}", (i * 7919) % 5000 / 3),
+                tableid: i % 3,
+                colid: 0,
+                rowid: (i * 7919) % 5000,
+            };
+            expected.push(row.clone());
+            sorter.push(row).unwrap();
+        }
+        expected.sort_unstable();
+        expected.dedup();
+        assert_eq!(sorter.runs(), 10);
+
+        let sorted = sorter.finish().unwrap();
+        let rows = sorted.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(rows, expected);
+
+        for run in 0..10 {
+            assert!(!std::path::Path::new(&format!("{prefix}.run-{run}")).exists());
+        }
+    }
+
+    #[test]
+    fn more_runs_than_merged_at_once() {
+        let prefix = prefix("rounds");
+        let mut sorter = RunSorter::new(&prefix, 10);
+        for i in (0..3000u32).rev() {
+            let row = TableRow {
+                tokenized: format!("key-{:04}", i % 1500),
+                tableid: 0,
+                colid: 0,
+                rowid: i / 1500,
+            };
+            sorter.push(row).unwrap();
+        }
+        assert_eq!(sorter.runs(), 300);
+
+        let rows = sorter.finish().unwrap();
+        let rows = rows.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(rows.len(), 3000);
+        assert!(rows.windows(2).all(|w| w[0] < w[1]));
+
+        let left = std::fs::read_dir(std::env::temp_dir())
+            .unwrap()
+            .filter(|e| {
+                let name = e.as_ref().unwrap().file_name();
+                name.to_string_lossy()
+                    .starts_with(&format!("sorter-rounds-{}", std::process::id()))
+            })
+            .count();
+        assert_eq!(left, 0);
+    }
+
+    #[test]
+    fn without_runs() {
+        let mut sorter = RunSorter::new(&prefix("memory"), 1000);
+        for i in (0..10u32).rev() {
+            let row = TableRow {
+                tokenized: format!("key-{i}"),
+                tableid: 0,
+                colid: 0,
+                rowid: i,
+            };
+            sorter.push(row).unwrap();
+        }
+        assert_eq!(sorter.runs(), 0);
+
+        let rows = sorter.finish().unwrap().map(|r| r.unwrap().rowid);
+        assert_eq!(rows.collect::<Vec<_>>(), (0..10).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,199 @@
+use crate::tablerow::{frame, Kind, ReadError};
+use anyhow::{bail, Result};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use varint_compression::decompress;
+
+/// Bytes of a bintable, by what they encode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sections {
+    pub header: u64,
+    /// compressed keys along with their lengths
+    pub keys: u64,
+    /// fsst symbol table
+    pub symbols: u64,
+    /// tableid, colid and rowid of the rows
+    pub locations: u64,
+    /// lengths and kinds of the records
+    pub framing: u64,
+    /// checksum records
+    pub checksums: u64,
+}
+
+impl Sections {
+    pub fn total(&self) -> u64 {
+        self.header + self.keys + self.symbols + self.locations + self.framing + self.checksums
+    }
+}
+
+/// Statistics of a bintable, written behind the last row by `BinTableWriter`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub rows: u64,
+    /// runs of rows with the same key, the number of distinct keys if the rows are sorted
+    pub keys: u64,
+    /// whether the rows are sorted by key
+    pub sorted: bool,
+    pub max_tableid: u32,
+    pub max_colid: u32,
+    pub max_rowid: u32,
+    /// bytes of the bintable without these statistics
+    pub bytes: Sections,
+}
+
+impl Stats {
+    fn encode(&self) -> String {
+        /*
+            Scheme: one <name>=<value> line per entry, like the header
+            readers skip names they don't know.
+        */
+        let b = &self.bytes;
+        [
+            ("rows", self.rows.to_string()),
+            ("keys", self.keys.to_string()),
+            ("sorted", self.sorted.to_string()),
+            ("max_tableid", self.max_tableid.to_string()),
+            ("max_colid", self.max_colid.to_string()),
+            ("max_rowid", self.max_rowid.to_string()),
+            ("bytes_header", b.header.to_string()),
+            ("bytes_keys", b.keys.to_string()),
+            ("bytes_symbols", b.symbols.to_string()),
+            ("bytes_locations", b.locations.to_string()),
+            ("bytes_framing", b.framing.to_string()),
+            ("bytes_checksums", b.checksums.to_string()),
+        ]
+        .iter()
+        .map(|(name, value)| format!("{name}={value}\n"))
+        .collect()
+    }
+
+    fn decode(s: &str) -> Result<Self> {
+        let mut stats = Stats::default();
+        for line in s.lines() {
+            let Some((name, value)) = line.split_once('=') else {
+                bail!("statistics entry without value: {line:?}");
+            };
+
+            let b = &mut stats.bytes;
+            match name {
+                "rows" => stats.rows = value.parse()?,
+                "keys" => stats.keys = value.parse()?,
+                "sorted" => stats.sorted = value.parse()?,
+                "max_tableid" => stats.max_tableid = value.parse()?,
+                "max_colid" => stats.max_colid = value.parse()?,
+                "max_rowid" => stats.max_rowid = value.parse()?,
+                "bytes_header" => b.header = value.parse()?,
+                "bytes_keys" => b.keys = value.parse()?,
+                "bytes_symbols" => b.symbols = value.parse()?,
+                "bytes_locations" => b.locations = value.parse()?,
+                "bytes_framing" => b.framing = value.parse()?,
+                "bytes_checksums" => b.checksums = value.parse()?,
+                _ => {}
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// Writes the statistics, framed like a row: <total length> <kind> <entries> <crc32c of the entries>
+    /// <total length of the record as u32>, so they can be found from the end of the file.
+    pub fn write_bin(&self, w: &mut impl Write) -> Result<()> {
+        let entries = self.encode();
+        let mut body = entries.as_bytes().to_vec();
+        body.extend(crc32c::crc32c(entries.as_bytes()).to_le_bytes());
+
+        // the record including the length at its end
+        let length = frame(Kind::Stats, &[body.as_slice(), &[0; 4]].concat()).len();
+        body.extend((length as u32).to_le_bytes());
+        w.write_all(&frame(Kind::Stats, &body))?;
+        Ok(())
+    }
+
+    /// Reads the fields following the kind byte
+    pub(crate) fn from_bin_raw(data: &[u8]) -> Result<Self, ReadError> {
+        if data.len() < 8 {
+            return Err(ReadError::Invalid("statistics record too short"));
+        }
+        let (entries, rest) = data.split_at(data.len() - 8);
+        let crc = u32::from_le_bytes(rest[..4].try_into().unwrap());
+        if crc32c::crc32c(entries) != crc {
+            return Err(ReadError::Invalid("checksum of the statistics"));
+        }
+
+        let entries = std::str::from_utf8(entries)
+            .map_err(|_| ReadError::Invalid("statistics are not utf-8"))?;
+        Stats::decode(entries).map_err(|_| ReadError::Invalid("statistics entries"))
+    }
+
+    /// Reads the statistics at the end of a bintable without reading its rows, `None` if it has none.
+    pub fn open(bintable: &str) -> std::io::Result<Option<Stats>> {
+        let mut file = File::open(bintable)?;
+        let length = file.metadata()?.len();
+        if length < 4 {
+            return Ok(None);
+        }
+
+        let mut end = [0; 4];
+        file.seek(SeekFrom::End(-4))?;
+        file.read_exact(&mut end)?;
+        let record = u32::from_le_bytes(end) as u64;
+        // the length of the shortest and the longest statistics record
+        if !(10..=length.min(4096)).contains(&record) {
+            return Ok(None);
+        }
+
+        let mut data = vec![0; record as usize];
+        file.seek(SeekFrom::End(-(record as i64)))?;
+        file.read_exact(&mut data)?;
+
+        let stats = match decompress(&data) {
+            Ok((inner, rest))
+                if inner == rest.len() as u64 && rest.first() == Some(&Kind::Stats.byte()) =>
+            {
+                Stats::from_bin_raw(&rest[1..]).ok()
+            }
+            _ => None,
+        };
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn found_from_the_end() {
+        let stats = Stats {
+            rows: 123_456,
+            keys: 789,
+            sorted: true,
+            max_tableid: 7,
+            max_colid: 3,
+            max_rowid: u32::MAX,
+            bytes: Sections {
+                header: 20,
+                keys: 4000,
+                symbols: 0,
+                locations: 800_000,
+                framing: 250_000,
+                checksums: 1234,
+            },
+        };
+
+        let path = std::env::temp_dir().join(format!("bintable2-stats-{}", std::process::id()));
+        let mut data = vec![0x55; 1000];
+        stats.write_bin(&mut data).unwrap();
+        std::fs::write(&path, &data).unwrap();
+        assert_eq!(Stats::open(path.to_str().unwrap()).unwrap(), Some(stats));
+
+        // a file without statistics, or with damaged ones
+        data[1010] ^= 1;
+        std::fs::write(&path, &data).unwrap();
+        assert_eq!(Stats::open(path.to_str().unwrap()).unwrap(), None);
+        std::fs::write(&path, &data[..1000]).unwrap();
+        assert_eq!(Stats::open(path.to_str().unwrap()).unwrap(), None);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
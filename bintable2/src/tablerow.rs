@@ -1,10 +1,14 @@
+use crate::header::Header;
+use crate::stats::Sections;
 use anyhow::Result;
 use fast_smaz::Smaz;
+use fsst::SymbolTable;
 // use sqlx::{postgres::PgRow, FromRow, Row};
 use std::io::Write;
 use varint_compression::*;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Rows are ordered by key, then by their location, like the rows of a sorted bintable.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct TableRow {
     pub tokenized: String,
     pub tableid: u32,
@@ -16,32 +20,146 @@ pub struct TableRow {
 pub enum ReadError {
     InitialNumber,
     Needed(usize),
+    /// the record can not be decoded
+    Invalid(&'static str),
 }
 
 #[derive(Debug, Default)]
 pub struct ParseAcc {
     pub last_tokenized: String,
+    /// symbol table of fsst compressed keys, keys are smaz compressed if there is none.
+    pub fsst: Option<SymbolTable>,
+    /// whether the symbol table was already written in the current block
+    fsst_written: bool,
+    /// header of the bintable, read along with the first row
+    pub header: Header,
+    /// rows written since the last checksum record
+    block: Block,
+    /// whether the checksum record in front of the first row was written
+    checksums_started: bool,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
-enum Kind {
+/// Rows of a bintable are written in blocks of about this many bytes, each followed by a checksum record.
+pub(crate) const BLOCK_SIZE: usize = 64 * 1024;
+
+/// Rows since the last checksum record
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Block {
+    /// CRC32C of the encoded rows
+    pub crc: u32,
+    pub bytes: u64,
+    pub rows: u64,
+}
+
+impl Block {
+    pub(crate) fn add(&mut self, record: &[u8]) {
+        self.crc = crc32c::crc32c_append(self.crc, record);
+        self.bytes += record.len() as u64;
+        self.rows += 1;
+    }
+
+    /// Checksum record closing the block: <total length> <kind> <crc32c> <bytes> <rows>
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut body = self.crc.to_le_bytes().to_vec();
+        body.extend(compress(self.bytes));
+        body.extend(compress(self.rows));
+        frame(Kind::Checksum, &body)
+    }
+
+    /// Reads the fields following the kind byte
+    pub(crate) fn decode(data: &[u8]) -> Result<Self, ReadError> {
+        if data.len() < 4 {
+            return Err(ReadError::Invalid("checksum record too short"));
+        }
+        let crc = u32::from_le_bytes(data[..4].try_into().unwrap());
+        let ([bytes, rows], rest) =
+            decompress_n(&data[4..]).map_err(|_| ReadError::Invalid("checksum record"))?;
+        if !rest.is_empty() {
+            return Err(ReadError::Invalid("checksum record too long"));
+        }
+        Ok(Block { crc, bytes, rows })
+    }
+}
+
+/// Frames a record: <total length> <kind> <body>
+pub(crate) fn frame(kind: Kind, body: &[u8]) -> Vec<u8> {
+    let mut record = compress(1 + body.len() as u64);
+    record.push(kind.byte());
+    record.extend(body);
+    record
+}
+
+impl ParseAcc {
+    /// Writes keys compressed with the given fsst symbol table instead of smaz.
+    /// The table is written along with the first key.
+    pub fn with_fsst(table: SymbolTable) -> Self {
+        ParseAcc {
+            fsst: Some(table),
+            ..Default::default()
+        }
+    }
+
+    /// Writes the checksum record of the rows written since the last one, has to be called after the last row.
+    /// The next row carries its key and the fsst symbol table again, so every block can be decoded without the blocks before.
+    pub fn finish(&mut self, w: &mut impl Write) -> Result<()> {
+        self.close_block(w)?;
+        Ok(())
+    }
+
+    /// Returns the length of the checksum record written
+    pub(crate) fn close_block(&mut self, w: &mut impl Write) -> Result<u64> {
+        if self.block.rows == 0 {
+            return Ok(0);
+        }
+        let record = self.block.encode();
+        w.write_all(&record)?;
+        self.block = Block::default();
+        self.last_tokenized.clear();
+        self.fsst_written = false;
+        Ok(record.len() as u64)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Kind {
     Same,
+    /// smaz compressed key
     Compressed,
+    /// fsst symbol table, followed by an fsst compressed key
+    FsstTable,
+    /// fsst compressed key, using the last symbol table
+    Fsst,
+    /// not a row but the header of the bintable
+    Header,
+    /// not a row but the checksum of the rows since the previous checksum record
+    Checksum,
+    /// not a row but the statistics of the bintable, behind the last row
+    Stats,
 }
 
 impl Kind {
-    fn from(v: u8) -> Kind {
+    pub(crate) fn from(v: u8) -> Result<Kind, ReadError> {
         match v {
-            0 => Self::Same,
-            1 => Self::Compressed,
-            _ => unreachable!("invalid byte set"),
+            0 => Ok(Self::Same),
+            1 => Ok(Self::Compressed),
+            2 => Ok(Self::FsstTable),
+            3 => Ok(Self::Fsst),
+            4 => Ok(Self::Header),
+            5 => Ok(Self::Checksum),
+            6 => Ok(Self::Stats),
+            _ => Err(ReadError::Invalid("unknown kind of record")),
         }
     }
 
-    fn byte(self) -> u8 {
+    pub(crate) fn byte(self) -> u8 {
         match self {
             Kind::Same => 0,
             Kind::Compressed => 1,
+            Kind::FsstTable => 2,
+            Kind::Fsst => 3,
+            Kind::Header => 4,
+            Kind::Checksum => 5,
+            Kind::Stats => 6,
         }
     }
 }
@@ -111,6 +229,46 @@ mod test {
             assert_eq!(retrieved[i], input[i], "testing element {i}");
         }
     }
+
+    #[test]
+    fn serde_fsst() {
+        let input = (0..1000u32)
+            .flat_map(|i| {
+                let tokenized = format!("id-{:05}", i / 2);
+                [0, 1].map(|colid| TableRow {
+                    tokenized: tokenized.clone(),
+                    tableid: i,
+                    colid,
+                    rowid: i * 3,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let sample = input
+            .iter()
+            .map(|r| r.tokenized.as_str())
+            .collect::<Vec<_>>();
+        let table = SymbolTable::train(&sample);
+
+        let mut buffer = Vec::new();
+        let mut acc = ParseAcc::with_fsst(table);
+        for row in &input {
+            row.write_bin(&mut buffer, &mut acc).unwrap();
+        }
+
+        // the reader gets the symbol table from the first row
+        let mut acc = ParseAcc::default();
+        let mut data: &[u8] = &buffer;
+        let mut retrieved = Vec::new();
+        while !data.is_empty() {
+            let (row, rest) = TableRow::from_bin(data, &mut acc).unwrap();
+            data = rest;
+            retrieved.push(row);
+        }
+
+        assert_eq!(retrieved, input);
+        assert!(acc.fsst.is_some());
+    }
 }
 
 impl TableRow {
@@ -118,6 +276,8 @@ impl TableRow {
         [self.tableid, self.colid, self.rowid]
     }
 
+    /// Reads the next row from the start of `data`. Headers and checksum records in front of it are skipped,
+    /// checksums are only verified by `BinTable`.
     pub fn from_bin<'b>(data: &'b [u8], acc: &mut ParseAcc) -> Result<(Self, &'b [u8]), ReadError> {
         let (need_length, rest) = match decompress(data) {
             Ok(d) => d,
@@ -131,75 +291,155 @@ impl TableRow {
         if rest.len() < need_length {
             return Err(ReadError::Needed(need_length - rest.len()));
         }
+        let (record, rest) = rest.split_at(need_length);
 
-        let v = TableRow::from_bin_raw(rest, acc);
-
-        Ok((v, &rest[need_length..]))
+        match Kind::from(*record.first().ok_or(ReadError::Invalid("empty record"))?)? {
+            Kind::Header => {
+                acc.header = Header::from_bin_raw(&record[1..])?;
+                TableRow::from_bin(rest, acc)
+            }
+            Kind::Checksum | Kind::Stats => TableRow::from_bin(rest, acc),
+            _ => Ok((TableRow::from_bin_raw(record, acc)?, rest)),
+        }
     }
 
-    pub fn from_bin_raw(data: &[u8], acc: &mut ParseAcc) -> Self {
-        let kind = Kind::from(data[0]);
-        let data = &data[1..];
+    /// Decodes a row from its record, which starts with the kind byte.
+    pub fn from_bin_raw(data: &[u8], acc: &mut ParseAcc) -> Result<Self, ReadError> {
+        let (&kind, data) = data
+            .split_first()
+            .ok_or(ReadError::Invalid("empty record"))?;
+        let kind = Kind::from(kind)?;
+
+        let data = if kind == Kind::FsstTable {
+            let (table, data) =
+                SymbolTable::try_read(data).ok_or(ReadError::Invalid("fsst symbol table"))?;
+            acc.fsst = Some(table);
+            data
+        } else {
+            data
+        };
 
         let (tokenized, data) = match kind {
+            Kind::Header | Kind::Checksum | Kind::Stats => {
+                return Err(ReadError::Invalid("not a row"))
+            }
             Kind::Same => (acc.last_tokenized.to_string(), data),
-            Kind::Compressed => {
-                let (len, data) = decompress(data).unwrap();
-                let n = len as usize;
-                let tokenized = &data[..n];
-                let tokenized = tokenized.smaz_decompress().unwrap();
-                let tokenized = unsafe { String::from_utf8_unchecked(tokenized) };
+            Kind::Compressed | Kind::FsstTable | Kind::Fsst => {
+                let (len, data) =
+                    decompress(data).map_err(|_| ReadError::Invalid("length of the key"))?;
+                if len > data.len() as u64 {
+                    return Err(ReadError::Invalid("key longer than its record"));
+                }
+                let (tokenized, data) = data.split_at(len as usize);
+                let tokenized = if kind == Kind::Compressed {
+                    tokenized
+                        .smaz_decompress()
+                        .map_err(|_| ReadError::Invalid("smaz compressed key"))?
+                } else {
+                    let table = acc
+                        .fsst
+                        .as_ref()
+                        .ok_or(ReadError::Invalid("fsst key without a symbol table"))?;
+                    table
+                        .try_decompress(tokenized)
+                        .ok_or(ReadError::Invalid("fsst compressed key"))?
+                };
+                let tokenized = String::from_utf8(tokenized)
+                    .map_err(|_| ReadError::Invalid("key is not utf-8"))?;
 
                 acc.last_tokenized = tokenized.clone();
 
-                (tokenized, &data[n..])
+                (tokenized, data)
             }
         };
 
-        let ([tableid, colid, rowid], _rest) = decompress_n(data).unwrap();
-
-        let tableid = tableid as u32;
-        let colid = colid as u32;
-        let rowid = rowid as u32;
+        let (location, rest) =
+            decompress_n(data).map_err(|_| ReadError::Invalid("location of the row"))?;
+        if !rest.is_empty() {
+            return Err(ReadError::Invalid("bytes after the location of the row"));
+        }
+        let [tableid, colid, rowid] = location
+            .map(u32::try_from)
+            .map(|n| n.map_err(|_| ReadError::Invalid("location out of range")));
 
-        Self {
+        Ok(Self {
             tokenized,
-            tableid,
-            colid,
-            rowid,
-        }
+            tableid: tableid?,
+            colid: colid?,
+            rowid: rowid?,
+        })
     }
 
+    /// Writes the row. Once a block is full, it is closed by a checksum record, `ParseAcc::finish`
+    /// has to be called after the last row to close the last block.
     pub fn write_bin(&self, w: &mut impl Write, acc: &mut ParseAcc) -> Result<()> {
+        self.write_counted(w, acc, &mut Sections::default())
+    }
+
+    /// Writes the row like `write_bin`, adds the bytes written to their sections.
+    pub(crate) fn write_counted(
+        &self,
+        w: &mut impl Write,
+        acc: &mut ParseAcc,
+        bytes: &mut Sections,
+    ) -> Result<()> {
+        if !acc.checksums_started {
+            // an empty block in front of the first row marks the rows as checksummed
+            let marker = Block::default().encode();
+            w.write_all(&marker)?;
+            bytes.checksums += marker.len() as u64;
+            acc.checksums_started = true;
+        }
+
         let kind = if self.tokenized == acc.last_tokenized {
             Kind::Same
-        } else {
+        } else if acc.fsst.is_none() {
             Kind::Compressed
-        };
-
-        let tokenized = if kind == Kind::Same {
-            Vec::new()
+        } else if acc.fsst_written {
+            Kind::Fsst
         } else {
-            acc.last_tokenized = self.tokenized.clone();
-            self.tokenized.smaz_compress()
+            Kind::FsstTable
         };
 
-        let len = compress(tokenized.len() as u64);
-        let nums = compress_list(&[self.tableid as u64, self.colid as u64, self.rowid as u64]);
+        let mut body = Vec::new();
+        if kind == Kind::FsstTable {
+            acc.fsst.as_ref().unwrap().write(&mut body);
+            acc.fsst_written = true;
+            bytes.symbols += body.len() as u64;
+        }
+
+        let tokenized = match kind {
+            Kind::Same => Vec::new(),
+            Kind::Compressed => self.tokenized.smaz_compress(),
+            Kind::Header | Kind::Checksum | Kind::Stats => {
+                unreachable!("rows are never written as {kind:?}")
+            }
+            Kind::FsstTable | Kind::Fsst => acc
+                .fsst
+                .as_ref()
+                .unwrap()
+                .compress(self.tokenized.as_bytes()),
+        };
 
-        let mut total_length = 1 + nums.len();
         if kind != Kind::Same {
-            total_length += len.len() + tokenized.len();
+            acc.last_tokenized = self.tokenized.clone();
+            let key = body.len();
+            body.extend(compress(tokenized.len() as u64));
+            body.extend(tokenized);
+            bytes.keys += (body.len() - key) as u64;
         }
-        let total_length = compress(total_length as u64);
+        let location = compress_list(&[self.tableid as u64, self.colid as u64, self.rowid as u64]);
+        bytes.locations += location.len() as u64;
+        body.extend(location);
 
-        w.write_all(&total_length)?;
-        w.write_all(&[kind.byte()])?;
-        if kind != Kind::Same {
-            w.write_all(&len)?;
-            w.write_all(&tokenized)?;
+        let record = frame(kind, &body);
+        bytes.framing += (record.len() - body.len()) as u64;
+        w.write_all(&record)?;
+        acc.block.add(&record);
+
+        if acc.block.bytes >= BLOCK_SIZE as u64 {
+            bytes.checksums += acc.close_block(w)?;
         }
-        w.write_all(&nums)?;
         Ok(())
     }
 }
@@ -0,0 +1,277 @@
+use std::fmt;
+use std::str::FromStr;
+use unicode_normalization::UnicodeNormalization;
+
+/// Turns the raw value of a cell into its keys.
+///
+/// Every step is optional, they are applied in this order:
+/// unicode NFKC normalization, lowercasing, splitting into words at whitespace,
+/// collapsing whitespace, number canonicalization, punctuation stripping and truncation.
+/// Leading and trailing whitespace is always removed, empty keys are dropped.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tokenizer {
+    pub nfkc: bool,
+    pub lowercase: bool,
+    /// every word of a cell becomes a key of its own
+    pub words: bool,
+    /// runs of whitespace become a single space
+    pub whitespace: bool,
+    /// numbers are written without plus sign, leading and trailing zeros: "+007.50" becomes "7.5"
+    pub numbers: bool,
+    /// characters that are neither alphanumeric nor whitespace are removed, unless the key is a number
+    pub punctuation: bool,
+    /// keys are truncated to at most this many characters
+    pub max_length: Option<usize>,
+}
+
+impl Tokenizer {
+    /// Keys of a cell, in the order they occur in.
+    pub fn tokenize(&self, cell: &str) -> Vec<String> {
+        let mut cell = cell.to_string();
+        if self.nfkc {
+            cell = cell.nfkc().collect();
+        }
+        if self.lowercase {
+            cell = cell.to_lowercase();
+        }
+
+        if self.words {
+            cell.split_whitespace()
+                .filter_map(|word| self.token(word))
+                .collect()
+        } else {
+            self.token(&cell).into_iter().collect()
+        }
+    }
+
+    fn token(&self, token: &str) -> Option<String> {
+        let mut token = if self.whitespace {
+            collapse_whitespace(token)
+        } else {
+            token.trim().to_string()
+        };
+
+        let number = self.numbers.then(|| canonical_number(&token)).flatten();
+        if let Some(number) = number {
+            token = number;
+        } else if self.punctuation {
+            token.retain(|c| c.is_alphanumeric() || c.is_whitespace());
+            token = if self.whitespace {
+                collapse_whitespace(&token)
+            } else {
+                token.trim().to_string()
+            };
+
+            if let Some(number) = self.numbers.then(|| canonical_number(&token)).flatten() {
+                token = number;
+            }
+        }
+
+        if let Some(max) = self.max_length {
+            if let Some((end, _)) = token.char_indices().nth(max) {
+                token.truncate(end);
+                token.truncate(token.trim_end().len());
+            }
+        }
+
+        (!token.is_empty()).then_some(token)
+    }
+}
+
+fn collapse_whitespace(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Canonical form of a decimal number, `None` if `s` is not one.
+/// Integers are rewritten digit by digit, so long ids keep all their digits.
+/// Only numbers in scientific notation are parsed as floating point.
+fn canonical_number(s: &str) -> Option<String> {
+    let (negative, unsigned) = match s.as_bytes().first()? {
+        b'-' => (true, &s[1..]),
+        b'+' => (false, &s[1..]),
+        _ => (false, s),
+    };
+
+    if unsigned.contains(['e', 'E']) {
+        let (mantissa, exponent) = unsigned.split_once(['e', 'E'])?;
+        let exponent = exponent.strip_prefix('+').unwrap_or(exponent);
+        canonical_number(mantissa)?;
+        exponent
+            .strip_prefix('-')
+            .unwrap_or(exponent)
+            .parse::<u32>()
+            .ok()?;
+
+        let value = s.parse::<f64>().ok().filter(|v| v.is_finite())?;
+        return Some(format!("{}", value + 0.0));
+    }
+
+    let (integer, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    if integer.is_empty() && fraction.is_empty() {
+        return None;
+    }
+    if !integer
+        .bytes()
+        .chain(fraction.bytes())
+        .all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let integer = match integer.trim_start_matches('0') {
+        "" => "0",
+        integer => integer,
+    };
+    let fraction = fraction.trim_end_matches('0');
+
+    let mut number = String::with_capacity(s.len());
+    if negative && (integer != "0" || !fraction.is_empty()) {
+        number.push('-');
+    }
+    number.push_str(integer);
+    if !fraction.is_empty() {
+        number.push('.');
+        number.push_str(fraction);
+    }
+
+    Some(number)
+}
+
+impl fmt::Display for Tokenizer {
+    /// Comma separated list of the enabled steps, `none` if there are none.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut steps = [
+            (self.nfkc, "nfkc"),
+            (self.lowercase, "lowercase"),
+            (self.words, "words"),
+            (self.whitespace, "whitespace"),
+            (self.numbers, "numbers"),
+            (self.punctuation, "punctuation"),
+        ]
+        .into_iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, name)| name.to_string())
+        .collect::<Vec<_>>();
+
+        if let Some(max) = self.max_length {
+            steps.push(format!("max-length={max}"));
+        }
+
+        if steps.is_empty() {
+            f.write_str("none")
+        } else {
+            f.write_str(&steps.join(","))
+        }
+    }
+}
+
+impl FromStr for Tokenizer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokenizer = Tokenizer::default();
+
+        for step in s.split(',').map(str::trim) {
+            match step {
+                "" | "none" => {}
+                "nfkc" => tokenizer.nfkc = true,
+                "lowercase" => tokenizer.lowercase = true,
+                "words" => tokenizer.words = true,
+                "whitespace" => tokenizer.whitespace = true,
+                "numbers" => tokenizer.numbers = true,
+                "punctuation" => tokenizer.punctuation = true,
+                _ => match step.strip_prefix("max-length=") {
+                    Some(max) => {
+                        tokenizer.max_length =
+                            Some(max.parse().map_err(|_| format!("invalid length {max:?}"))?)
+                    }
+                    None => {
+                        return Err(format!(
+                            "unknown step {step:?}, allowed: nfkc lowercase words whitespace numbers punctuation max-length=<n>"
+                        ))
+                    }
+                },
+            }
+        }
+
+        Ok(tokenizer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokenize(config: &str, cell: &str) -> Vec<String> {
+        config.parse::<Tokenizer>().unwrap().tokenize(cell)
+    }
+
+    #[test]
+    fn steps() {
+        assert_eq!(tokenize("none", "  Berlin \n"), ["Berlin"]);
+        assert!(tokenize("none", " \t ").is_empty());
+
+        assert_eq!(tokenize("nfkc", "ﬁle Ⅻ ①"), ["file XII 1"]);
+        assert_eq!(tokenize("lowercase", "ÄÖÜ Straße"), ["äöü straße"]);
+        assert_eq!(
+            tokenize("whitespace", " New \t York\n City"),
+            ["New York City"]
+        );
+        assert_eq!(
+            tokenize("punctuation", "\"St. Louis\" (MO)!"),
+            ["St Louis MO"]
+        );
+        assert_eq!(tokenize("max-length=4", "Hamburg"), ["Hamb"]);
+        assert_eq!(tokenize("max-length=4", "ab c"), ["ab c"]);
+        assert_eq!(tokenize("max-length=3", "ab cd"), ["ab"]);
+
+        assert_eq!(
+            tokenize("lowercase,words,punctuation", "The Quick, brown fox!"),
+            ["the", "quick", "brown", "fox"]
+        );
+        assert!(tokenize("words,punctuation", " -- ... ").is_empty());
+    }
+
+    #[test]
+    fn numbers() {
+        let n = |cell| tokenize("numbers", cell);
+        assert_eq!(n("007"), ["7"]);
+        assert_eq!(n("+3.1400"), ["3.14"]);
+        assert_eq!(n("-0.0"), ["0"]);
+        assert_eq!(n(".5"), ["0.5"]);
+        assert_eq!(n("12."), ["12"]);
+        assert_eq!(n("1.5e3"), ["1500"]);
+        assert_eq!(n("-2E-2"), ["-0.02"]);
+        // ids longer than the precision of floating point numbers keep their digits
+        assert_eq!(n("0012345678901234567890"), ["12345678901234567890"]);
+
+        for not_a_number in ["1.2.3", "12a", "-", ".", "e5", "1e", "inf", "NaN", "1,000"] {
+            assert_eq!(n(not_a_number), [not_a_number]);
+        }
+
+        // punctuation is kept in numbers, and stripped from everything else
+        assert_eq!(
+            tokenize("words,numbers,punctuation", "-1.50 kg, (042)"),
+            ["-1.5", "kg", "42"]
+        );
+    }
+
+    #[test]
+    fn display_and_parse() {
+        let tokenizer = Tokenizer {
+            nfkc: true,
+            lowercase: true,
+            punctuation: true,
+            max_length: Some(32),
+            ..Default::default()
+        };
+        let s = tokenizer.to_string();
+        assert_eq!(s, "nfkc,lowercase,punctuation,max-length=32");
+        assert_eq!(s.parse::<Tokenizer>(), Ok(tokenizer));
+
+        assert_eq!(Tokenizer::default().to_string(), "none");
+        assert_eq!("none".parse::<Tokenizer>(), Ok(Tokenizer::default()));
+        assert!("upper".parse::<Tokenizer>().is_err());
+        assert!("max-length=x".parse::<Tokenizer>().is_err());
+    }
+}
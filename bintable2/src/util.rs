@@ -1,20 +1,14 @@
-pub(crate) fn get_factor(path: &str) -> f32 {
-    if let Some((_, f)) = path.rsplit_once('-') {
-        f.parse().expect("valid float at the end of bintable name")
-    } else {
-        1.0
-    }
-}
+use crate::sampling::Sampling;
 
-/// Searches for the best version of a given corpus to use for streaming
-/// and adjusts the factor.
-/// For example: doubling the factor, if a bintable is found, that contains only 50% of the entire
-/// collection
+/// Searches for the smallest sample of a given corpus, that contains the desired sample.
+/// Samples are nested, so the factor stays the same.
+/// For example: a 10% sample is read from the 50% sample `corpus-row-0-0.5` instead of the whole corpus,
+/// if the seed (0) and mode (row) match.
 pub(crate) fn find_best_input(
     path: &str,
     corpus: &str,
-    factor: f32,
-) -> std::io::Result<(String, f32)> {
+    sampling: Sampling,
+) -> std::io::Result<String> {
     use std::fs::read_dir;
 
     if path.ends_with('/') {
@@ -31,13 +25,17 @@ pub(crate) fn find_best_input(
         let entry = entry?;
 
         let filename = entry.file_name().into_string().unwrap();
-        if !filename.starts_with(&corpus) {
+        let subset = match Sampling::parse_subset_name(&filename) {
+            Some((name, subset)) if name == corpus => subset,
+            _ => continue,
+        };
+
+        if subset.mode != sampling.mode || subset.seed != sampling.seed {
             continue;
         }
 
-        let f = get_factor(&filename);
-
-        if f < factor {
+        let f = subset.factor;
+        if f < sampling.factor {
             continue;
         }
 
@@ -47,5 +45,5 @@ pub(crate) fn find_best_input(
         }
     }
 
-    Ok((bestfile, factor / bestfactor))
+    Ok(bestfile)
 }
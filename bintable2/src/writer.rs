@@ -0,0 +1,190 @@
+use crate::header::Header;
+use crate::stats::Stats;
+use crate::tablerow::{ParseAcc, TableRow};
+use anyhow::{bail, Result};
+use fsst::SymbolTable;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// Writes the rows of a bintable, followed by their statistics.
+/// `finish` has to be called after the last row.
+pub struct BinTableWriter<W: Write> {
+    out: W,
+    acc: ParseAcc,
+    stats: Stats,
+    /// whether rows out of the order of their keys are rejected
+    require_sorted: bool,
+    last: Option<String>,
+}
+
+impl BinTableWriter<BufWriter<File>> {
+    pub fn create(path: &str) -> std::io::Result<Self> {
+        let out = File::create(path)?;
+        Ok(BinTableWriter::new(BufWriter::with_capacity(
+            1024 * 1024, /* 1MB */
+            out,
+        )))
+    }
+}
+
+impl<W: Write> BinTableWriter<W> {
+    /// Writes to `out` without buffering it
+    pub fn new(out: W) -> Self {
+        BinTableWriter {
+            out,
+            acc: ParseAcc::default(),
+            stats: Stats {
+                sorted: true,
+                ..Default::default()
+            },
+            require_sorted: false,
+            last: None,
+        }
+    }
+
+    /// Compresses the keys with the given fsst symbol table instead of smaz.
+    pub fn with_fsst(mut self, table: SymbolTable) -> Self {
+        self.acc = ParseAcc::with_fsst(table);
+        self
+    }
+
+    /// Rejects rows that are not sorted by key, locations of a key may come in any order.
+    pub fn sorted(mut self) -> Self {
+        self.require_sorted = true;
+        self
+    }
+
+    /// Writes the header, has to be called before the first row. Empty headers are not written.
+    pub fn write_header(&mut self, header: &Header) -> Result<()> {
+        if self.stats.rows > 0 {
+            bail!("the header has to be written before the rows");
+        }
+        if header.is_empty() {
+            return Ok(());
+        }
+
+        let mut record = Vec::new();
+        header.write_bin(&mut record)?;
+        self.out.write_all(&record)?;
+        self.stats.bytes.header += record.len() as u64;
+        Ok(())
+    }
+
+    pub fn write(&mut self, row: &TableRow) -> Result<()> {
+        let stats = &mut self.stats;
+        match &mut self.last {
+            Some(last) if *last == row.tokenized => {}
+            Some(last) => {
+                if row.tokenized < *last {
+                    if self.require_sorted {
+                        bail!(
+                            "rows must be sorted by key, {:?} follows {last:?}",
+                            row.tokenized
+                        );
+                    }
+                    stats.sorted = false;
+                }
+                stats.keys += 1;
+                last.clone_from(&row.tokenized);
+            }
+            None => {
+                stats.keys += 1;
+                self.last = Some(row.tokenized.clone());
+            }
+        }
+
+        row.write_counted(&mut self.out, &mut self.acc, &mut stats.bytes)?;
+        stats.rows += 1;
+        stats.max_tableid = stats.max_tableid.max(row.tableid);
+        stats.max_colid = stats.max_colid.max(row.colid);
+        stats.max_rowid = stats.max_rowid.max(row.rowid);
+        Ok(())
+    }
+
+    /// Statistics of the rows written so far
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Closes the last block, writes the statistics and flushes the output.
+    pub fn finish(mut self) -> Result<Stats> {
+        self.stats.bytes.checksums += self.acc.close_block(&mut self.out)?;
+        self.stats.write_bin(&mut self.out)?;
+        self.out.flush()?;
+        Ok(self.stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BinTable, Tokenizer};
+
+    fn rows() -> Vec<TableRow> {
+        (0..10_000u32)
+            .map(|i| TableRow {
+                tokenized: format!("key-{:05}", i / 4),
+                tableid: i % 5,
+                colid: i % 3,
+                rowid: i,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn statistics_behind_the_rows() {
+        let header = Header {
+            tokenizers: vec![Tokenizer::default()],
+        };
+        let mut rows = rows();
+        rows.sort();
+
+        let path = std::env::temp_dir().join(format!("bintable2-writer-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut writer = BinTableWriter::create(path).unwrap().sorted();
+        writer.write_header(&header).unwrap();
+        for row in &rows {
+            writer.write(row).unwrap();
+        }
+        let stats = writer.finish().unwrap();
+
+        assert_eq!(stats.rows, 10_000);
+        assert_eq!(stats.keys, 2500);
+        assert!(stats.sorted);
+        assert_eq!(
+            (stats.max_tableid, stats.max_colid, stats.max_rowid),
+            (4, 2, 9999)
+        );
+        // every byte but the statistics is accounted for
+        let mut footer = Vec::new();
+        stats.write_bin(&mut footer).unwrap();
+        let length = std::fs::metadata(path).unwrap().len();
+        assert_eq!(stats.bytes.total() + footer.len() as u64, length);
+        assert!(stats.bytes.header > 0 && stats.bytes.checksums > 0);
+
+        assert_eq!(Stats::open(path).unwrap(), Some(stats.clone()));
+        let mut table = BinTable::open(path).unwrap();
+        assert_eq!(table.header(), &header);
+        assert_eq!(table.by_ref().collect::<Vec<_>>(), rows);
+        assert_eq!(table.stats(), Some(&stats));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn sort_order() {
+        let rows = rows();
+        let mut writer = BinTableWriter::new(Vec::new());
+        writer.write(&rows[4]).unwrap();
+        writer.write(&rows[0]).unwrap();
+        assert!(!writer.stats().sorted);
+
+        // locations of a key may come in any order
+        let mut writer = BinTableWriter::new(Vec::new()).sorted();
+        writer.write(&rows[1]).unwrap();
+        writer.write(&rows[0]).unwrap();
+        assert!(writer.write(&rows[4]).is_ok());
+        assert!(writer.write(&rows[3]).is_err());
+        assert!(writer.write_header(&Header::default()).is_err());
+    }
+}
@@ -0,0 +1,63 @@
+use crate::util::common_prefix_len;
+use crate::BlockCoding;
+use std::borrow::Cow;
+
+use varint_compression::{compress, decompress};
+
+/// The longest prefix shared by all keys of a block is stored once, followed by the suffix of each key.
+#[derive(Default)]
+pub struct FrontCoding;
+
+impl BlockCoding for FrontCoding {
+    fn encode(&mut self, keys: &[Vec<u8>]) -> Box<[u8]> {
+        /*
+            Scheme: prefix: <varint>data
+            for key in block:
+                <varint>data
+        */
+        let prefixlen = keys.iter().skip(1).fold(keys[0].len(), |len, k| {
+            len.min(common_prefix_len(&keys[0], k))
+        });
+
+        let mut data = Vec::new();
+
+        data.extend(compress(prefixlen as u64));
+        data.extend(&keys[0][..prefixlen]);
+
+        for k in keys {
+            let k = &k[prefixlen..];
+            data.extend(compress(k.len() as u64));
+            data.extend(k);
+        }
+
+        data.into_boxed_slice()
+    }
+
+    fn first<'a>(&self, block: &'a [u8]) -> Cow<'a, [u8]> {
+        let (n, rest) = decompress(block).unwrap();
+        let (prefix, rest) = rest.split_at(n as usize);
+
+        let (n, rest) = decompress(rest).unwrap();
+
+        let mut key = prefix.to_vec();
+        key.extend(&rest[..n as usize]);
+        Cow::Owned(key)
+    }
+
+    fn decode(&self, block: &[u8], keys: &mut Vec<Vec<u8>>) {
+        let (n, rest) = decompress(block).unwrap();
+        let (prefix, mut input) = rest.split_at(n as usize);
+
+        while !input.is_empty() {
+            let (n, rest) = decompress(input).unwrap();
+            let (suffix, rest) = rest.split_at(n as usize);
+
+            let mut key = Vec::with_capacity(prefix.len() + suffix.len());
+            key.extend(prefix);
+            key.extend(suffix);
+
+            keys.push(key);
+            input = rest;
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use crate::util::common_prefix_len;
+use crate::BlockCoding;
+use std::borrow::Cow;
+
+use varint_compression::{compress, decompress};
+
+/// Each key only stores the bytes that differ from the key before it.
+#[derive(Default)]
+pub struct IncrementalCoding;
+
+/// Incremental coding with a full key every `RESTART` keys. Lookups binary search the restart keys
+/// and scan the encoded keys after the matching one, without decoding them into a buffer,
+/// and stop as soon as a greater key is reached.
+#[derive(Default)]
+pub struct ImprovedIncrementalCoding<const RESTART: usize = 4>;
+
+impl BlockCoding for IncrementalCoding {
+    fn encode(&mut self, keys: &[Vec<u8>]) -> Box<[u8]> {
+        let mut data = Vec::new();
+        encode(keys, &mut data);
+        data.into_boxed_slice()
+    }
+
+    fn first<'a>(&self, block: &'a [u8]) -> Cow<'a, [u8]> {
+        Cow::Borrowed(first(block))
+    }
+
+    fn decode(&self, block: &[u8], keys: &mut Vec<Vec<u8>>) {
+        decode(block, keys)
+    }
+}
+
+impl<const RESTART: usize> BlockCoding for ImprovedIncrementalCoding<RESTART> {
+    fn encode(&mut self, keys: &[Vec<u8>]) -> Box<[u8]> {
+        /*
+            Scheme: incremental coding (see `encode`), with a prefixlen of 0 for every RESTART-th key
+            restarts: <u32 offset> of every restart key but the first, little endian
+            <u32 number of restarts>
+        */
+        assert!(RESTART > 0, "restart interval of at least one key");
+
+        let mut data = Vec::new();
+        data.extend(compress(keys[0].len() as u64));
+        data.extend(&keys[0]);
+
+        let mut restarts = Vec::new();
+        for (i, pair) in keys.windows(2).enumerate() {
+            let (last, k) = (&pair[0], &pair[1]);
+
+            let prefixlen = if (i + 1) % RESTART == 0 {
+                restarts.push(data.len() as u32);
+                0
+            } else {
+                common_prefix_len(last, k)
+            };
+
+            let k = &k[prefixlen..];
+            data.extend(compress(prefixlen as u64));
+            data.extend(compress(k.len() as u64));
+            data.extend(k);
+        }
+
+        for offset in &restarts {
+            data.extend(offset.to_le_bytes());
+        }
+        data.extend((restarts.len() as u32).to_le_bytes());
+
+        data.into_boxed_slice()
+    }
+
+    fn first<'a>(&self, block: &'a [u8]) -> Cow<'a, [u8]> {
+        Cow::Borrowed(first(block))
+    }
+
+    fn decode(&self, block: &[u8], keys: &mut Vec<Vec<u8>>) {
+        decode(Restarts::of(block).keys, keys)
+    }
+
+    fn position(&self, block: &[u8], key: &[u8]) -> Option<usize> {
+        let restarts = Restarts::of(block);
+
+        // binary search the last restart with a key <= key
+        let (mut lo, mut hi) = (0, restarts.len());
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if restarts.key(mid) <= key {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        let restart = lo.checked_sub(1)?;
+
+        let (restart_key, mut input) = restarts.entry(restart);
+
+        // Instead of reconstructing every key, only the length of the prefix shared with the
+        // (smaller) key before is tracked. Keys sharing less of it are greater than `key`,
+        // keys sharing more of it are still smaller.
+        let mut matched = 0;
+        let mut entry = (0, restart_key);
+
+        // keys after the next restart are greater than `key`
+        for i in 0..RESTART {
+            let (prefixlen, remaining) = entry;
+
+            if prefixlen < matched {
+                return None;
+            }
+
+            if prefixlen == matched {
+                let rest = &key[matched..];
+                let n = common_prefix_len(remaining, rest);
+                matched += n;
+
+                match (n == remaining.len(), n == rest.len()) {
+                    (true, true) => return Some(restart * RESTART + i),
+                    // the key read is a prefix of `key`
+                    (true, false) => {}
+                    (false, true) => return None,
+                    (false, false) if remaining[n] > rest[n] => return None,
+                    (false, false) => {}
+                }
+            }
+
+            if input.is_empty() {
+                return None;
+            }
+
+            let (prefixlen, rest) = decompress(input).unwrap();
+            let (remainlen, rest) = decompress(rest).unwrap();
+            let (remaining, rest) = rest.split_at(remainlen as usize);
+
+            entry = (prefixlen as usize, remaining);
+            input = rest;
+        }
+
+        None
+    }
+}
+
+/// Restart points of a block encoded by `ImprovedIncrementalCoding`
+struct Restarts<'a> {
+    /// the incrementally coded keys
+    keys: &'a [u8],
+    /// offsets of the restart keys, without the first key
+    offsets: &'a [u8],
+}
+
+impl<'a> Restarts<'a> {
+    fn of(block: &'a [u8]) -> Self {
+        let (rest, count) = block.split_at(block.len() - 4);
+        let count = u32::from_le_bytes(count.try_into().unwrap()) as usize;
+        let (keys, offsets) = rest.split_at(rest.len() - count * 4);
+
+        Restarts { keys, offsets }
+    }
+
+    /// number of restarts, including the first key
+    fn len(&self) -> usize {
+        self.offsets.len() / 4 + 1
+    }
+
+    fn key(&self, restart: usize) -> &'a [u8] {
+        self.entry(restart).0
+    }
+
+    /// the restart key and the encoded keys following it
+    fn entry(&self, restart: usize) -> (&'a [u8], &'a [u8]) {
+        let input = if restart == 0 {
+            self.keys
+        } else {
+            let offset = &self.offsets[(restart - 1) * 4..restart * 4];
+            let offset = u32::from_le_bytes(offset.try_into().unwrap()) as usize;
+
+            // skip the prefix length, which is always 0
+            decompress(&self.keys[offset..]).unwrap().1
+        };
+
+        let (n, input) = decompress(input).unwrap();
+        input.split_at(n as usize)
+    }
+}
+
+fn first(block: &[u8]) -> &[u8] {
+    let (n, input) = decompress(block).unwrap();
+    &input[..n as usize]
+}
+
+/*
+    Scheme: first key: <varint>data
+    for every following key:
+        <varint prefixlen><varint>data
+    where prefixlen is the number of bytes shared with the previous key.
+*/
+pub(crate) fn encode(keys: &[Vec<u8>], data: &mut Vec<u8>) {
+    data.extend(compress(keys[0].len() as u64));
+    data.extend(&keys[0]);
+
+    let mut last = &keys[0] as &[u8];
+    for k in keys.iter().skip(1) {
+        let prefixlen = common_prefix_len(last, k);
+        last = k;
+        let k = &k[prefixlen..];
+        // first compress the length of the prefix
+        data.extend(compress(prefixlen as u64));
+        // then compress the length of the remaining bytes
+        data.extend(compress(k.len() as u64));
+        // and finally the remaining bytes
+        data.extend(k);
+    }
+}
+
+pub(crate) fn decode(data: &[u8], keys: &mut Vec<Vec<u8>>) {
+    let (n, input) = decompress(data).unwrap();
+    let (first, mut input) = input.split_at(n as usize);
+
+    // push the first, uncompressed key
+    let mut last = first.to_vec();
+
+    while !input.is_empty() {
+        // decode the length of the prefix
+        // and the length of the remaining substring
+        let (prefixlen, rest) = decompress(input).unwrap();
+        let (remainlen, rest) = decompress(rest).unwrap();
+        let (remaining, rest) = rest.split_at(remainlen as usize);
+
+        // take the shared bytes from the previous key + the new information
+        let mut key = Vec::with_capacity(prefixlen as usize + remaining.len());
+        key.extend(&last[..prefixlen as usize]);
+        key.extend(remaining);
+
+        keys.push(std::mem::replace(&mut last, key));
+        input = rest;
+    }
+
+    keys.push(last);
+}
@@ -0,0 +1,539 @@
+mod front_coding;
+mod incremental;
+mod repair;
+mod util;
+
+pub use front_coding::FrontCoding;
+pub use incremental::{ImprovedIncrementalCoding, IncrementalCoding};
+pub use repair::RePair;
+
+use dict_search::{Glob, Levenshtein, Matcher, Prefix};
+use std::borrow::Cow;
+
+/// Keys (and their values) in sorted order
+pub type Entries<'a, V> = Box<dyn Iterator<Item = (Vec<u8>, &'a V)> + 'a>;
+
+/// Compresses a block of sorted keys.
+pub trait BlockCoding: Default {
+    /// Encodes a block of (at least one) sorted keys.
+    fn encode(&mut self, keys: &[Vec<u8>]) -> Box<[u8]>;
+
+    /// The first key of a block. Used to find the block a key belongs to.
+    fn first<'a>(&self, block: &'a [u8]) -> Cow<'a, [u8]>;
+
+    /// Appends all keys of the block to `keys`.
+    fn decode(&self, block: &[u8], keys: &mut Vec<Vec<u8>>);
+
+    /// Position of `key` inside the block
+    fn position(&self, block: &[u8], key: &[u8]) -> Option<usize> {
+        let mut keys = Vec::new();
+        self.decode(block, &mut keys);
+        keys.iter().position(|k| k == key)
+    }
+}
+
+/// Static dictionary, built from keys in sorted order.
+pub trait SortedDict<V> {
+    /// Push a new key into the dictionary. Input MUST BE SORTED.
+    fn push(&mut self, key: Vec<u8>, value: V);
+
+    /// makes all pushed keys reliably available for retrieval
+    fn finish(&mut self);
+
+    /// Number of retrievable keys
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// values in the order of their keys
+    fn values(&self) -> &[V];
+
+    fn index_of(&self, key: &[u8]) -> Option<usize>;
+
+    fn get(&self, key: &[u8]) -> Option<&V> {
+        let index = self.index_of(key)?;
+        self.values().get(index)
+    }
+
+    fn key_at_index(&self, index: usize) -> Vec<u8>;
+
+    /// All keys that are `>= start` and `< end`
+    fn range(&self, start: &[u8], end: Option<&[u8]>) -> Entries<'_, V>;
+
+    fn iter(&self) -> Entries<'_, V> {
+        self.range(&[], None)
+    }
+
+    /// All keys accepted by `matcher`
+    fn search<'a>(&'a self, mut matcher: Box<dyn Matcher + 'a>) -> Entries<'a, V> {
+        let prefix = matcher.prefix().to_vec();
+
+        let entries = self
+            .range(&prefix, None)
+            .take_while(move |(key, _)| key.starts_with(&prefix))
+            .filter(move |(key, _)| matcher.matches(key));
+
+        Box::new(entries)
+    }
+
+    /// All keys starting with `prefix`
+    fn starts_with(&self, prefix: &[u8]) -> Entries<'_, V> {
+        self.search(Box::new(Prefix::new(prefix)))
+    }
+
+    /// All keys matching the glob `pattern` (`?` matches a single byte, `*` any number of bytes)
+    fn glob(&self, pattern: &[u8]) -> Entries<'_, V> {
+        self.search(Box::new(Glob::new(pattern)))
+    }
+
+    /// All keys within an edit distance of at most `k` bytes to `key`
+    fn fuzzy(&self, key: &[u8], k: u32) -> Entries<'_, V> {
+        self.search(Box::new(Levenshtein::new(key, k)))
+    }
+}
+
+/// Keys are compressed in blocks of `BLOCKSIZE` keys, using the block coding `C`.
+/// Values are stored as is.
+pub struct Dict<V, C = ImprovedIncrementalCoding, const BLOCKSIZE: usize = 16> {
+    coding: C,
+    blocks: Vec<Box<[u8]>>,
+    values: Vec<V>,
+    current_block: Vec<(Vec<u8>, V)>,
+}
+
+impl<V, C: BlockCoding, const B: usize> Default for Dict<V, C, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V, C: BlockCoding, const B: usize> Dict<V, C, B> {
+    pub fn new() -> Self {
+        Self::with_coding(C::default())
+    }
+
+    pub fn with_coding(coding: C) -> Self {
+        Self {
+            coding,
+            blocks: Vec::new(),
+            values: Vec::new(),
+            current_block: Vec::new(),
+        }
+    }
+
+    /// Number of bytes used by the encoded keys
+    pub fn heap_size(&self) -> usize {
+        self.blocks.iter().map(|b| b.len()).sum::<usize>()
+            + self.blocks.capacity() * std::mem::size_of::<Box<[u8]>>()
+    }
+
+    /// The block `key` belongs to, the last block starting with a key `<= key`.
+    /// Only this block is decoded to look up `key`.
+    pub fn block_of(&self, key: &[u8]) -> Option<usize> {
+        let blocks = self
+            .blocks
+            .partition_point(|block| *self.coding.first(block) <= *key);
+
+        blocks.checked_sub(1)
+    }
+
+    /// Number of bytes of an encoded block
+    pub fn block_len(&self, block: usize) -> usize {
+        self.blocks[block].len()
+    }
+
+    /// Appends a block of sorted keys, encoded with `C` elsewhere (e.g. on another thread),
+    /// along with the values of its keys. Only the last block may hold less than `B` keys.
+    pub fn push_block(&mut self, block: Box<[u8]>, values: Vec<V>) {
+        assert!(
+            self.current_block.is_empty(),
+            "blocks can not follow keys pushed one by one"
+        );
+        assert_eq!(
+            self.values.len(),
+            self.blocks.len() * B,
+            "only the last block may be partial"
+        );
+        assert!(
+            !values.is_empty() && values.len() <= B,
+            "between 1 and {B} keys per block"
+        );
+
+        self.blocks.push(block);
+        self.values.extend(values);
+    }
+
+    fn decode(&self, block: usize) -> Vec<Vec<u8>> {
+        let mut keys = Vec::with_capacity(B);
+        self.coding.decode(&self.blocks[block], &mut keys);
+        keys
+    }
+
+    fn flush(&mut self) {
+        let (keys, values): (Vec<_>, Vec<_>) = self.current_block.drain(..).unzip();
+
+        let block = self.coding.encode(&keys);
+        self.blocks.push(block);
+        self.values.extend(values);
+    }
+}
+
+impl<V, C: BlockCoding, const B: usize> SortedDict<V> for Dict<V, C, B> {
+    fn push(&mut self, key: Vec<u8>, value: V) {
+        // NOTE actually it is vital to assert that our input data is sorted at this point.
+
+        self.current_block.push((key, value));
+
+        if self.current_block.len() == B {
+            self.flush();
+        }
+    }
+
+    fn finish(&mut self) {
+        if !self.current_block.is_empty() {
+            self.flush();
+        }
+
+        self.blocks.shrink_to_fit();
+        self.values.shrink_to_fit();
+    }
+
+    fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    fn values(&self) -> &[V] {
+        &self.values
+    }
+
+    fn index_of(&self, key: &[u8]) -> Option<usize> {
+        let block = self.block_of(key)?;
+        let position = self.coding.position(&self.blocks[block], key)?;
+
+        Some(block * B + position)
+    }
+
+    fn key_at_index(&self, index: usize) -> Vec<u8> {
+        self.decode(index / B).swap_remove(index % B)
+    }
+
+    fn range(&self, start: &[u8], end: Option<&[u8]>) -> Entries<'_, V> {
+        let first = self.block_of(start).unwrap_or(0);
+
+        let start = start.to_vec();
+        let end = end.map(<[u8]>::to_vec);
+
+        let entries = (first..self.blocks.len())
+            .flat_map(|block| self.decode(block))
+            .zip(first * B..)
+            .skip_while(move |(key, _)| *key < start)
+            .take_while(move |(key, _)| end.as_ref().is_none_or(|end| key < end))
+            .map(|(key, index)| (key, &self.values[index]));
+
+        Box::new(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words() -> Vec<&'static str> {
+        // randomly generated words
+        let mut words = "absorb animal application arrow assertive affect attack anger ash abundant
+            acid appeal activity air aware afford appearance administration accompany anniversary
+            association acquaintance AIDS accent acquit address aquarium am approval adult apparatus
+            album absence academy arch abandon avant-garde acute archive apathy autonomy arm
+            adventure advocate allocation agriculture aunt assume affair analyst"
+            .split_whitespace()
+            .collect::<Vec<_>>();
+        words.sort();
+        words
+    }
+
+    fn dict<C: BlockCoding, const B: usize>() -> Dict<usize, C, B> {
+        let mut dict = Dict::new();
+        for (i, w) in words().into_iter().enumerate() {
+            dict.push(w.as_bytes().to_vec(), i);
+        }
+        dict.finish();
+        dict
+    }
+
+    fn keys(entries: Entries<'_, usize>) -> Vec<String> {
+        entries
+            .map(|(k, v)| {
+                assert_eq!(words()[*v].as_bytes(), k, "value belongs to key");
+                String::from_utf8(k).unwrap()
+            })
+            .collect()
+    }
+
+    /// runs a test against every block coding, with full and partial last blocks
+    fn for_all_codings(test: impl Fn(&dyn SortedDict<usize>)) {
+        test(&dict::<FrontCoding, 4>());
+        test(&dict::<FrontCoding, 7>());
+        test(&dict::<IncrementalCoding, 8>());
+        test(&dict::<ImprovedIncrementalCoding, 16>());
+        test(&dict::<ImprovedIncrementalCoding, 1>());
+        test(&dict::<ImprovedIncrementalCoding<1>, 8>());
+        test(&dict::<ImprovedIncrementalCoding<3>, 32>());
+        test(&dict::<RePair, 32>());
+        test(&dict::<RePair, 64>());
+    }
+
+    #[test]
+    fn pushed_blocks() {
+        fn check<C: BlockCoding, const B: usize>() {
+            let words = words();
+            let mut coding = C::default();
+
+            let mut blocks = Dict::<usize, C, B>::new();
+            for (i, chunk) in words.chunks(B).enumerate() {
+                let keys = chunk
+                    .iter()
+                    .map(|w| w.as_bytes().to_vec())
+                    .collect::<Vec<_>>();
+                let values = (i * B..i * B + chunk.len()).collect();
+                blocks.push_block(coding.encode(&keys), values);
+            }
+            blocks.finish();
+
+            let pushed = dict::<C, B>();
+            assert_eq!(blocks.len(), pushed.len());
+            assert_eq!(keys(blocks.iter()), keys(pushed.iter()));
+            for w in &words {
+                assert_eq!(blocks.get(w.as_bytes()), pushed.get(w.as_bytes()));
+            }
+        }
+
+        check::<FrontCoding, 7>();
+        check::<ImprovedIncrementalCoding, 16>();
+        check::<RePair, 32>();
+    }
+
+    #[test]
+    #[should_panic(expected = "only the last block may be partial")]
+    fn partial_block_in_between() {
+        let mut dict = Dict::<usize, FrontCoding, 4>::new();
+        dict.push_block(FrontCoding.encode(&[b"a".to_vec()]), vec![0]);
+        dict.push_block(FrontCoding.encode(&[b"b".to_vec()]), vec![1]);
+    }
+
+    #[test]
+    fn incremental_coding() {
+        let values = [b"aal".to_vec(), b"aachen".to_vec(), b"aachiluah".to_vec()];
+
+        let data = IncrementalCoding.encode(&values);
+        assert_eq!(&data[1..=3], b"aal", "expected aal to be uncompressed");
+        assert_eq!(data[4], 2, "expected 2 bytes to be saved");
+        assert_eq!(data[5], 4, "expected 4 bytes to compress aachen");
+        assert_eq!(&data[6..10], b"chen", "aachen => chen");
+
+        assert_eq!(data[10], 4, "4 bytes to be ommited for aachen => aachiluah");
+        assert_eq!(data[11], 5, "5 bytes to be used for iluah");
+        assert_eq!(
+            &data[12..],
+            b"iluah",
+            "expected incremental encoding to transfer"
+        );
+    }
+
+    #[test]
+    fn front_coding() {
+        let values = [b"aal".to_vec(), b"aachen".to_vec(), b"aachja".to_vec()];
+
+        let data = FrontCoding.encode(&values);
+        assert_eq!(
+            &data[..3],
+            &[2, b'a', b'a'],
+            "expected prefix aa to be stored once"
+        );
+        assert_eq!(&data[3..5], &[1, b'l'], "aal => l");
+    }
+
+    #[test]
+    fn prefixes() {
+        let testcases = [
+            ("aachen", "aachiluah", 4),
+            ("anderthen", "mond", 0),
+            ("arnold", "arsch", 2),
+            ("babc", "aabc", 0),
+            ("aal", "aal", 3),
+            ("aaligatoah", "aaligatoah", 10),
+            ("aaligatoah", "aaligatoahxyz", 10),
+            ("aaligatoahxyz", "aaligatoah", 10),
+        ];
+
+        for (a, b, n) in testcases {
+            assert_eq!(
+                util::common_prefix_len(a.as_bytes(), b.as_bytes()),
+                n,
+                "expected {a} and {b} to have a common prefix of {n}"
+            )
+        }
+    }
+
+    #[test]
+    fn blocks_roundtrip() {
+        let blocks = [
+            vec![b"a".to_vec()],
+            vec![b"".to_vec(), b"aa".to_vec(), b"aaa".to_vec()],
+            words().iter().map(|w| w.as_bytes().to_vec()).collect(),
+            (0..200u32)
+                .map(|i| format!("row-{i:05}").into_bytes())
+                .collect(),
+        ];
+
+        fn roundtrip<C: BlockCoding>(keys: &[Vec<u8>]) {
+            let mut coding = C::default();
+            let block = coding.encode(keys);
+
+            let mut decoded = Vec::new();
+            coding.decode(&block, &mut decoded);
+
+            assert_eq!(decoded, keys);
+            assert_eq!(*coding.first(&block), keys[0]);
+        }
+
+        for keys in &blocks {
+            roundtrip::<FrontCoding>(keys);
+            roundtrip::<IncrementalCoding>(keys);
+            roundtrip::<ImprovedIncrementalCoding>(keys);
+            roundtrip::<ImprovedIncrementalCoding<1>>(keys);
+            roundtrip::<ImprovedIncrementalCoding<7>>(keys);
+            roundtrip::<RePair>(keys);
+        }
+    }
+
+    #[test]
+    fn restart_aware_position() {
+        let mut keys = words()
+            .iter()
+            .map(|w| w.as_bytes().to_vec())
+            .collect::<Vec<_>>();
+        keys.insert(0, b"AID".to_vec());
+        keys.insert(2, b"AIDSS".to_vec());
+
+        fn check<const RESTART: usize>(keys: &[Vec<u8>]) {
+            let mut coding = ImprovedIncrementalCoding::<RESTART>;
+            let block = coding.encode(keys);
+
+            for (i, k) in keys.iter().enumerate() {
+                assert_eq!(coding.position(&block, k), Some(i), "position of {k:?}");
+
+                let mut missing = k.clone();
+                missing.push(b'!');
+                assert_eq!(coding.position(&block, &missing), None);
+                let shorter = &k[..k.len() - 1];
+                if !keys.iter().any(|k| k == shorter) {
+                    assert_eq!(coding.position(&block, shorter), None);
+                }
+            }
+
+            assert_eq!(coding.position(&block, b""), None);
+            assert_eq!(coding.position(&block, b"zzz"), None);
+        }
+
+        check::<1>(&keys);
+        check::<2>(&keys);
+        check::<4>(&keys);
+        check::<5>(&keys);
+        check::<64>(&keys);
+    }
+
+    #[test]
+    fn repair_compresses_repetitive_blocks() {
+        let keys = (0..200u32)
+            .map(|i| format!("row-{i:05}-suffix").into_bytes())
+            .collect::<Vec<_>>();
+
+        let incremental = IncrementalCoding.encode(&keys);
+        let repair = RePair.encode(&keys);
+
+        assert!(
+            repair.len() < incremental.len(),
+            "expected {} < {}",
+            repair.len(),
+            incremental.len()
+        );
+    }
+
+    #[test]
+    fn dictionary_insertion() {
+        for_all_codings(|dict| {
+            assert_eq!(dict.len(), words().len());
+
+            for (i, w) in words().into_iter().enumerate() {
+                assert_eq!(
+                    dict.get(w.as_bytes()),
+                    Some(&i),
+                    "expect key {w} to be in dictionary"
+                );
+                assert_eq!(dict.key_at_index(i), w.as_bytes());
+            }
+
+            for missing in ["", "a", "AID", "abc", "zoo", "animals"] {
+                assert_eq!(dict.get(missing.as_bytes()), None, "{missing} is not a key");
+            }
+        });
+    }
+
+    #[test]
+    fn unfinished_blocks_are_not_retrievable() {
+        let mut dict = Dict::<usize, IncrementalCoding, 4>::new();
+        for (i, w) in words().into_iter().take(6).enumerate() {
+            dict.push(w.as_bytes().to_vec(), i);
+        }
+
+        assert_eq!(dict.len(), 4);
+        dict.finish();
+        assert_eq!(dict.len(), 6);
+    }
+
+    #[test]
+    fn range() {
+        for_all_codings(|dict| {
+            assert_eq!(keys(dict.iter()), words());
+
+            let expected = words()
+                .into_iter()
+                .filter(|w| *w >= "acc" && *w < "air")
+                .collect::<Vec<_>>();
+            assert_eq!(keys(dict.range(b"acc", Some(b"air"))), expected);
+
+            assert_eq!(
+                keys(dict.range(b"aunt", None)),
+                ["aunt", "autonomy", "avant-garde", "aware"]
+            );
+            assert!(keys(dict.range(b"b", None)).is_empty());
+        });
+    }
+
+    #[test]
+    fn search() {
+        for_all_codings(|dict| {
+            assert_eq!(
+                keys(dict.starts_with(b"app")),
+                [
+                    "apparatus",
+                    "appeal",
+                    "appearance",
+                    "application",
+                    "approval"
+                ]
+            );
+            assert_eq!(keys(dict.starts_with(b"AIDS")), ["AIDS"]);
+            assert!(keys(dict.starts_with(b"b")).is_empty());
+
+            assert_eq!(keys(dict.glob(b"a*ance")), ["acquaintance", "appearance"]);
+            assert_eq!(keys(dict.glob(b"a?m")), ["arm"]);
+
+            assert_eq!(keys(dict.fuzzy(b"acid", 1)), ["acid"]);
+            assert_eq!(keys(dict.fuzzy(b"aunt", 1)), ["aunt"]);
+            assert_eq!(keys(dict.fuzzy(b"arc", 1)), ["arch", "arm"]);
+        });
+    }
+}
@@ -0,0 +1,138 @@
+use crate::incremental;
+use crate::BlockCoding;
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use varint_compression::{compress, decompress};
+
+/// A pair needs to occur at least this often to be replaced, otherwise the rule costs more than it saves.
+const MIN_PAIR_FREQUENCY: usize = 3;
+
+/// Keys are incrementally coded, then everything after the first key gets compressed with Re-Pair:
+/// the most frequent pair of adjacent symbols is replaced by a new symbol, until no pair is frequent enough.
+///
+/// Symbols are the bytes occurring in the block plus one per rule. There are at most 256 of them,
+/// so every symbol is stored as a single byte. The grammar is stored inside each block,
+/// which only pays off for larger blocks (32 keys and more).
+#[derive(Default)]
+pub struct RePair;
+
+impl BlockCoding for RePair {
+    fn encode(&mut self, keys: &[Vec<u8>]) -> Box<[u8]> {
+        /*
+            Scheme: first key: <varint>data
+            terminals: <varint>bytes
+            rules: <varint count>(<left symbol><right symbol>)*
+            symbols until the end of the block
+        */
+        let mut plain = Vec::new();
+        incremental::encode(keys, &mut plain);
+
+        // the first key stays uncompressed, so it can be compared without expanding the block.
+        let (n, rest) = decompress(&plain).unwrap();
+        let tail = &rest[n as usize..];
+        let head = &plain[..plain.len() - tail.len()];
+
+        let mut terminals = tail.to_vec();
+        terminals.sort_unstable();
+        terminals.dedup();
+
+        let mut symbol_of = [0u8; 256];
+        for (i, t) in terminals.iter().enumerate() {
+            symbol_of[*t as usize] = i as u8;
+        }
+
+        let mut symbols = tail
+            .iter()
+            .map(|b| symbol_of[*b as usize])
+            .collect::<Vec<_>>();
+        let mut rules = Vec::new();
+
+        while terminals.len() + rules.len() < 256 {
+            let mut counts = HashMap::new();
+            for pair in symbols.windows(2) {
+                *counts.entry((pair[0], pair[1])).or_insert(0) += 1;
+            }
+
+            // ties are broken by the smaller pair, so the encoding is deterministic
+            let best = counts
+                .into_iter()
+                .max_by_key(|(pair, count)| (*count, Reverse(*pair)))
+                .filter(|(_, count)| *count >= MIN_PAIR_FREQUENCY);
+
+            let (pair, _) = match best {
+                Some(best) => best,
+                None => break,
+            };
+
+            let symbol = (terminals.len() + rules.len()) as u8;
+            rules.push(pair);
+
+            // replace non overlapping occurrences, from left to right
+            let mut replaced = Vec::with_capacity(symbols.len());
+            let mut i = 0;
+            while i < symbols.len() {
+                if i + 1 < symbols.len() && (symbols[i], symbols[i + 1]) == pair {
+                    replaced.push(symbol);
+                    i += 2;
+                } else {
+                    replaced.push(symbols[i]);
+                    i += 1;
+                }
+            }
+            symbols = replaced;
+        }
+
+        let mut data = head.to_vec();
+
+        data.extend(compress(terminals.len() as u64));
+        data.extend(&terminals);
+
+        data.extend(compress(rules.len() as u64));
+        for (left, right) in rules {
+            data.push(left);
+            data.push(right);
+        }
+
+        data.extend(symbols);
+        data.into_boxed_slice()
+    }
+
+    fn first<'a>(&self, block: &'a [u8]) -> Cow<'a, [u8]> {
+        let (n, input) = decompress(block).unwrap();
+        Cow::Borrowed(&input[..n as usize])
+    }
+
+    fn decode(&self, block: &[u8], keys: &mut Vec<Vec<u8>>) {
+        let (n, rest) = decompress(block).unwrap();
+        let rest = &rest[n as usize..];
+        let head = &block[..block.len() - rest.len()];
+
+        let (n, rest) = decompress(rest).unwrap();
+        let (terminals, rest) = rest.split_at(n as usize);
+
+        let (n, rest) = decompress(rest).unwrap();
+        let (rules, symbols) = rest.split_at(n as usize * 2);
+
+        let mut plain = head.to_vec();
+        for symbol in symbols {
+            expand(*symbol, terminals, rules, &mut plain);
+        }
+
+        incremental::decode(&plain, keys)
+    }
+}
+
+fn expand(symbol: u8, terminals: &[u8], rules: &[u8], out: &mut Vec<u8>) {
+    let symbol = symbol as usize;
+    if symbol < terminals.len() {
+        out.push(terminals[symbol]);
+        return;
+    }
+
+    // rules only refer to symbols created before them, so the recursion is at most 256 deep
+    let rule = (symbol - terminals.len()) * 2;
+    expand(rules[rule], terminals, rules, out);
+    expand(rules[rule + 1], terminals, rules, out);
+}
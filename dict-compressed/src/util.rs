@@ -8,10 +8,3 @@ pub(crate) fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
 
     maxlen
 }
-
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
-pub enum Ordering {
-    LessOrInHere,
-    FoundAt(usize),
-    Greater,
-}
@@ -0,0 +1,97 @@
+/// Every n-th zero bit gets it's position sampled to speed up select queries. Must be a power of 2.
+const SELECT_SAMPLE: usize = 256;
+
+/// Append only bit vector. Supports rank and select queries, once it is finished.
+#[derive(Default)]
+pub(crate) struct BitVec {
+    words: Vec<u64>,
+    len: usize,
+    zero_count: usize,
+    /// number of set bits before each word
+    ranks: Vec<u32>,
+    /// position of every SELECT_SAMPLE-th zero bit
+    zeros: Vec<u32>,
+}
+
+impl BitVec {
+    pub fn push(&mut self, bit: bool) {
+        if self.len & 63 == 0 {
+            self.words.push(0);
+        }
+
+        if bit {
+            self.words[self.len / 64] |= 1 << (self.len % 64);
+        } else {
+            if self.zero_count & (SELECT_SAMPLE - 1) == 0 {
+                self.zeros.push(self.len as u32);
+            }
+            self.zero_count += 1;
+        }
+
+        self.len += 1;
+    }
+
+    /// builds the rank directory. Needs to be called after the last push.
+    pub fn finish(&mut self) {
+        self.ranks.clear();
+
+        let mut rank = 0;
+        for w in &self.words {
+            self.ranks.push(rank);
+            rank += w.count_ones();
+        }
+
+        self.words.shrink_to_fit();
+        self.ranks.shrink_to_fit();
+        self.zeros.shrink_to_fit();
+    }
+
+    pub fn get(&self, index: usize) -> bool {
+        self.words[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    /// number of set bits before `index`
+    pub fn rank1(&self, index: usize) -> usize {
+        let word = index / 64;
+        if word == self.words.len() {
+            return self.ranks.last().map(|r| *r as usize).unwrap_or(0)
+                + self
+                    .words
+                    .last()
+                    .map(|w| w.count_ones() as usize)
+                    .unwrap_or(0);
+        }
+
+        let mask = (1u64 << (index % 64)) - 1;
+        self.ranks[word] as usize + (self.words[word] & mask).count_ones() as usize
+    }
+
+    /// position of the n-th (starting at 0) zero bit
+    pub fn select0(&self, n: usize) -> usize {
+        let sample = self.zeros[n / SELECT_SAMPLE] as usize;
+        let mut remaining = n % SELECT_SAMPLE;
+
+        // zeros inside the first word, starting at the sampled position
+        let mut word = sample / 64;
+        let mut bits = !self.words[word] & (u64::MAX << (sample % 64));
+
+        loop {
+            let zeros = bits.count_ones() as usize;
+            if remaining < zeros {
+                for _ in 0..remaining {
+                    // clear lowest bit
+                    bits &= bits - 1;
+                }
+                return word * 64 + bits.trailing_zeros() as usize;
+            }
+
+            remaining -= zeros;
+            word += 1;
+            bits = !self.words[word];
+        }
+    }
+
+    pub fn heap_size(&self) -> usize {
+        self.words.capacity() * 8 + self.ranks.capacity() * 4 + self.zeros.capacity() * 4
+    }
+}
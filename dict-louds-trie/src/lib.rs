@@ -0,0 +1,419 @@
+mod bitvec;
+use bitvec::BitVec;
+use std::collections::VecDeque;
+use std::ops::Range;
+
+/// Succinct trie (LOUDS) mapping keys to values.
+///
+/// Nodes are numbered in breadth first order. Each node is encoded in the louds bitvector
+/// as one 1 bit per child followed by a single 0 bit.
+/// The n-th edge (in breadth first order) always leads to node n+1,
+/// so the label of that edge is all we need to store.
+pub struct Trie<V> {
+    /// label of the edge leading into node i+1
+    labels: Box<[u8]>,
+    louds: BitVec,
+    /// whether a key ends at node i
+    terminal: BitVec,
+    /// values, ordered by the rank of the nodes their keys end in
+    values: Vec<V>,
+}
+
+impl<V> Trie<V> {
+    /// Number of keys
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn values(&self) -> &[V] {
+        &self.values
+    }
+
+    /// Position of the value of `key` inside `values()`
+    pub fn index_of(&self, key: &[u8]) -> Option<usize> {
+        let node = self.find_node(key)?;
+        self.terminal.get(node).then(|| self.terminal.rank1(node))
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&V> {
+        let index = self.index_of(key)?;
+        self.values.get(index)
+    }
+
+    /// Iterates all keys in lexicographic order
+    pub fn iter(&self) -> Iter<'_, V> {
+        self.range(&[], None)
+    }
+
+    /// Iterates all keys starting with `prefix` in lexicographic order
+    pub fn starts_with(&self, prefix: &[u8]) -> Iter<'_, V> {
+        let mut iter = self.range(prefix, None);
+        iter.end = End::Prefix(prefix.to_vec());
+        iter
+    }
+
+    /// Iterates all keys in lexicographic order, that are `>= start` and `< end`.
+    pub fn range(&self, start: &[u8], end: Option<&[u8]>) -> Iter<'_, V> {
+        let mut iter = Iter {
+            trie: self,
+            stack: vec![self.edges(0)],
+            key: Vec::new(),
+            emit: None,
+            end: end.map(|e| End::Exclusive(e.to_vec())).unwrap_or(End::None),
+        };
+
+        iter.seek(start);
+        iter
+    }
+
+    /// Approximate number of bytes used on the heap, excluding the values.
+    pub fn heap_size(&self) -> usize {
+        self.labels.len() + self.louds.heap_size() + self.terminal.heap_size()
+    }
+
+    /// range of edges going out of a node
+    fn edges(&self, node: usize) -> Range<usize> {
+        let start = if node == 0 {
+            0
+        } else {
+            self.louds.select0(node - 1) + 1 - node
+        };
+
+        let end = self.louds.select0(node) - node;
+
+        start..end
+    }
+
+    fn child(&self, node: usize, label: u8) -> Option<usize> {
+        let edges = self.edges(node);
+        let i = self.labels[edges.clone()].binary_search(&label).ok()?;
+        Some(edges.start + i + 1)
+    }
+
+    fn find_node(&self, key: &[u8]) -> Option<usize> {
+        let mut node = 0;
+        for b in key {
+            node = self.child(node, *b)?;
+        }
+
+        Some(node)
+    }
+}
+
+enum End {
+    None,
+    Exclusive(Vec<u8>),
+    Prefix(Vec<u8>),
+}
+
+/// Depth first iterator over the keys of a trie. Yields (key, value) in lexicographic order.
+pub struct Iter<'a, V> {
+    trie: &'a Trie<V>,
+    /// remaining edges of each node on the path to the current node
+    stack: Vec<Range<usize>>,
+    /// key of the current node
+    key: Vec<u8>,
+    /// node that was just entered and might still need to be emitted
+    emit: Option<usize>,
+    end: End,
+}
+
+impl<'a, V> Iter<'a, V> {
+    /// position the iterator before the first key that is `>= start`
+    fn seek(&mut self, start: &[u8]) {
+        let trie = self.trie;
+        for b in start {
+            let edges = self.stack.last_mut().unwrap();
+            let labels = &trie.labels[edges.clone()];
+
+            // first edge with a label >= b
+            let i = labels.partition_point(|l| l < b);
+            edges.start += i;
+
+            if edges.start == edges.end || trie.labels[edges.start] > *b {
+                // every remaining key is greater than start
+                self.emit = None;
+                return;
+            }
+
+            let node = edges.start + 1;
+            edges.start += 1;
+            self.key.push(*b);
+            self.stack.push(trie.edges(node));
+            self.emit = Some(node);
+        }
+
+        // the current node equals start itself
+        if start.is_empty() {
+            self.emit = Some(0);
+        }
+    }
+
+    fn in_range(&self) -> bool {
+        match &self.end {
+            End::None => true,
+            End::Exclusive(end) => self.key < *end,
+            End::Prefix(prefix) => self.key.starts_with(prefix),
+        }
+    }
+}
+
+impl<'a, V> Iterator for Iter<'a, V> {
+    type Item = (Vec<u8>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let trie = self.trie;
+
+        loop {
+            if let Some(node) = self.emit.take() {
+                if !self.in_range() {
+                    // keys are visited in order, no key after this one can be in range either.
+                    self.stack.clear();
+                    return None;
+                }
+
+                if trie.terminal.get(node) {
+                    let value = &trie.values[trie.terminal.rank1(node)];
+                    return Some((self.key.clone(), value));
+                }
+            }
+
+            let edges = self.stack.last_mut()?;
+            if edges.start == edges.end {
+                self.stack.pop();
+                self.key.pop();
+                continue;
+            }
+
+            let edge = edges.start;
+            edges.start += 1;
+
+            let node = edge + 1;
+            self.key.push(trie.labels[edge]);
+            self.stack.push(trie.edges(node));
+            self.emit = Some(node);
+        }
+    }
+}
+
+/// Builds a trie from keys in sorted order.
+pub struct TrieBuilder<V> {
+    nodes: Vec<BuildNode<V>>,
+    /// nodes along the last key that was pushed
+    path: Vec<usize>,
+    last: Vec<u8>,
+}
+
+struct BuildNode<V> {
+    children: Vec<(u8, usize)>,
+    value: Option<V>,
+}
+
+impl<V> Default for TrieBuilder<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> TrieBuilder<V> {
+    pub fn new() -> Self {
+        TrieBuilder {
+            nodes: vec![BuildNode {
+                children: Vec::new(),
+                value: None,
+            }],
+            path: vec![0],
+            last: Vec::new(),
+        }
+    }
+
+    /// Push a new key into the trie. Input MUST BE SORTED and must not contain duplicates.
+    pub fn push(&mut self, key: &[u8], value: V) {
+        let is_first = self.nodes.len() == 1 && self.nodes[0].value.is_none();
+        assert!(
+            is_first || key > &self.last[..],
+            "keys must be pushed in sorted order, without duplicates"
+        );
+
+        let prefixlen = common_prefix_len(&self.last, key);
+        self.path.truncate(prefixlen + 1);
+
+        for b in &key[prefixlen..] {
+            let node = self.nodes.len();
+            self.nodes.push(BuildNode {
+                children: Vec::new(),
+                value: None,
+            });
+
+            let parent = *self.path.last().unwrap();
+            self.nodes[parent].children.push((*b, node));
+            self.path.push(node);
+        }
+
+        let node = *self.path.last().unwrap();
+        self.nodes[node].value = Some(value);
+
+        self.last.clear();
+        self.last.extend(key);
+    }
+
+    pub fn finish(self) -> Trie<V> {
+        let mut nodes = self.nodes;
+
+        let mut labels = Vec::with_capacity(nodes.len());
+        let mut louds = BitVec::default();
+        let mut terminal = BitVec::default();
+        let mut values = Vec::new();
+
+        let mut queue = VecDeque::from([0]);
+        while let Some(node) = queue.pop_front() {
+            let node = &mut nodes[node];
+
+            for (label, child) in &node.children {
+                labels.push(*label);
+                louds.push(true);
+                queue.push_back(*child);
+            }
+            louds.push(false);
+
+            let value = node.value.take();
+            terminal.push(value.is_some());
+            values.extend(value);
+
+            // free memory as soon as possible
+            node.children = Vec::new();
+        }
+
+        louds.finish();
+        terminal.finish();
+        values.shrink_to_fit();
+
+        Trie {
+            labels: labels.into_boxed_slice(),
+            louds,
+            terminal,
+            values,
+        }
+    }
+}
+
+fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words() -> Vec<&'static str> {
+        let mut words = "a aal aachen aachiluah ab abandon absence absorb academy accent
+            accompany acid acquit acute adult air am b bar bed ber bet behemoth zz"
+            .split_whitespace()
+            .collect::<Vec<_>>();
+        words.sort();
+        words
+    }
+
+    fn trie() -> Trie<usize> {
+        let mut builder = TrieBuilder::new();
+        for (i, w) in words().into_iter().enumerate() {
+            builder.push(w.as_bytes(), i);
+        }
+        builder.finish()
+    }
+
+    fn keys(iter: Iter<'_, usize>) -> Vec<String> {
+        iter.map(|(k, _)| String::from_utf8(k).unwrap()).collect()
+    }
+
+    #[test]
+    fn lookup() {
+        let trie = trie();
+        assert_eq!(trie.len(), words().len());
+
+        for (i, w) in words().into_iter().enumerate() {
+            assert_eq!(
+                trie.get(w.as_bytes()),
+                Some(&i),
+                "expect key {w} to be in trie"
+            );
+        }
+
+        for missing in ["", "aa", "aachenx", "c", "abandons", "zzz"] {
+            assert_eq!(trie.get(missing.as_bytes()), None, "{missing} is not a key");
+        }
+    }
+
+    #[test]
+    fn iterate_in_order() {
+        let trie = trie();
+        assert_eq!(keys(trie.iter()), words());
+
+        let values = trie.iter().map(|(_, v)| *v).collect::<Vec<_>>();
+        assert_eq!(values, (0..words().len()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn prefix() {
+        let trie = trie();
+
+        assert_eq!(keys(trie.starts_with(b"aac")), ["aachen", "aachiluah"]);
+        assert_eq!(
+            keys(trie.starts_with(b"be")),
+            ["bed", "behemoth", "ber", "bet"]
+        );
+        assert_eq!(keys(trie.starts_with(b"a")).len(), 17);
+        assert_eq!(keys(trie.starts_with(b"bar")), ["bar"]);
+        assert!(keys(trie.starts_with(b"c")).is_empty());
+        assert_eq!(keys(trie.starts_with(b"")), words());
+    }
+
+    #[test]
+    fn range() {
+        let trie = trie();
+
+        let expected = words()
+            .into_iter()
+            .filter(|w| *w >= "abc" && *w < "air")
+            .collect::<Vec<_>>();
+        assert_eq!(keys(trie.range(b"abc", Some(b"air"))), expected);
+
+        assert_eq!(keys(trie.range(b"bed", Some(b"ber"))), ["bed", "behemoth"]);
+        assert_eq!(keys(trie.range(b"bet", None)), ["bet", "zz"]);
+        assert_eq!(keys(trie.range(b"zzz", None)), Vec::<String>::new());
+        assert_eq!(
+            keys(trie.range(b"", Some(b"aal"))),
+            ["a", "aachen", "aachiluah"]
+        );
+    }
+
+    #[test]
+    fn many_keys() {
+        let keys = (0..20_000u32)
+            .map(|i| format!("{i:08}"))
+            .collect::<Vec<_>>();
+
+        let mut builder = TrieBuilder::new();
+        for (i, k) in keys.iter().enumerate() {
+            builder.push(k.as_bytes(), i);
+        }
+        let trie = builder.finish();
+
+        for (i, k) in keys.iter().enumerate() {
+            assert_eq!(trie.get(k.as_bytes()), Some(&i));
+        }
+        assert_eq!(trie.iter().count(), keys.len());
+    }
+
+    #[test]
+    #[should_panic]
+    fn unsorted_input() {
+        let mut builder = TrieBuilder::new();
+        builder.push(b"b", ());
+        builder.push(b"a", ());
+    }
+}
@@ -0,0 +1,303 @@
+/// Decides which keys of a sorted dictionary are part of a search result.
+///
+/// Keys are passed to `matches` in sorted order, which allows matchers to reuse work
+/// between consecutive keys that share a prefix.
+pub trait Matcher {
+    /// Every matching key starts with this prefix.
+    /// Dictionaries only need to scan the keys starting with it.
+    fn prefix(&self) -> &[u8];
+
+    fn matches(&mut self, key: &[u8]) -> bool;
+}
+
+/// All keys starting with a given prefix.
+pub struct Prefix(Vec<u8>);
+
+impl Prefix {
+    pub fn new(prefix: &[u8]) -> Self {
+        Prefix(prefix.to_vec())
+    }
+}
+
+impl Matcher for Prefix {
+    fn prefix(&self) -> &[u8] {
+        &self.0
+    }
+
+    fn matches(&mut self, key: &[u8]) -> bool {
+        key.starts_with(&self.0)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Token {
+    Byte(u8),
+    /// `?`, exactly one byte
+    Any,
+    /// `*`, any number of bytes
+    Many,
+}
+
+/// Glob pattern over bytes. `?` matches a single byte, `*` any number of bytes,
+/// `\` escapes the following byte.
+pub struct Glob {
+    tokens: Vec<Token>,
+    /// literal bytes before the first wildcard
+    prefix: Vec<u8>,
+}
+
+impl Glob {
+    pub fn new(pattern: &[u8]) -> Self {
+        let mut tokens = Vec::with_capacity(pattern.len());
+
+        let mut bytes = pattern.iter();
+        while let Some(b) = bytes.next() {
+            tokens.push(match b {
+                b'*' => Token::Many,
+                b'?' => Token::Any,
+                // a trailing backslash matches itself
+                b'\\' => Token::Byte(*bytes.next().unwrap_or(&b'\\')),
+                b => Token::Byte(*b),
+            });
+        }
+
+        let prefix = tokens
+            .iter()
+            .map_while(|t| match t {
+                Token::Byte(b) => Some(*b),
+                _ => None,
+            })
+            .collect();
+
+        Glob { tokens, prefix }
+    }
+
+    pub fn is_match(&self, key: &[u8]) -> bool {
+        let tokens = &self.tokens;
+
+        let (mut t, mut k) = (0, 0);
+        // position after the last `*` and the key position it was tried at
+        let mut backtrack = None;
+
+        while k < key.len() {
+            match tokens.get(t) {
+                Some(Token::Many) => {
+                    t += 1;
+                    backtrack = Some((t, k));
+                    continue;
+                }
+                Some(Token::Any) => {
+                    t += 1;
+                    k += 1;
+                    continue;
+                }
+                Some(Token::Byte(b)) if *b == key[k] => {
+                    t += 1;
+                    k += 1;
+                    continue;
+                }
+                _ => {}
+            }
+
+            // mismatch, let the last `*` consume one more byte
+            match backtrack {
+                Some((bt, bk)) => {
+                    t = bt;
+                    k = bk + 1;
+                    backtrack = Some((bt, bk + 1));
+                }
+                None => return false,
+            }
+        }
+
+        tokens[t..].iter().all(|t| *t == Token::Many)
+    }
+}
+
+impl Matcher for Glob {
+    fn prefix(&self) -> &[u8] {
+        &self.prefix
+    }
+
+    fn matches(&mut self, key: &[u8]) -> bool {
+        self.is_match(key)
+    }
+}
+
+/// All keys within a Levenshtein (edit) distance of at most `k` bytes to the query.
+///
+/// Works like a Levenshtein automaton: each state is one row of the edit distance matrix.
+/// The rows of the prefix shared with the previous key are kept, so a sorted scan only
+/// computes rows for the bytes that changed.
+/// Once a row exceeds `k` everywhere, all keys sharing that prefix are rejected without any work.
+pub struct Levenshtein {
+    query: Vec<u8>,
+    k: u32,
+    /// rows[i] is the state after the first i bytes of `last`
+    rows: Vec<Vec<u32>>,
+    last: Vec<u8>,
+    /// length of the shortest prefix of `last` that can't lead to a match anymore
+    dead: Option<usize>,
+}
+
+impl Levenshtein {
+    pub fn new(query: &[u8], k: u32) -> Self {
+        let start = (0..=query.len() as u32).collect();
+
+        Levenshtein {
+            query: query.to_vec(),
+            k,
+            rows: vec![start],
+            last: Vec::new(),
+            dead: None,
+        }
+    }
+
+    fn step(&self, row: &[u32], b: u8) -> Vec<u32> {
+        let mut next = Vec::with_capacity(row.len());
+        next.push(row[0] + 1);
+
+        for (j, q) in self.query.iter().enumerate() {
+            let substitution = row[j] + (*q != b) as u32;
+            let deletion = row[j + 1] + 1;
+            let insertion = next[j] + 1;
+            next.push(substitution.min(deletion).min(insertion));
+        }
+
+        next
+    }
+
+    /// Edit distance of `key` to the query, `None` if it is greater than `k`.
+    pub fn distance(&mut self, key: &[u8]) -> Option<u32> {
+        let shared = common_prefix_len(&self.last, key).min(self.rows.len() - 1);
+
+        self.last.clear();
+        self.last.extend(key);
+
+        match self.dead {
+            Some(dead) if dead <= shared => return None,
+            _ => self.dead = None,
+        }
+
+        self.rows.truncate(shared + 1);
+        for b in &key[shared..] {
+            let row = self.step(self.rows.last().unwrap(), *b);
+            let alive = row.iter().any(|d| *d <= self.k);
+            self.rows.push(row);
+
+            if !alive {
+                self.dead = Some(self.rows.len() - 1);
+                return None;
+            }
+        }
+
+        let distance = *self.rows.last().unwrap().last().unwrap();
+        (distance <= self.k).then_some(distance)
+    }
+}
+
+impl Matcher for Levenshtein {
+    fn prefix(&self) -> &[u8] {
+        // the first bytes of the query might be the ones that were misspelled
+        &[]
+    }
+
+    fn matches(&mut self, key: &[u8]) -> bool {
+        self.distance(key).is_some()
+    }
+}
+
+fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words() -> Vec<&'static str> {
+        let mut words = "a aal aachen aachiluah ab abandon absence absorb academy accent
+            accompany acid acquit acute adult air am b bar bed ber bet behemoth zz"
+            .split_whitespace()
+            .collect::<Vec<_>>();
+        words.sort();
+        words
+    }
+
+    fn search(mut matcher: impl Matcher) -> Vec<&'static str> {
+        words()
+            .into_iter()
+            .filter(|w| matcher.matches(w.as_bytes()))
+            .collect()
+    }
+
+    /// textbook dynamic programming, to compare the automaton against
+    fn edit_distance(a: &[u8], b: &[u8]) -> u32 {
+        let mut row = (0..=b.len() as u32).collect::<Vec<_>>();
+        for (i, x) in a.iter().enumerate() {
+            let mut next = vec![i as u32 + 1];
+            for (j, y) in b.iter().enumerate() {
+                next.push(
+                    (row[j] + (x != y) as u32)
+                        .min(row[j + 1] + 1)
+                        .min(next[j] + 1),
+                );
+            }
+            row = next;
+        }
+        row[b.len()]
+    }
+
+    #[test]
+    fn prefix() {
+        assert_eq!(search(Prefix::new(b"aac")), ["aachen", "aachiluah"]);
+        assert_eq!(search(Prefix::new(b"")), words());
+    }
+
+    #[test]
+    fn glob() {
+        assert_eq!(search(Glob::new(b"a*n")), ["aachen", "abandon"]);
+        assert_eq!(search(Glob::new(b"b?t")), ["bet"]);
+        assert_eq!(search(Glob::new(b"be*")), ["bed", "behemoth", "ber", "bet"]);
+        assert_eq!(search(Glob::new(b"*c*t")), ["accent", "acquit"]);
+        assert_eq!(search(Glob::new(b"*")), words());
+        assert_eq!(search(Glob::new(b"a?")), ["ab", "am"]);
+
+        assert!(Glob::new(b"a\\*").is_match(b"a*"));
+        assert!(!Glob::new(b"a\\*").is_match(b"ab"));
+        assert!(Glob::new(b"**a**").is_match(b"a"));
+        assert!(!Glob::new(b"").is_match(b"a"));
+    }
+
+    #[test]
+    fn glob_prefix() {
+        assert_eq!(Glob::new(b"aac*en").prefix(), b"aac");
+        assert_eq!(Glob::new(b"?ac").prefix(), b"");
+        assert_eq!(Glob::new(b"a\\?b").prefix(), b"a?b");
+    }
+
+    #[test]
+    fn fuzzy() {
+        assert_eq!(search(Levenshtein::new(b"bat", 1)), ["bar", "bet"]);
+        assert_eq!(search(Levenshtein::new(b"aachem", 1)), ["aachen"]);
+        assert_eq!(search(Levenshtein::new(b"zz", 0)), ["zz"]);
+    }
+
+    #[test]
+    fn fuzzy_equals_edit_distance() {
+        for query in ["", "a", "abc", "bet", "acadamy", "behmoth", "zzz"] {
+            for k in 0..4 {
+                let expected = words()
+                    .into_iter()
+                    .filter(|w| edit_distance(w.as_bytes(), query.as_bytes()) <= k)
+                    .collect::<Vec<_>>();
+
+                assert_eq!(
+                    search(Levenshtein::new(query.as_bytes(), k)),
+                    expected,
+                    "keys within distance {k} of {query}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+
+/// Number of symbols in a table, the remaining code is reserved for escapes.
+pub const MAX_SYMBOLS: usize = 255;
+
+/// Followed by a single byte, that is not part of the symbol table.
+const ESCAPE: u8 = 255;
+
+const MAX_SYMBOL_LEN: usize = 8;
+
+/// Rounds of training. Each round compresses the sample with the previous table
+/// and keeps the symbols (and concatenations of adjacent symbols) with the highest gain.
+const GENERATIONS: usize = 5;
+
+/// Static symbol table (FSST). Up to 255 symbols of 1 to 8 bytes are replaced by a single byte code.
+///
+/// Every string gets compressed on its own, so any string can be decompressed individually,
+/// as long as the table is at hand.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolTable {
+    symbols: Vec<Box<[u8]>>,
+    /// codes of all symbols starting with a given byte, longest symbols first
+    by_first: Vec<Vec<u8>>,
+}
+
+impl SymbolTable {
+    /// Trains a table on a sample of the strings that will be compressed.
+    pub fn train<S: AsRef<[u8]>>(sample: &[S]) -> Self {
+        let mut table = SymbolTable::default();
+
+        for _ in 0..GENERATIONS {
+            let mut counts: HashMap<Vec<u8>, usize> = HashMap::new();
+
+            for s in sample {
+                let mut s = s.as_ref();
+                let mut prev: Option<&[u8]> = None;
+
+                while !s.is_empty() {
+                    let symbol = match table.longest_match(s) {
+                        Some(code) => &table.symbols[code as usize] as &[u8],
+                        None => &s[..1],
+                    };
+
+                    *counts.entry(symbol.to_vec()).or_default() += 1;
+                    if symbol.len() > 1 {
+                        // single bytes need to stay candidates, or they end up escaped
+                        *counts.entry(s[..1].to_vec()).or_default() += 1;
+                    }
+
+                    if let Some(prev) = prev {
+                        let mut concat = prev.to_vec();
+                        concat.extend(symbol);
+                        concat.truncate(MAX_SYMBOL_LEN);
+                        *counts.entry(concat).or_default() += 1;
+                    }
+
+                    prev = Some(symbol);
+                    s = &s[symbol.len()..];
+                }
+            }
+
+            let mut candidates = counts
+                .into_iter()
+                .map(|(symbol, count)| (count * symbol.len(), symbol))
+                .collect::<Vec<_>>();
+
+            // highest gain first, ties are broken by the symbol so training is deterministic
+            candidates.sort_unstable_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+            candidates.truncate(MAX_SYMBOLS);
+
+            table = SymbolTable::new(candidates.into_iter().map(|(_, symbol)| symbol));
+        }
+
+        table
+    }
+
+    fn new(symbols: impl Iterator<Item = Vec<u8>>) -> Self {
+        let symbols = symbols.map(Vec::into_boxed_slice).collect::<Vec<_>>();
+        assert!(symbols.len() <= MAX_SYMBOLS, "too many symbols");
+
+        let mut by_first = vec![Vec::new(); 256];
+        for (code, symbol) in symbols.iter().enumerate() {
+            assert!(
+                !symbol.is_empty() && symbol.len() <= MAX_SYMBOL_LEN,
+                "invalid symbol length"
+            );
+            by_first[symbol[0] as usize].push(code as u8);
+        }
+
+        for codes in &mut by_first {
+            codes.sort_by_key(|code| std::cmp::Reverse(symbols[*code as usize].len()));
+        }
+
+        SymbolTable { symbols, by_first }
+    }
+
+    /// Number of symbols
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    fn longest_match(&self, s: &[u8]) -> Option<u8> {
+        let codes = self.by_first.get(s[0] as usize)?;
+        codes
+            .iter()
+            .copied()
+            .find(|code| s.starts_with(&self.symbols[*code as usize]))
+    }
+
+    /// Appends the compressed string to `out`.
+    pub fn compress_into(&self, mut s: &[u8], out: &mut Vec<u8>) {
+        while !s.is_empty() {
+            match self.longest_match(s) {
+                Some(code) => {
+                    out.push(code);
+                    s = &s[self.symbols[code as usize].len()..];
+                }
+                None => {
+                    out.push(ESCAPE);
+                    out.push(s[0]);
+                    s = &s[1..];
+                }
+            }
+        }
+    }
+
+    pub fn compress(&self, s: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(s.len());
+        self.compress_into(s, &mut out);
+        out
+    }
+
+    /// Appends the decompressed string to `out`.
+    pub fn decompress_into(&self, codes: &[u8], out: &mut Vec<u8>) {
+        self.try_decompress_into(codes, out)
+            .expect("codes of this table");
+    }
+
+    /// Appends the decompressed string to `out`, `None` if the codes were not compressed with this table.
+    pub fn try_decompress_into(&self, codes: &[u8], out: &mut Vec<u8>) -> Option<()> {
+        let mut codes = codes.iter();
+        while let Some(code) = codes.next() {
+            if *code == ESCAPE {
+                out.push(*codes.next()?);
+            } else {
+                out.extend(&**self.symbols.get(*code as usize)?);
+            }
+        }
+        Some(())
+    }
+
+    pub fn decompress(&self, codes: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(codes.len() * 2);
+        self.decompress_into(codes, &mut out);
+        out
+    }
+
+    pub fn try_decompress(&self, codes: &[u8]) -> Option<Vec<u8>> {
+        let mut out = Vec::with_capacity(codes.len() * 2);
+        self.try_decompress_into(codes, &mut out)?;
+        Some(out)
+    }
+
+    /// Serializes the table, so compressed strings can be stored along with it.
+    pub fn write(&self, out: &mut Vec<u8>) {
+        /*
+            Scheme: <u8 symbol count>
+            for each symbol:
+                <u8 len>bytes
+        */
+        out.push(self.symbols.len() as u8);
+        for symbol in &self.symbols {
+            out.push(symbol.len() as u8);
+            out.extend(&**symbol);
+        }
+    }
+
+    /// Reads a table written by `write`, returns the remaining data.
+    pub fn read(data: &[u8]) -> (Self, &[u8]) {
+        Self::try_read(data).expect("a symbol table")
+    }
+
+    /// Reads a table written by `write`, `None` if `data` does not start with a valid table.
+    pub fn try_read(data: &[u8]) -> Option<(Self, &[u8])> {
+        let (&count, mut data) = data.split_first()?;
+
+        let mut symbols = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let (&len, rest) = data.split_first()?;
+            let len = len as usize;
+            if !(1..=MAX_SYMBOL_LEN).contains(&len) || rest.len() < len {
+                return None;
+            }
+            symbols.push(rest[..len].to_vec());
+            data = &rest[len..];
+        }
+
+        Some((SymbolTable::new(symbols.into_iter()), data))
+    }
+
+    /// Approximate number of bytes used on the heap.
+    pub fn heap_size(&self) -> usize {
+        self.symbols.iter().map(|s| s.len() + 16).sum::<usize>()
+            + self
+                .by_first
+                .iter()
+                .map(|c| c.capacity() + 24)
+                .sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corpus() -> Vec<String> {
+        let mut keys = Vec::new();
+        for i in 0..2000 {
+            keys.push(format!("{}", i * 37));
+            keys.push(format!("id-{:06}", i * 13));
+            keys.push(format!("https://example.org/item/{i}"));
+            keys.push(format!("{}.{:02} eur", i % 97, i % 100));
+        }
+        keys
+    }
+
+    #[test]
+    fn roundtrip() {
+        let keys = corpus();
+        let table = SymbolTable::train(&keys);
+
+        let mut unusual = keys.clone();
+        unusual.extend(["", "ä", "\u{ff}\u{fe}", "never seen in training"].map(String::from));
+
+        for k in &unusual {
+            let compressed = table.compress(k.as_bytes());
+            assert_eq!(
+                table.decompress(&compressed),
+                k.as_bytes(),
+                "roundtrip of {k}"
+            );
+        }
+
+        // bytes that are not valid utf-8 as well
+        let bytes = (0..=255u8).collect::<Vec<_>>();
+        assert_eq!(table.decompress(&table.compress(&bytes)), bytes);
+    }
+
+    #[test]
+    fn compresses_trained_corpus() {
+        let keys = corpus();
+        let table = SymbolTable::train(&keys[..1000]);
+        assert!(table.len() <= MAX_SYMBOLS);
+
+        let original = keys.iter().map(|k| k.len()).sum::<usize>();
+        let compressed = keys
+            .iter()
+            .map(|k| table.compress(k.as_bytes()).len())
+            .sum::<usize>();
+
+        assert!(
+            compressed * 2 < original,
+            "expected at least 50% compression: {compressed} of {original}"
+        );
+    }
+
+    #[test]
+    fn empty_table_escapes_everything() {
+        let table = SymbolTable::default();
+        assert_eq!(table.compress(b"ab"), [ESCAPE, b'a', ESCAPE, b'b']);
+        assert_eq!(table.decompress(&[ESCAPE, b'a', ESCAPE, b'b']), b"ab");
+    }
+
+    #[test]
+    fn serialization() {
+        let table = SymbolTable::train(&corpus()[..500]);
+
+        let mut data = Vec::new();
+        table.write(&mut data);
+        data.extend(b"rest");
+
+        let (read, rest) = SymbolTable::read(&data);
+        assert_eq!(read, table);
+        assert_eq!(rest, b"rest");
+    }
+
+    #[test]
+    fn damaged_data() {
+        let table = SymbolTable::train(&corpus()[..500]);
+        let mut data = Vec::new();
+        table.write(&mut data);
+
+        assert!(SymbolTable::try_read(&data[..data.len() - 1]).is_none());
+        assert!(SymbolTable::try_read(&[1, 0]).is_none());
+        assert!(SymbolTable::try_read(&[]).is_none());
+
+        // codes beyond the symbols and an escape at the end
+        let table = SymbolTable::try_read(&[1, 2, b'a', b'b']).unwrap().0;
+        assert_eq!(table.try_decompress(&[0, ESCAPE, b'c']).unwrap(), b"abc");
+        assert_eq!(table.try_decompress(&[1]), None);
+        assert_eq!(table.try_decompress(&[0, ESCAPE]), None);
+    }
+}
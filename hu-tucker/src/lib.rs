@@ -0,0 +1,303 @@
+/// The terminator and all 256 byte values.
+const SYMBOLS: usize = 257;
+
+/// Marks a leaf in the decoding tree, the remaining bits are the symbol.
+const LEAF: u32 = 1 << 31;
+
+/// Order preserving compression of byte strings, using an optimal alphabetic (Hu-Tucker) code.
+///
+/// Every byte gets a prefix free code of variable length. Unlike a Huffman code,
+/// codes are assigned in the order of the bytes, so the compressed strings compare
+/// (as bytes) in the same order as the original strings. Sorted data stays sorted
+/// and can be searched without decompressing it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlphabeticCode {
+    /// code length of every symbol, the terminator is symbol 0, byte b is symbol b + 1
+    depths: Box<[u8]>,
+    /// codes, right aligned
+    codes: Box<[u64]>,
+    /// decoding tree, children of every inner node
+    tree: Vec<[u32; 2]>,
+}
+
+impl AlphabeticCode {
+    /// Builds the optimal alphabetic code for the byte frequencies of a sample.
+    /// Bytes that do not occur in the sample still get a (long) code.
+    pub fn train<S: AsRef<[u8]>>(sample: &[S]) -> Self {
+        let mut weights = vec![1u64; SYMBOLS];
+        for s in sample {
+            weights[0] += 1;
+            for b in s.as_ref() {
+                weights[*b as usize + 1] += 1;
+            }
+        }
+
+        AlphabeticCode::from_depths(depths(&weights))
+    }
+
+    /// Codes of an alphabetic tree follow from the depths of its leaves, read from left to right.
+    fn from_depths(depths: Box<[u8]>) -> Self {
+        assert_eq!(depths.len(), SYMBOLS, "depth for every symbol");
+
+        let mut codes = Vec::with_capacity(SYMBOLS);
+        let mut code = 0u64;
+        let mut depth = depths[0];
+        codes.push(code);
+
+        for d in depths[1..].iter().copied() {
+            code += 1;
+            if d >= depth {
+                code <<= d - depth;
+            } else {
+                code >>= depth - d;
+            }
+            depth = d;
+            codes.push(code);
+        }
+
+        let mut tree = vec![[0u32; 2]];
+        for (symbol, (code, depth)) in codes.iter().zip(depths.iter()).enumerate() {
+            let mut node = 0;
+            for i in (0..*depth).rev() {
+                let bit = (code >> i & 1) as usize;
+                if i == 0 {
+                    tree[node][bit] = LEAF | symbol as u32;
+                } else {
+                    if tree[node][bit] == 0 {
+                        tree.push([0, 0]);
+                        tree[node][bit] = (tree.len() - 1) as u32;
+                    }
+                    node = tree[node][bit] as usize;
+                }
+            }
+        }
+
+        AlphabeticCode {
+            depths,
+            codes: codes.into_boxed_slice(),
+            tree,
+        }
+    }
+
+    /// Appends the compressed string to `out`.
+    /// The string is terminated, so a string and its extensions stay distinguishable.
+    pub fn encode_into(&self, s: &[u8], out: &mut Vec<u8>) {
+        let mut writer = BitWriter {
+            out,
+            buffer: 0,
+            len: 0,
+        };
+        for b in s {
+            let symbol = *b as usize + 1;
+            writer.write(self.codes[symbol], self.depths[symbol]);
+        }
+        writer.write(self.codes[0], self.depths[0]);
+        writer.flush();
+    }
+
+    pub fn encode(&self, s: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(s.len());
+        self.encode_into(s, &mut out);
+        out
+    }
+
+    /// Appends the decompressed string to `out`.
+    pub fn decode_into(&self, data: &[u8], out: &mut Vec<u8>) {
+        let mut node = 0;
+        for byte in data {
+            for i in (0..8).rev() {
+                let child = self.tree[node][(byte >> i & 1) as usize];
+                if child & LEAF == 0 {
+                    node = child as usize;
+                    continue;
+                }
+
+                match child & !LEAF {
+                    0 => return,
+                    symbol => out.push((symbol - 1) as u8),
+                }
+                node = 0;
+            }
+        }
+
+        unreachable!("terminator at the end of the data");
+    }
+
+    pub fn decode(&self, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(data.len() * 2);
+        self.decode_into(data, &mut out);
+        out
+    }
+
+    /// Serializes the code.
+    pub fn write(&self, out: &mut Vec<u8>) {
+        /*
+            Scheme: <u8 depth> for every symbol, terminator first
+        */
+        out.extend(&*self.depths);
+    }
+
+    /// Reads a code written by `write`, returns the remaining data.
+    pub fn read(data: &[u8]) -> (Self, &[u8]) {
+        let (depths, data) = data.split_at(SYMBOLS);
+        (AlphabeticCode::from_depths(depths.into()), data)
+    }
+
+    /// Approximate number of bytes used on the heap.
+    pub fn heap_size(&self) -> usize {
+        self.depths.len() + self.codes.len() * 8 + self.tree.capacity() * 8
+    }
+}
+
+/// Depths of the leaves of an optimal alphabetic tree, in O(n²) using Knuth's bound on the roots.
+fn depths(weights: &[u64]) -> Box<[u8]> {
+    let n = weights.len();
+
+    let mut prefix = vec![0u64; n + 1];
+    for (i, w) in weights.iter().enumerate() {
+        prefix[i + 1] = prefix[i] + w;
+    }
+
+    // cost[i][j] of the optimal tree over the leaves i..=j, whose left subtree ends at root[i][j]
+    let mut cost = vec![vec![0u64; n]; n];
+    let mut root = vec![vec![0usize; n]; n];
+    for (i, r) in root.iter_mut().enumerate() {
+        r[i] = i;
+    }
+
+    for len in 2..=n {
+        for i in 0..=n - len {
+            let j = i + len - 1;
+
+            let lo = root[i][j - 1];
+            let hi = root[i + 1][j].min(j - 1);
+
+            let (best, k) = (lo..=hi)
+                .map(|k| (cost[i][k] + cost[k + 1][j], k))
+                .min()
+                .unwrap();
+
+            cost[i][j] = best + prefix[j + 1] - prefix[i];
+            root[i][j] = k;
+        }
+    }
+
+    let mut depths = vec![0u8; n];
+    let mut stack = vec![(0, n - 1, 0u32)];
+    while let Some((i, j, depth)) = stack.pop() {
+        if i == j {
+            assert!(depth <= 64, "codes are limited to 64 bits");
+            depths[i] = depth as u8;
+            continue;
+        }
+
+        let k = root[i][j];
+        stack.push((i, k, depth + 1));
+        stack.push((k + 1, j, depth + 1));
+    }
+
+    depths.into_boxed_slice()
+}
+
+struct BitWriter<'a> {
+    out: &'a mut Vec<u8>,
+    buffer: u8,
+    /// number of bits in the buffer
+    len: u8,
+}
+
+impl BitWriter<'_> {
+    fn write(&mut self, code: u64, depth: u8) {
+        for i in (0..depth).rev() {
+            self.buffer = self.buffer << 1 | (code >> i & 1) as u8;
+            self.len += 1;
+
+            if self.len == 8 {
+                self.out.push(self.buffer);
+                self.buffer = 0;
+                self.len = 0;
+            }
+        }
+    }
+
+    /// Pads the last byte with zeros.
+    fn flush(self) {
+        if self.len > 0 {
+            self.out.push(self.buffer << (8 - self.len));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corpus() -> Vec<String> {
+        let mut keys = Vec::new();
+        for i in 0..2000 {
+            keys.push(format!("{}", i * 37));
+            keys.push(format!("id-{:06}", i * 13));
+            keys.push(format!("https://example.org/item/{i}"));
+            keys.push(format!("{}.{:02} eur", i % 97, i % 100));
+        }
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+
+    #[test]
+    fn roundtrip() {
+        let code = AlphabeticCode::train(&corpus()[..500]);
+
+        let mut keys = corpus();
+        keys.extend(["", "ä", "never seen in training"].map(String::from));
+        for k in &keys {
+            assert_eq!(code.decode(&code.encode(k.as_bytes())), k.as_bytes());
+        }
+
+        let bytes = (0..=255u8).collect::<Vec<_>>();
+        assert_eq!(code.decode(&code.encode(&bytes)), bytes);
+    }
+
+    #[test]
+    fn preserves_order() {
+        let keys = corpus();
+        let code = AlphabeticCode::train(&keys);
+
+        let mut edge_cases = ["", "\0", "\0\0", "a", "a\0", "ab", "b", "\u{ff}"]
+            .map(|s| s.as_bytes().to_vec())
+            .to_vec();
+        edge_cases.extend(keys.iter().map(|k| k.as_bytes().to_vec()));
+        edge_cases.sort();
+
+        let encoded = edge_cases
+            .iter()
+            .map(|k| code.encode(k))
+            .collect::<Vec<_>>();
+        for (i, pair) in encoded.windows(2).enumerate() {
+            assert!(
+                pair[0] < pair[1],
+                "{:?} < {:?}",
+                edge_cases[i],
+                edge_cases[i + 1]
+            );
+        }
+
+        let original = keys.iter().map(|k| k.len()).sum::<usize>();
+        let compressed = encoded.iter().map(|k| k.len()).sum::<usize>();
+        assert!(compressed < original, "{compressed} of {original}");
+    }
+
+    #[test]
+    fn serialization() {
+        let code = AlphabeticCode::train(&corpus());
+
+        let mut data = Vec::new();
+        code.write(&mut data);
+        data.extend(b"rest");
+
+        let (read, rest) = AlphabeticCode::read(&data);
+        assert_eq!(read, code);
+        assert_eq!(rest, b"rest");
+    }
+}
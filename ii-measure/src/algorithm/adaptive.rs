@@ -0,0 +1,421 @@
+use fastpfor::Codec;
+use roaring_bitmap::GroupedBitmaps;
+
+use crate::inverted_index::InvertedIndex;
+use crate::table_lake::*;
+use std::collections::HashMap;
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+
+/// Posting lists with at most this many entries are stored inside the dictionary slot.
+const INLINE_LIMIT: usize = 2;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Encoding {
+    Inline,
+    VarInt,
+    GroupVarInt,
+    FastPfor,
+    Bitmap,
+}
+
+impl Encoding {
+    const ALL: [Encoding; 5] = [
+        Encoding::Inline,
+        Encoding::VarInt,
+        Encoding::GroupVarInt,
+        Encoding::FastPfor,
+        Encoding::Bitmap,
+    ];
+
+    fn from(v: u8) -> Encoding {
+        Encoding::ALL
+            .get(v as usize)
+            .copied()
+            .expect("valid encoding tag")
+    }
+
+    fn byte(self) -> u8 {
+        self as u8
+    }
+
+    fn str(self) -> &'static str {
+        match self {
+            Encoding::Inline => "inline",
+            Encoding::VarInt => "varint",
+            Encoding::GroupVarInt => "group varint",
+            Encoding::FastPfor => "fastpfor",
+            Encoding::Bitmap => "bitmap",
+        }
+    }
+}
+
+/// Entry of the dictionary. Very short posting lists don't need to go to the arena at all.
+#[derive(Copy, Clone)]
+enum Slot {
+    Inline {
+        len: u8,
+        locations: [TableLocation; INLINE_LIMIT],
+    },
+    /// Position of the tag byte inside the arena.
+    /// Scheme: <tag> <varint>length data
+    Arena(usize),
+}
+
+/// Picks the smallest of several encodings for each posting list individually.
+pub struct IIAdaptive {
+    ii: HashMap<String, Slot>,
+    arena: Vec<u8>,
+    codec: Codec,
+}
+
+/// Encodes posting lists and keeps track of which encoding was chosen how often.
+struct Encoder {
+    codec: Codec,
+    decisions: [usize; Encoding::ALL.len()],
+    buffer: Vec<u32>,
+}
+
+impl Encoder {
+    fn encode(&mut self, locations: &mut Vec<TableLocation>, arena: &mut Vec<u8>) -> Slot {
+        // posting lists are sets, sorting allows for d-gaps of the tableids
+        locations.sort_unstable();
+        locations.dedup();
+
+        if locations.len() <= INLINE_LIMIT {
+            self.decisions[Encoding::Inline as usize] += 1;
+
+            let mut inline = [TableLocation {
+                tableid: 0,
+                colid: 0,
+                rowid: 0,
+            }; INLINE_LIMIT];
+            inline[..locations.len()].copy_from_slice(locations);
+
+            return Slot::Inline {
+                len: locations.len() as u8,
+                locations: inline,
+            };
+        }
+
+        let candidates = [
+            (Encoding::VarInt, varint_encode(locations)),
+            (Encoding::GroupVarInt, group_varint_encode(locations)),
+            (Encoding::FastPfor, self.fastpfor_encode(locations)),
+            (Encoding::Bitmap, bitmap_encode(locations)),
+        ];
+
+        let (encoding, data) = candidates
+            .into_iter()
+            .min_by_key(|(_, data)| data.len())
+            .unwrap();
+
+        self.decisions[encoding as usize] += 1;
+
+        let start = arena.len();
+        arena.push(encoding.byte());
+        arena.extend(vbyte::compress(data.len() as u64));
+        arena.extend(data);
+
+        Slot::Arena(start)
+    }
+
+    /*
+        Scheme: <varint>count
+        for ids in [tableids, colids, rowids]:
+            <varint>length of compressed ids (in u32 words)
+        compressed words, little endian
+
+        SIMDFastPFor pads its output relative to 16 byte alignment, so the ids are compressed one after another
+        into a buffer starting at an aligned word and have to be decompressed from an aligned copy.
+    */
+    fn fastpfor_encode(&mut self, locations: &[TableLocation]) -> Vec<u8> {
+        let mut header = vbyte::compress(locations.len() as u64);
+
+        self.buffer.clear();
+        self.buffer.resize(3 * (locations.len() * 2 + 1024) + 4, 0);
+        let start = self.buffer.as_ptr().align_offset(16);
+        let mut offset = start;
+
+        let mut ids = Vec::with_capacity(locations.len());
+        for i in 0..3 {
+            ids.clear();
+            ids.extend(locations.iter().map(|l| l.integers()[i]));
+
+            let written = self
+                .codec
+                .compress(&ids, &mut self.buffer[offset..])
+                .expect("no buffer overflow");
+
+            header.extend(vbyte::compress(written as u64));
+            offset += written;
+        }
+
+        header.extend(
+            self.buffer[start..offset]
+                .iter()
+                .flat_map(|w| w.to_le_bytes()),
+        );
+        header
+    }
+}
+
+/// Tableids d-gaps, colids and rowids interleaved
+fn gapped_integers(locations: &[TableLocation]) -> impl Iterator<Item = u32> + '_ {
+    let mut last = 0;
+    locations.iter().flat_map(move |l| {
+        let gap = l.tableid - last;
+        last = l.tableid;
+        [gap, l.colid, l.rowid]
+    })
+}
+
+fn from_gapped_integers(ints: &[u32], count: usize) -> Vec<TableLocation> {
+    let mut locations = Vec::with_capacity(count);
+    let mut tableid = 0;
+    for i in 0..count {
+        tableid += ints[i * 3];
+        locations.push(TableLocation {
+            tableid,
+            colid: ints[i * 3 + 1],
+            rowid: ints[i * 3 + 2],
+        });
+    }
+
+    locations
+}
+
+fn varint_encode(locations: &[TableLocation]) -> Vec<u8> {
+    let ints = gapped_integers(locations)
+        .map(u64::from)
+        .collect::<Vec<_>>();
+    vbyte::compress_list(&ints)
+}
+
+/// Scheme: <varint>count, gve data
+fn group_varint_encode(locations: &[TableLocation]) -> Vec<u8> {
+    let mut data = vbyte::compress(locations.len() as u64);
+    data.extend(group_varint_encoding::compress(gapped_integers(locations)));
+    data
+}
+
+fn bitmap_encode(locations: &[TableLocation]) -> Vec<u8> {
+    let bitmaps: GroupedBitmaps = locations
+        .iter()
+        .map(|l| ((l.tableid as u64) << 32 | l.colid as u64, l.rowid))
+        .collect();
+
+    let mut data = Vec::new();
+    bitmaps.write(&mut data);
+    data
+}
+
+pub fn adaptive(receiver: Receiver<(String, TableLocation)>) -> (usize, Duration, IIAdaptive) {
+    let mut encoder = Encoder {
+        codec: Codec::simdfastpfor128(),
+        decisions: [0; Encoding::ALL.len()],
+        buffer: Vec::new(),
+    };
+
+    let mut ii = HashMap::new();
+    let mut arena = Vec::new();
+    let mut build_time = Duration::new(0, 0);
+
+    let mut count = 1;
+
+    let (mut curr_key, loc) = receiver.recv().expect("first item");
+    let mut group = vec![loc];
+
+    for (key, location) in receiver {
+        count += 1;
+        let starttime = Instant::now();
+
+        if key != curr_key {
+            let slot = encoder.encode(&mut group, &mut arena);
+            ii.insert(curr_key, slot);
+
+            curr_key = key;
+            group.clear();
+        }
+
+        group.push(location);
+
+        build_time += starttime.elapsed();
+    }
+
+    {
+        let starttime = Instant::now();
+
+        let slot = encoder.encode(&mut group, &mut arena);
+        ii.insert(curr_key, slot);
+
+        arena.shrink_to_fit();
+        ii.shrink_to_fit();
+
+        build_time += starttime.elapsed();
+    }
+
+    eprintln!("posting list encodings:");
+    for encoding in Encoding::ALL {
+        let n = encoder.decisions[encoding as usize];
+        eprintln!("{:>14}: {n}", encoding.str());
+    }
+
+    let ii = IIAdaptive {
+        ii,
+        arena,
+        codec: encoder.codec,
+    };
+
+    (count, build_time, ii)
+}
+
+impl IIAdaptive {
+    fn decode(&self, start: usize) -> Vec<TableLocation> {
+        let encoding = Encoding::from(self.arena[start]);
+        let (len, data) = vbyte::decompress(&self.arena[start + 1..]).expect("decompress length");
+        let data = &data[..len as usize];
+
+        match encoding {
+            Encoding::Inline => unreachable!("inline posting lists are not stored in the arena"),
+            Encoding::VarInt => {
+                let ints = vbyte::decompress_list(data).expect("decompress varints");
+                let ints = ints.into_iter().map(|i| i as u32).collect::<Vec<_>>();
+                from_gapped_integers(&ints, ints.len() / 3)
+            }
+            Encoding::GroupVarInt => {
+                let (count, data) = vbyte::decompress(data).expect("decompress count");
+                let ints = group_varint_encoding::decompress(data).collect();
+                from_gapped_integers(&ints, count as usize)
+            }
+            Encoding::FastPfor => {
+                let (count, mut data) = vbyte::decompress(data).expect("decompress count");
+                let count = count as usize;
+
+                let mut lengths = [0; 3];
+                for length in lengths.iter_mut() {
+                    let (l, rest) = vbyte::decompress(data).expect("decompress length");
+                    *length = l as usize;
+                    data = rest;
+                }
+
+                // aligned like the buffer the ids were compressed into
+                let mut words = vec![0u32; data.len() / 4 + 4];
+                let start = words.as_ptr().align_offset(16);
+                for (word, bytes) in words[start..].iter_mut().zip(data.chunks_exact(4)) {
+                    *word = u32::from_le_bytes(bytes.try_into().unwrap());
+                }
+
+                let mut offset = start;
+                let ids = lengths.map(|length| {
+                    let mut destination = vec![0; count];
+                    self.codec
+                        .decompress(&words[offset..offset + length], &mut destination)
+                        .expect("decompress data");
+                    offset += length;
+                    destination
+                });
+
+                (0..count)
+                    .map(|i| TableLocation::from_integers(&[ids[0][i], ids[1][i], ids[2][i]]))
+                    .collect()
+            }
+            Encoding::Bitmap => {
+                let (bitmaps, _) = GroupedBitmaps::read(data);
+                bitmaps
+                    .iter()
+                    .map(|(group, rowid)| TableLocation {
+                        tableid: (group >> 32) as u32,
+                        colid: group as u32,
+                        rowid,
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+impl InvertedIndex<Option<Vec<TableLocation>>> for IIAdaptive {
+    fn get(&self, key: &str) -> Option<Vec<TableLocation>> {
+        let locations = match *self.ii.get(key)? {
+            Slot::Inline { len, locations } => locations[..len as usize].to_vec(),
+            Slot::Arena(start) => self.decode(start),
+        };
+        Some(locations)
+    }
+}
+
+impl crate::util::RandomKeys for IIAdaptive {
+    fn random_keys_potentially_ordered(&self) -> Vec<String> {
+        self.ii.random_keys_potentially_ordered()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loc(tableid: u32, colid: u32, rowid: u32) -> TableLocation {
+        TableLocation {
+            tableid,
+            colid,
+            rowid,
+        }
+    }
+
+    #[test]
+    fn every_encoding_roundtrips() {
+        let mut encoder = Encoder {
+            codec: Codec::simdfastpfor128(),
+            decisions: [0; Encoding::ALL.len()],
+            buffer: Vec::new(),
+        };
+
+        let locations = (0..300)
+            .map(|i| loc(i / 7 * 3, i % 7, i * 11))
+            .collect::<Vec<_>>();
+
+        for encoding in &Encoding::ALL[1..] {
+            let data = match encoding {
+                Encoding::VarInt => varint_encode(&locations),
+                Encoding::GroupVarInt => group_varint_encode(&locations),
+                Encoding::FastPfor => encoder.fastpfor_encode(&locations),
+                _ => bitmap_encode(&locations),
+            };
+
+            let mut arena = vec![encoding.byte()];
+            arena.extend(vbyte::compress(data.len() as u64));
+            arena.extend(data);
+
+            let ii = IIAdaptive {
+                ii: HashMap::new(),
+                arena,
+                codec: Codec::simdfastpfor128(),
+            };
+
+            assert_eq!(ii.decode(0), locations, "{} roundtrip", encoding.str());
+        }
+    }
+
+    #[test]
+    fn short_lists_are_inlined() {
+        let mut encoder = Encoder {
+            codec: Codec::simdfastpfor128(),
+            decisions: [0; Encoding::ALL.len()],
+            buffer: Vec::new(),
+        };
+        let mut arena = Vec::new();
+
+        let mut locations = vec![loc(5, 1, 1), loc(2, 0, 3), loc(5, 1, 1)];
+        let slot = encoder.encode(&mut locations, &mut arena);
+
+        assert!(arena.is_empty());
+        assert_eq!(encoder.decisions[Encoding::Inline as usize], 1);
+        match slot {
+            Slot::Inline { len, locations } => {
+                assert_eq!(&locations[..len as usize], [loc(2, 0, 3), loc(5, 1, 1)])
+            }
+            Slot::Arena(_) => panic!("expected posting list to be inlined"),
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use std::{
+    sync::mpsc::Receiver,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    inverted_index::{merge_postings, InvertedIndex, KeySearch},
+    pipeline::{assemble_dict, encode_blocks, pipelined},
+    query::{Explain, Explanation},
+    table_lake::{Entry, TableLocation},
+    util::random_keys::DESIRED_KEY_COUNT,
+};
+use dict_compressed::{BlockCoding, Dict, SortedDict};
+use rand::random;
+
+/// Keys compressed in blocks of `B` keys using the block coding `C`, values are stored as is.
+pub struct IIBlockDict<C, const B: usize> {
+    dict: Dict<Vec<TableLocation>, C, B>,
+}
+
+pub fn block_dict<C: BlockCoding, const B: usize>(
+    receiver: Receiver<(String, TableLocation)>,
+) -> (usize, Duration, IIBlockDict<C, B>) {
+    let mut dict = Dict::new();
+
+    let mut build_time = Duration::new(0, 0);
+
+    let mut count = 1;
+
+    let (mut current_key, location_group) = receiver.recv().expect("first item from receiver");
+    let mut location_group = vec![location_group];
+    for (key, location) in receiver {
+        count += 1;
+        let starttime = Instant::now();
+
+        if key != current_key {
+            dict.push(current_key.into_bytes(), location_group.to_vec());
+            location_group.clear();
+            current_key = key;
+        }
+
+        location_group.push(location);
+
+        build_time += starttime.elapsed();
+    }
+
+    let starttime = Instant::now();
+    dict.push(current_key.into_bytes(), location_group);
+    dict.finish();
+    build_time += starttime.elapsed();
+
+    let ii = IIBlockDict { dict };
+
+    (count, build_time, ii)
+}
+
+/// Builds the same index as `block_dict`, encoding the keys on `workers` threads.
+pub fn block_dict_parallel<C: BlockCoding + 'static, const B: usize>(
+    workers: usize,
+) -> impl Fn(Receiver<Entry>) -> (usize, Duration, IIBlockDict<C, B>) {
+    pipelined(
+        workers,
+        |batch| encode_blocks::<C, B, _>(batch, |locations| locations),
+        |in_order| IIBlockDict {
+            dict: assemble_dict(in_order),
+        },
+    )
+}
+
+impl<C: BlockCoding, const B: usize> InvertedIndex<Option<Vec<TableLocation>>>
+    for IIBlockDict<C, B>
+{
+    fn get(&self, key: &str) -> Option<Vec<TableLocation>> {
+        self.dict.get(key.as_bytes()).cloned()
+    }
+}
+
+impl<C: BlockCoding, const B: usize> KeySearch for IIBlockDict<C, B> {
+    fn prefix(&self, prefix: &str) -> Vec<TableLocation> {
+        let lists = self.dict.starts_with(prefix.as_bytes());
+        merge_postings(lists.map(|(_, locations)| locations.iter().copied()))
+    }
+
+    fn glob(&self, pattern: &str) -> Vec<TableLocation> {
+        let lists = self.dict.glob(pattern.as_bytes());
+        merge_postings(lists.map(|(_, locations)| locations.iter().copied()))
+    }
+
+    fn fuzzy(&self, key: &str, k: u32) -> Vec<TableLocation> {
+        let lists = self.dict.fuzzy(key.as_bytes(), k);
+        merge_postings(lists.map(|(_, locations)| locations.iter().copied()))
+    }
+}
+
+impl<C: BlockCoding, const B: usize> Explain for IIBlockDict<C, B> {
+    fn explain(&self, key: &str) -> Option<Explanation> {
+        let block = self.dict.block_of(key.as_bytes())?;
+        let locations = self.dict.get(key.as_bytes());
+
+        Some(Explanation {
+            blocks: vec![block],
+            block_bytes: self.dict.block_len(block),
+            posting_bytes: locations.map(|l| std::mem::size_of_val(l.as_slice())),
+        })
+    }
+}
+
+impl<C: BlockCoding, const B: usize> crate::util::RandomKeys for IIBlockDict<C, B> {
+    fn random_keys_potentially_ordered(&self) -> Vec<String> {
+        random_keys(&self.dict)
+    }
+}
+
+/// Every location is pushed as its own key, without grouping.
+pub struct IIBlockDictBaseline<C, const B: usize> {
+    dict: Dict<TableLocation, C, B>,
+}
+
+pub fn block_dict_baseline<C: BlockCoding, const B: usize>(
+    receiver: Receiver<(String, TableLocation)>,
+) -> (usize, Duration, IIBlockDictBaseline<C, B>) {
+    let mut dict = Dict::new();
+
+    let mut build_time = Duration::new(0, 0);
+
+    let mut count = 0;
+
+    for (key, location) in receiver {
+        count += 1;
+        let starttime = Instant::now();
+
+        dict.push(key.into_bytes(), location);
+
+        build_time += starttime.elapsed();
+    }
+
+    let starttime = Instant::now();
+    dict.finish();
+    build_time += starttime.elapsed();
+
+    let ii = IIBlockDictBaseline { dict };
+
+    (count, build_time, ii)
+}
+
+impl<C: BlockCoding, const B: usize> InvertedIndex<Option<Vec<TableLocation>>>
+    for IIBlockDictBaseline<C, B>
+{
+    fn get(&self, key: &str) -> Option<Vec<TableLocation>> {
+        // we do this twice. More than anything to emulate the binary search
+        let _index = self.dict.index_of(key.as_bytes())?;
+
+        let index = self.dict.index_of(key.as_bytes())?;
+
+        let firstvalue = self.dict.values()[index];
+
+        // Note: this implementation is faulty
+        // and does not truly return actual values.
+        Some(vec![firstvalue])
+    }
+}
+
+impl<C: BlockCoding, const B: usize> crate::util::RandomKeys for IIBlockDictBaseline<C, B> {
+    fn random_keys_potentially_ordered(&self) -> Vec<String> {
+        random_keys(&self.dict)
+    }
+}
+
+fn random_keys<V>(dict: &impl SortedDict<V>) -> Vec<String> {
+    let max = dict.len() as f64;
+    (0..DESIRED_KEY_COUNT)
+        .map(|_| {
+            let position = (random::<f64>() * max).floor() as usize;
+            let bytes = dict.key_at_index(position);
+
+            String::from_utf8(bytes).expect("bytes to be valid utf-8")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dict_compressed::{FrontCoding, RePair};
+    use std::sync::mpsc::sync_channel;
+
+    fn receiver() -> Receiver<(String, TableLocation)> {
+        let (sender, receiver) = sync_channel(1024);
+
+        std::thread::spawn(move || {
+            for i in 0..300u32 {
+                for j in 0..(i % 3 + 1) {
+                    let location = TableLocation {
+                        tableid: i,
+                        colid: j,
+                        rowid: 0,
+                    };
+                    sender.send((format!("key-{i:04}"), location)).unwrap();
+                }
+            }
+        });
+
+        receiver
+    }
+
+    fn check<C: BlockCoding, const B: usize>() {
+        let (count, _, ii) = block_dict::<C, B>(receiver());
+        assert_eq!(count, (0..300).map(|i| i % 3 + 1).sum::<usize>());
+
+        for i in 0..300u32 {
+            let locations = ii.get(&format!("key-{i:04}")).unwrap();
+            assert_eq!(locations.len() as u32, i % 3 + 1);
+            assert!(locations.iter().all(|l| l.tableid == i));
+        }
+
+        let tables = |locations: Vec<TableLocation>| {
+            let mut tables = locations.iter().map(|l| l.tableid).collect::<Vec<_>>();
+            tables.dedup();
+            tables
+        };
+
+        assert_eq!(tables(ii.prefix("key-029")), (290..300).collect::<Vec<_>>());
+        assert_eq!(tables(ii.glob("key-0?15")), [15, 115, 215]);
+        // the key itself, 9 other ones, 9 other tens and 2 other hundreds
+        assert_eq!(tables(ii.fuzzy("key-0299", 1)).len(), 1 + 9 + 9 + 2);
+    }
+
+    #[test]
+    fn generic_over_block_coding() {
+        check::<FrontCoding, 8>();
+        check::<RePair, 64>();
+    }
+}
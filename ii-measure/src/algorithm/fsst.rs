@@ -0,0 +1,198 @@
+use std::{
+    collections::HashMap,
+    sync::mpsc::Receiver,
+    time::{Duration, Instant},
+};
+
+use super::incr_adv_ns::CompressedLocations;
+use crate::{
+    inverted_index::InvertedIndex,
+    table_lake::TableLocation,
+    util::{evenly_spaced, random_keys::DESIRED_KEY_COUNT},
+};
+use fsst::SymbolTable;
+use rand::random;
+
+/// Number of keys the symbol table gets trained on.
+const TRAINING_SAMPLE: usize = 1 << 14;
+
+/// How the posting list of a key is stored.
+pub trait Postings {
+    fn new(locations: Vec<TableLocation>) -> Self;
+    fn locations(&self) -> Vec<TableLocation>;
+}
+
+impl Postings for Vec<TableLocation> {
+    fn new(locations: Vec<TableLocation>) -> Self {
+        locations
+    }
+
+    fn locations(&self) -> Vec<TableLocation> {
+        self.clone()
+    }
+}
+
+impl Postings for CompressedLocations {
+    fn new(locations: Vec<TableLocation>) -> Self {
+        CompressedLocations::new(locations)
+    }
+
+    fn locations(&self) -> Vec<TableLocation> {
+        CompressedLocations::locations(self)
+    }
+}
+
+/// Keys are compressed using a symbol table trained on a sample of the keys.
+/// Unlike smaz, the table adapts to ids, numbers and codes of the corpus.
+pub struct IIFsst<P> {
+    table: SymbolTable,
+    data: HashMap<Box<[u8]>, P>,
+}
+
+/// Posting lists stored as is.
+pub fn fsst(
+    receiver: Receiver<(String, TableLocation)>,
+) -> (usize, Duration, IIFsst<Vec<TableLocation>>) {
+    build(receiver)
+}
+
+/// Posting lists compressed using group varint encoding (ns).
+pub fn fsst_ns(
+    receiver: Receiver<(String, TableLocation)>,
+) -> (usize, Duration, IIFsst<CompressedLocations>) {
+    build(receiver)
+}
+
+fn build<P: Postings>(receiver: Receiver<(String, TableLocation)>) -> (usize, Duration, IIFsst<P>) {
+    let mut build_time = Duration::new(0, 0);
+
+    let mut count = 1;
+
+    // the symbol table needs to be trained before any key can be compressed,
+    // so the groups are collected first.
+    let mut groups = Vec::new();
+
+    let (mut current_key, location_group) = receiver.recv().expect("first item from receiver");
+    let mut location_group = vec![location_group];
+    for (key, location) in receiver {
+        count += 1;
+        let starttime = Instant::now();
+
+        if key != current_key {
+            groups.push((current_key, P::new(location_group.to_vec())));
+            location_group.clear();
+            current_key = key;
+        }
+
+        location_group.push(location);
+
+        build_time += starttime.elapsed();
+    }
+
+    let starttime = Instant::now();
+    groups.push((current_key, P::new(location_group)));
+
+    let sample = evenly_spaced(&groups, TRAINING_SAMPLE)
+        .map(|(key, _)| key.as_bytes())
+        .collect::<Vec<_>>();
+    let table = SymbolTable::train(&sample);
+
+    let mut data = HashMap::with_capacity(groups.len());
+    for (key, postings) in groups {
+        let key = table.compress(key.as_bytes()).into_boxed_slice();
+        data.insert(key, postings);
+    }
+    build_time += starttime.elapsed();
+
+    eprintln!("fsst: {} symbols, {} bytes", table.len(), table.heap_size());
+
+    (count, build_time, IIFsst { table, data })
+}
+
+impl<P: Postings> InvertedIndex<Option<Vec<TableLocation>>> for IIFsst<P> {
+    fn get(&self, key: &str) -> Option<Vec<TableLocation>> {
+        let key = self.table.compress(key.as_bytes());
+        Some(self.data.get(&*key)?.locations())
+    }
+}
+
+impl<P> crate::util::RandomKeys for IIFsst<P> {
+    fn random_keys_potentially_ordered(&self) -> Vec<String> {
+        let mut v = Vec::with_capacity(DESIRED_KEY_COUNT);
+        let chance = DESIRED_KEY_COUNT as f64 / self.data.len() as f64;
+        v.extend(
+            self.data
+                .keys()
+                .filter(|_| random::<f64>() <= chance)
+                .map(|key| self.table.decompress(key))
+                .map(String::from_utf8)
+                .map(Result::unwrap),
+        );
+        v
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::sync_channel;
+
+    fn receiver() -> Receiver<(String, TableLocation)> {
+        let (sender, receiver) = sync_channel(1024);
+
+        std::thread::spawn(move || {
+            for i in 0..500u32 {
+                for j in 0..(i % 3 + 1) {
+                    let location = TableLocation {
+                        tableid: i,
+                        colid: j,
+                        rowid: 7,
+                    };
+                    sender.send((format!("id-{i:05}"), location)).unwrap();
+                }
+            }
+        });
+
+        receiver
+    }
+
+    #[test]
+    fn get_with_both_postings() {
+        let (count, _, ii) = fsst(receiver());
+        assert_eq!(count, (0..500).map(|i| i % 3 + 1).sum::<usize>());
+
+        let (_, _, ii_ns) = fsst_ns(receiver());
+
+        for i in 0..500u32 {
+            let key = format!("id-{i:05}");
+            let locations = ii.get(&key).unwrap();
+            assert_eq!(locations.len() as u32, i % 3 + 1);
+            assert!(locations.iter().all(|l| l.tableid == i && l.rowid == 7));
+            assert_eq!(ii_ns.get(&key), Some(locations));
+        }
+    }
+
+    #[test]
+    fn same_symbol_table_in_every_run() {
+        let table = || {
+            let (sender, receiver) = sync_channel(1024);
+            std::thread::spawn(move || {
+                // more keys than the table is trained on
+                for i in 0..4 * TRAINING_SAMPLE as u32 {
+                    let location = TableLocation {
+                        tableid: i,
+                        colid: 0,
+                        rowid: 0,
+                    };
+                    sender.send((format!("key-{i:06}"), location)).unwrap();
+                }
+            });
+
+            let mut table = Vec::new();
+            fsst(receiver).2.table.write(&mut table);
+            table
+        };
+
+        assert_eq!(table(), table());
+    }
+}
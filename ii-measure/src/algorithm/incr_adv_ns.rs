@@ -4,19 +4,23 @@ use std::{
 };
 
 use crate::{
-    inverted_index::InvertedIndex, table_lake::TableLocation, util::random_keys::DESIRED_KEY_COUNT,
+    inverted_index::{merge_postings, InvertedIndex, KeySearch},
+    pipeline::{assemble_dict, encode_blocks, pipelined},
+    query::{Explain, Explanation},
+    table_lake::{Entry, TableLocation},
+    util::random_keys::DESIRED_KEY_COUNT,
 };
-use dict_incremental_coding_improved::Dict;
+use dict_compressed::{Dict, ImprovedIncrementalCoding, SortedDict};
 use rand::random;
 
 /// List of u32s compressed using Group Varint Encoding (ns)
 #[derive(Clone)]
-struct CompressedLocations {
+pub struct CompressedLocations {
     data: Box<[u8]>,
 }
 
 impl CompressedLocations {
-    fn new(vs: Vec<TableLocation>) -> Self {
+    pub fn new(vs: Vec<TableLocation>) -> Self {
         let data = vs.into_iter().flat_map(TableLocation::integers);
         let mut data = group_varint_encoding::compress(data);
         data.shrink_to_fit();
@@ -33,7 +37,9 @@ impl CompressedLocations {
         // this can hhappen, because I decided to use 0 to extend our data to groups of 3.
 
         let i = data.len();
-        if data[i - 1] == 0 && data[i - 2] == 0 && data[i - 3] == 0 {
+        // otherwise the padding is shorter than a location and gets skipped by `locations`,
+        // while the zeros at the end are part of the last location.
+        if i.is_multiple_of(3) && data[i - 1] == 0 && data[i - 2] == 0 && data[i - 3] == 0 {
             data.pop();
             data.pop();
             data.pop();
@@ -42,6 +48,11 @@ impl CompressedLocations {
         data
     }
 
+    /// Number of bytes of the encoded locations
+    pub fn encoded_len(&self) -> usize {
+        self.data.len()
+    }
+
     pub fn locations(&self) -> Vec<TableLocation> {
         let data = self.get_data();
 
@@ -58,19 +69,20 @@ impl CompressedLocations {
     }
 }
 
-pub struct InvertedIndexIncrementalCodingNS {
-    dict: Dict<CompressedLocations, 16>,
+/// Keys are compressed in blocks of `B` keys.
+pub struct InvertedIndexIncrementalCodingNS<const B: usize = 16> {
+    dict: Dict<CompressedLocations, ImprovedIncrementalCoding, B>,
 }
 
-impl InvertedIndexIncrementalCodingNS {
+impl<const B: usize> InvertedIndexIncrementalCodingNS<B> {
     pub fn new(
         receiver: Receiver<(String, TableLocation)>,
-    ) -> (usize, Duration, InvertedIndexIncrementalCodingNS) {
+    ) -> (usize, Duration, InvertedIndexIncrementalCodingNS<B>) {
         let mut dict = Dict::new();
 
         let mut build_time = Duration::new(0, 0);
 
-        let mut count = 0;
+        let mut count = 1;
 
         let (mut current_key, location_group) = receiver.recv().expect("first item from receiver");
         let mut location_group = vec![location_group];
@@ -92,24 +104,76 @@ impl InvertedIndexIncrementalCodingNS {
             build_time += starttime.elapsed();
         }
 
+        let starttime = Instant::now();
+        dict.push(
+            current_key.into_bytes(),
+            CompressedLocations::new(location_group),
+        );
+        dict.finish();
+        build_time += starttime.elapsed();
+
         let ii = InvertedIndexIncrementalCodingNS { dict };
 
         (count, build_time, ii)
     }
 }
 
-impl InvertedIndex<Vec<TableLocation>> for InvertedIndexIncrementalCodingNS {
-    fn get(&self, key: &str) -> Vec<TableLocation> {
-        let index = self
-            .dict
-            .index_of(key.as_bytes())
-            .expect("to find key in dictionary");
+impl<const B: usize> InvertedIndexIncrementalCodingNS<B> {
+    /// Builds the same index as `new`, encoding keys and posting lists on `workers` threads.
+    pub fn parallel(workers: usize) -> impl Fn(Receiver<Entry>) -> (usize, Duration, Self) {
+        pipelined(
+            workers,
+            |batch| {
+                encode_blocks::<ImprovedIncrementalCoding, B, _>(batch, CompressedLocations::new)
+            },
+            |in_order| InvertedIndexIncrementalCodingNS {
+                dict: assemble_dict(in_order),
+            },
+        )
+    }
+}
+
+impl<const B: usize> InvertedIndex<Option<Vec<TableLocation>>>
+    for InvertedIndexIncrementalCodingNS<B>
+{
+    fn get(&self, key: &str) -> Option<Vec<TableLocation>> {
+        let index = self.dict.index_of(key.as_bytes())?;
 
-        self.dict.values()[index].locations()
+        Some(self.dict.values()[index].locations())
     }
 }
 
-impl crate::util::RandomKeys for InvertedIndexIncrementalCodingNS {
+impl<const B: usize> KeySearch for InvertedIndexIncrementalCodingNS<B> {
+    fn prefix(&self, prefix: &str) -> Vec<TableLocation> {
+        let lists = self.dict.starts_with(prefix.as_bytes());
+        merge_postings(lists.map(|(_, locations)| locations.locations()))
+    }
+
+    fn glob(&self, pattern: &str) -> Vec<TableLocation> {
+        let lists = self.dict.glob(pattern.as_bytes());
+        merge_postings(lists.map(|(_, locations)| locations.locations()))
+    }
+
+    fn fuzzy(&self, key: &str, k: u32) -> Vec<TableLocation> {
+        let lists = self.dict.fuzzy(key.as_bytes(), k);
+        merge_postings(lists.map(|(_, locations)| locations.locations()))
+    }
+}
+
+impl<const B: usize> Explain for InvertedIndexIncrementalCodingNS<B> {
+    fn explain(&self, key: &str) -> Option<Explanation> {
+        let block = self.dict.block_of(key.as_bytes())?;
+        let locations = self.dict.get(key.as_bytes());
+
+        Some(Explanation {
+            blocks: vec![block],
+            block_bytes: self.dict.block_len(block),
+            posting_bytes: locations.map(CompressedLocations::encoded_len),
+        })
+    }
+}
+
+impl<const B: usize> crate::util::RandomKeys for InvertedIndexIncrementalCodingNS<B> {
     fn random_keys_potentially_ordered(&self) -> Vec<String> {
         let max = self.dict.len() as f64;
         (0..DESIRED_KEY_COUNT)
@@ -122,3 +186,50 @@ impl crate::util::RandomKeys for InvertedIndexIncrementalCodingNS {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::sync_channel;
+
+    fn loc(tableid: u32, colid: u32, rowid: u32) -> TableLocation {
+        TableLocation {
+            tableid,
+            colid,
+            rowid,
+        }
+    }
+
+    #[test]
+    fn every_entry_is_indexed() {
+        let (sender, receiver) = sync_channel(1024);
+        std::thread::spawn(move || {
+            for i in 0..20u32 {
+                for j in 0..(i % 3 + 1) {
+                    sender.send((format!("key-{i:02}"), loc(i, j, 0))).unwrap();
+                }
+            }
+        });
+
+        let (count, _, ii) = InvertedIndexIncrementalCodingNS::<16>::new(receiver);
+        assert_eq!(count, (0..20).map(|i| i % 3 + 1).sum::<usize>());
+        // the last key, in a partial block
+        assert_eq!(ii.get("key-19").unwrap().len(), 2);
+        assert_eq!(ii.get("key-20"), None);
+    }
+
+    #[test]
+    fn locations_ending_in_zeros() {
+        // group varint pads to groups of 4 integers, the padding is no location
+        for locations in [
+            vec![loc(5, 0, 0)],
+            vec![loc(1, 2, 3), loc(5, 0, 0)],
+            vec![loc(1, 2, 3), loc(4, 5, 6), loc(5, 0, 0)],
+        ] {
+            assert_eq!(
+                CompressedLocations::new(locations.clone()).locations(),
+                locations
+            );
+        }
+    }
+}
@@ -6,7 +6,7 @@ use std::{
 use crate::{
     inverted_index::InvertedIndex, table_lake::TableLocation, util::random_keys::DESIRED_KEY_COUNT,
 };
-use dict_incremental_coding_improved::Dict;
+use dict_compressed::{Dict, ImprovedIncrementalCoding, SortedDict};
 use group_varint_encoding as gve;
 use group_varint_offset_encoding as gvoe;
 use rand::random;
@@ -14,7 +14,7 @@ use vbyte;
 
 /// List of u32s compressed using Group Varint Encoding (ns)
 #[derive(Clone)]
-struct CompressedLocations {
+pub struct CompressedLocations {
     // scheme:
     // tableids length in bytes (varint)
     // tableids gveo
@@ -23,7 +23,7 @@ struct CompressedLocations {
 }
 
 impl CompressedLocations {
-    fn new(vs: Vec<TableLocation>) -> Self {
+    pub fn new(vs: Vec<TableLocation>) -> Self {
         let mut tableids = Vec::with_capacity(vs.len());
         let mut ids = Vec::with_capacity(vs.len() * 2);
 
@@ -46,7 +46,12 @@ impl CompressedLocations {
         Self { data }
     }
 
-    fn locations(&self) -> Vec<TableLocation> {
+    /// Number of bytes of the encoded locations
+    pub fn encoded_len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn locations(&self) -> Vec<TableLocation> {
         let (len, rest) = vbyte::decompress(&self.data).expect("decompress length (vbyte)");
         let len = len as usize;
         let tableids = &rest[..len];
@@ -75,19 +80,20 @@ impl CompressedLocations {
     }
 }
 
-pub struct InvertedIndexIncrementalCodingNS {
-    dict: Dict<CompressedLocations, 16>,
+/// Keys are compressed in blocks of `B` keys.
+pub struct InvertedIndexIncrementalCodingNS<const B: usize = 16> {
+    dict: Dict<CompressedLocations, ImprovedIncrementalCoding, B>,
 }
 
-impl InvertedIndexIncrementalCodingNS {
+impl<const B: usize> InvertedIndexIncrementalCodingNS<B> {
     pub fn new(
         receiver: Receiver<(String, TableLocation)>,
-    ) -> (usize, Duration, InvertedIndexIncrementalCodingNS) {
+    ) -> (usize, Duration, InvertedIndexIncrementalCodingNS<B>) {
         let mut dict = Dict::new();
 
         let mut build_time = Duration::new(0, 0);
 
-        let mut count = 0;
+        let mut count = 1;
 
         let (mut current_key, location_group) = receiver.recv().expect("first item from receiver");
         let mut location_group = vec![location_group];
@@ -109,24 +115,31 @@ impl InvertedIndexIncrementalCodingNS {
             build_time += starttime.elapsed();
         }
 
+        let starttime = Instant::now();
+        dict.push(
+            current_key.into_bytes(),
+            CompressedLocations::new(location_group),
+        );
+        dict.finish();
+        build_time += starttime.elapsed();
+
         let ii = InvertedIndexIncrementalCodingNS { dict };
 
         (count, build_time, ii)
     }
 }
 
-impl InvertedIndex<Vec<TableLocation>> for InvertedIndexIncrementalCodingNS {
-    fn get(&self, key: &str) -> Vec<TableLocation> {
-        let index = self
-            .dict
-            .index_of(key.as_bytes())
-            .expect("to find key in dictionary");
+impl<const B: usize> InvertedIndex<Option<Vec<TableLocation>>>
+    for InvertedIndexIncrementalCodingNS<B>
+{
+    fn get(&self, key: &str) -> Option<Vec<TableLocation>> {
+        let index = self.dict.index_of(key.as_bytes())?;
 
-        self.dict.values()[index].locations()
+        Some(self.dict.values()[index].locations())
     }
 }
 
-impl crate::util::RandomKeys for InvertedIndexIncrementalCodingNS {
+impl<const B: usize> crate::util::RandomKeys for InvertedIndexIncrementalCodingNS<B> {
     fn random_keys_potentially_ordered(&self) -> Vec<String> {
         let max = self.dict.len() as f64;
         (0..DESIRED_KEY_COUNT)
@@ -6,7 +6,7 @@ use std::{
 use crate::{
     inverted_index::InvertedIndex, table_lake::TableLocation, util::random_keys::DESIRED_KEY_COUNT,
 };
-use dict_incremental_coding::Dict;
+use dict_compressed::{Dict, IncrementalCoding, SortedDict};
 use rand::random;
 
 /// List of u32s compressed using Group Varint Encoding (ns)
@@ -33,7 +33,9 @@ impl CompressedLocations {
         // this can hhappen, because I decided to use 0 to extend our data to groups of 3.
 
         let i = data.len();
-        if data[i - 1] == 0 && data[i - 2] == 0 && data[i - 3] == 0 {
+        // otherwise the padding is shorter than a location and gets skipped by `locations`,
+        // while the zeros at the end are part of the last location.
+        if i.is_multiple_of(3) && data[i - 1] == 0 && data[i - 2] == 0 && data[i - 3] == 0 {
             data.pop();
             data.pop();
             data.pop();
@@ -58,19 +60,20 @@ impl CompressedLocations {
     }
 }
 
-pub struct InvertedIndexIncrementalCodingNS {
-    dict: Dict<CompressedLocations, 16>,
+/// Keys are compressed in blocks of `B` keys.
+pub struct InvertedIndexIncrementalCodingNS<const B: usize = 16> {
+    dict: Dict<CompressedLocations, IncrementalCoding, B>,
 }
 
-impl InvertedIndexIncrementalCodingNS {
+impl<const B: usize> InvertedIndexIncrementalCodingNS<B> {
     pub fn new(
         receiver: Receiver<(String, TableLocation)>,
-    ) -> (usize, Duration, InvertedIndexIncrementalCodingNS) {
+    ) -> (usize, Duration, InvertedIndexIncrementalCodingNS<B>) {
         let mut dict = Dict::new();
 
         let mut build_time = Duration::new(0, 0);
 
-        let mut count = 0;
+        let mut count = 1;
 
         let (mut current_key, location_group) = receiver.recv().expect("first item from receiver");
         let mut location_group = vec![location_group];
@@ -92,24 +95,31 @@ impl InvertedIndexIncrementalCodingNS {
             build_time += starttime.elapsed();
         }
 
+        let starttime = Instant::now();
+        dict.push(
+            current_key.into_bytes(),
+            CompressedLocations::new(location_group),
+        );
+        dict.finish();
+        build_time += starttime.elapsed();
+
         let ii = InvertedIndexIncrementalCodingNS { dict };
 
         (count, build_time, ii)
     }
 }
 
-impl InvertedIndex<Vec<TableLocation>> for InvertedIndexIncrementalCodingNS {
-    fn get(&self, key: &str) -> Vec<TableLocation> {
-        let index = self
-            .dict
-            .index_of(key.as_bytes())
-            .expect("to find key in dictionary");
+impl<const B: usize> InvertedIndex<Option<Vec<TableLocation>>>
+    for InvertedIndexIncrementalCodingNS<B>
+{
+    fn get(&self, key: &str) -> Option<Vec<TableLocation>> {
+        let index = self.dict.index_of(key.as_bytes())?;
 
-        self.dict.values()[index].locations()
+        Some(self.dict.values()[index].locations())
     }
 }
 
-impl crate::util::RandomKeys for InvertedIndexIncrementalCodingNS {
+impl<const B: usize> crate::util::RandomKeys for InvertedIndexIncrementalCodingNS<B> {
     fn random_keys_potentially_ordered(&self) -> Vec<String> {
         let max = self.dict.len() as f64;
         (0..DESIRED_KEY_COUNT)
@@ -0,0 +1,90 @@
+use std::{
+    sync::mpsc::Receiver,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    inverted_index::{merge_postings, InvertedIndex, KeySearch},
+    table_lake::TableLocation,
+    util::random_keys::DESIRED_KEY_COUNT,
+};
+use dict_louds_trie::{Trie, TrieBuilder};
+use dict_search::{Glob, Levenshtein, Matcher};
+use rand::random;
+
+/// Keys stored in a succinct (LOUDS) trie, values are stored as is.
+/// Directly comparable to `frontcoding+dedup`.
+pub struct IILouds {
+    trie: Trie<Vec<TableLocation>>,
+}
+
+pub fn louds(receiver: Receiver<(String, TableLocation)>) -> (usize, Duration, IILouds) {
+    let mut builder = TrieBuilder::new();
+
+    let mut build_time = Duration::new(0, 0);
+
+    let mut count = 1;
+
+    let (mut current_key, location_group) = receiver.recv().expect("first item from receiver");
+    let mut location_group = vec![location_group];
+    for (key, location) in receiver {
+        count += 1;
+        let starttime = Instant::now();
+
+        if key != current_key {
+            builder.push(current_key.as_bytes(), location_group.to_vec());
+            location_group.clear();
+            current_key = key;
+        }
+
+        location_group.push(location);
+
+        build_time += starttime.elapsed();
+    }
+
+    let starttime = Instant::now();
+    builder.push(current_key.as_bytes(), location_group);
+    let trie = builder.finish();
+    build_time += starttime.elapsed();
+
+    (count, build_time, IILouds { trie })
+}
+
+impl InvertedIndex<Option<Vec<TableLocation>>> for IILouds {
+    fn get(&self, key: &str) -> Option<Vec<TableLocation>> {
+        self.trie.get(key.as_bytes()).cloned()
+    }
+}
+
+impl KeySearch for IILouds {
+    fn prefix(&self, prefix: &str) -> Vec<TableLocation> {
+        let lists = self.trie.starts_with(prefix.as_bytes());
+        merge_postings(lists.map(|(_, locations)| locations.iter().copied()))
+    }
+
+    fn glob(&self, pattern: &str) -> Vec<TableLocation> {
+        let glob = Glob::new(pattern.as_bytes());
+        let lists = self.trie.starts_with(glob.prefix());
+        let lists = lists.filter(|(key, _)| glob.is_match(key));
+        merge_postings(lists.map(|(_, locations)| locations.iter().copied()))
+    }
+
+    fn fuzzy(&self, key: &str, k: u32) -> Vec<TableLocation> {
+        // the trie yields keys in sorted order, so the automaton can reuse shared prefixes
+        let mut automaton = Levenshtein::new(key.as_bytes(), k);
+        let lists = self.trie.iter().filter(|(key, _)| automaton.matches(key));
+        merge_postings(lists.map(|(_, locations)| locations.iter().copied()))
+    }
+}
+
+impl crate::util::RandomKeys for IILouds {
+    fn random_keys_potentially_ordered(&self) -> Vec<String> {
+        // the trie offers no random access to it's keys, so we sample while iterating.
+        let chance = DESIRED_KEY_COUNT as f64 / self.trie.len() as f64;
+        self.trie
+            .iter()
+            .filter(|_| random::<f64>() <= chance)
+            .map(|(key, _)| String::from_utf8(key).expect("bytes to be valid utf-8"))
+            .collect()
+    }
+}
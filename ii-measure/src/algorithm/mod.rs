@@ -5,13 +5,18 @@ pub mod ns_arena;
 mod smaz;
 mod smaz_ns;
 
-pub mod frontcoding;
+pub mod adaptive;
+pub mod block_dict;
+pub mod fsst;
 pub mod incr_adv_ns;
 pub mod incr_adv_ns_adv;
 pub mod incr_ns;
-pub mod incremental;
+pub mod louds;
+pub mod mphf;
+pub mod order_preserving;
 pub mod pfor_split;
 pub mod pfor_x;
+pub mod roaring;
 pub mod vbyte;
 pub mod vbyte_arena;
 pub mod vbyteincr;
@@ -0,0 +1,246 @@
+use std::{
+    sync::mpsc::Receiver,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    inverted_index::InvertedIndex, table_lake::TableLocation, util::random_keys::DESIRED_KEY_COUNT,
+};
+use dict_compressed::{Dict, FrontCoding, SortedDict};
+use mphf::{hash, Mphf, DEFAULT_GAMMA};
+use rand::seq::SliceRandom;
+
+use super::vbyte_arena::VBList;
+
+/// Seed of the fingerprint hash, chosen not to overlap with the seeds of the mphf levels.
+const FINGERPRINT_SEED: u64 = u64::MAX;
+
+fn fingerprint(key: &[u8]) -> u16 {
+    hash(key, FINGERPRINT_SEED) as u16
+}
+
+/// Static index without any keys inside the dictionary.
+/// Keys are mapped to their posting list using a minimal perfect hash function,
+/// a 16 bit fingerprint rejects most of the keys that are not part of the index.
+pub struct IIMphf {
+    mphf: Mphf,
+    /// indexed by the hash of the key.
+    /// Not present if the front coded keys verify each hit.
+    fingerprints: Option<Box<[u16]>>,
+    /// indexed by the hash of the key
+    values: Box<[VBList]>,
+    arena: Box<[u8]>,
+
+    /// Front coded keys, mapping to the hash of the key.
+    /// Only present if exact verification is required.
+    keys: Option<Dict<u32, FrontCoding, 16>>,
+
+    /// The keys aren't retrievable without front coded keys,
+    /// so we need to remember some keys to be able to measure retrieval at all.
+    /// Dropped before the size of the index is measured.
+    sample: Vec<String>,
+}
+
+/// Index with 16 bit fingerprints only, may return false positives.
+pub fn mphf(receiver: Receiver<(String, TableLocation)>) -> (usize, Duration, IIMphf) {
+    build(receiver, false)
+}
+
+/// Index with front coded keys instead of fingerprints, that are used to verify each hit.
+pub fn mphf_verified(receiver: Receiver<(String, TableLocation)>) -> (usize, Duration, IIMphf) {
+    build(receiver, true)
+}
+
+fn build(receiver: Receiver<(String, TableLocation)>, verify: bool) -> (usize, Duration, IIMphf) {
+    let mut build_time = Duration::new(0, 0);
+
+    let mut keys = Vec::new();
+    let mut lists = Vec::new();
+    let mut arena = Vec::new();
+
+    let mut count = 1;
+
+    let (mut current_key, location) = receiver.recv().expect("first item from receiver");
+    let mut location_group = vec![location];
+    for (key, location) in receiver {
+        count += 1;
+        let starttime = Instant::now();
+
+        if key != current_key {
+            lists.push(VBList::from_table_locations(
+                &mut arena,
+                location_group.drain(..),
+            ));
+            keys.push(current_key);
+            current_key = key;
+        }
+
+        location_group.push(location);
+
+        build_time += starttime.elapsed();
+    }
+
+    let starttime = Instant::now();
+
+    lists.push(VBList::from_table_locations(
+        &mut arena,
+        location_group.into_iter(),
+    ));
+    keys.push(current_key);
+
+    eprintln!("building mphf over {} keys", keys.len());
+    let mphf = Mphf::new(&keys, DEFAULT_GAMMA);
+
+    let mut fingerprints = (!verify).then(|| vec![0; keys.len()]);
+    let mut values = lists.clone();
+    let mut dict = verify.then(Dict::new);
+
+    for (key, list) in keys.iter().zip(lists) {
+        let h = mphf.index(key.as_bytes());
+        values[h] = list;
+
+        if let Some(fingerprints) = &mut fingerprints {
+            fingerprints[h] = fingerprint(key.as_bytes());
+        }
+
+        if let Some(dict) = &mut dict {
+            dict.push(key.as_bytes().to_vec(), h as u32);
+        }
+    }
+
+    if let Some(dict) = &mut dict {
+        dict.finish();
+    }
+
+    build_time += starttime.elapsed();
+
+    let sample = if verify {
+        Vec::new()
+    } else {
+        let mut rng = rand::thread_rng();
+        keys.choose_multiple(&mut rng, DESIRED_KEY_COUNT)
+            .cloned()
+            .collect()
+    };
+    drop(keys);
+
+    let ii = IIMphf {
+        mphf,
+        fingerprints: fingerprints.map(Vec::into_boxed_slice),
+        values: values.into_boxed_slice(),
+        arena: arena.into_boxed_slice(),
+        keys: dict,
+        sample,
+    };
+
+    (count, build_time, ii)
+}
+
+impl InvertedIndex<Option<Vec<TableLocation>>> for IIMphf {
+    fn get(&self, key: &str) -> Option<Vec<TableLocation>> {
+        let key = key.as_bytes();
+        if self.mphf.is_empty() {
+            return None;
+        }
+
+        let h = self.mphf.index(key);
+        if let Some(fingerprints) = &self.fingerprints {
+            if fingerprints[h] != fingerprint(key) {
+                return None;
+            }
+        }
+
+        if let Some(dict) = &self.keys {
+            if *dict.get(key)? as usize != h {
+                return None;
+            }
+        }
+
+        Some(self.values[h].locations(&self.arena))
+    }
+}
+
+impl crate::util::RandomKeys for IIMphf {
+    fn random_keys_potentially_ordered(&self) -> Vec<String> {
+        let dict = match &self.keys {
+            Some(dict) => dict,
+            None => return self.sample.clone(),
+        };
+
+        let max = dict.len() as f64;
+        (0..DESIRED_KEY_COUNT)
+            .map(|_| {
+                let position = (rand::random::<f64>() * max).floor() as usize;
+                let bytes = dict.key_at_index(position);
+
+                String::from_utf8(bytes).expect("bytes to be valid utf-8")
+            })
+            .collect()
+    }
+
+    fn drop_random_keys(&mut self) {
+        self.sample = Vec::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::sync_channel;
+
+    fn index(verify: bool) -> IIMphf {
+        let (sender, receiver) = sync_channel(1024);
+
+        std::thread::spawn(move || {
+            for i in 0..500u32 {
+                for j in 0..(i % 4 + 1) {
+                    let location = TableLocation {
+                        tableid: i,
+                        colid: j,
+                        rowid: i * j,
+                    };
+                    sender.send((format!("{i:04}"), location)).unwrap();
+                }
+            }
+        });
+
+        build(receiver, verify).2
+    }
+
+    #[test]
+    fn lookup() {
+        for verify in [false, true] {
+            let ii = index(verify);
+
+            for i in 0..500u32 {
+                let locations = ii.get(&format!("{i:04}")).expect("key to be present");
+                assert_eq!(locations.len() as u32, i % 4 + 1);
+                assert!(locations.iter().all(|l| l.tableid == i));
+            }
+        }
+    }
+
+    #[test]
+    fn sample_is_dropped_before_measuring() {
+        use crate::util::RandomKeys;
+
+        let mut ii = index(false);
+        let keys = ii.random_keys();
+        assert!(!keys.is_empty());
+        assert!(keys.iter().all(|key| ii.get(key).is_some()));
+
+        ii.drop_random_keys();
+        assert_eq!(ii.sample.capacity(), 0);
+        assert!(ii.get(&keys[0]).is_some());
+    }
+
+    #[test]
+    fn verification_rejects_all_misses() {
+        let ii = index(true);
+        assert!(ii.fingerprints.is_none());
+
+        for i in 500..5000u32 {
+            assert_eq!(ii.get(&format!("{i:04}")), None);
+        }
+    }
+}
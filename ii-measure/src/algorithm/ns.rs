@@ -10,7 +10,7 @@ use std::time::{Duration, Instant};
 // as the implementation does not consider that elements may not come in blocks of precisely 4.
 pub type Compressed4Wise = HashMap<String, (Vec<u8>, u8)>;
 
-pub(crate) fn ns_4_wise(
+pub fn ns_4_wise(
     receiver: Receiver<(String, TableLocation)>,
 ) -> (usize, Duration, Compressed4Wise) {
     let mut ii: Compressed4Wise = HashMap::new();
@@ -84,8 +84,8 @@ pub fn ns_raw(receiver: Receiver<(String, TableLocation)>) -> (usize, Duration,
     (data.len(), build_time, InvIdxNsRaw { data })
 }
 
-impl InvertedIndex<Vec<TableLocation>> for InvIdxNsRaw {
-    fn get(&self, key: &str) -> Vec<TableLocation> {
+impl InvertedIndex<Option<Vec<TableLocation>>> for InvIdxNsRaw {
+    fn get(&self, key: &str) -> Option<Vec<TableLocation>> {
         fn get_start_point(a: &[(String, Vec<u8>)], index: usize, elem: &String) -> Ordering {
             if index == 0 {
                 return a[0].0.cmp(elem);
@@ -124,8 +124,7 @@ impl InvertedIndex<Vec<TableLocation>> for InvIdxNsRaw {
         let key = key.to_string();
 
         let startindex =
-            binary_search_by_index(&self.data, 0, self.data.len(), get_start_point, &key)
-                .unwrap_or(0);
+            binary_search_by_index(&self.data, 0, self.data.len(), get_start_point, &key)?;
 
         let endindex = binary_search_by_index(&self.data, 0, self.data.len(), get_end_point, &key)
             .unwrap_or(6);
@@ -145,7 +144,7 @@ impl InvertedIndex<Vec<TableLocation>> for InvIdxNsRaw {
             v.push(location);
         }
 
-        v
+        Some(v)
     }
 }
 
@@ -50,8 +50,8 @@ pub fn ns_arena(receiver: Receiver<(String, TableLocation)>) -> (usize, Duration
     (data.len(), build_time, NSIndex { data, arena })
 }
 
-impl InvertedIndex<Vec<TableLocation>> for NSIndex {
-    fn get(&self, key: &str) -> Vec<TableLocation> {
+impl InvertedIndex<Option<Vec<TableLocation>>> for NSIndex {
+    fn get(&self, key: &str) -> Option<Vec<TableLocation>> {
         fn get_start_point<T>(a: &[(String, T)], index: usize, elem: &String) -> Ordering {
             if index == 0 {
                 return a[0].0.cmp(elem);
@@ -90,8 +90,7 @@ impl InvertedIndex<Vec<TableLocation>> for NSIndex {
         let key = key.to_string();
 
         let startindex =
-            binary_search_by_index(&self.data, 0, self.data.len(), get_start_point, &key)
-                .unwrap_or(0);
+            binary_search_by_index(&self.data, 0, self.data.len(), get_start_point, &key)?;
 
         let endindex = binary_search_by_index(&self.data, 0, self.data.len(), get_end_point, &key)
             .unwrap_or(6);
@@ -114,7 +113,7 @@ impl InvertedIndex<Vec<TableLocation>> for NSIndex {
             v.push(location);
         }
 
-        v
+        Some(v)
     }
 }
 
@@ -0,0 +1,231 @@
+use std::{
+    sync::mpsc::Receiver,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    inverted_index::InvertedIndex,
+    table_lake::TableLocation,
+    util::{evenly_spaced, random_keys::DESIRED_KEY_COUNT},
+};
+use hu_tucker::AlphabeticCode;
+use is_sorted::IsSorted;
+use rand::random;
+
+/// Number of keys the code gets trained on.
+const TRAINING_SAMPLE: usize = 1 << 14;
+
+/// Compressed keys compare in the same order as the original keys,
+/// so the sorted entries are binary searched without decompressing a single key.
+pub struct IIOrderPreservingRaw {
+    code: AlphabeticCode,
+    data: Vec<(Box<[u8]>, TableLocation)>,
+}
+
+/// Every location is stored along with its compressed key, like the `smaz` baseline.
+pub fn hutucker_raw(
+    receiver: Receiver<(String, TableLocation)>,
+) -> (usize, Duration, IIOrderPreservingRaw) {
+    let mut build_time = Duration::new(0, 0);
+
+    let mut entries = Vec::new();
+    for entry in receiver {
+        let starttime = Instant::now();
+        entries.push(entry);
+        build_time += starttime.elapsed();
+    }
+
+    let starttime = Instant::now();
+    let code = train(entries.iter().map(|(key, _)| key));
+
+    let mut data = entries
+        .into_iter()
+        .map(|(key, location)| (code.encode(key.as_bytes()).into_boxed_slice(), location))
+        .collect::<Vec<_>>();
+    sort(&mut data);
+    build_time += starttime.elapsed();
+
+    (data.len(), build_time, IIOrderPreservingRaw { code, data })
+}
+
+impl InvertedIndex<Vec<TableLocation>> for IIOrderPreservingRaw {
+    fn get(&self, key: &str) -> Vec<TableLocation> {
+        let key = self.code.encode(key.as_bytes());
+        let key = &key as &[u8];
+
+        let startindex = self.data.partition_point(|(k, _)| &**k < key);
+        let endindex = startindex + self.data[startindex..].partition_point(|(k, _)| &**k == key);
+
+        self.data[startindex..endindex]
+            .iter()
+            .map(|(_, location)| *location)
+            .collect()
+    }
+}
+
+impl crate::util::RandomKeys for IIOrderPreservingRaw {
+    fn random_keys_potentially_ordered(&self) -> Vec<String> {
+        random_keys(&self.code, &self.data)
+    }
+}
+
+/// Grouped posting lists, sorted by their compressed key.
+pub struct IIOrderPreserving {
+    code: AlphabeticCode,
+    data: Vec<(Box<[u8]>, Vec<TableLocation>)>,
+}
+
+pub fn hutucker(
+    receiver: Receiver<(String, TableLocation)>,
+) -> (usize, Duration, IIOrderPreserving) {
+    let mut build_time = Duration::new(0, 0);
+
+    let mut count = 1;
+
+    // the code needs to be trained before any key can be compressed,
+    // so the groups are collected first.
+    let mut groups = Vec::new();
+
+    let (mut current_key, location_group) = receiver.recv().expect("first item from receiver");
+    let mut location_group = vec![location_group];
+    for (key, location) in receiver {
+        count += 1;
+        let starttime = Instant::now();
+
+        if key != current_key {
+            groups.push((current_key, location_group.to_vec()));
+            location_group.clear();
+            current_key = key;
+        }
+
+        location_group.push(location);
+
+        build_time += starttime.elapsed();
+    }
+
+    let starttime = Instant::now();
+    groups.push((current_key, location_group));
+
+    let code = train(groups.iter().map(|(key, _)| key));
+
+    let mut data = groups
+        .into_iter()
+        .map(|(key, locations)| (code.encode(key.as_bytes()).into_boxed_slice(), locations))
+        .collect::<Vec<_>>();
+    sort(&mut data);
+    build_time += starttime.elapsed();
+
+    (count, build_time, IIOrderPreserving { code, data })
+}
+
+impl InvertedIndex<Option<Vec<TableLocation>>> for IIOrderPreserving {
+    fn get(&self, key: &str) -> Option<Vec<TableLocation>> {
+        let key = self.code.encode(key.as_bytes());
+
+        let index = self.data.binary_search_by(|(k, _)| (**k).cmp(&key)).ok()?;
+
+        Some(self.data[index].1.clone())
+    }
+}
+
+impl crate::util::RandomKeys for IIOrderPreserving {
+    fn random_keys_potentially_ordered(&self) -> Vec<String> {
+        random_keys(&self.code, &self.data)
+    }
+}
+
+fn train<'a>(keys: impl Iterator<Item = &'a String>) -> AlphabeticCode {
+    let keys = keys.collect::<Vec<_>>();
+
+    let sample = evenly_spaced(&keys, TRAINING_SAMPLE)
+        .copied()
+        .collect::<Vec<_>>();
+
+    AlphabeticCode::train(&sample)
+}
+
+/// The keys arrive in the order of postgres, which does not necessarily match the byte order.
+fn sort<T: Ord>(data: &mut [(Box<[u8]>, T)]) {
+    if !IsSorted::is_sorted(&mut data.iter()) {
+        eprintln!("sorting data");
+        data.sort_unstable();
+    }
+}
+
+fn random_keys<T>(code: &AlphabeticCode, data: &[(Box<[u8]>, T)]) -> Vec<String> {
+    (0..DESIRED_KEY_COUNT)
+        .map(|_| {
+            let index = random::<f64>() * data.len() as f64;
+            let key = code.decode(&data[index as usize].0);
+
+            String::from_utf8(key).expect("bytes to be valid utf-8")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::sync_channel;
+
+    fn receiver() -> Receiver<(String, TableLocation)> {
+        let (sender, receiver) = sync_channel(1024);
+
+        std::thread::spawn(move || {
+            // not sorted by bytes, like the collation of postgres
+            for i in (0..500u32).rev() {
+                for j in 0..(i % 3 + 1) {
+                    let location = TableLocation {
+                        tableid: i,
+                        colid: j,
+                        rowid: 0,
+                    };
+                    sender.send((format!("key-{i}"), location)).unwrap();
+                }
+            }
+        });
+
+        receiver
+    }
+
+    #[test]
+    fn search_on_compressed_keys() {
+        let (count, _, raw) = hutucker_raw(receiver());
+        let (grouped_count, _, grouped) = hutucker(receiver());
+        assert_eq!(count, grouped_count);
+
+        for i in 0..500u32 {
+            let key = format!("key-{i}");
+            let locations = grouped.get(&key).unwrap();
+            assert_eq!(locations.len() as u32, i % 3 + 1);
+            assert!(locations.iter().all(|l| l.tableid == i));
+
+            let mut raw_locations = raw.get(&key);
+            raw_locations.sort_by_key(|l| l.colid);
+            assert_eq!(raw_locations, locations);
+        }
+    }
+
+    #[test]
+    fn same_code_in_every_run() {
+        // more keys than the code is trained on
+        let keys = (0..4 * TRAINING_SAMPLE)
+            .map(|i| {
+                // the weights of rare bytes depend on the sample most
+                let hash = (i as u64).wrapping_mul(0x9e3779b97f4a7c15) >> 32;
+                let rare = hash
+                    .is_multiple_of(97)
+                    .then(|| (b'g' + (hash / 97 % 20) as u8) as char);
+                format!("{hash:x}{}", rare.unwrap_or('-'))
+            })
+            .collect::<Vec<_>>();
+        let encoded = || {
+            let code = train(keys.iter());
+            keys.iter()
+                .map(|key| code.encode(key.as_bytes()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(encoded(), encoded());
+    }
+}
@@ -109,9 +109,9 @@ pub fn pforsplit(
     (count, build_time, ii)
 }
 
-impl InvertedIndex<Vec<TableLocation>> for IIFastPforSplit {
-    fn get(&self, key: &str) -> Vec<TableLocation> {
-        let index = *self.ii.get(key).expect("to find index");
+impl InvertedIndex<Option<Vec<TableLocation>>> for IIFastPforSplit {
+    fn get(&self, key: &str) -> Option<Vec<TableLocation>> {
+        let index = *self.ii.get(key)?;
 
         let mut start = index.start;
         let values = index.length.map(|length| {
@@ -139,7 +139,7 @@ impl InvertedIndex<Vec<TableLocation>> for IIFastPforSplit {
             tables.push(l);
         }
 
-        tables
+        Some(tables)
     }
 }
 
@@ -98,9 +98,9 @@ pub fn pfor(receiver: Receiver<(String, TableLocation)>) -> (usize, Duration, II
     (count, build_time, ii)
 }
 
-impl InvertedIndex<Vec<TableLocation>> for IIFastPfor {
-    fn get(&self, key: &str) -> Vec<TableLocation> {
-        let index = *self.ii.get(key).expect("to find index");
+impl InvertedIndex<Option<Vec<TableLocation>>> for IIFastPfor {
+    fn get(&self, key: &str) -> Option<Vec<TableLocation>> {
+        let index = *self.ii.get(key)?;
 
         let compressed_data =
             &self.compressed_data[index.start..(index.start + index.length as usize)];
@@ -121,7 +121,7 @@ impl InvertedIndex<Vec<TableLocation>> for IIFastPfor {
             tables.push(l);
         }
 
-        tables
+        Some(tables)
     }
 }
 
@@ -0,0 +1,143 @@
+use crate::inverted_index::InvertedIndex;
+use crate::table_lake::*;
+use roaring_bitmap::GroupedBitmaps;
+use std::collections::HashMap;
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+
+/// (tableid, colid) packed into a single u64, used as group inside the bitmaps.
+fn pack(tableid: u32, colid: u32) -> u64 {
+    (tableid as u64) << 32 | colid as u64
+}
+
+fn unpack(group: u64) -> (u32, u32) {
+    ((group >> 32) as u32, group as u32)
+}
+
+/// Posting lists of rowids stored as roaring bitmaps, grouped by (tableid, colid).
+/// Very frequent keys tend to cluster inside few tables, which is where this shines.
+pub struct IIRoaring {
+    ii: HashMap<String, GroupedBitmaps>,
+}
+
+fn locations(bitmaps: &GroupedBitmaps) -> Vec<TableLocation> {
+    let mut tables = Vec::with_capacity(bitmaps.len() as usize);
+    for (group, rowid) in bitmaps.iter() {
+        let (tableid, colid) = unpack(group);
+        tables.push(TableLocation {
+            tableid,
+            colid,
+            rowid,
+        });
+    }
+
+    tables
+}
+
+pub fn roaring(receiver: Receiver<(String, TableLocation)>) -> (usize, Duration, IIRoaring) {
+    let mut ii = HashMap::new();
+    let mut build_time = Duration::new(0, 0);
+
+    let mut count = 0;
+
+    let (mut curr_key, loc) = receiver.recv().expect("first item");
+    let mut group = vec![loc];
+    count += 1;
+
+    for (key, location) in receiver {
+        count += 1;
+        let starttime = Instant::now();
+
+        if key != curr_key {
+            let bitmaps = group
+                .iter()
+                .map(|l| (pack(l.tableid, l.colid), l.rowid))
+                .collect();
+            ii.insert(curr_key, bitmaps);
+
+            curr_key = key;
+            group.clear();
+        }
+
+        group.push(location);
+
+        build_time += starttime.elapsed();
+    }
+
+    {
+        let starttime = Instant::now();
+
+        let bitmaps = group
+            .iter()
+            .map(|l| (pack(l.tableid, l.colid), l.rowid))
+            .collect();
+        ii.insert(curr_key, bitmaps);
+        ii.shrink_to_fit();
+
+        build_time += starttime.elapsed();
+    }
+
+    (count, build_time, IIRoaring { ii })
+}
+
+impl InvertedIndex<Option<Vec<TableLocation>>> for IIRoaring {
+    fn get(&self, key: &str) -> Option<Vec<TableLocation>> {
+        let bitmaps = self.ii.get(key)?;
+        Some(locations(bitmaps))
+    }
+}
+
+impl crate::util::RandomKeys for IIRoaring {
+    fn random_keys_potentially_ordered(&self) -> Vec<String> {
+        self.ii.random_keys_potentially_ordered()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::sync_channel;
+
+    #[test]
+    fn get_and_intersect() {
+        let (sender, receiver) = sync_channel(64);
+
+        let rows = [
+            ("no", 1, 0, 5),
+            ("no", 1, 0, 2),
+            ("no", 2, 1, 7),
+            ("yes", 1, 0, 2),
+            ("yes", 2, 1, 7),
+            ("yes", 3, 0, 1),
+        ];
+        for (key, tableid, colid, rowid) in rows {
+            let location = TableLocation {
+                tableid,
+                colid,
+                rowid,
+            };
+            sender.send((key.to_string(), location)).unwrap();
+        }
+        drop(sender);
+
+        let (count, _, ii) = roaring(receiver);
+        assert_eq!(count, rows.len());
+
+        let loc = |tableid, colid, rowid| TableLocation {
+            tableid,
+            colid,
+            rowid,
+        };
+
+        assert_eq!(
+            ii.get("no").unwrap(),
+            [loc(1, 0, 2), loc(1, 0, 5), loc(2, 1, 7)],
+            "locations are ordered by (tableid, colid, rowid)"
+        );
+        assert_eq!(ii.get("yes").unwrap().len(), 3);
+        assert_eq!(ii.get("maybe"), None);
+
+        let intersection = ii.ii["no"].intersect(&ii.ii["yes"]);
+        assert_eq!(locations(&intersection), [loc(1, 0, 2), loc(2, 1, 7)]);
+    }
+}
@@ -1,8 +1,7 @@
-use crate::inverted_index::{binary_search_by_index, InvertedIndex};
+use crate::inverted_index::InvertedIndex;
 use crate::table_lake::*;
 use crate::util::random_keys::{RandomKeys, DESIRED_KEY_COUNT};
 use rand::random;
-use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::sync::mpsc::Receiver;
 use std::time::{Duration, Instant};
@@ -11,10 +10,10 @@ pub struct SmazInvertedIndex {
     data: HashMap<Vec<u8>, Vec<TableLocation>>,
 }
 
-impl InvertedIndex<Vec<TableLocation>> for SmazInvertedIndex {
-    fn get(&self, key: &str) -> Vec<TableLocation> {
+impl InvertedIndex<Option<Vec<TableLocation>>> for SmazInvertedIndex {
+    fn get(&self, key: &str) -> Option<Vec<TableLocation>> {
         let key = fast_smaz::compress(key);
-        self.data.get(&key).expect("to find key").clone()
+        self.data.get(&key).cloned()
     }
 }
 
@@ -35,9 +34,7 @@ impl RandomKeys for SmazInvertedIndex {
     }
 }
 
-pub(crate) fn smaz(
-    receiver: Receiver<(String, TableLocation)>,
-) -> (usize, Duration, SmazInvertedIndex) {
+pub fn smaz(receiver: Receiver<(String, TableLocation)>) -> (usize, Duration, SmazInvertedIndex) {
     let mut data = HashMap::new();
     let mut entry_count = 0;
 
@@ -71,6 +68,10 @@ pub(crate) fn smaz(
     (entry_count, build_time, SmazInvertedIndex { data })
 }
 
+/// Entries sorted by their smaz compressed key.
+///
+/// Smaz is not order preserving, so the entries are sorted by the compressed bytes,
+/// which only allows exact lookups. See `order_preserving` for range queries on compressed keys.
 pub struct SmazInvertedIndexRaw {
     data: Vec<(Vec<u8>, TableLocation)>,
 }
@@ -92,10 +93,11 @@ pub fn smaz_raw(
         build_time += starttime.elapsed();
     }
 
-    // let starttime = Instant::now();
-    // eprintln!("sorting data");
-    // data.sort();
-    // build_time += starttime.elapsed();
+    // the order of the input is lost during compression
+    let starttime = Instant::now();
+    eprintln!("sorting data");
+    data.sort_unstable();
+    build_time += starttime.elapsed();
 
     (data.len(), build_time, SmazInvertedIndexRaw { data })
 }
@@ -105,86 +107,26 @@ impl InvertedIndex<Vec<TableLocation>> for SmazInvertedIndexRaw {
         let key = fast_smaz::compress(key);
         let key = &key as &[u8];
 
-        fn get_start_point(a: &[(Vec<u8>, TableLocation)], index: usize, elem: &[u8]) -> Ordering {
-            let value = &a[index].0 as &[u8];
-            if index == 0 {
-                return value.cmp(elem);
-            }
-
-            match value.cmp(elem) {
-                Ordering::Equal => {
-                    if (&a[index - 1].0 as &[u8]) < elem {
-                        Ordering::Equal
-                    } else {
-                        Ordering::Greater
-                    }
-                }
-                o => o,
-            }
-        }
-
-        fn get_end_point(a: &[(Vec<u8>, TableLocation)], index: usize, elem: &[u8]) -> Ordering {
-            let value = &a[index].0 as &[u8];
-            if index + 1 == a.len() {
-                return value.cmp(elem);
-            }
-
-            match value.cmp(elem) {
-                Ordering::Equal => {
-                    if (&a[index + 1].0 as &[u8]) < elem {
-                        Ordering::Equal
-                    } else {
-                        Ordering::Less
-                    }
-                }
-                o => o,
-            }
-        }
-
-        let startindex =
-            binary_search_by_index(&self.data, 0, self.data.len(), get_start_point, key)
-                .unwrap_or(0);
+        let startindex = self.data.partition_point(|(k, _)| (k as &[u8]) < key);
+        let endindex = startindex + self.data[startindex..].partition_point(|(k, _)| k == key);
 
-        let endindex =
-            binary_search_by_index(&self.data, 0, self.data.len(), get_end_point, key).unwrap_or(6);
-
-        let size = endindex - startindex;
-
-        let mut v = Vec::with_capacity(size);
-
-        for (_, location) in self.data[startindex..endindex].iter() {
-            v.push(*location);
-        }
-
-        v
+        self.data[startindex..endindex]
+            .iter()
+            .map(|(_, location)| *location)
+            .collect()
     }
 }
 
 impl crate::util::RandomKeys for SmazInvertedIndexRaw {
     fn random_keys_potentially_ordered(&self) -> Vec<String> {
-        Vec::new()
-
-        // let mut v = Vec::with_capacity(self.data.len() / 10);
-
-        // let mut s: &[u8] = &[];
-        // for elem in self.data.iter() {
-        //     let si = &elem.0 as &[u8];
-        //     if s == si {
-        //         continue;
-        //     }
-
-        //     s = si;
-        //     v.push(si);
-        // }
-
-        // (0..crate::util::random_keys::DESIRED_KEY_COUNT)
-        //     .map(|_| {
-        //         let index = random::<f64>() * v.len() as f64;
-        //         let s = v[index as usize];
-        //         let s = fast_smaz::decompress(s).expect("smaz decompress key");
-
-        //         String::from_utf8(s).expect("smaz valid utf8 string")
-        //     })
-        //     .collect()
+        (0..DESIRED_KEY_COUNT)
+            .map(|_| {
+                let index = random::<f64>() * self.data.len() as f64;
+                let key = &self.data[index as usize].0;
+                let key = fast_smaz::decompress(key).expect("smaz decompress key");
+
+                String::from_utf8(key).expect("smaz valid utf8 string")
+            })
+            .collect()
     }
 }
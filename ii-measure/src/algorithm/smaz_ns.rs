@@ -11,10 +11,10 @@ pub struct SmazNsInvertedIndex {
     data: HashMap<Vec<u8>, Vec<u8>>,
 }
 
-impl InvertedIndex<Vec<TableLocation>> for SmazNsInvertedIndex {
-    fn get(&self, key: &str) -> Vec<TableLocation> {
+impl InvertedIndex<Option<Vec<TableLocation>>> for SmazNsInvertedIndex {
+    fn get(&self, key: &str) -> Option<Vec<TableLocation>> {
         let key = fast_smaz::compress(key);
-        let data = self.data.get(&key).expect("to find key");
+        let data = self.data.get(&key)?;
         let data = decompress(data).collect();
 
         let mut locations = Vec::with_capacity(data.len() / 3);
@@ -27,7 +27,7 @@ impl InvertedIndex<Vec<TableLocation>> for SmazNsInvertedIndex {
             locations.push(location);
         }
 
-        locations
+        Some(locations)
     }
 }
 
@@ -48,7 +48,7 @@ impl RandomKeys for SmazNsInvertedIndex {
     }
 }
 
-pub(crate) fn smaz_ns(
+pub fn smaz_ns(
     receiver: Receiver<(String, TableLocation)>,
 ) -> (usize, Duration, SmazNsInvertedIndex) {
     let mut data = HashMap::new();
@@ -1,4 +1,5 @@
 use crate::inverted_index::InvertedIndex;
+use crate::pipeline::{pipelined, Batch};
 use crate::table_lake::*;
 use std::collections::HashMap;
 use std::sync::mpsc::Receiver;
@@ -70,15 +71,15 @@ pub struct VByteEncoded {
 impl VByteEncoded {
     pub fn new(receiver: Receiver<(String, TableLocation)>) -> (usize, Duration, Self) {
         let mut data = HashMap::new();
-        let mut entry_count = 0;
+        let mut entry_count = 1;
 
         // we're using the index to group received indices
-        // zzz is pretty late in the alphabet, so not the first thing that we receive. nice
-        let mut group_id = String::from("zzzz");
+        let (mut group_id, location) = receiver.recv().expect("first item from receiver");
 
         // we're using an intermediate buffer
         // to collect the integers we'd like to compress
         let mut current_buffer = Vec::<TableLocation>::with_capacity(256);
+        current_buffer.push(location);
 
         let mut build_time = Duration::new(0, 0);
 
@@ -101,14 +102,39 @@ impl VByteEncoded {
             entry_count += 1;
         }
 
+        let starttime = Instant::now();
+        let entry = VBList::from_table_locations(current_buffer.into_iter());
+        data.insert(group_id, entry);
+        build_time += starttime.elapsed();
+
         (entry_count, build_time, Self { data })
     }
 }
 
-impl InvertedIndex<Vec<TableLocation>> for VByteEncoded {
-    fn get(&self, key: &str) -> Vec<TableLocation> {
-        let data = self.data.get(key).expect("key to be present");
-        data.locations()
+impl VByteEncoded {
+    /// Builds the index on `workers` threads, which encode the posting lists.
+    pub fn parallel(workers: usize) -> impl Fn(Receiver<Entry>) -> (usize, Duration, Self) {
+        pipelined(
+            workers,
+            |batch: Batch| {
+                batch
+                    .into_iter()
+                    .map(|(key, locations)| {
+                        (key, VBList::from_table_locations(locations.into_iter()))
+                    })
+                    .collect::<Vec<_>>()
+            },
+            |in_order| Self {
+                data: in_order.flatten().collect(),
+            },
+        )
+    }
+}
+
+impl InvertedIndex<Option<Vec<TableLocation>>> for VByteEncoded {
+    fn get(&self, key: &str) -> Option<Vec<TableLocation>> {
+        let data = self.data.get(key)?;
+        Some(data.locations())
     }
 }
 
@@ -1,4 +1,5 @@
 use crate::inverted_index::InvertedIndex;
+use crate::pipeline::{pipelined, Batch};
 use crate::table_lake::*;
 use std::collections::HashMap;
 use std::sync::mpsc::Receiver;
@@ -19,8 +20,24 @@ impl VBList {
         arena: &mut Vec<u8>,
         vs: impl Iterator<Item = TableLocation>,
     ) -> Self {
+        let data = Self::encode(vs);
+        Self::from_encoded(arena, &data)
+    }
+
+    /// Appends a list encoded by `encode` to the arena.
+    pub fn from_encoded(arena: &mut Vec<u8>, data: &[u8]) -> Self {
         let data_start = arena.len();
+        arena.extend_from_slice(data);
+        let data_end = arena.len();
+
+        VBList {
+            data_start,
+            data_end,
+        }
+    }
 
+    /// The encoded list, without storing it in an arena.
+    pub fn encode(vs: impl Iterator<Item = TableLocation>) -> Vec<u8> {
         let mut cr = Vec::new();
         let mut t = Vec::new();
         for v in vs {
@@ -32,15 +49,11 @@ impl VBList {
         let vdata = vbyte::compress_list(&cr);
         let vbytelen = vdata.len();
 
-        arena.extend(vbyte::compress(vbytelen as u64));
-        arena.extend(vdata);
-        arena.extend(group_varint_encoding::compress(t));
-        let data_end = arena.len();
+        let mut data = vbyte::compress(vbytelen as u64);
+        data.extend(vdata);
+        data.extend(group_varint_encoding::compress(t));
 
-        VBList {
-            data_start,
-            data_end,
-        }
+        data
     }
 
     pub fn locations(self, arena: &[u8]) -> Vec<TableLocation> {
@@ -83,15 +96,15 @@ impl VByteEncoded {
     pub fn new(receiver: Receiver<(String, TableLocation)>) -> (usize, Duration, Self) {
         let mut arena = Vec::new();
         let mut data = HashMap::new();
-        let mut entry_count = 0;
+        let mut entry_count = 1;
 
         // we're using the index to group received indices
-        // zzz is pretty late in the alphabet, so not the first thing that we receive. nice
-        let mut group_id = String::from("zzzz");
+        let (mut group_id, location) = receiver.recv().expect("first item from receiver");
 
         // we're using an intermediate buffer
         // to collect the integers we'd like to compress
         let mut current_buffer = Vec::<TableLocation>::with_capacity(256);
+        current_buffer.push(location);
 
         let mut build_time = Duration::new(0, 0);
 
@@ -115,14 +128,45 @@ impl VByteEncoded {
             entry_count += 1;
         }
 
+        let starttime = Instant::now();
+        let entry = VBList::from_table_locations(&mut arena, current_buffer.into_iter());
+        data.insert(group_id, entry);
+        build_time += starttime.elapsed();
+
         (entry_count, build_time, Self { data, arena })
     }
 }
 
-impl InvertedIndex<Vec<TableLocation>> for VByteEncoded {
-    fn get(&self, key: &str) -> Vec<TableLocation> {
-        let data = self.data.get(key).expect("key to be present");
-        data.locations(&self.arena)
+impl VByteEncoded {
+    /// Builds the index on `workers` threads, which encode the posting lists.
+    /// The encoded lists are appended to the arena in key order.
+    pub fn parallel(workers: usize) -> impl Fn(Receiver<Entry>) -> (usize, Duration, Self) {
+        pipelined(
+            workers,
+            |batch: Batch| {
+                batch
+                    .into_iter()
+                    .map(|(key, locations)| (key, VBList::encode(locations.into_iter())))
+                    .collect::<Vec<_>>()
+            },
+            |in_order| {
+                let mut arena = Vec::new();
+                let mut data = HashMap::new();
+                for (key, encoded) in in_order.flatten() {
+                    data.insert(key, VBList::from_encoded(&mut arena, &encoded));
+                }
+                arena.shrink_to_fit();
+
+                Self { data, arena }
+            },
+        )
+    }
+}
+
+impl InvertedIndex<Option<Vec<TableLocation>>> for VByteEncoded {
+    fn get(&self, key: &str) -> Option<Vec<TableLocation>> {
+        let data = self.data.get(key)?;
+        Some(data.locations(&self.arena))
     }
 }
 
@@ -6,22 +6,23 @@ use std::{
 use crate::{
     inverted_index::InvertedIndex, table_lake::TableLocation, util::random_keys::DESIRED_KEY_COUNT,
 };
-use dict_incremental_coding_improved::Dict;
+use dict_compressed::{Dict, ImprovedIncrementalCoding, SortedDict};
 use rand::random;
 
 use super::vbyte::VBList;
 
-pub struct VByteEncoded {
-    dict: Dict<VBList, 16>,
+/// Keys are compressed in blocks of `B` keys.
+pub struct VByteEncoded<const B: usize = 16> {
+    dict: Dict<VBList, ImprovedIncrementalCoding, B>,
 }
 
-impl VByteEncoded {
-    pub fn new(receiver: Receiver<(String, TableLocation)>) -> (usize, Duration, VByteEncoded) {
+impl<const B: usize> VByteEncoded<B> {
+    pub fn new(receiver: Receiver<(String, TableLocation)>) -> (usize, Duration, VByteEncoded<B>) {
         let mut dict = Dict::new();
 
         let mut build_time = Duration::new(0, 0);
 
-        let mut count = 0;
+        let mut count = 1;
 
         let (mut current_key, location_group) = receiver.recv().expect("first item from receiver");
         let mut location_group = vec![location_group];
@@ -43,24 +44,29 @@ impl VByteEncoded {
             build_time += starttime.elapsed();
         }
 
+        let starttime = Instant::now();
+        dict.push(
+            current_key.into_bytes(),
+            VBList::from_table_locations(location_group.into_iter()),
+        );
+        dict.finish();
+        build_time += starttime.elapsed();
+
         let ii = VByteEncoded { dict };
 
         (count, build_time, ii)
     }
 }
 
-impl InvertedIndex<Vec<TableLocation>> for VByteEncoded {
-    fn get(&self, key: &str) -> Vec<TableLocation> {
-        let index = self
-            .dict
-            .index_of(key.as_bytes())
-            .expect("to find key in dictionary");
+impl<const B: usize> InvertedIndex<Option<Vec<TableLocation>>> for VByteEncoded<B> {
+    fn get(&self, key: &str) -> Option<Vec<TableLocation>> {
+        let index = self.dict.index_of(key.as_bytes())?;
 
-        self.dict.values()[index].locations()
+        Some(self.dict.values()[index].locations())
     }
 }
 
-impl crate::util::RandomKeys for VByteEncoded {
+impl<const B: usize> crate::util::RandomKeys for VByteEncoded<B> {
     fn random_keys_potentially_ordered(&self) -> Vec<String> {
         let max = self.dict.len() as f64;
         (0..DESIRED_KEY_COUNT)
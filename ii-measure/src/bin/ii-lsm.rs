@@ -0,0 +1,181 @@
+use ii_measure::lsm::{LsmIndex, TieredPolicy};
+use ii_measure::util::indices_from_bintable;
+use ii_measure::Entry;
+use rand::rngs::StdRng;
+use rand::seq::IteratorRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::BTreeMap;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use structopt::StructOpt;
+
+use jemallocator::Jemalloc;
+
+#[global_allocator]
+static GLOBAL: Jemalloc = Jemalloc;
+
+#[derive(StructOpt)]
+#[structopt(
+    name = "ii-lsm",
+    about = "Insert the tables of a bintable one by one into a segmented (LSM) index and measure \
+             the write amplification and lookup times against the number of segments."
+)]
+struct Config {
+    /// Bintable to index, all of its entries are held in memory to insert them table by table
+    #[structopt()]
+    table: String,
+
+    /// Locations in the memtable before it is flushed into a segment
+    #[structopt(long, default_value = "100000")]
+    flush: usize,
+
+    /// Segments of a tier merged into one segment of the next tier
+    #[structopt(long, default_value = "4")]
+    fanout: usize,
+
+    /// Share of the tables deleted after all tables were inserted
+    #[structopt(long, default_value = "0")]
+    delete: f64,
+
+    /// Seed of the looked up keys and the deleted tables
+    #[structopt(long, default_value = "0")]
+    seed: u64,
+
+    /// Print the csv header
+    #[structopt(short, long)]
+    header: bool,
+}
+
+/// Keys looked up after every flush
+const LOOKUP_KEYS: usize = 1000;
+
+/// State of the index after a step of the benchmark
+struct Report {
+    step: &'static str,
+    tables: usize,
+    segments: usize,
+    memtable: usize,
+    bytes: usize,
+    write_amplification: f64,
+    lookup: Duration,
+}
+
+fn report(step: &'static str, tables: usize, index: &LsmIndex, keys: &[String]) -> Report {
+    let starttime = Instant::now();
+    for key in keys {
+        black_box(index.get(key));
+    }
+    let lookup = starttime.elapsed() / keys.len().max(1) as u32;
+
+    let report = Report {
+        step,
+        tables,
+        segments: index.segments().len(),
+        memtable: index.memtable_locations(),
+        bytes: index.bytes(),
+        write_amplification: index.stats().write_amplification(),
+        lookup,
+    };
+    eprintln!(
+        "{step}: {tables} tables, {} segments, lookup {}ns",
+        report.segments,
+        lookup.as_nanos()
+    );
+    report
+}
+
+fn main() {
+    let Config {
+        table,
+        flush,
+        fanout,
+        delete,
+        seed,
+        header,
+    } = Config::from_args();
+
+    if fanout < 2 || flush == 0 || !(0.0..=1.0).contains(&delete) {
+        eprintln!("--fanout must be at least 2, --flush at least 1 and --delete between 0 and 1");
+        std::process::exit(1);
+    }
+
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    // tables are inserted one by one, the bintable is sorted by key
+    eprintln!("reading {table}");
+    let mut tables = BTreeMap::<u32, Vec<Entry>>::new();
+    let mut keys = Vec::with_capacity(LOOKUP_KEYS);
+    for (i, (key, location)) in indices_from_bintable(&table, None).into_iter().enumerate() {
+        // reservoir sample of the keys
+        if keys.len() < LOOKUP_KEYS {
+            keys.push(key.clone());
+        } else {
+            let j = rng.gen_range(0..=i);
+            if j < LOOKUP_KEYS {
+                keys[j].clone_from(&key);
+            }
+        }
+        tables
+            .entry(location.tableid)
+            .or_default()
+            .push((key, location));
+    }
+
+    let tableids = tables.keys().copied().collect::<Vec<_>>();
+    let mut index = LsmIndex::<16>::new(TieredPolicy::new(flush, fanout));
+    let mut reports = Vec::new();
+
+    let mut inserted = 0;
+    for entries in tables.into_values() {
+        let flushes = index.stats().flushes;
+        for (key, location) in entries {
+            index.insert(key, location);
+        }
+        inserted += 1;
+
+        if index.stats().flushes > flushes {
+            reports.push(report("insert", inserted, &index, &keys));
+        }
+    }
+
+    index.flush();
+    index.wait_for_merges();
+    reports.push(report("merged", inserted, &index, &keys));
+
+    if delete > 0.0 {
+        let count = (tableids.len() as f64 * delete).round() as usize;
+        for tableid in tableids.iter().choose_multiple(&mut rng, count) {
+            index.delete_table(*tableid);
+        }
+        inserted -= count;
+        reports.push(report("delete", inserted, &index, &keys));
+    }
+
+    index.compact();
+    reports.push(report("compact", inserted, &index, &keys));
+
+    // slowdown against a single segment
+    let compacted = reports
+        .last()
+        .expect("compacted index")
+        .lookup
+        .as_nanos()
+        .max(1);
+    let stats = index.stats();
+    eprintln!(
+        "{} flushes, {} merges, {} bytes written",
+        stats.flushes, stats.merges, stats.written_bytes
+    );
+
+    if header {
+        println!("step;tables;segments;memtable_locations;bytes;write_amplification;lookup_avg_nanosec;slowdown;flush;fanout");
+    }
+    for r in reports {
+        let lookup = r.lookup.as_nanos();
+        let slowdown = lookup as f64 / compacted as f64;
+        println!(
+            "{};{};{};{};{};{:.3};{lookup};{slowdown:.3};{flush};{fanout}",
+            r.step, r.tables, r.segments, r.memtable, r.bytes, r.write_amplification
+        );
+    }
+}
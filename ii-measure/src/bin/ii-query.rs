@@ -0,0 +1,87 @@
+use ii_measure::kinds::CompressionAlgorithm;
+use ii_measure::query::{self, repl};
+use ii_measure::util::{indices_from_bintable, load_metadata};
+use std::io::{BufRead, IsTerminal, Write};
+use structopt::StructOpt;
+
+use jemallocator::Jemalloc;
+
+#[global_allocator]
+static GLOBAL: Jemalloc = Jemalloc;
+
+#[derive(StructOpt)]
+#[structopt(
+    name = "ii-query",
+    about = "Build an inverted index from a bintable and query it interactively. Commands are read from stdin, type help for a list."
+)]
+struct Config {
+    #[structopt(short, long)]
+    algorithm: CompressionAlgorithm,
+
+    /// Bintable to index
+    #[structopt()]
+    table: String,
+
+    /// Number of keys per block (8, 16, 32, 64 or 128, 16 is default).
+    /// Only supported by the incremental coding algorithms (incr*, vbyte+ns+incr+dedup).
+    #[structopt(long)]
+    blocksize: Option<usize>,
+
+    /// Output format, text or json (one object per line)
+    #[structopt(long, default_value = "text")]
+    format: repl::Format,
+
+    /// Number of locations shown per query
+    #[structopt(long, default_value = "20")]
+    limit: usize,
+}
+
+fn main() {
+    let Config {
+        algorithm,
+        table,
+        blocksize,
+        format,
+        limit,
+    } = Config::from_args();
+
+    let blocksize = algorithm
+        .check_implemented()
+        .and_then(|()| algorithm.checked_blocksize(blocksize))
+        .unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(1);
+        });
+
+    eprintln!("indexing {} using {}", table, algorithm.str());
+    let metadata = load_metadata(&table);
+    let receiver = indices_from_bintable(&table, None);
+    let (entries, build_time, index) = query::build(algorithm, blocksize, receiver);
+    eprintln!("{entries} entries indexed in {}ms", build_time.as_millis());
+
+    let mut repl = repl::Repl::new(&*index, metadata.as_ref());
+    repl.format = format;
+    repl.limit = limit;
+
+    let interactive = std::io::stdin().is_terminal();
+    let prompt = || {
+        if interactive {
+            eprint!("> ");
+            std::io::stderr().flush().expect("flush prompt");
+        }
+    };
+
+    prompt();
+    let stdout = std::io::stdout();
+    for line in std::io::stdin().lock().lines() {
+        let line = line.expect("read command");
+        let Some(output) = repl.execute(&line) else {
+            break;
+        };
+
+        if !output.is_empty() {
+            writeln!(stdout.lock(), "{output}").expect("write output");
+        }
+        prompt();
+    }
+}
@@ -0,0 +1,87 @@
+use ii_measure::kinds::CompressionAlgorithm;
+use ii_measure::log::Logger;
+use ii_measure::query::build_logging;
+use ii_measure::serve::{serve, Server};
+use ii_measure::table_lake::runtime;
+use ii_measure::util::{indices_from_bintable, load_metadata};
+use std::net::SocketAddr;
+use structopt::StructOpt;
+
+use jemallocator::Jemalloc;
+
+#[global_allocator]
+static GLOBAL: Jemalloc = Jemalloc;
+
+#[derive(StructOpt)]
+#[structopt(
+    name = "ii-serve",
+    about = "Build an inverted index from a bintable and answer queries over HTTP with JSON."
+)]
+struct Config {
+    #[structopt(short, long)]
+    algorithm: CompressionAlgorithm,
+
+    /// Bintable to index
+    #[structopt()]
+    table: String,
+
+    /// Number of keys per block (8, 16, 32, 64 or 128, 16 is default).
+    /// Only supported by the incremental coding algorithms (incr*, vbyte+ns+incr+dedup).
+    #[structopt(long)]
+    blocksize: Option<usize>,
+
+    /// Address to listen on
+    #[structopt(long, default_value = "127.0.0.1:8080")]
+    address: SocketAddr,
+
+    /// Number of locations returned per key, unless a request sets `limit`
+    #[structopt(long, default_value = "100")]
+    limit: usize,
+}
+
+fn basename(s: &str) -> String {
+    s.rsplit('/').next().unwrap().to_owned()
+}
+
+fn main() {
+    let Config {
+        algorithm,
+        table,
+        blocksize,
+        address,
+        limit,
+    } = Config::from_args();
+
+    let blocksize = algorithm
+        .check_implemented()
+        .and_then(|()| algorithm.checked_blocksize(blocksize))
+        .unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(1);
+        });
+
+    eprintln!("indexing {} using {}", table, algorithm.str());
+    let server = Server::start(
+        move || {
+            let mut log = Logger::new(
+                algorithm.str().to_string(),
+                basename(&table),
+                false,
+                false,
+                None,
+                blocksize,
+                None,
+            )
+            .with_metadata(load_metadata(&table));
+
+            let receiver = indices_from_bintable(&table, None);
+            let index = build_logging(algorithm, blocksize, receiver, &mut log);
+            eprintln!("{}", log.to_json());
+
+            (index, log)
+        },
+        limit,
+    );
+
+    runtime().block_on(serve(server, address)).expect("serve");
+}
@@ -1,4 +1,6 @@
-use crate::kinds::CompressionAlgorithm;
+use bintable2::SampleMode;
+use ii_measure::kinds::CompressionAlgorithm;
+use ii_measure::shard::Partition;
 use structopt::StructOpt;
 
 #[derive(StructOpt)]
@@ -13,6 +15,16 @@ pub struct Config {
     #[structopt(short, long)]
     pub factor: Option<f32>,
 
+    /// What to sample: single rows, entire posting lists (key) or entire tables (table)
+    #[structopt(long, default_value = "row")]
+    pub sample: SampleMode,
+
+    /// Seed of the sample, the same seed always samples the same rows of the same source.
+    /// Postgres hashes rows with `hashtextextended`, bintables with their own hash,
+    /// so a table sampled from postgres and from its bintable export keeps different rows
+    #[structopt(long, default_value = "0")]
+    pub seed: u64,
+
     #[structopt(short, long)]
     pub algorithm: CompressionAlgorithm,
 
@@ -22,9 +34,20 @@ pub struct Config {
     #[structopt()]
     pub table: String,
 
+    /// Read the table from postgres instead of a bintable
     #[structopt(short, long)]
     pub database: bool,
 
+    /// Postgres connection string, defaults to $DATABASE_URL
+    /// or is composed from $DATABASE_USER, $DATABASE_PASSWORD, $DATABASE_DB, $DATABASE_HOST and $DATABASE_PORT
+    #[structopt(long)]
+    pub database_url: Option<String>,
+
+    /// File to store the position of the last batch taken from postgres in.
+    /// An interrupted run resumes from it, its measurement is logged as partial.
+    #[structopt(long)]
+    pub checkpoint: Option<String>,
+
     /// Wether or not to include header in output csv
     #[structopt(short, long)]
     pub header: bool,
@@ -36,4 +59,30 @@ pub struct Config {
     /// Wether this run was performed in parrallel along with other instances
     #[structopt(short, long)]
     pub multi_proc: bool,
+
+    /// Also measure prefix, glob and fuzzy search.
+    /// Only supported by algorithms over sorted dictionaries, ignored otherwise.
+    #[structopt(long)]
+    pub search: bool,
+
+    /// Number of keys per block (8, 16, 32, 64 or 128, 16 is default).
+    /// Only supported by the incremental coding algorithms (incr*, vbyte+ns+incr+dedup).
+    #[structopt(long)]
+    pub blocksize: Option<usize>,
+
+    /// Split the index into this many shards, each built on its own thread.
+    /// Not supported by the FastPFor algorithms (pfor+dedup, pfor_split+dedup, adaptive+dedup).
+    #[structopt(long)]
+    pub shards: Option<usize>,
+
+    /// How keys are assigned to shards: hash, or range (sorted keys split into ranges of the same size,
+    /// the source is read twice to count its entries)
+    #[structopt(long, default_value = "hash")]
+    pub partition: Partition,
+
+    /// Encode the posting lists on this many worker threads, while the entries are grouped and the
+    /// index is assembled on threads of their own. Only supported by frontcoding+dedup, incr+dedup, repair+dedup,
+    /// vbyte+ns+dedup, vbyte+arena+ns+dedup and incr_adv+dedup+ns.
+    #[structopt(long)]
+    pub workers: Option<usize>,
 }
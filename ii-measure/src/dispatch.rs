@@ -0,0 +1,135 @@
+//! Selects the index builder of a `CompressionAlgorithm`, shared by `ii-measure`, `ii-query` and `ii-serve`.
+
+use crate::inverted_index::{InvertedIndex, KeySearch};
+use crate::kinds::CompressionAlgorithm;
+use crate::measure::Locations;
+use crate::query::Explain;
+use crate::util::RandomKeys;
+use crate::{algorithm, with_blocksize, Entry};
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+/// Receives the builder of the index chosen on the command line, by what the index supports.
+pub trait Builder {
+    type Output;
+
+    /// Index only supporting exact lookups
+    fn lookup<F, II, O>(self, build: F) -> Self::Output
+    where
+        F: Fn(Receiver<Entry>) -> (usize, Duration, II) + Sync,
+        II: InvertedIndex<O> + RandomKeys + Send + 'static,
+        O: Locations + 'static;
+
+    /// Index over sorted keys
+    fn search<F, II, O>(self, build: F) -> Self::Output
+    where
+        F: Fn(Receiver<Entry>) -> (usize, Duration, II) + Sync,
+        II: InvertedIndex<O> + KeySearch + RandomKeys + Send + 'static,
+        O: Locations + 'static;
+
+    /// Index over sorted keys, stored in blocks
+    fn blocks<F, II, O>(self, build: F) -> Self::Output
+    where
+        F: Fn(Receiver<Entry>) -> (usize, Duration, II) + Sync,
+        II: InvertedIndex<O> + KeySearch + Explain + RandomKeys + Send + 'static,
+        O: Locations + 'static;
+
+    /// Index sharing a FastPFor codec, which can only be built and used on a single thread
+    fn local<F, II, O>(self, build: F) -> Self::Output
+    where
+        F: Fn(Receiver<Entry>) -> (usize, Duration, II),
+        II: InvertedIndex<O> + RandomKeys + 'static,
+        O: Locations + 'static;
+}
+
+/// Passes the index builder of `algorithm` to `builder`. `workers` selects the builders encoding on a pool of workers,
+/// it is ignored by algorithms not supporting it.
+/// The algorithm has to be checked with `CompressionAlgorithm::check_implemented` and `blocksize` with
+/// `CompressionAlgorithm::checked_blocksize`.
+pub fn build<T: Builder>(
+    algorithm: CompressionAlgorithm,
+    blocksize: Option<usize>,
+    workers: Option<usize>,
+    builder: T,
+) -> T::Output {
+    use dict_compressed::{FrontCoding, IncrementalCoding, RePair};
+    use CompressionAlgorithm::*;
+
+    let b = builder;
+    match algorithm {
+        Baseline => b.lookup(algorithm::baseline),
+        BaselineExact => b.lookup(algorithm::baseline_exact),
+        DedupHash => b.lookup(algorithm::dedup_hash),
+        DedupBTree => b.lookup(algorithm::dedup_btree),
+
+        NSDedup => b.lookup(algorithm::ns_4_wise),
+        NS => b.lookup(algorithm::ns_raw),
+        NSArena => b.lookup(algorithm::ns_arena::ns_arena),
+
+        SmazFastPforDedup => unreachable!("{} is not implemented", algorithm.str()),
+        SmazDedup => b.lookup(algorithm::smaz),
+        Smaz => b.lookup(algorithm::smaz_raw),
+
+        FsstDedup => b.lookup(algorithm::fsst::fsst),
+        FsstNSDedup => b.lookup(algorithm::fsst::fsst_ns),
+
+        HuTucker => b.lookup(algorithm::order_preserving::hutucker_raw),
+        HuTuckerDedup => b.lookup(algorithm::order_preserving::hutucker),
+
+        FrontCodingBaseline => {
+            b.lookup(algorithm::block_dict::block_dict_baseline::<FrontCoding, 8>)
+        }
+        FrontCodingDedup => match workers {
+            Some(w) => b.blocks(algorithm::block_dict::block_dict_parallel::<FrontCoding, 8>(w)),
+            None => b.blocks(algorithm::block_dict::block_dict::<FrontCoding, 8>),
+        },
+
+        IncrCodingBaseline => {
+            b.lookup(algorithm::block_dict::block_dict_baseline::<IncrementalCoding, 8>)
+        }
+        IncrCodingDedup => with_blocksize!(blocksize.unwrap(), B => match workers {
+            Some(w) => b.blocks(algorithm::block_dict::block_dict_parallel::<IncrementalCoding, B>(w)),
+            None => b.blocks(algorithm::block_dict::block_dict::<IncrementalCoding, B>),
+        }),
+        RePairDedup => match workers {
+            Some(w) => b.blocks(algorithm::block_dict::block_dict_parallel::<RePair, 64>(w)),
+            None => b.blocks(algorithm::block_dict::block_dict::<RePair, 64>),
+        },
+
+        VByteArenaNSDedup => match workers {
+            Some(w) => b.lookup(algorithm::vbyte_arena::VByteEncoded::parallel(w)),
+            None => b.lookup(algorithm::vbyte_arena::VByteEncoded::new),
+        },
+        VByteNSDedup => match workers {
+            Some(w) => b.lookup(algorithm::vbyte::VByteEncoded::parallel(w)),
+            None => b.lookup(algorithm::vbyte::VByteEncoded::new),
+        },
+        VByteNSIncrDedup => with_blocksize!(blocksize.unwrap(), B => b.lookup(
+            algorithm::vbyteincr::VByteEncoded::<B>::new
+        )),
+
+        IncrCodingDedupNS => with_blocksize!(blocksize.unwrap(), B => b.lookup(
+            algorithm::incr_ns::InvertedIndexIncrementalCodingNS::<B>::new
+        )),
+        IncrCodingAdvancedDedupNS => with_blocksize!(blocksize.unwrap(), B => match workers {
+            Some(w) => b.blocks(algorithm::incr_adv_ns::InvertedIndexIncrementalCodingNS::<B>::parallel(w)),
+            None => b.blocks(algorithm::incr_adv_ns::InvertedIndexIncrementalCodingNS::<B>::new),
+        }),
+        IncrCodingAdvancedDedupNSAdvanced => with_blocksize!(blocksize.unwrap(), B => b.lookup(
+            algorithm::incr_adv_ns_adv::InvertedIndexIncrementalCodingNS::<B>::new
+        )),
+
+        SmazNSDedup => b.lookup(algorithm::smaz_ns),
+
+        FastPforDedup => b.local(algorithm::pfor_x::pfor),
+        FastPforSplitDedup => b.local(algorithm::pfor_split::pforsplit),
+
+        RoaringDedup => b.lookup(algorithm::roaring::roaring),
+        AdaptiveDedup => b.local(algorithm::adaptive::adaptive),
+
+        MphfDedup => b.lookup(algorithm::mphf::mphf),
+        MphfFrontCodingDedup => b.lookup(algorithm::mphf::mphf_verified),
+
+        LoudsDedup => b.search(algorithm::louds::louds),
+    }
+}
@@ -5,6 +5,30 @@ pub trait InvertedIndex<O> {
     fn get(&self, key: &str) -> O;
 }
 
+/// Inexact lookups on indices over sorted keys.
+/// Each query returns the posting lists of all matching keys, merged into a single sorted list.
+pub trait KeySearch {
+    /// all keys starting with `prefix`
+    fn prefix(&self, prefix: &str) -> Vec<TableLocation>;
+
+    /// all keys matching the glob `pattern` (`?` matches a single byte, `*` any number of bytes)
+    fn glob(&self, pattern: &str) -> Vec<TableLocation>;
+
+    /// all keys within an edit distance of at most `k` bytes to `key`
+    fn fuzzy(&self, key: &str, k: u32) -> Vec<TableLocation>;
+}
+
+/// Merges posting lists into one sorted list, without duplicates.
+pub(crate) fn merge_postings<L>(lists: impl Iterator<Item = L>) -> Vec<TableLocation>
+where
+    L: IntoIterator<Item = TableLocation>,
+{
+    let mut merged = lists.flatten().collect::<Vec<_>>();
+    merged.sort_unstable();
+    merged.dedup();
+    merged
+}
+
 impl InvertedIndex<Vec<TableLocation>> for Vec<(String, TableLocation)> {
     fn get(&self, key: &str) -> Vec<TableLocation> {
         fn get_start_point(a: &[(String, TableLocation)], index: usize, elem: &String) -> Ordering {
@@ -114,6 +138,32 @@ mod tests {
         }
     }
 
+    #[test]
+    fn merged_postings_are_sorted_and_unique() {
+        let location = |tableid, rowid| TableLocation {
+            tableid,
+            colid: 0,
+            rowid,
+        };
+
+        let lists = vec![
+            vec![location(3, 1), location(7, 0)],
+            vec![location(1, 2), location(3, 1)],
+            vec![],
+            vec![location(3, 0)],
+        ];
+
+        assert_eq!(
+            merge_postings(lists.into_iter()),
+            [
+                location(1, 2),
+                location(3, 0),
+                location(3, 1),
+                location(7, 0)
+            ]
+        );
+    }
+
     #[test]
     fn sorted_vec_inverted_index_search() {
         let ii = "abbcccddddfffffgggggghhhhhiiiijjjkklmmnnnoooooppppppqqrrstuuuuuvw"
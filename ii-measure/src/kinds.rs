@@ -19,12 +19,20 @@ pub enum CompressionAlgorithm {
     SmazDedup,
     Smaz,
 
+    FsstDedup,
+    FsstNSDedup,
+
+    HuTucker,
+    HuTuckerDedup,
+
     FrontCodingBaseline,
     FrontCodingDedup,
 
     IncrCodingBaseline,
     IncrCodingDedup,
 
+    RePairDedup,
+
     IncrCodingDedupNS,
 
     IncrCodingAdvancedDedupNS,
@@ -36,8 +44,19 @@ pub enum CompressionAlgorithm {
 
     SmazFastPforDedup,
     SmazNSDedup,
+
+    RoaringDedup,
+    AdaptiveDedup,
+
+    MphfDedup,
+    MphfFrontCodingDedup,
+
+    LoudsDedup,
 }
 
+/// Block sizes supported by `--blocksize`
+pub const BLOCKSIZES: [usize; 5] = [8, 16, 32, 64, 128];
+
 impl CompressionAlgorithm {
     fn lookup() -> Vec<(CompressionAlgorithm, &'static str)> {
         use CompressionAlgorithm::*;
@@ -53,10 +72,15 @@ impl CompressionAlgorithm {
             (NS, "ns"),
             (NSArena, "ns_arena"),
             (Smaz, "smaz"),
+            (FsstDedup, "fsst+dedup"),
+            (FsstNSDedup, "fsst+ns+dedup"),
+            (HuTucker, "hutucker"),
+            (HuTuckerDedup, "hutucker+dedup"),
             (FrontCodingBaseline, "frontcoding"),
             (FrontCodingDedup, "frontcoding+dedup"),
             (IncrCodingBaseline, "incr"),
             (IncrCodingDedup, "incr+dedup"),
+            (RePairDedup, "repair+dedup"),
             (IncrCodingDedupNS, "incr+dedup+ns"),
             (IncrCodingAdvancedDedupNS, "incr_adv+dedup+ns"),
             (IncrCodingAdvancedDedupNSAdvanced, "incr_adv+dedup+ns_adv"),
@@ -65,9 +89,89 @@ impl CompressionAlgorithm {
             (FastPforSplitDedup, "pfor_split+dedup"),
             (SmazFastPforDedup, "smaz+pfor+dedup"),
             (SmazNSDedup, "smaz+ns+dedup"),
+            (RoaringDedup, "roaring+dedup"),
+            (AdaptiveDedup, "adaptive+dedup"),
+            (MphfDedup, "mphf+dedup"),
+            (MphfFrontCodingDedup, "mphf+fc+dedup"),
+            (LoudsDedup, "louds+dedup"),
         ]
     }
 
+    /// Keys per block of the algorithms storing keys in blocks
+    pub fn blocksize(self, configured: Option<usize>) -> Option<usize> {
+        use CompressionAlgorithm::*;
+        match self {
+            FrontCodingBaseline | FrontCodingDedup | IncrCodingBaseline => Some(8),
+            MphfFrontCodingDedup => Some(16),
+            RePairDedup => Some(64),
+            _ if self.tunable_blocksize() => Some(configured.unwrap_or(16)),
+            _ => None,
+        }
+    }
+
+    /// Whether the block size can be set using `--blocksize`
+    pub fn tunable_blocksize(self) -> bool {
+        use CompressionAlgorithm::*;
+        matches!(
+            self,
+            IncrCodingDedup
+                | IncrCodingDedupNS
+                | IncrCodingAdvancedDedupNS
+                | IncrCodingAdvancedDedupNSAdvanced
+                | VByteNSIncrDedup
+        )
+    }
+
+    /// Whether the index can be built in shards using `--shards`.
+    /// FastPFor codecs are shared by all indices and keep state while encoding, so they can not be used by several threads.
+    pub fn shardable(self) -> bool {
+        use CompressionAlgorithm::*;
+        !matches!(
+            self,
+            FastPforDedup | FastPforSplitDedup | AdaptiveDedup | SmazFastPforDedup
+        )
+    }
+
+    /// Whether the index can be built by a pool of workers with `--workers`.
+    /// The FastPFor codecs are shared and can not encode on several threads.
+    pub fn parallel(self) -> bool {
+        use CompressionAlgorithm::*;
+        matches!(
+            self,
+            FrontCodingDedup
+                | IncrCodingDedup
+                | RePairDedup
+                | VByteNSDedup
+                | VByteArenaNSDedup
+                | IncrCodingAdvancedDedupNS
+        )
+    }
+
+    /// Rejects the algorithms that can not build an index.
+    pub fn check_implemented(self) -> Result<(), String> {
+        match self {
+            CompressionAlgorithm::SmazFastPforDedup => {
+                Err(format!("{} is not implemented", self.str()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks a block size passed with `--blocksize` and returns the block size used.
+    pub fn checked_blocksize(self, configured: Option<usize>) -> Result<Option<usize>, String> {
+        if let Some(b) = configured {
+            if !self.tunable_blocksize() {
+                return Err(format!("--blocksize is not supported by {}", self.str()));
+            }
+
+            if !BLOCKSIZES.contains(&b) {
+                return Err(format!("--blocksize must be one of {BLOCKSIZES:?}"));
+            }
+        }
+
+        Ok(self.blocksize(configured))
+    }
+
     pub fn str(self) -> &'static str {
         CompressionAlgorithm::lookup()
             .into_iter()
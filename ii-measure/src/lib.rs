@@ -0,0 +1,47 @@
+pub mod algorithm;
+pub mod db;
+pub mod dispatch;
+pub mod inverted_index;
+pub mod kinds;
+pub mod log;
+pub mod lsm;
+pub mod measure;
+pub mod pipeline;
+pub mod query;
+pub mod serve;
+pub mod shard;
+pub mod table_lake;
+pub mod util;
+pub mod wal;
+
+pub use table_lake::*;
+
+/// Evaluates `$body` with the const `$b` set to the block size chosen on the command line.
+#[macro_export]
+macro_rules! with_blocksize {
+    ($blocksize:expr, $b:ident => $body:expr) => {
+        match $blocksize {
+            8 => {
+                const $b: usize = 8;
+                $body
+            }
+            16 => {
+                const $b: usize = 16;
+                $body
+            }
+            32 => {
+                const $b: usize = 32;
+                $body
+            }
+            64 => {
+                const $b: usize = 64;
+                $body
+            }
+            128 => {
+                const $b: usize = 128;
+                $body
+            }
+            n => unreachable!("unsupported block size {n}"),
+        }
+    };
+}
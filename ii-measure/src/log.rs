@@ -1,3 +1,6 @@
+use crate::shard::Sharding;
+use bintable2::{Metadata, Sampling};
+use serde_json::{json, Value};
 use std::time::Duration;
 
 pub type MemData = (usize, usize, Duration, Duration);
@@ -11,10 +14,22 @@ pub struct Logger {
     header: bool,
     multi_proc: bool,
     label: String,
+    blocksize: Option<usize>,
+    sampling: Option<Sampling>,
+    /// where the rows were sampled, postgres and bintables hash rows differently
+    source: &'static str,
+    /// names of the tables and columns, if the bintable has them
+    metadata: Option<Metadata>,
+    sharding: Option<Sharding>,
+    workers: Option<usize>,
+    /// cpu time of the whole process while building
+    build_cpu: Option<Duration>,
+    /// resumed from a checkpoint, so only the rest of the table was indexed
+    partial: bool,
 }
 
 pub fn print_header() {
-    println!("cells;bytes;build_duration_nanosec;build_duration_total_nanosec;retr_duration_avg_nanosec;algorithm;table;multi_proc;label");
+    println!("cells;bytes;build_duration_nanosec;build_duration_total_nanosec;retr_duration_avg_nanosec;algorithm;table;multi_proc;label;blocksize;sample_mode;sample_factor;sample_seed;sample_source;shards;partition;workers;build_cpu_nanosec;partial");
 }
 
 impl Logger {
@@ -25,6 +40,8 @@ impl Logger {
         header: bool,
         multi_proc: bool,
         label: Option<String>,
+        blocksize: Option<usize>,
+        sampling: Option<Sampling>,
     ) -> Self {
         Logger {
             memdata: None,
@@ -34,9 +51,46 @@ impl Logger {
             header,
             multi_proc,
             label: label.unwrap_or_default(),
+            blocksize,
+            sampling,
+            source: "bintable",
+            metadata: None,
+            sharding: None,
+            workers: None,
+            build_cpu: None,
+            partial: false,
         }
     }
 
+    pub fn with_metadata(mut self, metadata: Option<Metadata>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    pub fn with_sharding(mut self, sharding: Option<Sharding>) -> Self {
+        self.sharding = sharding;
+        self
+    }
+
+    pub fn with_workers(mut self, workers: Option<usize>) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    pub fn with_source(mut self, source: &'static str) -> Self {
+        self.source = source;
+        self
+    }
+
+    pub fn with_partial(mut self, partial: bool) -> Self {
+        self.partial = partial;
+        self
+    }
+
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.metadata.as_ref()
+    }
+
     pub fn print(&self) {
         if self.header {
             print_header();
@@ -53,13 +107,69 @@ impl Logger {
         let label = &self.label;
 
         let multi_proc = self.multi_proc;
-        println!("{cells};{bytes};{duration};{total_duration};{retr_duration};{algorithm};{table};{multi_proc};{label}");
+        let blocksize = self.blocksize.map(|b| b.to_string()).unwrap_or_default();
+
+        // the whole corpus is read without sampling
+        let (mode, factor, seed, source) = match self.sampling {
+            Some(s) => (s.mode.str(), s.factor, s.seed.to_string(), self.source),
+            None => ("none", 1.0, String::new(), ""),
+        };
+
+        // a single index is not partitioned
+        let (shards, partition) = match self.sharding {
+            Some(s) => (s.shards.to_string(), s.partition.str()),
+            None => (String::new(), ""),
+        };
+
+        let workers = self.workers.map(|w| w.to_string()).unwrap_or_default();
+        let cpu_duration = self.build_cpu.expect("build cpu time").as_nanos();
+        let partial = self.partial;
+
+        println!("{cells};{bytes};{duration};{total_duration};{retr_duration};{algorithm};{table};{multi_proc};{label};{blocksize};{mode};{factor};{seed};{source};{shards};{partition};{workers};{cpu_duration};{partial}");
+    }
+
+    /// The columns of the csv as json, missing measurements are null.
+    pub fn to_json(&self) -> Value {
+        let (mode, factor, seed, source) = match self.sampling {
+            Some(s) => (s.mode.str(), s.factor, Some(s.seed), Some(self.source)),
+            None => ("none", 1.0, None, None),
+        };
+
+        let mut json = json!({
+            "retr_duration_avg_nanosec": self.retrieval.map(|d| d.as_nanos() as u64),
+            "algorithm": self.algorithm,
+            "table": self.table,
+            "multi_proc": self.multi_proc,
+            "label": self.label,
+            "blocksize": self.blocksize,
+            "sample_mode": mode,
+            "sample_factor": factor,
+            "sample_seed": seed,
+            "sample_source": source,
+            "shards": self.sharding.map(|s| s.shards),
+            "partition": self.sharding.map(|s| s.partition.str()),
+            "workers": self.workers,
+            "build_cpu_nanosec": self.build_cpu.map(|d| d.as_nanos() as u64),
+            "partial": self.partial,
+        });
+        if let Some((cells, bytes, duration, total_duration)) = self.memdata {
+            json["cells"] = json!(cells);
+            json["bytes"] = json!(bytes);
+            json["build_duration_nanosec"] = json!(duration.as_nanos() as u64);
+            json["build_duration_total_nanosec"] = json!(total_duration.as_nanos() as u64);
+        }
+
+        json
     }
 
     pub fn memory_info(&mut self, data: MemData) {
         self.memdata = Some(data);
     }
 
+    pub fn build_cpu_info(&mut self, duration: Duration) {
+        self.build_cpu = Some(duration);
+    }
+
     pub fn retrieval_info(&mut self, duration: Duration) {
         self.retrieval = Some(duration);
     }
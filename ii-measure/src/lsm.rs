@@ -0,0 +1,462 @@
+//! Index maintained under continuous table additions, as a log structured merge tree of segments.
+//!
+//! New locations go into a mutable in-memory segment (the memtable), which is flushed into an immutable
+//! segment using the `incr_adv+dedup+ns_adv` layout once it is full. Segments are merged with a tiered policy
+//! on background threads: as soon as a tier has `fanout` segments, they are merged into one segment of the next tier.
+//! Deleted tables are filtered by a set of tombstones and dropped from the segments while merging.
+
+use crate::algorithm::incr_adv_ns_adv::CompressedLocations;
+use crate::inverted_index::{merge_postings, InvertedIndex};
+use crate::TableLocation;
+use dict_compressed::{Dict, ImprovedIncrementalCoding, SortedDict};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// When the memtable is flushed and segments are merged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TieredPolicy {
+    /// Locations in the memtable before it is flushed
+    pub flush_locations: usize,
+    /// Segments of a tier merged into one segment of the next tier
+    pub fanout: usize,
+}
+
+impl TieredPolicy {
+    pub fn new(flush_locations: usize, fanout: usize) -> Self {
+        assert!(flush_locations > 0, "at least one location per flush");
+        assert!(fanout > 1, "at least two segments per merge");
+        TieredPolicy {
+            flush_locations,
+            fanout,
+        }
+    }
+}
+
+/// Immutable segment, keys are compressed in blocks of `B` keys.
+pub struct Segment<const B: usize> {
+    dict: Dict<CompressedLocations, ImprovedIncrementalCoding, B>,
+    /// 0 for flushed segments, merges of tier `t` are of tier `t + 1`
+    tier: usize,
+    locations: usize,
+    bytes: usize,
+}
+
+impl<const B: usize> Segment<B> {
+    /// Builds a segment from posting lists sorted by key, lists without locations are skipped.
+    fn new(lists: impl Iterator<Item = (Vec<u8>, Vec<TableLocation>)>, tier: usize) -> Self {
+        let mut dict = Dict::new();
+        let mut locations = 0;
+        let mut bytes = 0;
+
+        for (key, list) in lists.filter(|(_, list)| !list.is_empty()) {
+            locations += list.len();
+            let list = CompressedLocations::new(list);
+            bytes += list.encoded_len();
+            dict.push(key, list);
+        }
+        dict.finish();
+        bytes += dict.heap_size();
+
+        Segment {
+            dict,
+            tier,
+            locations,
+            bytes,
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<Vec<TableLocation>> {
+        self.dict.get(key.as_bytes()).map(|l| l.locations())
+    }
+
+    fn lists(&self) -> Lists<'_> {
+        Box::new(self.dict.iter().map(|(key, l)| (key, l.locations())))
+    }
+
+    pub fn tier(&self) -> usize {
+        self.tier
+    }
+
+    /// Number of locations in the segment
+    pub fn locations(&self) -> usize {
+        self.locations
+    }
+
+    /// Encoded size of the keys and posting lists
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+/// Posting lists sorted by key
+type Lists<'a> = Box<dyn Iterator<Item = (Vec<u8>, Vec<TableLocation>)> + 'a>;
+
+/// Merges the posting lists of several sources sorted by key, without the locations of deleted tables.
+/// Lists can become empty, if all of their tables were deleted.
+fn merge_lists<'a>(
+    sources: Vec<Lists<'a>>,
+    tombstones: &'a HashSet<u32>,
+) -> impl Iterator<Item = (Vec<u8>, Vec<TableLocation>)> + 'a {
+    let mut sources = sources
+        .into_iter()
+        .map(Iterator::peekable)
+        .collect::<Vec<_>>();
+
+    std::iter::from_fn(move || {
+        let key = sources
+            .iter_mut()
+            .filter_map(|l| l.peek().map(|(key, _)| key.clone()))
+            .min()?;
+
+        let postings = sources
+            .iter_mut()
+            .filter_map(|l| l.next_if(|(k, _)| *k == key))
+            .map(|(_, list)| list);
+        let mut list = merge_postings(postings);
+        list.retain(|l| !tombstones.contains(&l.tableid));
+
+        Some((key, list))
+    })
+}
+
+/// Merges segments into one segment of `tier`, without the locations of deleted tables.
+fn merge<const B: usize>(
+    segments: &[Arc<Segment<B>>],
+    tombstones: &HashSet<u32>,
+    tier: usize,
+) -> Segment<B> {
+    let lists = segments.iter().map(|s| s.lists()).collect();
+    Segment::new(merge_lists(lists, tombstones), tier)
+}
+
+/// Merge running on a background thread
+struct Merge<const B: usize> {
+    inputs: Vec<Arc<Segment<B>>>,
+    merged: JoinHandle<Segment<B>>,
+}
+
+/// Locations written by flushes and merges, to compute the write amplification
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WriteStats {
+    /// locations inserted into the index
+    pub inserted: usize,
+    /// locations written into segments, by flushes and merges
+    pub written: usize,
+    /// encoded bytes of all segments written
+    pub written_bytes: usize,
+    pub flushes: usize,
+    pub merges: usize,
+}
+
+impl WriteStats {
+    /// Locations written per location inserted
+    pub fn write_amplification(&self) -> f64 {
+        if self.inserted == 0 {
+            return 0.0;
+        }
+        self.written as f64 / self.inserted as f64
+    }
+}
+
+/// Index over a memtable and immutable segments. Table ids of deleted tables must not be reused.
+pub struct LsmIndex<const B: usize = 16> {
+    policy: TieredPolicy,
+    memtable: BTreeMap<String, Vec<TableLocation>>,
+    memtable_locations: usize,
+    segments: Vec<Arc<Segment<B>>>,
+    merges: Vec<Merge<B>>,
+    tombstones: HashSet<u32>,
+    stats: WriteStats,
+}
+
+impl<const B: usize> LsmIndex<B> {
+    pub fn new(policy: TieredPolicy) -> Self {
+        LsmIndex {
+            policy,
+            memtable: BTreeMap::new(),
+            memtable_locations: 0,
+            segments: Vec::new(),
+            merges: Vec::new(),
+            tombstones: HashSet::new(),
+            stats: WriteStats::default(),
+        }
+    }
+
+    /// Inserts a location of `key`, flushes the memtable once it is full.
+    pub fn insert(&mut self, key: String, location: TableLocation) {
+        self.memtable.entry(key).or_default().push(location);
+        self.memtable_locations += 1;
+        self.stats.inserted += 1;
+
+        if self.memtable_locations >= self.policy.flush_locations {
+            self.flush();
+        }
+    }
+
+    /// Deletes all locations of the table. They are filtered from lookups right away
+    /// and dropped from the segments by merges.
+    pub fn delete_table(&mut self, tableid: u32) {
+        self.tombstones.insert(tableid);
+
+        for list in self.memtable.values_mut() {
+            let len = list.len();
+            list.retain(|l| l.tableid != tableid);
+            self.memtable_locations -= len - list.len();
+        }
+        self.memtable.retain(|_, list| !list.is_empty());
+    }
+
+    /// Writes the memtable into a segment of tier 0 and merges full tiers.
+    pub fn flush(&mut self) {
+        if self.memtable.is_empty() {
+            return;
+        }
+
+        let memtable = std::mem::take(&mut self.memtable);
+        let lists = memtable.into_iter().map(|(key, mut list)| {
+            list.sort_unstable();
+            (key.into_bytes(), list)
+        });
+        let segment = Segment::new(lists, 0);
+        self.memtable_locations = 0;
+
+        self.stats.flushes += 1;
+        self.stats.written += segment.locations;
+        self.stats.written_bytes += segment.bytes;
+        self.segments.push(Arc::new(segment));
+
+        self.maintain();
+    }
+
+    /// Replaces the inputs of finished merges by their results and starts merging full tiers.
+    fn maintain(&mut self) {
+        let (finished, running) = std::mem::take(&mut self.merges)
+            .into_iter()
+            .partition::<Vec<_>, _>(|m| m.merged.is_finished());
+        self.merges = running;
+        for merge in finished {
+            self.install(merge);
+        }
+
+        while let Some(inputs) = self.full_tier() {
+            let tier = inputs[0].tier + 1;
+            let segments = inputs.clone();
+            let tombstones = self.tombstones.clone();
+            let merged = thread::spawn(move || merge(&segments, &tombstones, tier));
+
+            self.merges.push(Merge { inputs, merged });
+        }
+    }
+
+    /// The oldest `fanout` segments of the lowest tier with enough segments not being merged yet
+    fn full_tier(&self) -> Option<Vec<Arc<Segment<B>>>> {
+        let merging = |s: &Arc<Segment<B>>| {
+            self.merges
+                .iter()
+                .any(|m| m.inputs.iter().any(|i| Arc::ptr_eq(i, s)))
+        };
+
+        let mut tiers = BTreeMap::<usize, Vec<_>>::new();
+        for segment in self.segments.iter().filter(|s| !merging(s)) {
+            tiers
+                .entry(segment.tier)
+                .or_default()
+                .push(Arc::clone(segment));
+        }
+
+        tiers
+            .into_values()
+            .find(|segments| segments.len() >= self.policy.fanout)
+            .map(|mut segments| {
+                segments.truncate(self.policy.fanout);
+                segments
+            })
+    }
+
+    fn install(&mut self, merge: Merge<B>) {
+        let merged = merge.merged.join().expect("segments to be merged");
+        self.replace(&merge.inputs, merged);
+    }
+
+    /// Replaces the merged segments by the result of their merge
+    fn replace(&mut self, inputs: &[Arc<Segment<B>>], merged: Segment<B>) {
+        self.segments
+            .retain(|s| !inputs.iter().any(|i| Arc::ptr_eq(i, s)));
+
+        self.stats.merges += 1;
+        self.stats.written += merged.locations;
+        self.stats.written_bytes += merged.bytes;
+        if merged.locations > 0 {
+            self.segments.push(Arc::new(merged));
+        }
+    }
+
+    /// Waits for all merges, including the ones started by finished merges.
+    pub fn wait_for_merges(&mut self) {
+        while !self.merges.is_empty() {
+            let merge = self.merges.remove(0);
+            self.install(merge);
+            self.maintain();
+        }
+    }
+
+    /// Flushes the memtable and merges all segments into one, which drops all deleted tables.
+    pub fn compact(&mut self) {
+        self.flush();
+        self.wait_for_merges();
+
+        if self.segments.len() > 1 || !self.tombstones.is_empty() {
+            let tier = self.segments.iter().map(|s| s.tier + 1).max().unwrap_or(0);
+            let merged = merge(&self.segments, &self.tombstones, tier);
+            self.replace(&self.segments.clone(), merged);
+        }
+
+        // no segment contains the deleted tables anymore
+        self.tombstones.clear();
+    }
+
+    /// Locations of `key` in the memtable and all segments, empty if it is not indexed.
+    pub fn get(&self, key: &str) -> Vec<TableLocation> {
+        let memtable = self.memtable.get(key).cloned();
+        let segments = self.segments.iter().filter_map(|s| s.get(key));
+
+        let mut locations = merge_postings(memtable.into_iter().chain(segments));
+        if !self.tombstones.is_empty() {
+            locations.retain(|l| !self.tombstones.contains(&l.tableid));
+        }
+        locations
+    }
+
+    /// All keys of the memtable and the segments in sorted order, along with their merged locations
+    pub fn lists(&self) -> impl Iterator<Item = (String, Vec<TableLocation>)> + '_ {
+        let memtable = self
+            .memtable
+            .iter()
+            .map(|(key, list)| (key.clone().into_bytes(), list.clone()));
+
+        let mut sources: Vec<Lists<'_>> = vec![Box::new(memtable)];
+        sources.extend(self.segments.iter().map(|s| s.lists()));
+
+        merge_lists(sources, &self.tombstones)
+            .filter(|(_, list)| !list.is_empty())
+            .map(|(key, list)| (String::from_utf8(key).expect("valid utf-8 key"), list))
+    }
+
+    pub fn segments(&self) -> &[Arc<Segment<B>>] {
+        &self.segments
+    }
+
+    pub fn memtable_locations(&self) -> usize {
+        self.memtable_locations
+    }
+
+    /// Encoded size of all segments
+    pub fn bytes(&self) -> usize {
+        self.segments.iter().map(|s| s.bytes).sum()
+    }
+
+    pub fn stats(&self) -> WriteStats {
+        self.stats
+    }
+}
+
+impl<const B: usize> InvertedIndex<Vec<TableLocation>> for LsmIndex<B> {
+    fn get(&self, key: &str) -> Vec<TableLocation> {
+        LsmIndex::get(self, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(tableid: u32, rowid: u32) -> TableLocation {
+        TableLocation {
+            tableid,
+            colid: 0,
+            rowid,
+        }
+    }
+
+    /// every table has the keys `key-0` to `key-9`, with one location each
+    fn insert_tables(index: &mut LsmIndex, tables: std::ops::Range<u32>) {
+        for tableid in tables {
+            for k in 0..10 {
+                index.insert(format!("key-{k}"), location(tableid, k));
+            }
+        }
+    }
+
+    fn tables(index: &LsmIndex, key: &str) -> Vec<u32> {
+        index.get(key).iter().map(|l| l.tableid).collect()
+    }
+
+    #[test]
+    fn tiered_merges() {
+        let mut index = LsmIndex::new(TieredPolicy::new(20, 2));
+
+        // 2 tables per flush
+        insert_tables(&mut index, 0..2);
+        assert_eq!(index.segments().len(), 1);
+        assert_eq!(index.memtable_locations(), 0);
+
+        insert_tables(&mut index, 2..14);
+        index.wait_for_merges();
+        // 7 flushes: 4 (tier 2) + 2 (tier 1) + 1 (tier 0)
+        let mut tiers = index
+            .segments()
+            .iter()
+            .map(|s| s.tier())
+            .collect::<Vec<_>>();
+        tiers.sort();
+        assert_eq!(tiers, [0, 1, 2]);
+        assert_eq!(index.stats().flushes, 7);
+        assert_eq!(index.stats().merges, 4);
+
+        insert_tables(&mut index, 14..15);
+        assert_eq!(index.memtable_locations(), 10);
+        assert_eq!(tables(&index, "key-3"), (0..15).collect::<Vec<_>>());
+        assert_eq!(index.get("key-3")[4], location(4, 3));
+        assert!(index.get("missing").is_empty());
+
+        let stats = index.stats();
+        assert_eq!(stats.inserted, 150);
+        // every location is written once by its flush, 120 locations twice more by merges
+        assert_eq!(stats.written, 140 + 120 + 80);
+        assert!((stats.write_amplification() - 340.0 / 150.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn deleted_tables() {
+        let mut index = LsmIndex::new(TieredPolicy::new(30, 3));
+        insert_tables(&mut index, 0..10);
+        index.delete_table(2);
+        index.delete_table(9);
+        assert_eq!(tables(&index, "key-0"), [0, 1, 3, 4, 5, 6, 7, 8]);
+
+        index.compact();
+        assert_eq!(index.segments().len(), 1);
+        assert_eq!(index.segments()[0].locations(), 80);
+        assert_eq!(tables(&index, "key-9"), [0, 1, 3, 4, 5, 6, 7, 8]);
+
+        insert_tables(&mut index, 10..11);
+        index.delete_table(10);
+        assert_eq!(index.memtable_locations(), 0);
+        assert_eq!(tables(&index, "key-1").len(), 8);
+    }
+
+    #[test]
+    fn merges_drop_deleted_tables() {
+        let mut index = LsmIndex::new(TieredPolicy::new(10, 2));
+        insert_tables(&mut index, 0..1);
+        index.delete_table(0);
+        insert_tables(&mut index, 1..2);
+        index.wait_for_merges();
+
+        // the merge of both segments only contains table 1
+        assert_eq!(index.segments().len(), 1);
+        assert_eq!(index.segments()[0].locations(), 10);
+        assert_eq!(tables(&index, "key-5"), [1]);
+    }
+}
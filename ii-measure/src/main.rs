@@ -1,23 +1,21 @@
-mod util;
-use util::*;
-
-mod inverted_index;
-mod kinds;
-mod measure;
-mod table_lake;
-use log::Logger;
-use structopt::StructOpt;
-use table_lake::*;
-
-mod algorithm;
 mod cli;
-mod db;
-mod log;
 
-use measure::measure_logging;
+use ii_measure::dispatch::{self, Builder};
+use ii_measure::inverted_index::{InvertedIndex, KeySearch};
+use ii_measure::log::{self, Logger};
+use ii_measure::measure::{self, Locations};
+use ii_measure::query::Explain;
+use ii_measure::shard::{self, count_entries, Partition, Sharding};
+use ii_measure::util::*;
+use ii_measure::Entry;
+use std::path::Path;
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+use structopt::StructOpt;
 
 use crate::cli::Config;
 
+use bintable2::Sampling;
 use jemallocator::Jemalloc;
 
 #[global_allocator]
@@ -30,12 +28,21 @@ fn main() {
     let Config {
         label,
         database,
+        database_url,
+        checkpoint,
         algorithm,
         table,
         header,
         header_only,
         mut factor,
+        sample,
+        seed,
         multi_proc,
+        search,
+        blocksize,
+        shards,
+        partition,
+        workers,
     } = cli::Config::from_args();
 
     if header_only {
@@ -43,6 +50,32 @@ fn main() {
         std::process::exit(1);
     }
 
+    let blocksize = algorithm
+        .check_implemented()
+        .and_then(|()| algorithm.checked_blocksize(blocksize))
+        .unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(1);
+        });
+
+    if shards.is_some() && !algorithm.shardable() {
+        eprintln!("--shards is not supported by {}", algorithm.str());
+        std::process::exit(1);
+    }
+    if shards == Some(0) {
+        eprintln!("--shards must be at least 1");
+        std::process::exit(1);
+    }
+
+    if workers.is_some() && !algorithm.parallel() {
+        eprintln!("--workers is not supported by {}", algorithm.str());
+        std::process::exit(1);
+    }
+    if workers == Some(0) {
+        eprintln!("--workers must be at least 1");
+        std::process::exit(1);
+    }
+
     if factor == Some(1.0) {
         factor = None;
     }
@@ -53,6 +86,11 @@ fn main() {
             std::process::exit(0);
         }
 
+        if f > 1.0 {
+            eprintln!("factor must be at most 1");
+            std::process::exit(1);
+        }
+
         // if f >= 0.5
         //     && &table == "main_tokenized"
         //     && (algorithm == Baseline || algorithm == Smaz || algorithm == NS)
@@ -67,15 +105,46 @@ fn main() {
 
         let percentage = f * 100.;
         eprintln!(
-            "benchmarking {} on {}% {}",
+            "benchmarking {} on {}% {} (sampling {} with seed {})",
             algorithm.str(),
             percentage,
-            table
+            table,
+            sample,
+            seed
         );
     } else {
         eprintln!("benchmarking {} on {}", algorithm.str(), table);
     }
 
+    let sampling = factor.map(|f| Sampling::new(sample, f, seed));
+
+    let read = |checkpoint| {
+        if database {
+            indices_sqlx(&table, sampling, database_url.clone(), checkpoint)
+        } else {
+            indices_from_bintable(&table, sampling)
+        }
+    };
+
+    // a resumed run only reads the rows after the checkpoint
+    let partial = database && checkpoint.as_ref().is_some_and(|c| Path::new(c).exists());
+    if partial {
+        eprintln!(
+            "resuming from {}, the measurement is logged as partial",
+            checkpoint.as_ref().unwrap()
+        );
+    }
+
+    let sharding = shards.map(|shards| {
+        let sharding = Sharding::new(shards, partition);
+        if partition == Partition::Range {
+            eprintln!("counting entries for range partitioning");
+            sharding.with_entries(count_entries(read(None)))
+        } else {
+            sharding
+        }
+    });
+
     // init information logger
     let log = Logger::new(
         algorithm.str().to_string(),
@@ -83,75 +152,88 @@ fn main() {
         header,
         multi_proc,
         label,
-    );
-
-    let receiver = if database {
-        indices_sqlx(&table, factor)
+        blocksize,
+        sampling,
+    )
+    .with_metadata(if database {
+        None
     } else {
-        indices_from_bintable(&table, factor)
+        load_metadata(&table)
+    })
+    .with_sharding(sharding)
+    .with_workers(workers)
+    .with_source(if database { "postgres" } else { "bintable" })
+    .with_partial(partial);
+
+    let receiver = read(checkpoint);
+
+    let measure = Measure {
+        receiver,
+        log,
+        sharding,
+        search,
     };
+    dispatch::build(algorithm, blocksize, workers, measure);
+}
 
-    // Select Compression Algorithm and perfom
-    use kinds::CompressionAlgorithm::*;
-    match algorithm {
-        Baseline => measure_logging(algorithm::baseline, receiver, log),
-        BaselineExact => measure_logging(algorithm::baseline_exact, receiver, log),
-        DedupHash => measure_logging(algorithm::dedup_hash, receiver, log),
-        DedupBTree => measure_logging(algorithm::dedup_btree, receiver, log),
-
-        NSDedup => measure_logging(algorithm::ns_4_wise, receiver, log),
-        NS => measure_logging(algorithm::ns_raw, receiver, log),
-        NSArena => measure_logging(algorithm::ns_arena::ns_arena, receiver, log),
-
-        SmazFastPforDedup => {
-            unimplemented!("not going to be implemented");
-        }
-        SmazDedup => measure_logging(algorithm::smaz, receiver, log),
-        Smaz => measure_logging(algorithm::smaz_raw, receiver, log),
+/// Measures the index chosen by `dispatch::build`, built in shards if requested
+struct Measure {
+    receiver: Receiver<Entry>,
+    log: Logger,
+    sharding: Option<Sharding>,
+    search: bool,
+}
 
-        FrontCodingBaseline => {
-            measure_logging(algorithm::frontcoding::frontcoding_baseline, receiver, log)
-        }
-        FrontCodingDedup => measure_logging(algorithm::frontcoding::frontcoding, receiver, log),
-
-        IncrCodingBaseline => measure_logging(
-            algorithm::incremental::incrementalcoding_baseline,
-            receiver,
-            log,
-        ),
-        IncrCodingDedup => {
-            measure_logging(algorithm::incremental::incrementalcoding, receiver, log)
+impl Builder for Measure {
+    type Output = ();
+
+    fn lookup<F, II, O>(self, build: F)
+    where
+        F: Fn(Receiver<Entry>) -> (usize, Duration, II) + Sync,
+        II: InvertedIndex<O> + RandomKeys + Send + 'static,
+        O: Locations + 'static,
+    {
+        match self.sharding {
+            Some(sharding) => {
+                measure::measure_logging(shard::sharded(build, sharding), self.receiver, self.log)
+            }
+            None => measure::measure_logging(build, self.receiver, self.log),
         }
+    }
 
-        VByteArenaNSDedup => {
-            measure_logging(algorithm::vbyte_arena::VByteEncoded::new, receiver, log)
+    fn search<F, II, O>(self, build: F)
+    where
+        F: Fn(Receiver<Entry>) -> (usize, Duration, II) + Sync,
+        II: InvertedIndex<O> + KeySearch + RandomKeys + Send + 'static,
+        O: Locations + 'static,
+    {
+        match self.sharding {
+            Some(sharding) => measure::measure_search_logging(
+                shard::sharded(build, sharding),
+                self.receiver,
+                self.log,
+                self.search,
+            ),
+            None => measure::measure_search_logging(build, self.receiver, self.log, self.search),
         }
-        VByteNSDedup => measure_logging(algorithm::vbyte::VByteEncoded::new, receiver, log),
-
-        VByteNSIncrDedup => measure_logging(algorithm::vbyteincr::VByteEncoded::new, receiver, log),
-
-        IncrCodingDedupNS => measure_logging(
-            algorithm::incr_ns::InvertedIndexIncrementalCodingNS::new,
-            receiver,
-            log,
-        ),
-
-        IncrCodingAdvancedDedupNS => measure_logging(
-            algorithm::incr_adv_ns::InvertedIndexIncrementalCodingNS::new,
-            receiver,
-            log,
-        ),
-
-        IncrCodingAdvancedDedupNSAdvanced => measure_logging(
-            algorithm::incr_adv_ns_adv::InvertedIndexIncrementalCodingNS::new,
-            receiver,
-            log,
-        ),
+    }
 
-        SmazNSDedup => measure_logging(algorithm::smaz_ns, receiver, log),
+    fn blocks<F, II, O>(self, build: F)
+    where
+        F: Fn(Receiver<Entry>) -> (usize, Duration, II) + Sync,
+        II: InvertedIndex<O> + KeySearch + Explain + RandomKeys + Send + 'static,
+        O: Locations + 'static,
+    {
+        self.search(build)
+    }
 
-        FastPforDedup => measure_logging(algorithm::pfor_x::pfor, receiver, log),
-        FastPforSplitDedup => measure_logging(algorithm::pfor_split::pforsplit, receiver, log),
-        // _ => panic!("algorithm {} not yet implemented", algorithm.str()),
+    // not shardable
+    fn local<F, II, O>(self, build: F)
+    where
+        F: Fn(Receiver<Entry>) -> (usize, Duration, II),
+        II: InvertedIndex<O> + RandomKeys + 'static,
+        O: Locations + 'static,
+    {
+        measure::measure_logging(build, self.receiver, self.log)
     }
 }
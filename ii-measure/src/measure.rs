@@ -1,6 +1,8 @@
-use crate::inverted_index::InvertedIndex;
+use crate::inverted_index::{InvertedIndex, KeySearch};
 use crate::util::RandomKeys;
 use crate::{log::Logger, TableLocation};
+use bintable2::Metadata;
+use cpu_time::ProcessTime;
 use std::io::Write;
 use std::sync::mpsc::Receiver;
 use std::time::{Duration, Instant};
@@ -8,7 +10,7 @@ use std::time::{Duration, Instant};
 fn retrieval<T, O>(ii: &T, log: &mut Logger)
 where
     T: InvertedIndex<O> + RandomKeys,
-    O: Sized,
+    O: Locations,
 {
     eprintln!("Step 2. Measure retrieval time.");
 
@@ -17,6 +19,10 @@ where
         return log.retrieval_info(Duration::from_millis(1));
     }
 
+    if let Some(metadata) = log.metadata() {
+        show_hits(ii, &keys[0], metadata);
+    }
+
     let total_attempts = keys.len().min(1) as u32;
     let starttime = Instant::now();
 
@@ -41,24 +47,137 @@ where
     log.retrieval_info(average_retrieval_time);
 }
 
+/// Result of a lookup
+pub trait Locations {
+    fn locations(self) -> Vec<TableLocation>;
+}
+
+impl Locations for Vec<TableLocation> {
+    fn locations(self) -> Vec<TableLocation> {
+        self
+    }
+}
+
+impl Locations for Option<Vec<TableLocation>> {
+    fn locations(self) -> Vec<TableLocation> {
+        self.unwrap_or_default()
+    }
+}
+
+/// Number of hits printed along with the names of their tables and columns.
+const SHOWN_HITS: usize = 5;
+
+/// Prints where a key occurs, as a sanity check of the index.
+fn show_hits<T, O>(ii: &T, key: &str, metadata: &Metadata)
+where
+    T: InvertedIndex<O>,
+    O: Locations,
+{
+    eprintln!("{key:?} occurs in");
+    for location in ii.get(key).locations().into_iter().take(SHOWN_HITS) {
+        eprintln!(
+            "    {}",
+            metadata.describe(location.tableid, location.colid, location.rowid)
+        );
+    }
+}
+
+/// Fuzzy search has to scan the whole dictionary, so only few keys are used to measure searches.
+const SEARCH_KEY_COUNT: usize = 32;
+
+fn search<T>(ii: &T)
+where
+    T: KeySearch + RandomKeys,
+{
+    eprintln!("Step 3. Measure search time.");
+
+    let keys = ii.random_keys();
+    let keys = &keys[..keys.len().min(SEARCH_KEY_COUNT)];
+    if keys.is_empty() {
+        return;
+    }
+
+    fn measure(name: &str, keys: &[String], query: impl Fn(&str) -> Vec<TableLocation>) {
+        let mut locations = 0;
+
+        let starttime = Instant::now();
+        for key in keys {
+            locations += query(key).len();
+        }
+        let average_time = starttime.elapsed() / keys.len() as u32;
+
+        eprintln!(
+            "{name}: {}ns on average, {} locations on average",
+            average_time.as_nanos(),
+            locations / keys.len()
+        );
+    }
+
+    // the first half of each key, so every query has at least one hit
+    let head = |key: &str| -> String { key.chars().take(key.chars().count() / 2).collect() };
+
+    measure("prefix", keys, |key| ii.prefix(&head(key)));
+    measure("glob", keys, |key| {
+        let last = key.chars().last().map(String::from).unwrap_or_default();
+        ii.glob(&format!("{}*{last}", head(key)))
+    });
+    measure("fuzzy k=1", keys, |key| ii.fuzzy(key, 1));
+}
+
 pub fn measure_logging<F, II, O>(
+    algorithm: F,
+    receiver: Receiver<(String, TableLocation)>,
+    log: Logger,
+) where
+    F: Fn(Receiver<(String, TableLocation)>) -> (usize, Duration, II),
+    II: InvertedIndex<O> + RandomKeys,
+    O: Locations,
+{
+    measure_logging_with(algorithm, receiver, log, |_| {});
+}
+
+/// Like `measure_logging`, but also measures prefix, glob and fuzzy searches if `with_search` is set.
+/// Search times are only printed to stderr and do not change the csv output.
+pub fn measure_search_logging<F, II, O>(
+    algorithm: F,
+    receiver: Receiver<(String, TableLocation)>,
+    log: Logger,
+    with_search: bool,
+) where
+    F: Fn(Receiver<(String, TableLocation)>) -> (usize, Duration, II),
+    II: InvertedIndex<O> + RandomKeys + KeySearch,
+    O: Locations,
+{
+    measure_logging_with(algorithm, receiver, log, |ii| {
+        if with_search {
+            search(ii)
+        }
+    });
+}
+
+fn measure_logging_with<F, II, O>(
     algorithm: F,
     receiver: Receiver<(String, TableLocation)>,
     mut log: Logger,
+    after_retrieval: impl FnOnce(&II),
 ) where
     F: Fn(Receiver<(String, TableLocation)>) -> (usize, Duration, II),
     II: InvertedIndex<O> + RandomKeys,
-    O: Sized,
+    O: Locations,
 {
     eprintln!("Step 1. Measure insertion time.");
 
     let starttime = Instant::now();
+    let cputime = ProcessTime::now();
 
-    let (entry_count, build_time, ii) = algorithm(receiver);
+    let (entry_count, build_time, mut ii) = algorithm(receiver);
 
     let insertion_time = starttime.elapsed();
+    log.build_cpu_info(cputime.elapsed());
 
     retrieval(&ii, &mut log);
+    after_retrieval(&ii);
+    ii.drop_random_keys();
     log.memory_info((entry_count, get_size(ii), build_time, insertion_time));
     log.print();
 }
@@ -77,3 +196,13 @@ fn get_size<T>(t: T) -> usize {
 
     with - without
 }
+
+/// Bytes currently allocated by jemalloc
+pub(crate) fn allocated() -> usize {
+    use jemalloc_ctl::{epoch, stats};
+    let e = epoch::mib().unwrap();
+    let allocated = stats::allocated::mib().unwrap();
+
+    e.advance().unwrap();
+    allocated.read().unwrap()
+}
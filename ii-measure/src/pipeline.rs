@@ -0,0 +1,337 @@
+//! Builds a single index on several threads: the calling thread groups the entries into posting lists,
+//! a pool of workers encodes batches of them and the assembler puts the encoded batches together in key order.
+
+use crate::{Entry, TableLocation};
+use dict_compressed::{BlockCoding, Dict, SortedDict};
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{channel, sync_channel, Receiver, Sender, SyncSender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use threadpool::ThreadPool;
+
+/// Number of posting lists encoded by a worker at once.
+/// A multiple of every block size, so a batch never splits a block of keys.
+pub const BATCH_SIZE: usize = 1024;
+
+/// Batches handed to the workers per worker, but not yet assembled.
+/// Keeps the grouping from running ahead of the encoding.
+const BATCHES_PER_WORKER: usize = 4;
+
+/// Posting lists of consecutive keys, sorted by key
+pub type Batch = Vec<(String, Vec<TableLocation>)>;
+
+/// Encoded batches in the order of their keys, as built by the workers.
+pub struct InOrder<T> {
+    encoded: Receiver<(usize, Duration, thread::Result<T>)>,
+    /// batches that were encoded before the ones preceding them
+    pending: BTreeMap<usize, (Duration, thread::Result<T>)>,
+    next: usize,
+    /// frees the place of an assembled batch for the next one
+    assembled: Receiver<()>,
+    encode_time: Duration,
+}
+
+impl<T> Iterator for InOrder<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let (encode_time, encoded) = loop {
+            if let Some(encoded) = self.pending.remove(&self.next) {
+                break encoded;
+            }
+
+            // all workers are done once the grouping thread and every job dropped their senders
+            let Ok((i, encode_time, encoded)) = self.encoded.recv() else {
+                assert!(self.pending.is_empty(), "batch {} to be encoded", self.next);
+                return None;
+            };
+            self.pending.insert(i, (encode_time, encoded));
+        };
+
+        self.next += 1;
+        self.encode_time += encode_time;
+        self.assembled.recv().expect("batch to be handed out");
+
+        match encoded {
+            Ok(encoded) => Some(encoded),
+            Err(panic) => panic::resume_unwind(panic),
+        }
+    }
+}
+
+/// Wraps a batch encoder and an assembler, so they build an index on `workers` threads.
+/// The build time is the time spent grouping, encoding and assembling, summed over all threads.
+pub fn pipelined<E, T, A, II>(
+    workers: usize,
+    encode: E,
+    assemble: A,
+) -> impl Fn(Receiver<Entry>) -> (usize, Duration, II)
+where
+    E: Fn(Batch) -> T + Send + Sync + 'static,
+    T: Send + 'static,
+    A: Fn(&mut InOrder<T>) -> II,
+{
+    assert!(workers > 0, "at least one worker");
+    let encode = Arc::new(encode);
+
+    move |receiver| build_pipelined(receiver, workers, &encode, &assemble)
+}
+
+fn build_pipelined<E, T, A, II>(
+    receiver: Receiver<Entry>,
+    workers: usize,
+    encode: &Arc<E>,
+    assemble: &A,
+) -> (usize, Duration, II)
+where
+    E: Fn(Batch) -> T + Send + Sync + 'static,
+    T: Send + 'static,
+    A: Fn(&mut InOrder<T>) -> II,
+{
+    let (sender, encoded) = channel();
+    let (handed_out, assembled) = sync_channel(workers * BATCHES_PER_WORKER);
+
+    thread::scope(|scope| {
+        // dropped before the grouping thread is joined if the assembler panics, which stops the grouping
+        let mut in_order = InOrder {
+            encoded,
+            pending: BTreeMap::new(),
+            next: 0,
+            assembled,
+            encode_time: Duration::ZERO,
+        };
+
+        let encode = Arc::clone(encode);
+        let grouping = scope.spawn(move || group(receiver, workers, encode, sender, handed_out));
+
+        let starttime = Instant::now();
+        let ii = assemble(&mut in_order);
+        // an assembler stopping early would stall the grouping
+        in_order.by_ref().for_each(drop);
+        let assemble_time = starttime.elapsed();
+
+        let (count, group_time) = grouping.join().expect("entries to be grouped");
+        let build_time = group_time + in_order.encode_time + assemble_time;
+
+        (count, build_time, ii)
+    })
+}
+
+/// Groups the entries into batches of posting lists and hands them to the workers.
+/// Returns the number of entries and the time spent grouping.
+fn group<E, T>(
+    receiver: Receiver<Entry>,
+    workers: usize,
+    encode: Arc<E>,
+    sender: Sender<(usize, Duration, thread::Result<T>)>,
+    handed_out: SyncSender<()>,
+) -> (usize, Duration)
+where
+    E: Fn(Batch) -> T + Send + Sync + 'static,
+    T: Send + 'static,
+{
+    let pool = ThreadPool::with_name(String::from("encoder"), workers);
+
+    let mut batches = 0;
+    let mut hand_out = |batch: Batch| {
+        // blocks while the assembler is behind, fails if it stopped
+        if handed_out.send(()).is_err() {
+            return false;
+        }
+
+        let (i, encode, sender) = (batches, Arc::clone(&encode), sender.clone());
+        pool.execute(move || {
+            let starttime = Instant::now();
+            let encoded = panic::catch_unwind(AssertUnwindSafe(|| encode(batch)));
+            sender.send((i, starttime.elapsed(), encoded)).ok();
+        });
+        batches += 1;
+        true
+    };
+
+    let mut count = 0;
+    let mut group_time = Duration::ZERO;
+    let mut batch = Batch::with_capacity(BATCH_SIZE);
+    for (key, location) in receiver {
+        count += 1;
+        let starttime = Instant::now();
+
+        match batch.last_mut() {
+            Some((last, locations)) if *last == key => locations.push(location),
+            _ => {
+                if batch.len() == BATCH_SIZE {
+                    let full = std::mem::replace(&mut batch, Batch::with_capacity(BATCH_SIZE));
+                    if !hand_out(full) {
+                        break;
+                    }
+                }
+                batch.push((key, vec![location]));
+            }
+        }
+
+        group_time += starttime.elapsed();
+    }
+
+    if !batch.is_empty() {
+        hand_out(batch);
+    }
+
+    (count, group_time)
+}
+
+/// Blocks of keys encoded with a `BlockCoding`, along with the values of their keys
+pub type Blocks<V> = Vec<(Box<[u8]>, Vec<V>)>;
+
+/// Encodes the keys of a batch in blocks of `B` keys with `C`, and their posting lists with `encode`.
+pub fn encode_blocks<C: BlockCoding, const B: usize, V>(
+    batch: Batch,
+    encode: impl Fn(Vec<TableLocation>) -> V,
+) -> Blocks<V> {
+    let mut coding = C::default();
+    let mut blocks = Vec::with_capacity(batch.len().div_ceil(B));
+
+    let mut batch = batch.into_iter().peekable();
+    while batch.peek().is_some() {
+        let (keys, values): (Vec<_>, Vec<_>) = batch
+            .by_ref()
+            .take(B)
+            .map(|(key, locations)| (key.into_bytes(), encode(locations)))
+            .unzip();
+        blocks.push((coding.encode(&keys), values));
+    }
+
+    blocks
+}
+
+/// Assembles the dictionary from the blocks of all batches.
+pub fn assemble_dict<C: BlockCoding, const B: usize, V>(
+    in_order: &mut InOrder<Blocks<V>>,
+) -> Dict<V, C, B> {
+    let mut dict = Dict::new();
+    for (block, values) in in_order.flatten() {
+        dict.push_block(block, values);
+    }
+    dict.finish();
+
+    dict
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// every key has one to three locations
+    fn entries(keys: u32) -> usize {
+        (0..keys).map(|i| i as usize % 3 + 1).sum()
+    }
+
+    fn receiver(keys: u32) -> Receiver<Entry> {
+        let (sender, receiver) = sync_channel(64);
+        thread::spawn(move || {
+            for i in 0..keys {
+                for j in 0..(i % 3 + 1) {
+                    let location = TableLocation {
+                        tableid: i,
+                        colid: j,
+                        rowid: 0,
+                    };
+                    sender.send((format!("key-{i:05}"), location)).unwrap();
+                }
+            }
+        });
+        receiver
+    }
+
+    fn collect(in_order: &mut InOrder<Batch>) -> Batch {
+        in_order.flatten().collect()
+    }
+
+    #[test]
+    fn batches_in_key_order() {
+        let keys = BATCH_SIZE as u32 * 5 + 17;
+        let build = pipelined(
+            3,
+            |batch: Batch| {
+                // later batches overtake the first one
+                if batch[0].0 == "key-00000" {
+                    thread::sleep(Duration::from_millis(50));
+                }
+                batch
+            },
+            collect,
+        );
+
+        let (count, _, lists) = build(receiver(keys));
+        assert_eq!(count, entries(keys));
+        assert_eq!(lists.len(), keys as usize);
+        for (i, (key, locations)) in lists.into_iter().enumerate() {
+            assert_eq!(key, format!("key-{i:05}"));
+            assert_eq!(locations.len(), i % 3 + 1);
+        }
+    }
+
+    #[test]
+    fn assembler_stopping_early() {
+        let build = pipelined(2, |batch: Batch| batch, |in_order| in_order.next());
+        let keys = BATCH_SIZE as u32 * 20;
+        let (count, _, first) = build(receiver(keys));
+        assert_eq!(count, entries(keys));
+        assert_eq!(first.unwrap().len(), BATCH_SIZE);
+    }
+
+    #[test]
+    fn same_index_as_sequential() {
+        use crate::algorithm::{block_dict, incr_adv_ns, vbyte, vbyte_arena};
+        use crate::inverted_index::InvertedIndex;
+        use dict_compressed::IncrementalCoding;
+
+        fn check<II: InvertedIndex<Option<Vec<TableLocation>>>>(
+            sequential: impl Fn(Receiver<Entry>) -> (usize, Duration, II),
+            parallel: impl Fn(Receiver<Entry>) -> (usize, Duration, II),
+        ) {
+            let keys = BATCH_SIZE as u32 * 2 + 100;
+            let (count, _, sequential) = sequential(receiver(keys));
+            let (parallel_count, _, parallel) = parallel(receiver(keys));
+            assert_eq!(count, parallel_count);
+
+            for i in 0..keys {
+                let key = format!("key-{i:05}");
+                let locations = sequential.get(&key);
+                assert!(locations.is_some(), "{key}");
+                assert_eq!(locations, parallel.get(&key), "{key}");
+            }
+            assert_eq!(sequential.get("zzzz"), None);
+            assert_eq!(parallel.get("zzzz"), None);
+        }
+
+        check(
+            block_dict::block_dict::<IncrementalCoding, 32>,
+            block_dict::block_dict_parallel::<IncrementalCoding, 32>(4),
+        );
+        check(
+            incr_adv_ns::InvertedIndexIncrementalCodingNS::<16>::new,
+            incr_adv_ns::InvertedIndexIncrementalCodingNS::<16>::parallel(2),
+        );
+        check(
+            vbyte_arena::VByteEncoded::new,
+            vbyte_arena::VByteEncoded::parallel(3),
+        );
+        check(vbyte::VByteEncoded::new, vbyte::VByteEncoded::parallel(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "encoder failed")]
+    fn panicking_worker() {
+        let build = pipelined(
+            2,
+            |batch: Batch| {
+                assert!(batch[0].0 != "key-01024", "encoder failed");
+                batch
+            },
+            collect,
+        );
+        build(receiver(BATCH_SIZE as u32 * 3));
+    }
+}
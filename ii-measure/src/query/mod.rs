@@ -0,0 +1,248 @@
+//! Type erased access to the indices of `ii-measure`, used by `ii-query` and `ii-serve`.
+
+pub mod repl;
+
+use crate::dispatch::{self, Builder};
+use crate::inverted_index::{InvertedIndex, KeySearch};
+use crate::kinds::CompressionAlgorithm;
+use crate::log::Logger;
+use crate::measure::{allocated, Locations};
+use crate::util::RandomKeys;
+use crate::{Entry, TableLocation};
+use bintable2::Metadata;
+use cpu_time::ProcessTime;
+use serde_json::{json, Value};
+use std::marker::PhantomData;
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+
+/// What a lookup had to decode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    /// blocks of keys decoded, in the order they were decoded in
+    pub blocks: Vec<usize>,
+    /// encoded size of the decoded blocks
+    pub block_bytes: usize,
+    /// encoded size of the posting list, `None` if the key is not indexed
+    pub posting_bytes: Option<usize>,
+}
+
+/// Indices storing their keys in blocks.
+pub trait Explain {
+    fn explain(&self, key: &str) -> Option<Explanation>;
+}
+
+/// An index built by any `CompressionAlgorithm`.
+pub trait QueryIndex {
+    /// Locations of `key`, empty if it is not indexed.
+    fn get(&self, key: &str) -> Vec<TableLocation>;
+
+    /// Merged locations of all keys starting with `prefix`, `None` if the index can not search its keys.
+    fn prefix(&self, prefix: &str) -> Option<Vec<TableLocation>>;
+
+    /// `None` if the index does not store its keys in blocks.
+    fn explain(&self, key: &str) -> Option<Explanation>;
+
+    /// Random keys of the index
+    fn keys(&self) -> Vec<String>;
+
+    /// Frees what the index only keeps to draw random keys, see `RandomKeys::drop_random_keys`.
+    fn drop_keys(&mut self);
+}
+
+/// Index only supporting exact lookups
+struct Lookup<II, O>(II, PhantomData<fn() -> O>);
+
+impl<II, O> QueryIndex for Lookup<II, O>
+where
+    II: InvertedIndex<O> + RandomKeys,
+    O: Locations,
+{
+    fn get(&self, key: &str) -> Vec<TableLocation> {
+        self.0.get(key).locations()
+    }
+
+    fn prefix(&self, _prefix: &str) -> Option<Vec<TableLocation>> {
+        None
+    }
+
+    fn explain(&self, _key: &str) -> Option<Explanation> {
+        None
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.0.random_keys()
+    }
+
+    fn drop_keys(&mut self) {
+        self.0.drop_random_keys()
+    }
+}
+
+/// Index over sorted keys
+struct Search<II, O>(II, PhantomData<fn() -> O>);
+
+impl<II, O> QueryIndex for Search<II, O>
+where
+    II: InvertedIndex<O> + KeySearch + RandomKeys,
+    O: Locations,
+{
+    fn get(&self, key: &str) -> Vec<TableLocation> {
+        self.0.get(key).locations()
+    }
+
+    fn prefix(&self, prefix: &str) -> Option<Vec<TableLocation>> {
+        Some(self.0.prefix(prefix))
+    }
+
+    fn explain(&self, _key: &str) -> Option<Explanation> {
+        None
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.0.random_keys()
+    }
+
+    fn drop_keys(&mut self) {
+        self.0.drop_random_keys()
+    }
+}
+
+/// Index over sorted keys, stored in blocks
+struct Blocks<II, O>(II, PhantomData<fn() -> O>);
+
+impl<II, O> QueryIndex for Blocks<II, O>
+where
+    II: InvertedIndex<O> + KeySearch + Explain + RandomKeys,
+    O: Locations,
+{
+    fn get(&self, key: &str) -> Vec<TableLocation> {
+        self.0.get(key).locations()
+    }
+
+    fn prefix(&self, prefix: &str) -> Option<Vec<TableLocation>> {
+        Some(self.0.prefix(prefix))
+    }
+
+    fn explain(&self, key: &str) -> Option<Explanation> {
+        self.0.explain(key)
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.0.random_keys()
+    }
+
+    fn drop_keys(&mut self) {
+        self.0.drop_random_keys()
+    }
+}
+
+/// Number of entries, build time and the index
+pub type Built = (usize, Duration, Box<dyn QueryIndex>);
+
+/// Builds the index chosen by `dispatch::build` and boxes it by what it supports.
+struct Query(Receiver<Entry>);
+
+impl Builder for Query {
+    type Output = Built;
+
+    fn lookup<F, II, O>(self, build: F) -> Built
+    where
+        F: Fn(Receiver<Entry>) -> (usize, Duration, II) + Sync,
+        II: InvertedIndex<O> + RandomKeys + Send + 'static,
+        O: Locations + 'static,
+    {
+        self.local(build)
+    }
+
+    fn search<F, II, O>(self, build: F) -> Built
+    where
+        F: Fn(Receiver<Entry>) -> (usize, Duration, II) + Sync,
+        II: InvertedIndex<O> + KeySearch + RandomKeys + Send + 'static,
+        O: Locations + 'static,
+    {
+        let (count, build_time, ii) = build(self.0);
+        (count, build_time, Box::new(Search(ii, PhantomData)))
+    }
+
+    fn blocks<F, II, O>(self, build: F) -> Built
+    where
+        F: Fn(Receiver<Entry>) -> (usize, Duration, II) + Sync,
+        II: InvertedIndex<O> + KeySearch + Explain + RandomKeys + Send + 'static,
+        O: Locations + 'static,
+    {
+        let (count, build_time, ii) = build(self.0);
+        (count, build_time, Box::new(Blocks(ii, PhantomData)))
+    }
+
+    fn local<F, II, O>(self, build: F) -> Built
+    where
+        F: Fn(Receiver<Entry>) -> (usize, Duration, II),
+        II: InvertedIndex<O> + RandomKeys + 'static,
+        O: Locations + 'static,
+    {
+        let (count, build_time, ii) = build(self.0);
+        (count, build_time, Box::new(Lookup(ii, PhantomData)))
+    }
+}
+
+/// Builds the index of `algorithm` from the entries of `receiver`.
+/// The algorithm has to be checked with `CompressionAlgorithm::check_implemented` and `blocksize` with
+/// `CompressionAlgorithm::checked_blocksize`.
+pub fn build(
+    algorithm: CompressionAlgorithm,
+    blocksize: Option<usize>,
+    receiver: Receiver<Entry>,
+) -> Built {
+    dispatch::build(algorithm, blocksize, None, Query(receiver))
+}
+
+/// Builds the index of `algorithm` like `ii-measure` does and logs the number of entries, its size, the build times
+/// and the retrieval time. The size is the memory still allocated after building and retrieval,
+/// without what the index only keeps to draw random keys, so nothing else should allocate meanwhile.
+pub fn build_logging(
+    algorithm: CompressionAlgorithm,
+    blocksize: Option<usize>,
+    receiver: Receiver<(String, TableLocation)>,
+    log: &mut Logger,
+) -> Box<dyn QueryIndex> {
+    let before = allocated();
+    let starttime = Instant::now();
+    let cputime = ProcessTime::now();
+
+    let (entries, build_time, mut index) = build(algorithm, blocksize, receiver);
+
+    let insertion_time = starttime.elapsed();
+    log.build_cpu_info(cputime.elapsed());
+
+    retrieval_logging(&*index, log);
+    index.drop_keys();
+    let size = allocated().saturating_sub(before);
+    log.memory_info((entries, size, build_time, insertion_time));
+
+    index
+}
+
+/// Measures the average time to look up random keys of the index and logs it.
+fn retrieval_logging(index: &dyn QueryIndex, log: &mut Logger) {
+    let keys = index.keys();
+    if keys.is_empty() {
+        return log.retrieval_info(Duration::from_millis(1));
+    }
+
+    let starttime = Instant::now();
+    for key in &keys {
+        index.get(key);
+    }
+
+    log.retrieval_info(starttime.elapsed() / keys.len() as u32);
+}
+
+/// A location as json, described with the names of its table and column if they are known
+pub fn location_json(l: &TableLocation, metadata: Option<&Metadata>) -> Value {
+    let mut value = json!({ "tableid": l.tableid, "colid": l.colid, "rowid": l.rowid });
+    if let Some(metadata) = metadata {
+        value["where"] = json!(metadata.describe(l.tableid, l.colid, l.rowid));
+    }
+    value
+}
@@ -0,0 +1,605 @@
+use super::QueryIndex;
+use crate::TableLocation;
+use bintable2::Metadata;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+pub const HELP: &str = "\
+get <key>          locations of a key
+prefix <p>         locations of all keys starting with p
+and <k1> <k2> ...  rows containing all keys, use \"..\" for keys with spaces
+stats <key>        number of locations, tables and columns, encoded size
+explain [<key>]    decoded blocks and latency of a lookup, of the last key by default
+keys [<n>]         n random keys of the index
+format text|json   output format
+limit <n>          number of locations shown
+help               this text
+quit               exit";
+
+/// Number of keys printed by `keys` by default
+const KEY_COUNT: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            s => Err(format!("unknown format {s:?}, allowed: text json")),
+        }
+    }
+}
+
+/// Executes the commands of `ii-query` on an index, one line at a time.
+pub struct Repl<'a> {
+    index: &'a dyn QueryIndex,
+    metadata: Option<&'a Metadata>,
+    pub format: Format,
+    /// number of locations shown per query
+    pub limit: usize,
+    last_key: Option<String>,
+}
+
+impl<'a> Repl<'a> {
+    pub fn new(index: &'a dyn QueryIndex, metadata: Option<&'a Metadata>) -> Self {
+        Repl {
+            index,
+            metadata,
+            format: Format::Text,
+            limit: 20,
+            last_key: None,
+        }
+    }
+
+    /// Output of a command, `None` if the session should end.
+    pub fn execute(&mut self, line: &str) -> Option<String> {
+        let line = line.trim();
+        let (command, argument) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let argument = unquote(argument.trim());
+
+        let result = match command {
+            "" => return Some(String::new()),
+            "quit" | "exit" => return None,
+            "help" => Ok(Response::Help),
+            "get" => self.get(argument),
+            "prefix" => self.prefix(argument),
+            "and" => self.and(argument),
+            "stats" => self.stats(argument),
+            "explain" => self.explain(argument),
+            "keys" => self.keys(argument),
+            "format" => argument.parse().map(|format| {
+                self.format = format;
+                Response::Ok
+            }),
+            "limit" => argument
+                .parse()
+                .map(|limit| {
+                    self.limit = limit;
+                    Response::Ok
+                })
+                .map_err(|_| format!("invalid limit {argument:?}")),
+            _ => Err(format!("unknown command {command:?}, see help")),
+        };
+
+        Some(match self.format {
+            Format::Text => match result {
+                Ok(response) => self.text(response),
+                Err(e) => format!("error: {e}"),
+            },
+            Format::Json => match result {
+                Ok(response) => self.json(response),
+                Err(e) => json!({ "error": e }),
+            }
+            .to_string(),
+        })
+    }
+
+    fn get(&mut self, key: &str) -> Result<Response, String> {
+        let key = required(key, "get <key>")?;
+        self.last_key = Some(key.to_string());
+
+        let starttime = Instant::now();
+        let locations = self.index.get(key);
+        let latency = starttime.elapsed();
+
+        Ok(Response::Locations(locations, latency))
+    }
+
+    fn prefix(&mut self, prefix: &str) -> Result<Response, String> {
+        let prefix = required(prefix, "prefix <p>")?;
+
+        let starttime = Instant::now();
+        let locations = self
+            .index
+            .prefix(prefix)
+            .ok_or("this index can not search its keys")?;
+        let latency = starttime.elapsed();
+
+        Ok(Response::Locations(locations, latency))
+    }
+
+    fn and(&mut self, argument: &str) -> Result<Response, String> {
+        let keys = split_keys(argument);
+        if keys.is_empty() {
+            return Err("usage: and <k1> <k2> ...".to_string());
+        }
+
+        let starttime = Instant::now();
+        let mut rows: Option<BTreeSet<(u32, u32)>> = None;
+        let mut locations = Vec::new();
+        for key in &keys {
+            let found = self.index.get(key);
+            let found_rows = found.iter().map(|l| (l.tableid, l.rowid)).collect();
+            rows = Some(match rows {
+                Some(rows) => rows.intersection(&found_rows).copied().collect(),
+                None => found_rows,
+            });
+            locations.extend(found);
+        }
+        let rows = rows.unwrap_or_default();
+        locations.retain(|l| rows.contains(&(l.tableid, l.rowid)));
+        locations.sort_unstable();
+        let latency = starttime.elapsed();
+
+        Ok(Response::Rows(
+            rows.into_iter().collect(),
+            locations,
+            latency,
+        ))
+    }
+
+    fn stats(&mut self, key: &str) -> Result<Response, String> {
+        let key = required(key, "stats <key>")?;
+
+        let locations = self.index.get(key);
+        let tables = locations.iter().map(|l| l.tableid).collect::<BTreeSet<_>>();
+        let columns = locations
+            .iter()
+            .map(|l| (l.tableid, l.colid))
+            .collect::<BTreeSet<_>>();
+        let encoded = self.index.explain(key).and_then(|e| e.posting_bytes);
+
+        Ok(Response::Stats {
+            locations: locations.len(),
+            tables: tables.len(),
+            columns: columns.len(),
+            encoded,
+        })
+    }
+
+    fn explain(&mut self, key: &str) -> Result<Response, String> {
+        let key = match key {
+            "" => self
+                .last_key
+                .clone()
+                .ok_or("usage: explain <key>, no key was looked up yet")?,
+            key => key.to_string(),
+        };
+
+        let starttime = Instant::now();
+        let locations = self.index.get(&key).len();
+        let latency = starttime.elapsed();
+
+        let explanation = self.index.explain(&key);
+        Ok(Response::Explain {
+            key,
+            locations,
+            latency,
+            explanation,
+        })
+    }
+
+    fn keys(&mut self, count: &str) -> Result<Response, String> {
+        let count = match count {
+            "" => KEY_COUNT,
+            count => count
+                .parse()
+                .map_err(|_| format!("invalid count {count:?}"))?,
+        };
+
+        let mut keys = self.index.keys();
+        keys.sort_unstable();
+        keys.dedup();
+        keys.truncate(count);
+
+        Ok(Response::Keys(keys))
+    }
+
+    /// Where a location is, with the names of the table and column if they are known
+    fn describe(&self, l: &TableLocation) -> String {
+        match self.metadata {
+            Some(metadata) => metadata.describe(l.tableid, l.colid, l.rowid),
+            None => format!("table {}, column {}, row {}", l.tableid, l.colid, l.rowid),
+        }
+    }
+
+    fn table_name(&self, tableid: u32) -> String {
+        let table = self.metadata.and_then(|m| m.table(tableid));
+        match table {
+            Some(t) if !t.source.is_empty() => t.source.clone(),
+            Some(t) if !t.name.is_empty() => t.name.clone(),
+            _ => format!("table {tableid}"),
+        }
+    }
+
+    fn shown<'l>(&self, locations: &'l [TableLocation]) -> &'l [TableLocation] {
+        &locations[..locations.len().min(self.limit)]
+    }
+
+    fn text(&self, response: Response) -> String {
+        let mut lines = Vec::new();
+        let more = |lines: &mut Vec<String>, shown: usize, total: usize| {
+            if shown < total {
+                lines.push(format!("... {} more", total - shown));
+            }
+        };
+
+        match response {
+            Response::Ok => {}
+            Response::Help => lines.push(HELP.to_string()),
+            Response::Locations(locations, latency) => {
+                lines.push(format!(
+                    "{} locations in {}µs",
+                    locations.len(),
+                    latency.as_micros()
+                ));
+                let shown = self.shown(&locations);
+                lines.extend(shown.iter().map(|l| format!("  {}", self.describe(l))));
+                more(&mut lines, shown.len(), locations.len());
+            }
+            Response::Rows(rows, locations, latency) => {
+                lines.push(format!(
+                    "{} rows contain all keys, found in {}µs",
+                    rows.len(),
+                    latency.as_micros()
+                ));
+                let shown = &rows[..rows.len().min(self.limit)];
+                for &(tableid, rowid) in shown {
+                    lines.push(format!("  {}, row {rowid}", self.table_name(tableid)));
+                    let cells = locations
+                        .iter()
+                        .filter(|l| (l.tableid, l.rowid) == (tableid, rowid));
+                    lines.extend(cells.map(|l| format!("    {}", self.describe(l))));
+                }
+                more(&mut lines, shown.len(), rows.len());
+            }
+            Response::Stats {
+                locations,
+                tables,
+                columns,
+                encoded,
+            } => {
+                lines.push(format!("locations: {locations}"));
+                lines.push(format!("tables:    {tables}"));
+                lines.push(format!("columns:   {columns}"));
+                if let Some(encoded) = encoded {
+                    lines.push(format!("encoded:   {encoded} bytes"));
+                }
+            }
+            Response::Explain {
+                key,
+                locations,
+                latency,
+                explanation,
+            } => {
+                lines.push(format!(
+                    "{key:?}: {locations} locations in {}µs",
+                    latency.as_micros()
+                ));
+                match explanation {
+                    Some(e) => {
+                        lines.push(format!(
+                            "decoded blocks {:?} ({} bytes)",
+                            e.blocks, e.block_bytes
+                        ));
+                        match e.posting_bytes {
+                            Some(bytes) => lines.push(format!("posting list: {bytes} bytes")),
+                            None => lines.push("key is not indexed".to_string()),
+                        }
+                    }
+                    None => lines.push("this index does not store its keys in blocks".to_string()),
+                }
+            }
+            Response::Keys(keys) => lines.extend(keys.iter().map(|k| format!("{k:?}"))),
+        }
+
+        lines.join("\n")
+    }
+
+    fn location_json(&self, l: &TableLocation) -> Value {
+        super::location_json(l, self.metadata)
+    }
+
+    fn json(&self, response: Response) -> Value {
+        match response {
+            Response::Ok => json!({ "ok": true }),
+            Response::Help => json!({ "help": HELP }),
+            Response::Locations(locations, latency) => json!({
+                "count": locations.len(),
+                "latency_ns": latency.as_nanos() as u64,
+                "locations": self
+                    .shown(&locations)
+                    .iter()
+                    .map(|l| self.location_json(l))
+                    .collect::<Vec<_>>(),
+            }),
+            Response::Rows(rows, locations, latency) => json!({
+                "count": rows.len(),
+                "latency_ns": latency.as_nanos() as u64,
+                "rows": rows
+                    .iter()
+                    .take(self.limit)
+                    .map(|&(tableid, rowid)| json!({
+                        "tableid": tableid,
+                        "rowid": rowid,
+                        "locations": locations
+                            .iter()
+                            .filter(|l| (l.tableid, l.rowid) == (tableid, rowid))
+                            .map(|l| self.location_json(l))
+                            .collect::<Vec<_>>(),
+                    }))
+                    .collect::<Vec<_>>(),
+            }),
+            Response::Stats {
+                locations,
+                tables,
+                columns,
+                encoded,
+            } => json!({
+                "locations": locations,
+                "tables": tables,
+                "columns": columns,
+                "encoded_bytes": encoded,
+            }),
+            Response::Explain {
+                key,
+                locations,
+                latency,
+                explanation,
+            } => json!({
+                "key": key,
+                "locations": locations,
+                "latency_ns": latency.as_nanos() as u64,
+                "blocks": explanation.as_ref().map(|e| &e.blocks),
+                "block_bytes": explanation.as_ref().map(|e| e.block_bytes),
+                "posting_bytes": explanation.and_then(|e| e.posting_bytes),
+            }),
+            Response::Keys(keys) => json!({ "keys": keys }),
+        }
+    }
+}
+
+enum Response {
+    Ok,
+    Help,
+    Locations(Vec<TableLocation>, Duration),
+    /// (tableid, rowid) of the rows and the locations of the keys in them
+    Rows(Vec<(u32, u32)>, Vec<TableLocation>, Duration),
+    Stats {
+        locations: usize,
+        tables: usize,
+        columns: usize,
+        encoded: Option<usize>,
+    },
+    Explain {
+        key: String,
+        locations: usize,
+        latency: Duration,
+        explanation: Option<super::Explanation>,
+    },
+    Keys(Vec<String>),
+}
+
+fn required<'k>(argument: &'k str, usage: &str) -> Result<&'k str, String> {
+    if argument.is_empty() {
+        Err(format!("usage: {usage}"))
+    } else {
+        Ok(argument)
+    }
+}
+
+/// Keys can be quoted to keep leading or trailing whitespace.
+fn unquote(s: &str) -> &str {
+    s.strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(s)
+}
+
+/// Splits at whitespace, except inside of double quotes.
+fn split_keys(s: &str) -> Vec<String> {
+    let mut keys = Vec::new();
+    let mut key = String::new();
+    let mut quoted = false;
+    for c in s.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !key.is_empty() {
+                    keys.push(std::mem::take(&mut key));
+                }
+            }
+            c => key.push(c),
+        }
+    }
+    if !key.is_empty() {
+        keys.push(key);
+    }
+
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kinds::CompressionAlgorithm;
+    use crate::query::build;
+    use std::sync::mpsc::sync_channel;
+
+    fn index(algorithm: &str) -> Box<dyn QueryIndex> {
+        let (sender, receiver) = sync_channel(1024);
+        std::thread::spawn(move || {
+            let rows = [
+                ("berlin", 0, 0, 0),
+                ("berlin", 1, 2, 4),
+                ("germany", 0, 1, 0),
+                ("germany", 0, 1, 1),
+                ("germany", 1, 3, 4),
+                ("hamburg", 0, 0, 1),
+                ("new york", 2, 0, 0),
+            ];
+            for (key, tableid, colid, rowid) in rows {
+                let location = TableLocation {
+                    tableid,
+                    colid,
+                    rowid,
+                };
+                sender.send((key.to_string(), location)).unwrap();
+            }
+        });
+
+        let algorithm = algorithm.parse::<CompressionAlgorithm>().unwrap();
+        let blocksize = algorithm.checked_blocksize(None).unwrap();
+        build(algorithm, blocksize, receiver).2
+    }
+
+    #[test]
+    fn commands() {
+        let index = index("incr_adv+dedup+ns");
+        let mut repl = Repl::new(&*index, None);
+
+        let get = repl.execute("get germany").unwrap();
+        assert!(get.starts_with("3 locations in "));
+        assert!(get.contains("table 1, column 3, row 4"));
+        assert!(repl
+            .execute("get \"new york\"")
+            .unwrap()
+            .starts_with("1 locations"));
+        assert!(repl
+            .execute("get paris")
+            .unwrap()
+            .starts_with("0 locations"));
+
+        assert!(repl
+            .execute("prefix ber")
+            .unwrap()
+            .starts_with("2 locations"));
+
+        let and = repl.execute("and germany berlin").unwrap();
+        assert!(and.starts_with("2 rows contain all keys"));
+        assert!(and.contains("table 1, row 4\n    table 1, column 2, row 4"));
+
+        let stats = repl.execute("stats germany").unwrap();
+        assert!(stats.contains("tables:    2"));
+        assert!(stats.contains("columns:   2"));
+        assert!(stats.contains("encoded:   "));
+
+        let explain = repl.execute("explain").unwrap();
+        assert!(explain.starts_with("\"paris\": 0 locations"));
+        assert!(explain.contains("key is not indexed"));
+        assert!(repl
+            .execute("explain hamburg")
+            .unwrap()
+            .contains("decoded blocks [0]"));
+
+        assert_eq!(repl.execute("limit 1").unwrap(), "");
+        assert!(repl.execute("get germany").unwrap().ends_with("... 2 more"));
+
+        assert!(repl.execute("nothing").unwrap().starts_with("error: "));
+        assert_eq!(repl.execute("quit"), None);
+    }
+
+    #[test]
+    fn last_key_of_the_incremental_codings() {
+        for algorithm in [
+            "incr+dedup+ns",
+            "incr_adv+dedup+ns",
+            "incr_adv+dedup+ns_adv",
+            "vbyte+ns+incr+dedup",
+        ] {
+            let index = index(algorithm);
+            let mut repl = Repl::new(&*index, None);
+
+            // "new york" is the last key, in an unfinished block
+            let get = repl.execute("get \"new york\"").unwrap();
+            assert!(get.starts_with("1 locations"), "{algorithm}: {get}");
+            let get = repl.execute("get germany").unwrap();
+            assert!(get.starts_with("3 locations"), "{algorithm}: {get}");
+        }
+    }
+
+    #[test]
+    fn json_output() {
+        let index = index("frontcoding+dedup");
+        let mut metadata = Metadata::default();
+        metadata.tables.push(bintable2::Table {
+            name: "cities".to_string(),
+            source: "cities.csv".to_string(),
+            ..Default::default()
+        });
+        let mut repl = Repl::new(&*index, Some(&metadata));
+
+        let parse = |s: String| serde_json::from_str::<Value>(&s).unwrap();
+
+        assert_eq!(
+            parse(repl.execute("format json").unwrap()),
+            json!({ "ok": true })
+        );
+
+        let get = parse(repl.execute("get berlin").unwrap());
+        assert_eq!(get["count"], 2);
+        assert_eq!(get["locations"][0]["where"], "cities.csv, column 0, row 0");
+        assert_eq!(get["locations"][1]["tableid"], 1);
+
+        let and = parse(repl.execute("and germany hamburg").unwrap());
+        assert_eq!(
+            and["rows"],
+            json!([{ "tableid": 0, "rowid": 1, "locations": [
+                { "tableid": 0, "colid": 0, "rowid": 1, "where": "cities.csv, column 0, row 1" },
+                { "tableid": 0, "colid": 1, "rowid": 1, "where": "cities.csv, column 1, row 1" },
+            ]}])
+        );
+
+        let stats = parse(repl.execute("stats germany").unwrap());
+        assert_eq!(stats["locations"], 3);
+        assert_eq!(stats["encoded_bytes"], 3 * 12);
+
+        assert!(parse(repl.execute("format xml").unwrap())["error"].is_string());
+    }
+
+    #[test]
+    fn without_blocks_or_search() {
+        let index = index("roaring+dedup");
+        let mut repl = Repl::new(&*index, None);
+
+        assert!(repl
+            .execute("get germany")
+            .unwrap()
+            .starts_with("3 locations"));
+        // roaring+dedup returns no posting list for keys it does not index
+        assert!(repl
+            .execute("get paris")
+            .unwrap()
+            .starts_with("0 locations"));
+        assert!(repl.execute("prefix ger").unwrap().starts_with("error: "));
+        assert!(repl
+            .execute("explain germany")
+            .unwrap()
+            .contains("does not store its keys in blocks"));
+        assert!(!repl.execute("stats germany").unwrap().contains("encoded"));
+    }
+
+    #[test]
+    fn quoted_keys() {
+        assert_eq!(split_keys(" a \"b c\"  d"), ["a", "b c", "d"]);
+        assert_eq!(unquote("\" a \""), " a ");
+    }
+}
@@ -0,0 +1,540 @@
+//! HTTP/JSON interface of `ii-serve`.
+//!
+//! ```text
+//! GET  /get?key=<key>[&limit=<n>]                   locations of a key
+//! POST /batch    {"keys": [..]}                     locations of many keys
+//! POST /query    {"all": [..], "any": [..], "not": [..]}
+//!                                                   rows containing all keys of `all`, one of `any` and none of `not`
+//! POST /overlap  {"keys": [..], "top": 10}          columns containing most of the keys
+//! GET  /stats                                       size and build times of the index
+//! ```
+
+use crate::log::Logger;
+use crate::query::{location_json, QueryIndex};
+use crate::TableLocation;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, sync_channel, Sender};
+use std::sync::Arc;
+use std::thread::spawn;
+use std::time::{Duration, Instant};
+
+/// Number of columns returned by `/overlap` by default
+const TOP_COLUMNS: usize = 10;
+
+type Job = Box<dyn FnOnce(&dyn QueryIndex) + Send>;
+
+type Error = (StatusCode, Json<Value>);
+
+type Response = Result<Json<Value>, Error>;
+
+/// Answers requests using an index living on a thread of its own.
+/// Some indices can not be shared between threads (FastPFor codecs keep state while decoding),
+/// so all lookups are sent to this thread and run one after another.
+pub struct Server {
+    jobs: Sender<Job>,
+    /// measurements of the build, and the names of the tables if they are known
+    log: Logger,
+    /// number of locations returned per key, unless a request asks for a different number
+    limit: usize,
+    /// number of queries answered by the index
+    requests: AtomicU64,
+}
+
+impl Server {
+    /// Builds the index on a new thread, which then answers the lookups. Returns once the index is built.
+    pub fn start<F>(build: F, limit: usize) -> Self
+    where
+        F: FnOnce() -> (Box<dyn QueryIndex>, Logger) + Send + 'static,
+    {
+        let (jobs, receiver) = channel::<Job>();
+        let (built, log) = sync_channel(1);
+
+        spawn(move || {
+            let (index, log) = build();
+            built.send(log).expect("send log");
+
+            for job in receiver {
+                job(&*index);
+            }
+        });
+
+        Server {
+            jobs,
+            log: log.recv().expect("index to be built"),
+            limit,
+            requests: AtomicU64::new(0),
+        }
+    }
+
+    /// Runs `job` on the thread of the index.
+    async fn ask<T, F>(&self, job: F) -> Result<T, Error>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn QueryIndex) -> T + Send + 'static,
+    {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        let job: Job = Box::new(move |index| {
+            // the request may have been cancelled meanwhile
+            let _ = sender.send(job(index));
+        });
+
+        self.jobs.send(job).map_err(|_| unavailable())?;
+        receiver.await.map_err(|_| unavailable())
+    }
+
+    fn lookup_json(
+        &self,
+        key: &str,
+        (locations, latency): (Vec<TableLocation>, Duration),
+        limit: Option<usize>,
+    ) -> Value {
+        let limit = limit.unwrap_or(self.limit);
+        let shown = locations
+            .iter()
+            .take(limit)
+            .map(|l| location_json(l, self.log.metadata()))
+            .collect::<Vec<_>>();
+
+        json!({
+            "key": key,
+            "count": locations.len(),
+            "latency_ns": latency.as_nanos() as u64,
+            "locations": shown,
+        })
+    }
+
+    fn column_json(&self, (tableid, colid): (u32, u32), overlap: usize) -> Value {
+        let mut value = json!({ "tableid": tableid, "colid": colid, "overlap": overlap });
+        if let Some(metadata) = self.log.metadata() {
+            if let Some(table) = metadata.table(tableid) {
+                value["table"] = json!(table.name);
+                value["source"] = json!(table.source);
+            }
+            if let Some(column) = metadata.column(tableid, colid) {
+                value["column"] = json!(column.name);
+            }
+        }
+        value
+    }
+}
+
+/// The thread of the index panicked
+fn unavailable() -> Error {
+    let error = json!({ "error": "the index is not available" });
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(error))
+}
+
+fn bad_request(error: String) -> Error {
+    (StatusCode::BAD_REQUEST, Json(json!({ "error": error })))
+}
+
+fn timed<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let starttime = Instant::now();
+    let result = f();
+    (result, starttime.elapsed())
+}
+
+/// (tableid, rowid) of the rows containing `key`
+fn rows(index: &dyn QueryIndex, key: &str) -> BTreeSet<(u32, u32)> {
+    index
+        .get(key)
+        .iter()
+        .map(|l| (l.tableid, l.rowid))
+        .collect()
+}
+
+fn boolean_rows(index: &dyn QueryIndex, query: &Boolean) -> BTreeSet<(u32, u32)> {
+    let mut result: Option<BTreeSet<(u32, u32)>> = None;
+    for key in &query.all {
+        let found = rows(index, key);
+        result = Some(match result {
+            Some(result) => result.intersection(&found).copied().collect(),
+            None => found,
+        });
+    }
+
+    if !query.any.is_empty() {
+        let any = query.any.iter().flat_map(|key| rows(index, key)).collect();
+        result = Some(match result {
+            Some(result) => result.intersection(&any).copied().collect(),
+            None => any,
+        });
+    }
+
+    let mut result = result.unwrap_or_default();
+    for key in &query.not {
+        for row in rows(index, key) {
+            result.remove(&row);
+        }
+    }
+
+    result
+}
+
+/// Columns sorted by the number of distinct keys they contain, most first
+fn overlapping_columns(index: &dyn QueryIndex, keys: &[String]) -> Vec<((u32, u32), usize)> {
+    let keys = keys.iter().collect::<BTreeSet<_>>();
+
+    let mut overlap = HashMap::new();
+    for key in keys {
+        let columns = index
+            .get(key)
+            .iter()
+            .map(|l| (l.tableid, l.colid))
+            .collect::<BTreeSet<_>>();
+        for column in columns {
+            *overlap.entry(column).or_insert(0) += 1;
+        }
+    }
+
+    let mut overlap = overlap.into_iter().collect::<Vec<_>>();
+    overlap.sort_unstable_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+    overlap
+}
+
+#[derive(Deserialize)]
+struct Get {
+    key: String,
+    limit: Option<usize>,
+}
+
+async fn get_key(State(server): State<Arc<Server>>, Query(query): Query<Get>) -> Response {
+    let key = query.key.clone();
+    let found = server.ask(move |index| timed(|| index.get(&key))).await?;
+
+    Ok(Json(server.lookup_json(&query.key, found, query.limit)))
+}
+
+#[derive(Deserialize)]
+struct Batch {
+    keys: Vec<String>,
+    limit: Option<usize>,
+}
+
+async fn batch(State(server): State<Arc<Server>>, Json(batch): Json<Batch>) -> Response {
+    let keys = batch.keys.clone();
+    let found = server
+        .ask(move |index| {
+            keys.iter()
+                .map(|key| timed(|| index.get(key)))
+                .collect::<Vec<_>>()
+        })
+        .await?;
+
+    let results = batch
+        .keys
+        .iter()
+        .zip(found)
+        .map(|(key, found)| server.lookup_json(key, found, batch.limit))
+        .collect::<Vec<_>>();
+
+    Ok(Json(json!({ "results": results })))
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Boolean {
+    all: Vec<String>,
+    any: Vec<String>,
+    not: Vec<String>,
+    limit: Option<usize>,
+}
+
+async fn boolean(State(server): State<Arc<Server>>, Json(query): Json<Boolean>) -> Response {
+    if query.all.is_empty() && query.any.is_empty() {
+        return Err(bad_request(
+            "query needs at least one key in all or any".to_string(),
+        ));
+    }
+
+    let limit = query.limit.unwrap_or(server.limit);
+    let (rows, latency) = server
+        .ask(move |index| timed(|| boolean_rows(index, &query)))
+        .await?;
+
+    let shown = rows
+        .iter()
+        .take(limit)
+        .map(|&(tableid, rowid)| {
+            let mut row = json!({ "tableid": tableid, "rowid": rowid });
+            if let Some(table) = server.log.metadata().and_then(|m| m.table(tableid)) {
+                row["source"] = json!(table.source);
+            }
+            row
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Json(json!({
+        "count": rows.len(),
+        "latency_ns": latency.as_nanos() as u64,
+        "rows": shown,
+    })))
+}
+
+#[derive(Deserialize)]
+struct Overlap {
+    keys: Vec<String>,
+    top: Option<usize>,
+}
+
+async fn overlap(State(server): State<Arc<Server>>, Json(query): Json<Overlap>) -> Response {
+    if query.keys.is_empty() {
+        return Err(bad_request("overlap needs at least one key".to_string()));
+    }
+
+    let top = query.top.unwrap_or(TOP_COLUMNS);
+    let (overlap, latency) = server
+        .ask(move |index| timed(|| overlapping_columns(index, &query.keys)))
+        .await?;
+
+    let columns = overlap
+        .into_iter()
+        .take(top)
+        .map(|(column, count)| server.column_json(column, count))
+        .collect::<Vec<_>>();
+
+    Ok(Json(json!({
+        "latency_ns": latency.as_nanos() as u64,
+        "columns": columns,
+    })))
+}
+
+async fn stats(State(server): State<Arc<Server>>) -> Json<Value> {
+    let mut stats = server.log.to_json();
+    stats["requests"] = json!(server.requests.load(Ordering::Relaxed));
+    if let Some(metadata) = server.log.metadata() {
+        stats["tables"] = json!(metadata.tables.len());
+    }
+
+    Json(stats)
+}
+
+pub fn router(server: Arc<Server>) -> Router {
+    Router::new()
+        .route("/get", get(get_key))
+        .route("/batch", post(batch))
+        .route("/query", post(boolean))
+        .route("/overlap", post(overlap))
+        .route("/stats", get(stats))
+        .with_state(server)
+}
+
+/// Answers requests until the process is stopped.
+pub async fn serve(server: Server, address: SocketAddr) -> std::io::Result<()> {
+    let listener = tokio::net::TcpListener::bind(address).await?;
+    eprintln!("listening on http://{}", listener.local_addr()?);
+
+    axum::serve(listener, router(Arc::new(server))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kinds::CompressionAlgorithm;
+    use crate::query::build_logging;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use bintable2::{Column, Metadata, Table};
+    use std::sync::mpsc::sync_channel;
+    use tower::ServiceExt;
+
+    fn server() -> Router {
+        let (sender, receiver) = sync_channel(1024);
+        std::thread::spawn(move || {
+            let rows = [
+                ("berlin", 0, 0, 0),
+                ("berlin", 1, 0, 2),
+                ("france", 0, 1, 2),
+                ("germany", 0, 1, 0),
+                ("germany", 0, 1, 1),
+                ("germany", 1, 1, 2),
+                ("hamburg", 0, 0, 1),
+                ("paris", 0, 0, 2),
+            ];
+            for (key, tableid, colid, rowid) in rows {
+                let location = TableLocation {
+                    tableid,
+                    colid,
+                    rowid,
+                };
+                sender.send((key.to_string(), location)).unwrap();
+            }
+        });
+
+        let metadata = Metadata {
+            tables: vec![Table {
+                name: "cities".to_string(),
+                source: "cities.csv".to_string(),
+                rows: 3,
+                columns: vec![
+                    Column {
+                        name: "city".to_string(),
+                        ..Default::default()
+                    },
+                    Column {
+                        name: "country".to_string(),
+                        ..Default::default()
+                    },
+                ],
+            }],
+        };
+
+        let server = Server::start(
+            move || {
+                let algorithm = CompressionAlgorithm::FrontCodingDedup;
+                let mut log = Logger::new(
+                    algorithm.str().to_string(),
+                    "cities".to_string(),
+                    false,
+                    false,
+                    None,
+                    algorithm.blocksize(None),
+                    None,
+                )
+                .with_metadata(Some(metadata));
+                let index = build_logging(algorithm, algorithm.blocksize(None), receiver, &mut log);
+                (index, log)
+            },
+            100,
+        );
+
+        router(Arc::new(server))
+    }
+
+    async fn request(router: &Router, method: &str, uri: &str, body: Value) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn lookups() {
+        runtime().block_on(async {
+            let router = server();
+
+            let (status, get) =
+                request(&router, "GET", "/get?key=germany&limit=2", json!({})).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(get["count"], 3);
+            assert_eq!(get["locations"].as_array().unwrap().len(), 2);
+            assert_eq!(
+                get["locations"][0]["where"],
+                "cities.csv, column \"country\", row 0"
+            );
+
+            let (_, missing) = request(&router, "GET", "/get?key=rome", json!({})).await;
+            assert_eq!(missing["count"], 0);
+
+            let (_, batch) = request(
+                &router,
+                "POST",
+                "/batch",
+                json!({ "keys": ["berlin", "rome", "paris"] }),
+            )
+            .await;
+            let counts = batch["results"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|r| r["count"].as_u64().unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(counts, [2, 0, 1]);
+
+            let (status, _) = request(&router, "GET", "/get", json!({})).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            let (status, _) = request(&router, "GET", "/nothing", json!({})).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        });
+    }
+
+    #[test]
+    fn boolean_and_overlap() {
+        runtime().block_on(async {
+            let router = server();
+
+            let rows = |response: Value| {
+                response["rows"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|r| (r["tableid"].as_u64().unwrap(), r["rowid"].as_u64().unwrap()))
+                    .collect::<Vec<_>>()
+            };
+
+            let (_, all) =
+                request(&router, "POST", "/query", json!({ "all": ["berlin", "germany"] })).await;
+            assert_eq!(rows(all.clone()), [(0, 0), (1, 2)]);
+            assert_eq!(all["rows"][0]["source"], "cities.csv");
+
+            let (_, any) = request(
+                &router,
+                "POST",
+                "/query",
+                json!({ "any": ["hamburg", "paris"], "not": ["france"] }),
+            )
+            .await;
+            assert_eq!(rows(any), [(0, 1)]);
+
+            let (_, both) = request(
+                &router,
+                "POST",
+                "/query",
+                json!({ "all": ["germany"], "any": ["hamburg", "berlin"], "not": ["berlin"] }),
+            )
+            .await;
+            assert_eq!(rows(both), [(0, 1)]);
+
+            let (status, error) = request(&router, "POST", "/query", json!({ "not": ["x"] })).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert!(error["error"].is_string());
+
+            let (_, overlap) = request(
+                &router,
+                "POST",
+                "/overlap",
+                json!({ "keys": ["berlin", "hamburg", "paris", "germany", "berlin"], "top": 2 }),
+            )
+            .await;
+            assert_eq!(
+                overlap["columns"],
+                json!([
+                    { "tableid": 0, "colid": 0, "overlap": 3, "table": "cities", "source": "cities.csv", "column": "city" },
+                    { "tableid": 0, "colid": 1, "overlap": 1, "table": "cities", "source": "cities.csv", "column": "country" },
+                ])
+            );
+
+            let (_, stats) = request(&router, "GET", "/stats", json!({})).await;
+            assert_eq!(stats["algorithm"], "frontcoding+dedup");
+            assert_eq!(stats["blocksize"], 8);
+            assert_eq!(stats["cells"], 8);
+            assert_eq!(stats["tables"], 1);
+            // the invalid query never reached the index
+            assert_eq!(stats["requests"], 4);
+        });
+    }
+}
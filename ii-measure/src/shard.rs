@@ -0,0 +1,378 @@
+use crate::inverted_index::{merge_postings, InvertedIndex, KeySearch};
+use crate::measure::{allocated, Locations};
+use crate::util::random_keys::DESIRED_KEY_COUNT;
+use crate::util::RandomKeys;
+use crate::{Entry, TableLocation};
+use rand::seq::SliceRandom;
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::str::FromStr;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const CHANNEL_BOUND: usize = 1024;
+
+/// How keys are assigned to shards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Partition {
+    /// by the hash of the key
+    Hash,
+    /// consecutive ranges of sorted keys, with about the same number of entries each
+    Range,
+}
+
+impl Partition {
+    pub fn str(self) -> &'static str {
+        match self {
+            Partition::Hash => "hash",
+            Partition::Range => "range",
+        }
+    }
+}
+
+impl FromStr for Partition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "hash" => Ok(Partition::Hash),
+            "range" => Ok(Partition::Range),
+            _ => Err(String::from("allowed: hash range")),
+        }
+    }
+}
+
+impl fmt::Display for Partition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sharding {
+    pub shards: usize,
+    pub partition: Partition,
+    /// Number of entries that will be read, needed to split them into ranges.
+    pub entries: Option<usize>,
+}
+
+impl Sharding {
+    pub fn new(shards: usize, partition: Partition) -> Self {
+        assert!(shards > 0, "at least one shard");
+        Sharding {
+            shards,
+            partition,
+            entries: None,
+        }
+    }
+
+    /// Range partitioning needs the number of entries, which are counted in a first pass over the source.
+    pub fn with_entries(mut self, entries: usize) -> Self {
+        self.entries = Some(entries);
+        self
+    }
+}
+
+/// Counts the entries of a source, for range partitioning.
+pub fn count_entries(receiver: Receiver<Entry>) -> usize {
+    receiver.into_iter().count()
+}
+
+fn hash_shard(key: &str, shards: usize) -> usize {
+    // not randomly seeded, so the shard of a key is the same for every index
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    (hasher.finish() % shards as u64) as usize
+}
+
+/// Index split into shards, each one built on its own thread by the same algorithm.
+/// Lookups are routed to the shard owning the key, searches are sent to all shards.
+pub struct Sharded<II, O> {
+    /// `None` if no key was assigned to the shard
+    shards: Vec<Option<II>>,
+    partition: Partition,
+    /// first key of each shard, to route keys of range partitioned shards
+    firsts: Vec<String>,
+    _output: PhantomData<fn() -> O>,
+}
+
+/// Statistics of a shard, printed once all shards are built
+struct ShardInfo {
+    entries: usize,
+    build_time: Duration,
+    wall_time: Duration,
+}
+
+/// Wraps the index builder `build`, so it builds a sharded index.
+/// The number of entries and the build time are the sums over all shards.
+pub fn sharded<F, II, O>(
+    build: F,
+    sharding: Sharding,
+) -> impl Fn(Receiver<Entry>) -> (usize, Duration, Sharded<II, O>)
+where
+    F: Fn(Receiver<Entry>) -> (usize, Duration, II) + Sync,
+    II: Send,
+{
+    move |receiver| build_sharded(&build, sharding, receiver)
+}
+
+fn build_sharded<F, II, O>(
+    build: &F,
+    sharding: Sharding,
+    receiver: Receiver<Entry>,
+) -> (usize, Duration, Sharded<II, O>)
+where
+    F: Fn(Receiver<Entry>) -> (usize, Duration, II) + Sync,
+    II: Send,
+{
+    let Sharding {
+        shards,
+        partition,
+        entries,
+    } = sharding;
+    let entries = match partition {
+        Partition::Range => entries.expect("number of entries for range partitioning"),
+        Partition::Hash => 0,
+    };
+
+    thread::scope(|scope| {
+        // builders are started on the first key of their shard, as they expect at least one entry
+        let mut senders: Vec<Option<SyncSender<_>>> = (0..shards).map(|_| None).collect();
+        let mut builders = (0..shards).map(|_| None).collect::<Vec<_>>();
+        let mut firsts = vec![String::new(); shards];
+
+        let mut range_shard = 0;
+        let mut last_key = String::new();
+        for (count, (key, location)) in receiver.into_iter().enumerate() {
+            let shard = match partition {
+                Partition::Hash => hash_shard(&key, shards),
+                Partition::Range => {
+                    // posting lists are never split
+                    let end = (range_shard + 1) * entries / shards;
+                    if count > 0 && count >= end && key != last_key && range_shard + 1 < shards {
+                        range_shard += 1;
+                    }
+                    last_key.clone_from(&key);
+                    range_shard
+                }
+            };
+
+            let sender = senders[shard].get_or_insert_with(|| {
+                let (sender, receiver) = sync_channel(CHANNEL_BOUND);
+                firsts[shard].clone_from(&key);
+                builders[shard] = Some(scope.spawn(move || {
+                    let starttime = Instant::now();
+                    let (entries, build_time, ii) = build(receiver);
+                    let info = ShardInfo {
+                        entries,
+                        build_time,
+                        wall_time: starttime.elapsed(),
+                    };
+                    (info, ii)
+                }));
+                sender
+            });
+            sender.send((key, location)).expect("send entry to shard");
+        }
+        drop(senders);
+
+        let mut total_entries = 0;
+        let mut total_build_time = Duration::ZERO;
+        let mut built = Vec::with_capacity(shards);
+        for (i, builder) in builders.into_iter().enumerate() {
+            let Some(builder) = builder else {
+                eprintln!("shard {i}: empty");
+                built.push(None);
+                continue;
+            };
+
+            let (info, ii) = builder.join().expect("shard to be built");
+            eprintln!(
+                "shard {i}: {} entries starting at {:?}, build {}ms, wall {}ms",
+                info.entries,
+                firsts[i],
+                info.build_time.as_millis(),
+                info.wall_time.as_millis()
+            );
+            total_entries += info.entries;
+            total_build_time += info.build_time;
+            built.push(Some(ii));
+        }
+
+        let ii = Sharded {
+            shards: built,
+            partition,
+            firsts,
+            _output: PhantomData,
+        };
+        (total_entries, total_build_time, ii)
+    })
+}
+
+impl<II, O> Sharded<II, O> {
+    fn shard_of(&self, key: &str) -> usize {
+        match self.partition {
+            Partition::Hash => hash_shard(key, self.shards.len()),
+            Partition::Range => {
+                // empty shards only occur at the end of range partitioned shards
+                let filled = self.shards.iter().take_while(|s| s.is_some()).count();
+                self.firsts[..filled]
+                    .partition_point(|first| first.as_str() <= key)
+                    .saturating_sub(1)
+            }
+        }
+    }
+}
+
+impl<II, O> InvertedIndex<Vec<TableLocation>> for Sharded<II, O>
+where
+    II: InvertedIndex<O>,
+    O: Locations,
+{
+    fn get(&self, key: &str) -> Vec<TableLocation> {
+        match &self.shards[self.shard_of(key)] {
+            Some(shard) => shard.get(key).locations(),
+            None => Vec::new(),
+        }
+    }
+}
+
+impl<II: KeySearch, O> KeySearch for Sharded<II, O> {
+    fn prefix(&self, prefix: &str) -> Vec<TableLocation> {
+        merge_postings(self.shards.iter().flatten().map(|s| s.prefix(prefix)))
+    }
+
+    fn glob(&self, pattern: &str) -> Vec<TableLocation> {
+        merge_postings(self.shards.iter().flatten().map(|s| s.glob(pattern)))
+    }
+
+    fn fuzzy(&self, key: &str, k: u32) -> Vec<TableLocation> {
+        merge_postings(self.shards.iter().flatten().map(|s| s.fuzzy(key, k)))
+    }
+}
+
+impl<II: RandomKeys, O> RandomKeys for Sharded<II, O> {
+    fn random_keys_potentially_ordered(&self) -> Vec<String> {
+        let keys = self
+            .shards
+            .iter()
+            .flatten()
+            .flat_map(|s| s.random_keys_potentially_ordered())
+            .collect::<Vec<_>>();
+
+        keys.choose_multiple(&mut rand::thread_rng(), DESIRED_KEY_COUNT)
+            .cloned()
+            .collect()
+    }
+
+    fn drop_random_keys(&mut self) {
+        self.shards
+            .iter_mut()
+            .flatten()
+            .for_each(RandomKeys::drop_random_keys);
+    }
+}
+
+impl<II, O> Drop for Sharded<II, O> {
+    /// The memory of every shard is measured while it is dropped, the total is measured by `measure_logging`.
+    fn drop(&mut self) {
+        for (i, shard) in self.shards.iter_mut().enumerate() {
+            if let Some(shard) = shard.take() {
+                let with = allocated();
+                drop(shard);
+                eprintln!("shard {i}: {} bytes", with.saturating_sub(allocated()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::block_dict::block_dict;
+    use dict_compressed::FrontCoding;
+
+    fn entries() -> Vec<Entry> {
+        let mut entries = Vec::new();
+        for i in 0..500u32 {
+            for j in 0..(i % 4 + 1) {
+                let location = TableLocation {
+                    tableid: i,
+                    colid: j,
+                    rowid: 7,
+                };
+                entries.push((format!("key-{i:04}"), location));
+            }
+        }
+        entries
+    }
+
+    fn receiver() -> Receiver<Entry> {
+        let (sender, receiver) = sync_channel(64);
+        thread::spawn(move || {
+            for entry in entries() {
+                sender.send(entry).unwrap();
+            }
+        });
+        receiver
+    }
+
+    fn check(sharding: Sharding) {
+        let build = sharded(block_dict::<FrontCoding, 8>, sharding);
+        let (count, _, ii) = build(receiver());
+        assert_eq!(count, entries().len());
+        assert_eq!(ii.shards.len(), sharding.shards);
+
+        for i in 0..500u32 {
+            let locations = ii.get(&format!("key-{i:04}"));
+            assert_eq!(locations.len() as u32, i % 4 + 1);
+            assert!(locations.iter().all(|l| l.tableid == i));
+        }
+
+        let tables = ii
+            .prefix("key-012")
+            .iter()
+            .map(|l| l.tableid)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tables.len(),
+            (120..130).map(|i| i % 4 + 1).sum::<u32>() as usize
+        );
+        assert_eq!(ii.random_keys().len(), DESIRED_KEY_COUNT);
+    }
+
+    #[test]
+    fn hash_partitioned() {
+        check(Sharding::new(4, Partition::Hash));
+        check(Sharding::new(1, Partition::Hash));
+    }
+
+    #[test]
+    fn range_partitioned() {
+        let entries = count_entries(receiver());
+        check(Sharding::new(3, Partition::Range).with_entries(entries));
+
+        // more shards than keys leaves the last shards empty
+        let (sender, receiver) = sync_channel(8);
+        for key in ["a", "a", "b"] {
+            let location = TableLocation {
+                tableid: 0,
+                colid: 0,
+                rowid: 0,
+            };
+            sender.send((key.to_string(), location)).unwrap();
+        }
+        drop(sender);
+
+        let sharding = Sharding::new(4, Partition::Range).with_entries(3);
+        let (count, _, ii) = sharded(block_dict::<FrontCoding, 8>, sharding)(receiver);
+        assert_eq!(count, 3);
+        assert_eq!(ii.firsts[..2], ["a", "b"]);
+        assert!(ii.shards[2].is_none() && ii.shards[3].is_none());
+        assert_eq!(ii.get("a").len(), 2);
+        assert_eq!(ii.get("b").len(), 1);
+    }
+}
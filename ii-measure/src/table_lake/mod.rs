@@ -4,7 +4,12 @@ pub use bintable2::TableRow;
 pub use sqlx_adapter::*;
 
 use core::panic;
+use std::pin::Pin;
 use std::sync::mpsc::SyncSender;
+use std::sync::OnceLock;
+use tokio::runtime::Runtime;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::{Stream, StreamExt};
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub struct TableLocation {
@@ -39,28 +44,130 @@ impl TableLocation {
 
 pub type Entry = (String, TableLocation);
 
-/// Trait used to digest multiple tables
-/// from various sources.
-pub trait TableLakeReader {
-    fn read(&mut self, ch: SyncSender<Entry>);
+/// Rows buffered between a source and its consumer, reading pauses while they are not taken.
+const STREAM_BOUND: usize = 1024;
+
+/// The runtime shared by all asynchronous sources, so they compose with each other.
+pub fn runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("start runtime")
+    })
+}
+
+pub type RowStream = Pin<Box<dyn Stream<Item = anyhow::Result<TableRow>> + Send>>;
+
+/// Rows of multiple tables, read either by blocking on an iterator
+/// or by polling a stream on the shared runtime (postgres, bintables as `AsyncBinTable`).
+pub enum TableLakeReader {
+    Sync(Box<dyn Iterator<Item = TableRow> + Send>),
+    Async(RowStream),
 }
 
-impl<I: Iterator<Item = TableRow>> TableLakeReader for I {
-    fn read(&mut self, ch: SyncSender<Entry>) {
-        for row in self {
-            let TableRow {
-                tokenized,
-                tableid,
-                colid,
-                rowid,
-            } = row;
-            let location = TableLocation {
-                tableid,
-                colid,
-                rowid,
-            };
-            ch.send((tokenized, location))
-                .expect("streadm tablelocation");
+impl TableLakeReader {
+    pub fn from_rows(rows: impl Iterator<Item = TableRow> + Send + 'static) -> Self {
+        TableLakeReader::Sync(Box::new(rows))
+    }
+
+    pub fn from_stream(
+        rows: impl Stream<Item = anyhow::Result<TableRow>> + Send + 'static,
+    ) -> Self {
+        TableLakeReader::Async(Box::pin(rows))
+    }
+
+    /// Sends the entries of all rows to `ch`, blocks while it is full.
+    /// Asynchronous sources are driven on the shared runtime from the calling thread.
+    pub fn read(self, ch: SyncSender<Entry>) {
+        match self {
+            TableLakeReader::Sync(rows) => {
+                for row in rows {
+                    ch.send(entry(row)).expect("streadm tablelocation");
+                }
+            }
+            TableLakeReader::Async(mut rows) => runtime().block_on(async {
+                while let Some(row) = rows.next().await {
+                    let row = row.expect("read row");
+                    ch.send(entry(row)).expect("streadm tablelocation");
+                }
+            }),
+        }
+    }
+
+    /// Rows as a stream on the shared runtime. Iterators are read on a blocking thread,
+    /// which pauses while `STREAM_BOUND` rows are not taken yet.
+    pub fn into_stream(self) -> RowStream {
+        match self {
+            TableLakeReader::Sync(rows) => {
+                let (sender, receiver) = tokio::sync::mpsc::channel(STREAM_BOUND);
+                runtime().spawn_blocking(move || {
+                    for row in rows {
+                        if sender.blocking_send(Ok(row)).is_err() {
+                            // the stream was dropped
+                            break;
+                        }
+                    }
+                });
+                Box::pin(ReceiverStream::new(receiver))
+            }
+            TableLakeReader::Async(rows) => rows,
         }
     }
 }
+
+fn entry(row: TableRow) -> Entry {
+    let TableRow {
+        tokenized,
+        tableid,
+        colid,
+        rowid,
+    } = row;
+    let location = TableLocation {
+        tableid,
+        colid,
+        rowid,
+    };
+    (tokenized, location)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::sync_channel;
+
+    fn rows() -> Vec<TableRow> {
+        (0..5000u32)
+            .map(|i| TableRow {
+                tokenized: format!("key-{}", i / 4),
+                tableid: i % 3,
+                colid: 0,
+                rowid: i,
+            })
+            .collect()
+    }
+
+    fn read(reader: TableLakeReader) -> Vec<Entry> {
+        // smaller than the rows, so reading has to wait for the receiver
+        let (sender, receiver) = sync_channel(8);
+        std::thread::spawn(move || reader.read(sender));
+        receiver.into_iter().collect()
+    }
+
+    #[test]
+    fn sync_and_async_sources() {
+        let expected = rows().into_iter().map(entry).collect::<Vec<_>>();
+
+        let sync = TableLakeReader::from_rows(rows().into_iter());
+        assert_eq!(read(sync), expected);
+
+        let stream = tokio_stream::iter(rows().into_iter().map(Ok));
+        assert_eq!(read(TableLakeReader::from_stream(stream)), expected);
+
+        // an iterator read as a stream and back
+        let sync = TableLakeReader::from_rows(rows().into_iter());
+        let stream = TableLakeReader::Async(sync.into_stream());
+        assert_eq!(read(stream), expected);
+    }
+}
@@ -1,68 +1,420 @@
-use crate::{table_lake::TableLocation, Entry, TableLakeReader};
-use rand::*;
+use super::{runtime, TableLakeReader, TableRow, STREAM_BOUND};
+use anyhow::{bail, Context, Result};
+use bintable2::{SampleMode, Sampling};
 use sqlx::{Pool, Postgres};
-use std::sync::mpsc::SyncSender;
-use tokio_stream::StreamExt;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc::{Receiver, Sender};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::{Stream, StreamExt};
 
+/// Rows fetched per query. Batches continue after the last row of the previous batch (keyset pagination),
+/// instead of using an offset, which would need to skip all rows read before.
+/// Without an index on `(tokenized, tableid, colid, rowid)` every batch sorts the remaining table.
+const BATCH_SIZE: i64 = 50_000;
+
+/// Seeded hash of the sampled part of a row, uniformly distributed in 0..2^32.
+/// Unlike `TABLESAMPLE`, the sample does not depend on the physical layout of the table,
+/// so it stays the same across batches and resumed runs.
+fn sample_hash(sampling: &Sampling) -> String {
+    let value = match sampling.mode {
+        SampleMode::Row => "tokenized || ' ' || tableid || ' ' || colid || ' ' || rowid",
+        SampleMode::Key => "tokenized",
+        SampleMode::Table => "tableid::text",
+    };
+
+    format!(
+        "(hashtextextended({value}, {}) & 4294967295)",
+        sampling.seed as i64
+    )
+}
+
+/// Longest identifier postgres accepts (NAMEDATALEN - 1)
+const MAX_IDENTIFIER_LEN: usize = 63;
+
+type Row = (String, i32, i32, i32);
+
+/// Sent by the reading task. The markers are passed along with the rows,
+/// so the checkpoint is only written once the consumer took every row before it.
+enum Item {
+    Row(TableRow),
+    /// end of a batch, continuing after `Cursor`
+    Batch(Cursor),
+    /// the whole table has been read
+    Done,
+}
+
+/// Reads all rows of a table in the order of their keys, in batches.
 pub struct SqlxCollection {
     pool: Pool<Postgres>,
+    /// quoted table name
     table: String,
-    factor: Option<f32>,
+    sampling: Option<Sampling>,
+    checkpoint: Option<PathBuf>,
 }
 
 impl SqlxCollection {
-    pub fn new(pool: Pool<Postgres>, table: impl Into<String>, factor: Option<f32>) -> Self {
-        SqlxCollection {
+    pub fn new(pool: Pool<Postgres>, table: &str, sampling: Option<Sampling>) -> Result<Self> {
+        Ok(SqlxCollection {
             pool,
-            table: table.into(),
-            factor,
-        }
+            table: quote_identifier(table)?,
+            sampling,
+            checkpoint: None,
+        })
     }
-}
 
-impl TableLakeReader for SqlxCollection {
-    fn read(&mut self, ch: SyncSender<Entry>) {
+    /// Stores the position after every consumed batch in `path`, and resumes from it if it exists.
+    /// The file is removed once the whole table has been consumed.
+    pub fn with_checkpoint(mut self, path: impl Into<PathBuf>) -> Self {
+        self.checkpoint = Some(path.into());
+        self
+    }
+
+    async fn batch(&self, cursor: Option<&Cursor>) -> sqlx::Result<Vec<Row>> {
+        let mut conditions = Vec::new();
+        if cursor.is_some() {
+            conditions.push("(tokenized, tableid, colid, rowid) > ($1, $2, $3, $4)".to_string());
+        }
+        if let Some(sampling) = &self.sampling {
+            let param = if cursor.is_some() { 5 } else { 1 };
+            conditions.push(format!("{} < ${param}", sample_hash(sampling)));
+        }
+
+        let filter = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+
         let query = format!(
             "
             SELECT tokenized, tableid, colid, rowid
             FROM {}
-            ORDER BY tokenized
+            {filter}
+            ORDER BY tokenized, tableid, colid, rowid
+            LIMIT {BATCH_SIZE}
         ",
             self.table
         );
 
-        let mut rng = thread_rng();
+        let mut query = sqlx::query_as::<_, Row>(&query);
+        if let Some(cursor) = cursor {
+            query = query
+                .bind(&cursor.tokenized)
+                .bind(cursor.tableid)
+                .bind(cursor.colid)
+                .bind(cursor.rowid);
+        }
+        if let Some(sampling) = &self.sampling {
+            query = query.bind(sample_threshold(sampling.factor));
+        }
 
-        let coroutine = async {
-            let query = sqlx::query_as::<_, (String, i32, i32, i32)>(&query);
-            let mut rows = query.fetch(&self.pool);
+        query.fetch_all(&self.pool).await
+    }
+}
+
+impl SqlxCollection {
+    /// Reads the batches in a task on the shared runtime,
+    /// which pauses while `STREAM_BOUND` rows are not taken from the stream.
+    pub fn into_reader(self) -> TableLakeReader {
+        let (sender, receiver) = tokio::sync::mpsc::channel(STREAM_BOUND);
+        let checkpoint = self.checkpoint.clone();
+        runtime().spawn(async move {
+            if let Err(e) = self.read(&sender).await {
+                // the receiver is gone, if sending fails
+                let _ = sender.send(Err(e)).await;
+            }
+        });
+
+        TableLakeReader::from_stream(rows(receiver, checkpoint))
+    }
 
-            eprintln!("start reading");
-            while let Some(row) = rows.try_next().await.expect("read row from sqlx") {
-                if let Some(f) = self.factor {
-                    let random_number = rng.gen::<f32>();
-                    if random_number < f {
-                        continue;
-                    }
+    async fn read(&self, sender: &Sender<Result<Item>>) -> Result<()> {
+        let mut cursor = match &self.checkpoint {
+            Some(path) => Cursor::load(path)?,
+            None => None,
+        };
+
+        if let Some(cursor) = &cursor {
+            eprintln!("resuming after {}", cursor.tokenized);
+        }
+
+        eprintln!("start reading");
+        loop {
+            let batch = self
+                .batch(cursor.as_ref())
+                .await
+                .context("read batch from sqlx")?;
+
+            let complete = (batch.len() as i64) < BATCH_SIZE;
+            let last = match batch.last() {
+                Some(last) => Cursor::from(last),
+                None => break,
+            };
+
+            let items = batch
+                .into_iter()
+                .map(|row| Item::Row(table_row(row)))
+                .chain([Item::Batch(last.clone())]);
+            for item in items {
+                if sender.send(Ok(item)).await.is_err() {
+                    // the stream was dropped
+                    return Ok(());
                 }
+            }
+            cursor = Some(last);
 
-                let (tokenized, tableid, colid, rowid) = row;
-                ch.send((
-                    tokenized,
-                    TableLocation {
-                        tableid: tableid as u32,
-                        colid: colid as u32,
-                        rowid: rowid as u32,
-                    },
-                ))
-                .expect("send to channel");
+            if complete {
+                break;
             }
+        }
+
+        // the stream may have been dropped in the meantime
+        let _ = sender.send(Ok(Item::Done)).await;
+        Ok(())
+    }
+}
+
+/// Rows sent by the reading task. Markers are only polled after the consumer took the rows before them.
+fn rows(
+    receiver: Receiver<Result<Item>>,
+    checkpoint: Option<PathBuf>,
+) -> impl Stream<Item = Result<TableRow>> {
+    ReceiverStream::new(receiver).filter_map(move |item| match item {
+        Ok(Item::Row(row)) => Some(Ok(row)),
+        Ok(Item::Batch(cursor)) => consumed(checkpoint.as_deref(), Some(cursor)).err().map(Err),
+        Ok(Item::Done) => consumed(checkpoint.as_deref(), None).err().map(Err),
+        Err(e) => Some(Err(e)),
+    })
+}
+
+/// Stores `cursor` once the rows before it have been consumed.
+/// Without a cursor the whole table has been consumed, so the checkpoint is removed.
+fn consumed(checkpoint: Option<&Path>, cursor: Option<Cursor>) -> Result<()> {
+    let Some(path) = checkpoint else {
+        return Ok(());
+    };
+
+    match cursor {
+        Some(cursor) => cursor.store(path).context("write checkpoint")?,
+        None => {
+            if path.exists() {
+                std::fs::remove_file(path).context("remove checkpoint")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn table_row((tokenized, tableid, colid, rowid): Row) -> TableRow {
+    TableRow {
+        tokenized,
+        tableid: tableid as u32,
+        colid: colid as u32,
+        rowid: rowid as u32,
+    }
+}
+
+/// Rows with a hash below the threshold are part of the sample.
+fn sample_threshold(factor: f32) -> i64 {
+    (factor.clamp(0.0, 1.0) as f64 * (1u64 << 32) as f64) as i64
+}
+
+/// Quotes a (schema qualified) table name, so it can safely be used inside a query.
+/// Quoted names are case sensitive.
+pub fn quote_identifier(name: &str) -> Result<String> {
+    let parts = name
+        .split('.')
+        .map(|part| {
+            if part.is_empty() {
+                bail!("empty identifier in table name {name:?}");
+            }
+            if part.len() > MAX_IDENTIFIER_LEN {
+                bail!("identifier {part:?} is longer than {MAX_IDENTIFIER_LEN} bytes");
+            }
+            if part.contains('\0') {
+                bail!("identifier {part:?} contains a nul byte");
+            }
+
+            Ok(format!("\"{}\"", part.replace('"', "\"\"")))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(parts.join("."))
+}
+
+/// Position after the last row read
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cursor {
+    tokenized: String,
+    tableid: i32,
+    colid: i32,
+    rowid: i32,
+}
+
+impl From<&Row> for Cursor {
+    fn from((tokenized, tableid, colid, rowid): &Row) -> Self {
+        Cursor {
+            tokenized: tokenized.clone(),
+            tableid: *tableid,
+            colid: *colid,
+            rowid: *rowid,
+        }
+    }
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        /*
+            Scheme: tableid\tcolid\trowid\ttokenized
+            the key comes last, as it may contain tabs and newlines itself.
+        */
+        format!(
+            "{}\t{}\t{}\t{}",
+            self.tableid, self.colid, self.rowid, self.tokenized
+        )
+    }
+
+    fn decode(s: &str) -> Result<Self> {
+        let mut parts = s.splitn(4, '\t');
+        let mut next = || parts.next().context("checkpoint to have 4 fields");
+
+        let tableid = next()?.parse()?;
+        let colid = next()?.parse()?;
+        let rowid = next()?.parse()?;
+        let tokenized = next()?.to_string();
+
+        Ok(Cursor {
+            tokenized,
+            tableid,
+            colid,
+            rowid,
+        })
+    }
+
+    fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let s = std::fs::read_to_string(path)?;
+        Cursor::decode(&s).map(Some)
+    }
+
+    /// Replaces the checkpoint atomically, so a crash never leaves a partial one behind.
+    fn store(&self, path: &Path) -> Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+
+        std::fs::write(&tmp, self.encode())?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers_are_quoted() {
+        assert_eq!(
+            quote_identifier("main_tokenized").unwrap(),
+            "\"main_tokenized\""
+        );
+        assert_eq!(
+            quote_identifier("public.Tables").unwrap(),
+            "\"public\".\"Tables\""
+        );
+        assert_eq!(
+            quote_identifier("x\"; DROP TABLE y; --").unwrap(),
+            "\"x\"\"; DROP TABLE y; --\""
+        );
+
+        assert!(quote_identifier("").is_err());
+        assert!(quote_identifier("public.").is_err());
+        assert!(quote_identifier(&"a".repeat(64)).is_err());
+    }
+
+    #[test]
+    fn checkpoint_roundtrip() {
+        let cursor = Cursor {
+            tokenized: "with\ttab and\nnewline".to_string(),
+            tableid: 3,
+            colid: -1,
+            rowid: 42,
         };
 
-        tokio::runtime::Builder::new_multi_thread()
-            .enable_all()
-            .build()
-            .unwrap()
-            .block_on(coroutine);
+        let path =
+            std::env::temp_dir().join(format!("ii-measure-{}.checkpoint", std::process::id()));
+        cursor.store(&path).unwrap();
+        assert_eq!(Cursor::load(&path).unwrap(), Some(cursor));
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(Cursor::load(&path).unwrap(), None);
+    }
+
+    #[test]
+    fn checkpoint_after_consumption() {
+        let path = std::env::temp_dir().join(format!(
+            "ii-measure-{}-consumed.checkpoint",
+            std::process::id()
+        ));
+        let row = |rowid| TableRow {
+            tokenized: "key".to_string(),
+            tableid: 0,
+            colid: 0,
+            rowid,
+        };
+        let cursor = Cursor {
+            tokenized: "key".to_string(),
+            tableid: 0,
+            colid: 0,
+            rowid: 1,
+        };
+
+        let (sender, receiver) = tokio::sync::mpsc::channel(8);
+        for item in [
+            Item::Row(row(0)),
+            Item::Row(row(1)),
+            Item::Batch(cursor.clone()),
+            Item::Row(row(2)),
+            Item::Done,
+        ] {
+            sender.try_send(Ok(item)).unwrap();
+        }
+        drop(sender);
+
+        runtime().block_on(async {
+            let mut rows = Box::pin(rows(receiver, Some(path.clone())));
+
+            // the whole batch is queued, but not consumed yet
+            assert_eq!(rows.next().await.unwrap().unwrap().rowid, 0);
+            assert_eq!(rows.next().await.unwrap().unwrap().rowid, 1);
+            assert_eq!(Cursor::load(&path).unwrap(), None);
+
+            assert_eq!(rows.next().await.unwrap().unwrap().rowid, 2);
+            assert_eq!(Cursor::load(&path).unwrap(), Some(cursor));
+
+            assert!(rows.next().await.is_none());
+            assert!(!path.exists());
+        });
+    }
+
+    #[test]
+    fn sample_hash_per_mode() {
+        let hash = |mode| sample_hash(&Sampling::new(mode, 0.5, u64::MAX));
+        assert_eq!(
+            hash(SampleMode::Key),
+            "(hashtextextended(tokenized, -1) & 4294967295)"
+        );
+        assert!(hash(SampleMode::Table).contains("tableid::text"));
+        assert!(hash(SampleMode::Row).contains("rowid"));
+    }
+
+    #[test]
+    fn sampling_keeps_the_factor() {
+        assert_eq!(sample_threshold(0.0), 0);
+        assert_eq!(sample_threshold(0.25), 1 << 30);
+        assert_eq!(sample_threshold(1.0), 1 << 32);
     }
 }
@@ -7,37 +7,84 @@ pub use random_keys::RandomKeys;
 
 use crate::db::sqlx_pool;
 use crate::table_lake::*;
-use bintable2::{BinTable, BinTableSampler};
+use bintable2::{AsyncBinTable, BinTableSampler, Metadata, Sampling};
+use tokio_stream::StreamExt;
 
 const CHANNEL_BOUND: usize = 32;
 
+/// Reads the bintable as an `AsyncBinTable` on the shared runtime, damage found while reading is a panic.
 pub fn indices_from_bintable(
     bintable: &str,
-    factor: Option<f32>,
+    sampling: Option<Sampling>,
 ) -> Receiver<(String, TableLocation)> {
     let (sender, receiver) = sync_channel(CHANNEL_BOUND);
 
-    if let Some(factor) = factor {
-        let bintable = bintable.to_string();
-        spawn(move || {
-            let mut bintable =
-                BinTableSampler::open(&bintable, factor).expect("open bintable sampler");
-            bintable.read(sender)
+    let path = match sampling {
+        Some(sampling) => {
+            let path = BinTableSampler::input(bintable, sampling).expect("find sample");
+            eprintln!("reading {path}");
+            path
+        }
+        None => bintable.to_string(),
+    };
+    let rows = runtime()
+        .block_on(AsyncBinTable::open(&path))
+        .expect("open bintable");
+
+    let rows = rows
+        .map(|row| row.map_err(anyhow::Error::from))
+        .filter(move |row| match (row, sampling) {
+            (Ok(row), Some(sampling)) => {
+                sampling.keeps(&row.tokenized, row.tableid, row.colid, row.rowid)
+            }
+            _ => true,
         });
-    } else {
-        let mut bintable = BinTable::open(bintable).expect("open bintable");
-        spawn(move || bintable.read(sender));
-    }
+    let reader = TableLakeReader::from_stream(rows);
+    spawn(move || reader.read(sender));
 
     receiver
 }
 
-pub fn indices_sqlx(table: &str, factor: Option<f32>) -> Receiver<(String, TableLocation)> {
+/// Names of the tables and columns of a bintable, stored beside it by `ingest-tables` or `bintable-info --metadata`.
+pub fn load_metadata(bintable: &str) -> Option<Metadata> {
+    let metadata = Metadata::open(bintable).expect("read metadata");
+    if let Some(metadata) = &metadata {
+        let columns = metadata
+            .tables
+            .iter()
+            .map(|t| t.columns.len())
+            .sum::<usize>();
+        eprintln!(
+            "metadata of {} tables with {columns} columns",
+            metadata.tables.len()
+        );
+    }
+
+    metadata
+}
+
+pub fn indices_sqlx(
+    table: &str,
+    sampling: Option<Sampling>,
+    url: Option<String>,
+    checkpoint: Option<String>,
+) -> Receiver<(String, TableLocation)> {
     let (sender, receiver) = sync_channel(CHANNEL_BOUND);
 
-    let pool = sqlx_pool();
-    let mut database = SqlxCollection::new(pool, table, factor);
+    let pool = sqlx_pool(url);
+    let mut database = SqlxCollection::new(pool, table, sampling).expect("valid table name");
+    if let Some(checkpoint) = checkpoint {
+        database = database.with_checkpoint(checkpoint);
+    }
 
-    spawn(move || database.read(sender));
+    let reader = database.into_reader();
+    spawn(move || reader.read(sender));
     receiver
 }
+
+/// At most `count` items, evenly spaced over `items`. Unlike a random sample, the same items are chosen in every run,
+/// so codes trained on them and the sizes measured do not change between runs.
+pub fn evenly_spaced<T>(items: &[T], count: usize) -> impl Iterator<Item = &T> {
+    let step = items.len().div_ceil(count.max(1)).max(1);
+    items.iter().step_by(step)
+}
@@ -20,6 +20,9 @@ pub trait RandomKeys {
 
         keys
     }
+
+    /// Frees what the index only keeps to draw random keys, so it is not counted in the size of the index.
+    fn drop_random_keys(&mut self) {}
 }
 
 impl<T> RandomKeys for &[(String, T)] {
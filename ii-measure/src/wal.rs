@@ -0,0 +1,567 @@
+//! Write-ahead log and snapshots, which make updates of an `LsmIndex` survive crashes.
+//!
+//! Every insert or delete of a batch is appended to the log as a record, before it is applied to the index:
+//!
+//! ```text
+//! <length: u32><crc32c: u32><lsn: u64><payload: length bytes>
+//! ```
+//!
+//! All integers are little endian. The checksum covers the log sequence number (lsn) and the payload.
+//! Records are written and fsync'd in groups, an operation is durable once its group has been synced.
+//! A crash can leave a torn record at the end of the log. Recovery reads all records up to the first
+//! incomplete or corrupt one, cuts the log after them and replays them over the last snapshot.
+//!
+//! A checkpoint writes all posting lists of the index into a snapshot, a sorted bintable named after the lsn of
+//! the last operation it contains. Afterwards the log is truncated. Records, which are already part of the snapshot,
+//! are skipped by the recovery, so a crash between writing the snapshot and truncating the log is harmless.
+
+use crate::lsm::{LsmIndex, TieredPolicy};
+use crate::{Entry, TableLocation};
+use anyhow::{ensure, Context, Result};
+use bintable2::{BinTable, BinTableWriter, TableRow};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// length, checksum and lsn in front of the payload
+const RECORD_HEADER: usize = 4 + 4 + 8;
+
+const INSERT: u8 = 0;
+const DELETE_TABLES: u8 = 1;
+
+/// An update of the index, stored as one record of the log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    /// Locations of keys to insert
+    Insert(Vec<Entry>),
+    /// Table ids of deleted tables
+    DeleteTables(Vec<u32>),
+}
+
+impl Operation {
+    fn encode(&self) -> Vec<u8> {
+        let mut payload = Vec::new();
+        match self {
+            Operation::Insert(entries) => {
+                payload.push(INSERT);
+                payload.extend((entries.len() as u32).to_le_bytes());
+                for (key, location) in entries {
+                    payload.extend((key.len() as u32).to_le_bytes());
+                    payload.extend(key.as_bytes());
+                    for n in [location.tableid, location.colid, location.rowid] {
+                        payload.extend(n.to_le_bytes());
+                    }
+                }
+            }
+            Operation::DeleteTables(tableids) => {
+                payload.push(DELETE_TABLES);
+                payload.extend((tableids.len() as u32).to_le_bytes());
+                for tableid in tableids {
+                    payload.extend(tableid.to_le_bytes());
+                }
+            }
+        }
+        payload
+    }
+
+    /// Decodes a payload, `None` if it is malformed
+    fn decode(payload: &[u8]) -> Option<Self> {
+        let (&kind, data) = payload.split_first()?;
+        let mut fields = Fields(data);
+
+        let count = fields.u32()? as usize;
+        let operation = match kind {
+            INSERT => {
+                let mut entries = Vec::with_capacity(count.min(data.len()));
+                for _ in 0..count {
+                    let len = fields.u32()? as usize;
+                    let key = String::from_utf8(fields.take(len)?.to_vec()).ok()?;
+                    let location = TableLocation {
+                        tableid: fields.u32()?,
+                        colid: fields.u32()?,
+                        rowid: fields.u32()?,
+                    };
+                    entries.push((key, location));
+                }
+                Operation::Insert(entries)
+            }
+            DELETE_TABLES => {
+                Operation::DeleteTables((0..count).map(|_| fields.u32()).collect::<Option<_>>()?)
+            }
+            _ => return None,
+        };
+
+        fields.0.is_empty().then_some(operation)
+    }
+}
+
+/// Fields of a payload not decoded yet
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Fields<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (taken, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Some(taken)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    }
+}
+
+/// Reads the records of a log up to the first incomplete or corrupt one.
+/// Returns them along with the length of the valid part of the log.
+fn read_records(data: &[u8]) -> (Vec<(u64, Operation)>, usize) {
+    let mut records = Vec::new();
+    let mut offset = 0;
+
+    while let Some(header) = data.get(offset..offset + RECORD_HEADER) {
+        let len = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+        let crc = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        let Some(record) = data.get(offset + 8..offset + RECORD_HEADER + len) else {
+            break;
+        };
+        if crc32c::crc32c(record) != crc {
+            break;
+        }
+
+        let lsn = u64::from_le_bytes(record[0..8].try_into().unwrap());
+        let in_order = records.last().is_none_or(|(last, _)| lsn > *last);
+        match Operation::decode(&record[8..]) {
+            Some(operation) if in_order => records.push((lsn, operation)),
+            _ => break,
+        }
+        offset += RECORD_HEADER + len;
+    }
+
+    (records, offset)
+}
+
+/// Append only log of operations, synced every `group` records.
+pub struct Wal {
+    file: File,
+    /// records not written to the file yet
+    pending: Vec<u8>,
+    pending_records: usize,
+    group: usize,
+    next_lsn: u64,
+}
+
+impl Wal {
+    /// Opens or creates the log at `path` and returns the operations logged after the lsn `after`.
+    /// A torn or corrupt tail left by a crash is cut off. New operations continue after the last lsn
+    /// in the log, but at least after `after`.
+    pub fn open(path: &Path, after: u64, group: usize) -> Result<(Self, Vec<(u64, Operation)>)> {
+        ensure!(group > 0, "at least one record per group");
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .with_context(|| format!("open log {}", path.display()))?;
+
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        let (records, valid) = read_records(&data);
+        if valid < data.len() {
+            eprintln!(
+                "cutting {} bytes of a torn record off {}",
+                data.len() - valid,
+                path.display()
+            );
+            file.set_len(valid as u64)?;
+            file.sync_all()?;
+        }
+
+        let last = records.last().map_or(0, |(lsn, _)| *lsn);
+        let wal = Wal {
+            file,
+            pending: Vec::new(),
+            pending_records: 0,
+            group,
+            next_lsn: last.max(after) + 1,
+        };
+        let records = records
+            .into_iter()
+            .filter(|(lsn, _)| *lsn > after)
+            .collect();
+
+        Ok((wal, records))
+    }
+
+    /// Appends an operation and returns its lsn. Completes a group, syncs it.
+    pub fn append(&mut self, operation: &Operation) -> Result<u64> {
+        let lsn = self.next_lsn;
+        self.next_lsn += 1;
+
+        let mut record = lsn.to_le_bytes().to_vec();
+        record.extend(operation.encode());
+        let len = u32::try_from(record.len() - 8).context("record of at most 4GB")?;
+
+        self.pending.extend(len.to_le_bytes());
+        self.pending.extend(crc32c::crc32c(&record).to_le_bytes());
+        self.pending.extend(record);
+        self.pending_records += 1;
+
+        if self.pending_records >= self.group {
+            self.sync()?;
+        }
+        Ok(lsn)
+    }
+
+    /// Writes and fsyncs all pending records, so all operations appended so far are durable.
+    pub fn sync(&mut self) -> Result<()> {
+        if self.pending_records == 0 {
+            return Ok(());
+        }
+
+        self.file.write_all(&self.pending)?;
+        self.file.sync_data()?;
+        self.pending.clear();
+        self.pending_records = 0;
+        Ok(())
+    }
+
+    /// Removes all records, once they are part of a snapshot. Lsns keep counting up.
+    pub fn truncate(&mut self) -> Result<()> {
+        self.sync()?;
+        self.file.set_len(0)?;
+        self.file.sync_all()?;
+        Ok(())
+    }
+
+    /// Lsn of the last operation appended, 0 if there is none
+    pub fn last_lsn(&self) -> u64 {
+        self.next_lsn - 1
+    }
+}
+
+impl Drop for Wal {
+    fn drop(&mut self) {
+        if let Err(e) = self.sync() {
+            eprintln!("failed to sync the log: {e}");
+        }
+    }
+}
+
+const LOG: &str = "wal.log";
+
+fn snapshot_name(lsn: u64) -> String {
+    format!("snapshot-{lsn:020}.bin")
+}
+
+/// Lsns of all snapshots in `dir`, in ascending order
+fn snapshots(dir: &Path) -> Result<Vec<u64>> {
+    let mut lsns = Vec::new();
+    for file in std::fs::read_dir(dir)? {
+        let name = file?.file_name();
+        let lsn = name
+            .to_str()
+            .and_then(|n| n.strip_prefix("snapshot-"))
+            .and_then(|n| n.strip_suffix(".bin"))
+            .and_then(|n| n.parse::<u64>().ok());
+        lsns.extend(lsn);
+    }
+    lsns.sort_unstable();
+    Ok(lsns)
+}
+
+fn apply<const B: usize>(index: &mut LsmIndex<B>, operation: Operation) {
+    match operation {
+        Operation::Insert(entries) => {
+            for (key, location) in entries {
+                index.insert(key, location);
+            }
+        }
+        Operation::DeleteTables(tableids) => {
+            for tableid in tableids {
+                index.delete_table(tableid);
+            }
+        }
+    }
+}
+
+/// `LsmIndex` whose updates are logged, stored in a directory with the log and the snapshots.
+pub struct DurableIndex<const B: usize = 16> {
+    dir: PathBuf,
+    index: LsmIndex<B>,
+    wal: Wal,
+}
+
+impl<const B: usize> DurableIndex<B> {
+    /// Opens or creates the index in `dir`: loads the latest snapshot and replays the log over it.
+    /// The log is synced every `group` operations.
+    pub fn open(dir: impl Into<PathBuf>, policy: TieredPolicy, group: usize) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("create index directory {}", dir.display()))?;
+
+        let mut index = LsmIndex::new(policy);
+        let snapshot = snapshots(&dir)?.pop().unwrap_or(0);
+        if snapshot > 0 {
+            let path = dir.join(snapshot_name(snapshot));
+            let rows = BinTable::open(path.to_str().context("utf-8 path")?)
+                .with_context(|| format!("open snapshot {}", path.display()))?;
+            for row in rows {
+                let location = TableLocation {
+                    tableid: row.tableid,
+                    colid: row.colid,
+                    rowid: row.rowid,
+                };
+                index.insert(row.tokenized, location);
+            }
+        }
+
+        let (wal, operations) = Wal::open(&dir.join(LOG), snapshot, group)?;
+        eprintln!(
+            "recovered snapshot {snapshot} and {} logged operations",
+            operations.len()
+        );
+        for (_, operation) in operations {
+            apply(&mut index, operation);
+        }
+
+        Ok(DurableIndex { dir, index, wal })
+    }
+
+    /// Logs and inserts the locations of keys, returns the lsn of the operation.
+    pub fn insert(&mut self, entries: Vec<Entry>) -> Result<u64> {
+        self.update(Operation::Insert(entries))
+    }
+
+    /// Logs and deletes tables, returns the lsn of the operation.
+    pub fn delete_tables(&mut self, tableids: Vec<u32>) -> Result<u64> {
+        self.update(Operation::DeleteTables(tableids))
+    }
+
+    fn update(&mut self, operation: Operation) -> Result<u64> {
+        let lsn = self.wal.append(&operation)?;
+        apply(&mut self.index, operation);
+        Ok(lsn)
+    }
+
+    /// Makes all operations durable, without waiting for their group to complete.
+    pub fn sync(&mut self) -> Result<()> {
+        self.wal.sync()
+    }
+
+    /// Writes a snapshot of the index, removes the older snapshots and truncates the log.
+    pub fn checkpoint(&mut self) -> Result<()> {
+        self.wal.sync()?;
+        let lsn = self.wal.last_lsn();
+        if lsn == 0 {
+            return Ok(());
+        }
+
+        // the snapshot only appears under its name once it is complete
+        let tmp = self.dir.join("snapshot.tmp");
+        let mut out = BufWriter::with_capacity(1024 * 1024 /* 1MB */, File::create(&tmp)?);
+        let mut snapshot = BinTableWriter::new(&mut out).sorted();
+        for (key, list) in self.index.lists() {
+            for location in list {
+                let row = TableRow {
+                    tokenized: key.clone(),
+                    tableid: location.tableid,
+                    colid: location.colid,
+                    rowid: location.rowid,
+                };
+                snapshot.write(&row)?;
+            }
+        }
+        snapshot.finish()?;
+        out.into_inner()?.sync_all()?;
+        std::fs::rename(&tmp, self.dir.join(snapshot_name(lsn)))?;
+        File::open(&self.dir)?.sync_all()?;
+
+        for older in snapshots(&self.dir)?.into_iter().filter(|s| *s < lsn) {
+            std::fs::remove_file(self.dir.join(snapshot_name(older)))?;
+        }
+        self.wal.truncate()
+    }
+
+    pub fn index(&self) -> &LsmIndex<B> {
+        &self.index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(tableid: u32, rowid: u32) -> TableLocation {
+        TableLocation {
+            tableid,
+            colid: 0,
+            rowid,
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("ii-measure-wal-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn operations() -> Vec<Operation> {
+        (0..6)
+            .map(|i| match i % 3 {
+                2 => Operation::DeleteTables(vec![i - 2]),
+                _ => Operation::Insert(
+                    (0..i + 1)
+                        .map(|k| (format!("key-{k}"), location(i, k)))
+                        .collect(),
+                ),
+            })
+            .collect()
+    }
+
+    /// Posting lists of the index, to compare indices
+    fn lists(index: &DurableIndex) -> Vec<(String, Vec<TableLocation>)> {
+        index.index().lists().collect()
+    }
+
+    #[test]
+    fn encoded_operations() {
+        for operation in operations() {
+            assert_eq!(Operation::decode(&operation.encode()), Some(operation));
+        }
+
+        let mut encoded = operations()[1].encode();
+        encoded.push(0);
+        assert_eq!(Operation::decode(&encoded), None);
+        assert_eq!(Operation::decode(&encoded[..encoded.len() - 3]), None);
+    }
+
+    #[test]
+    fn torn_log() {
+        let dir = temp_dir("torn");
+        let path = dir.join(LOG);
+        let (mut wal, _) = Wal::open(&path, 0, 1).unwrap();
+        let mut ends = vec![0];
+        for operation in operations() {
+            wal.append(&operation).unwrap();
+            ends.push(std::fs::metadata(&path).unwrap().len() as usize);
+        }
+        drop(wal);
+        let log = std::fs::read(&path).unwrap();
+
+        // a crash at every byte keeps all records written completely
+        for offset in 0..=log.len() {
+            std::fs::write(&path, &log[..offset]).unwrap();
+            let (mut wal, records) = Wal::open(&path, 0, 1).unwrap();
+
+            let complete = ends.iter().rposition(|end| *end <= offset).unwrap();
+            assert_eq!(records.len(), complete, "crash at byte {offset}");
+            for (i, (lsn, operation)) in records.into_iter().enumerate() {
+                assert_eq!(lsn, i as u64 + 1);
+                assert_eq!(operation, operations()[i]);
+            }
+            assert_eq!(
+                std::fs::metadata(&path).unwrap().len() as usize,
+                ends[complete]
+            );
+
+            // the log continues after the last complete record
+            let lsn = wal.append(&Operation::DeleteTables(vec![7])).unwrap();
+            assert_eq!(lsn, complete as u64 + 1);
+            drop(wal);
+            let (_, records) = Wal::open(&path, 0, 1).unwrap();
+            assert_eq!(records.len(), complete + 1);
+        }
+    }
+
+    #[test]
+    fn corrupt_record() {
+        let dir = temp_dir("corrupt");
+        let path = dir.join(LOG);
+        let (mut wal, _) = Wal::open(&path, 0, 1).unwrap();
+        for operation in operations() {
+            wal.append(&operation).unwrap();
+        }
+        drop(wal);
+
+        // flip a bit in the key of the second record
+        let mut log = std::fs::read(&path).unwrap();
+        let second = RECORD_HEADER + u32::from_le_bytes(log[0..4].try_into().unwrap()) as usize;
+        log[second + RECORD_HEADER + 10] ^= 1;
+        std::fs::write(&path, &log).unwrap();
+
+        let (_, records) = Wal::open(&path, 0, 1).unwrap();
+        assert_eq!(records, [(1, operations()[0].clone())]);
+        assert_eq!(std::fs::metadata(&path).unwrap().len() as usize, second);
+    }
+
+    #[test]
+    fn group_sync() {
+        let dir = temp_dir("group");
+        let path = dir.join(LOG);
+        let (mut wal, _) = Wal::open(&path, 0, 3).unwrap();
+        let operations = operations();
+
+        wal.append(&operations[0]).unwrap();
+        wal.append(&operations[1]).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+        wal.append(&operations[2]).unwrap();
+        assert_eq!(Wal::open(&path, 0, 1).unwrap().1.len(), 3);
+
+        wal.append(&operations[3]).unwrap();
+        wal.sync().unwrap();
+        assert_eq!(Wal::open(&path, 0, 1).unwrap().1.len(), 4);
+        // records up to an lsn are part of a snapshot
+        assert_eq!(Wal::open(&path, 2, 1).unwrap().1.len(), 2);
+    }
+
+    fn open(dir: &Path) -> DurableIndex {
+        DurableIndex::open(dir, TieredPolicy::new(8, 2), 2).unwrap()
+    }
+
+    #[test]
+    fn recovery() {
+        let dir = temp_dir("recovery");
+        let mut index = open(&dir);
+        let mut operations = operations().into_iter();
+        for operation in operations.by_ref().take(3) {
+            index.update(operation).unwrap();
+        }
+        index.checkpoint().unwrap();
+        assert_eq!(std::fs::metadata(dir.join(LOG)).unwrap().len(), 0);
+        let snapshot = lists(&index);
+
+        for operation in operations {
+            index.update(operation).unwrap();
+        }
+        index.sync().unwrap();
+        let expected = lists(&index);
+        let log = std::fs::read(dir.join(LOG)).unwrap();
+        drop(index);
+
+        let index = open(&dir);
+        assert_eq!(lists(&index), expected);
+        assert_eq!(index.wal.last_lsn(), 6);
+        drop(index);
+
+        // an empty log only recovers the snapshot
+        std::fs::write(dir.join(LOG), []).unwrap();
+        let mut index = open(&dir);
+        assert_eq!(lists(&index), snapshot);
+        assert_eq!(index.insert(Vec::new()).unwrap(), 4);
+        drop(index);
+
+        // crashed after writing a snapshot, before truncating the log
+        std::fs::write(dir.join(LOG), &log).unwrap();
+        let mut index = open(&dir);
+        index.checkpoint().unwrap();
+        std::fs::write(dir.join(LOG), &log).unwrap();
+        let index = open(&dir);
+        assert_eq!(lists(&index), expected);
+        assert_eq!(snapshots(&dir).unwrap(), [6]);
+    }
+}
@@ -0,0 +1,118 @@
+mod source;
+
+use anyhow::{bail, Context, Result};
+use bintable2::{
+    BinTableWriter, Header, Metadata, MetadataBuilder, RunSorter, TableRow, Tokenizer,
+};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+#[structopt(
+    name = "ingest-tables",
+    about = "Tokenize a directory of csv, tsv, json lines and parquet tables into a sorted bintable."
+)]
+struct Config {
+    /// Directory containing the tables, subdirectories are included
+    #[structopt()]
+    input: PathBuf,
+
+    /// The bintable to write, names and statistics of the tables are written to <output>.meta
+    #[structopt()]
+    output: String,
+
+    /// Comma separated steps to turn cells into keys, recorded in the header of the bintable:
+    /// nfkc lowercase words whitespace numbers punctuation max-length=<n>
+    #[structopt(short, long, default_value = "none")]
+    tokenizer: Tokenizer,
+
+    /// Rows sorted in memory at once, more rows are sorted in runs written beside the output and merged.
+    /// A row takes about 40 bytes plus its key.
+    #[structopt(long, default_value = "16777216")]
+    run_rows: usize,
+
+    /// Bintable whose metadata assigns the table ids, tables keep the id of the same path in it.
+    /// Defaults to the metadata of the output, if it exists.
+    #[structopt(long)]
+    ids_from: Option<String>,
+}
+
+fn main() -> Result<()> {
+    let config = Config::from_args();
+
+    let tables = source::find_tables(&config.input)?;
+    if tables.is_empty() {
+        bail!("no tables found in {:?}", config.input);
+    }
+
+    // ids of an earlier ingest are kept, so adding a table does not renumber the others
+    let known = match &config.ids_from {
+        Some(bintable) => {
+            Metadata::open(bintable)?.with_context(|| format!("{bintable} has no metadata"))?
+        }
+        None => Metadata::open(&config.output)?.unwrap_or_default(),
+    };
+    let ids = source::table_ids(&tables, &config.input, &known)?;
+
+    // removed tables keep their names, so their ids are not given out again
+    let mut metadata = MetadataBuilder::with_names(&known);
+
+    let mut rows = RunSorter::new(&config.output, config.run_rows);
+    for (&tableid, path) in ids.iter().zip(&tables) {
+        eprintln!("{tableid}: {path:?}");
+
+        let mut table_rows = 0;
+        // the first error writing a run
+        let mut spilled = Ok(());
+        let columns = source::read_table(path, |colid, rowid, cell| {
+            table_rows = table_rows.max(rowid + 1);
+            for tokenized in config.tokenizer.tokenize(cell) {
+                if spilled.is_ok() {
+                    spilled = rows.push(TableRow {
+                        tokenized,
+                        tableid,
+                        colid,
+                        rowid,
+                    });
+                }
+            }
+        })?;
+        spilled.context("write sorted run")?;
+
+        let source = source::source(path, &config.input);
+        let name = Path::new(&source)
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        metadata.name(tableid, &name, &source, &columns, table_rows);
+    }
+
+    eprintln!("merging {} sorted runs", rows.runs() + 1);
+    // a word occurring twice in a cell is indexed once
+    let rows = rows.finish()?;
+
+    eprintln!("writing");
+    let mut out = BinTableWriter::create(&config.output)
+        .context("create output")?
+        .sorted();
+    let header = Header {
+        tokenizers: vec![config.tokenizer],
+    };
+    out.write_header(&header)?;
+
+    for row in rows {
+        let row = row?;
+        metadata.add(&row);
+        out.write(&row)?;
+    }
+    out.finish()?;
+
+    let mut meta =
+        BufWriter::new(File::create(Metadata::path(&config.output)).context("create metadata")?);
+    metadata.finish().write_bin(&mut meta)?;
+    meta.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,329 @@
+use anyhow::{bail, Context, Result};
+use bintable2::Metadata;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::record::Field;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Tsv,
+    JsonLines,
+    Parquet,
+}
+
+impl Format {
+    pub fn of(path: &Path) -> Option<Format> {
+        match path.extension()?.to_str()? {
+            "csv" => Some(Format::Csv),
+            "tsv" => Some(Format::Tsv),
+            "jsonl" | "ndjson" => Some(Format::JsonLines),
+            "parquet" => Some(Format::Parquet),
+            _ => None,
+        }
+    }
+}
+
+/// All tables below `dir`, sorted by their path.
+pub fn find_tables(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut tables = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir).with_context(|| format!("read {dir:?}"))? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if Format::of(&path).is_some() {
+                tables.push(path);
+            }
+        }
+    }
+
+    tables.sort();
+    Ok(tables)
+}
+
+/// Path of a table relative to `dir`, stored as the source of the table in the metadata.
+pub fn source(path: &Path, dir: &Path) -> String {
+    let file = path.strip_prefix(dir).unwrap_or(path);
+    file.to_string_lossy().into_owned()
+}
+
+/// Table ids of `tables` below `dir`. Tables keep the id of their source in `known`, the metadata of an earlier ingest,
+/// so adding or removing a table does not renumber the others. New tables get ids after all known ones,
+/// in the order of their paths. Ids of removed tables are not given out again, as long as their metadata is kept.
+pub fn table_ids(tables: &[PathBuf], dir: &Path, known: &Metadata) -> Result<Vec<u32>> {
+    let known_ids = known
+        .tables
+        .iter()
+        .enumerate()
+        .filter(|(_, table)| !table.source.is_empty())
+        .map(|(tableid, table)| (table.source.as_str(), tableid as u32))
+        .collect::<HashMap<_, _>>();
+
+    let mut next = known.tables.len() as u64;
+    tables
+        .iter()
+        .map(|path| match known_ids.get(source(path, dir).as_str()) {
+            Some(&tableid) => Ok(tableid),
+            None => {
+                let tableid = u32::try_from(next).context("more tables than table ids")?;
+                next += 1;
+                Ok(tableid)
+            }
+        })
+        .collect()
+}
+
+/// Calls `cell(colid, rowid, value)` for every non null cell of a table and returns the names of its columns.
+/// The column id is the position of the column in the file, the row id the position of the row (not counting the header).
+pub fn read_table(path: &Path, mut cell: impl FnMut(u32, u32, &str)) -> Result<Vec<String>> {
+    let format = Format::of(path).with_context(|| format!("unknown format of {path:?}"))?;
+    let columns = match format {
+        Format::Csv => read_csv(path, b',', &mut cell),
+        Format::Tsv => read_csv(path, b'\t', &mut cell),
+        Format::JsonLines => read_json_lines(path, &mut cell),
+        Format::Parquet => read_parquet(path, &mut cell),
+    };
+
+    columns.with_context(|| format!("read {path:?}"))
+}
+
+fn id(i: usize) -> Result<u32> {
+    u32::try_from(i).context("id to fit into 32 bits")
+}
+
+/// The first record is the header. Records may have more or less fields than the header.
+fn read_csv(
+    path: &Path,
+    delimiter: u8,
+    cell: &mut impl FnMut(u32, u32, &str),
+) -> Result<Vec<String>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_path(path)?;
+
+    let mut columns = reader
+        .byte_headers()?
+        .iter()
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .collect::<Vec<_>>();
+
+    for (rowid, record) in reader.byte_records().enumerate() {
+        let record = record?;
+        let rowid = id(rowid)?;
+
+        if record.len() > columns.len() {
+            columns.resize(record.len(), String::new());
+        }
+
+        for (colid, value) in record.iter().enumerate() {
+            cell(id(colid)?, rowid, &String::from_utf8_lossy(value));
+        }
+    }
+
+    Ok(columns)
+}
+
+/// Every line is an object, its keys are the columns.
+/// Columns get their ids in the order they first appear in.
+fn read_json_lines(path: &Path, cell: &mut impl FnMut(u32, u32, &str)) -> Result<Vec<String>> {
+    let reader = BufReader::new(File::open(path)?);
+
+    let mut columns: Vec<String> = Vec::new();
+    let mut rowid = 0;
+    for (line_number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let object = match serde_json::from_str(&line)
+            .with_context(|| format!("parse line {}", line_number + 1))?
+        {
+            Value::Object(object) => object,
+            _ => bail!("line {} is not an object", line_number + 1),
+        };
+
+        for (key, value) in object {
+            let colid = match columns.iter().position(|c| *c == key) {
+                Some(colid) => colid,
+                None => {
+                    columns.push(key);
+                    columns.len() - 1
+                }
+            };
+
+            match value {
+                Value::Null => {}
+                Value::String(s) => cell(id(colid)?, id(rowid)?, &s),
+                // numbers and booleans as written, nested values as compact json
+                value => cell(id(colid)?, id(rowid)?, &value.to_string()),
+            }
+        }
+
+        rowid += 1;
+    }
+
+    Ok(columns)
+}
+
+/// The top level fields of the schema are the columns.
+fn read_parquet(path: &Path, cell: &mut impl FnMut(u32, u32, &str)) -> Result<Vec<String>> {
+    let reader = SerializedFileReader::new(File::open(path)?)?;
+
+    let columns = reader
+        .metadata()
+        .file_metadata()
+        .schema()
+        .get_fields()
+        .iter()
+        .map(|field| field.name().to_string())
+        .collect();
+
+    for (rowid, row) in reader.get_row_iter(None)?.enumerate() {
+        let row = row?;
+        let rowid = id(rowid)?;
+
+        for (colid, (_, value)) in row.get_column_iter().enumerate() {
+            match value {
+                Field::Null => {}
+                Field::Str(s) => cell(id(colid)?, rowid, s),
+                Field::Bytes(b) => cell(id(colid)?, rowid, &String::from_utf8_lossy(b.data())),
+                value => cell(id(colid)?, rowid, &value.to_string()),
+            }
+        }
+    }
+
+    Ok(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::data_type::{ByteArray, ByteArrayType, Int32Type};
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+    use std::sync::Arc;
+
+    fn temp_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ingest-tables-{}-{name}", std::process::id()))
+    }
+
+    fn cells(path: &Path) -> (Vec<String>, Vec<(u32, u32, String)>) {
+        let mut cells = Vec::new();
+        let columns = read_table(path, |colid, rowid, value| {
+            cells.push((colid, rowid, value.to_string()))
+        })
+        .unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        (columns, cells)
+    }
+
+    fn expected() -> Vec<(u32, u32, String)> {
+        vec![
+            (0, 0, "Berlin".to_string()),
+            (1, 0, "3645".to_string()),
+            (0, 1, "Hamburg, Germany".to_string()),
+        ]
+    }
+
+    #[test]
+    fn ids_of_known_tables() {
+        let dir = Path::new("lake");
+        let known = Metadata {
+            tables: ["b.csv", "d.csv", "gone.csv"]
+                .map(|source| bintable2::Table {
+                    source: source.to_string(),
+                    ..Default::default()
+                })
+                .to_vec(),
+        };
+
+        let tables = ["a.csv", "b.csv", "c/c.csv", "d.csv"].map(|t| dir.join(t));
+        assert_eq!(table_ids(&tables, dir, &known).unwrap(), [3, 0, 4, 1]);
+
+        let fresh = table_ids(&tables, dir, &Metadata::default()).unwrap();
+        assert_eq!(fresh, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn csv() {
+        let path = temp_file("cities.csv");
+        std::fs::write(
+            &path,
+            "city,population\nBerlin,3645\n\"Hamburg, Germany\"\n",
+        )
+        .unwrap();
+
+        let (columns, cells) = cells(&path);
+        assert_eq!(columns, ["city", "population"]);
+        assert_eq!(cells, expected());
+    }
+
+    #[test]
+    fn json_lines() {
+        let path = temp_file("cities.jsonl");
+        std::fs::write(
+            &path,
+            "{\"city\": \"Berlin\", \"population\": 3645}\n\n{\"city\": \"Hamburg, Germany\", \"population\": null}\n",
+        )
+        .unwrap();
+
+        let (columns, cells) = cells(&path);
+        assert_eq!(columns, ["city", "population"]);
+        assert_eq!(cells, expected());
+    }
+
+    #[test]
+    fn parquet() {
+        let path = temp_file("cities.parquet");
+
+        let schema = parse_message_type(
+            "message cities { REQUIRED BYTE_ARRAY city (UTF8); OPTIONAL INT32 population; }",
+        )
+        .unwrap();
+        let mut writer = SerializedFileWriter::new(
+            File::create(&path).unwrap(),
+            Arc::new(schema),
+            Default::default(),
+        )
+        .unwrap();
+        let mut row_group = writer.next_row_group().unwrap();
+
+        let mut column = row_group.next_column().unwrap().unwrap();
+        column
+            .typed::<ByteArrayType>()
+            .write_batch(
+                &[
+                    ByteArray::from("Berlin"),
+                    ByteArray::from("Hamburg, Germany"),
+                ],
+                None,
+                None,
+            )
+            .unwrap();
+        column.close().unwrap();
+
+        let mut column = row_group.next_column().unwrap().unwrap();
+        column
+            .typed::<Int32Type>()
+            .write_batch(&[3645], Some(&[1, 0]), None)
+            .unwrap();
+        column.close().unwrap();
+
+        row_group.close().unwrap();
+        writer.close().unwrap();
+
+        let (columns, cells) = cells(&path);
+        assert_eq!(columns, ["city", "population"]);
+        assert_eq!(cells, expected());
+    }
+}
@@ -0,0 +1,38 @@
+/// Fixed size bit vector with constant time rank queries.
+pub(crate) struct BitVec {
+    words: Box<[u64]>,
+    /// number of set bits before each word
+    ranks: Box<[u32]>,
+}
+
+impl BitVec {
+    pub fn from_words(words: Vec<u64>) -> Self {
+        let mut ranks = Vec::with_capacity(words.len());
+        let mut rank = 0;
+        for w in &words {
+            ranks.push(rank);
+            rank += w.count_ones();
+        }
+
+        BitVec {
+            words: words.into_boxed_slice(),
+            ranks: ranks.into_boxed_slice(),
+        }
+    }
+
+    pub fn get(&self, index: usize) -> bool {
+        self.words[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    /// number of set bits before `index`
+    pub fn rank(&self, index: usize) -> usize {
+        let word = index / 64;
+        let mask = (1u64 << (index % 64)) - 1;
+        self.ranks[word] as usize + (self.words[word] & mask).count_ones() as usize
+    }
+
+    pub fn heap_size(&self) -> usize {
+        self.words.len() * std::mem::size_of::<u64>()
+            + self.ranks.len() * std::mem::size_of::<u32>()
+    }
+}
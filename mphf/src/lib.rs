@@ -0,0 +1,179 @@
+mod bitvec;
+use bitvec::BitVec;
+use std::collections::HashMap;
+
+/// Space/time trade-off of the construction. Larger values need more bits per key, but fewer levels.
+pub const DEFAULT_GAMMA: f64 = 2.0;
+
+const MAX_LEVELS: usize = 32;
+
+/// Minimal perfect hash function over a static set of keys (BBHash).
+///
+/// Every key of the set maps to a distinct index in `0..len`.
+/// Keys outside of the set map to an arbitrary index, use a fingerprint or the key itself to reject them.
+pub struct Mphf {
+    /// bit array of all levels concatenated
+    bits: BitVec,
+    /// offset of each level inside `bits`
+    levels: Vec<(usize, usize)>,
+    /// keys that could not be placed in any level
+    fallback: HashMap<Box<[u8]>, usize>,
+    len: usize,
+}
+
+impl Mphf {
+    pub fn new<K: AsRef<[u8]>>(keys: &[K], gamma: f64) -> Self {
+        Self::with_levels(keys, gamma, MAX_LEVELS)
+    }
+
+    fn with_levels<K: AsRef<[u8]>>(keys: &[K], gamma: f64, max_levels: usize) -> Self {
+        let mut remaining: Vec<&[u8]> = keys.iter().map(AsRef::as_ref).collect();
+        let len = remaining.len();
+
+        let mut words = Vec::new();
+        let mut levels = Vec::new();
+
+        while !remaining.is_empty() && levels.len() < max_levels {
+            let level = levels.len() as u64;
+
+            // round up to full words, so every level starts at a word boundary
+            let size = ((remaining.len() as f64 * gamma).ceil() as usize).max(1);
+            let size = size.div_ceil(64) * 64;
+
+            let mut taken = vec![0u64; size / 64];
+            let mut collision = vec![0u64; size / 64];
+
+            for key in &remaining {
+                let i = (hash(key, level) % size as u64) as usize;
+                let (word, bit) = (i / 64, 1 << (i % 64));
+
+                if taken[word] & bit != 0 {
+                    collision[word] |= bit;
+                }
+                taken[word] |= bit;
+            }
+
+            for (t, c) in taken.iter_mut().zip(collision.iter()) {
+                *t &= !c;
+            }
+
+            remaining.retain(|key| {
+                let i = (hash(key, level) % size as u64) as usize;
+                collision[i / 64] & (1 << (i % 64)) != 0
+            });
+
+            levels.push((words.len() * 64, size));
+            words.extend(taken);
+        }
+
+        let bits = BitVec::from_words(words);
+        let placed = len - remaining.len();
+
+        let fallback = remaining
+            .into_iter()
+            .enumerate()
+            .map(|(i, key)| (Box::from(key), placed + i))
+            .collect();
+
+        Mphf {
+            bits,
+            levels,
+            fallback,
+            len,
+        }
+    }
+
+    /// Number of keys
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Index of the key inside `0..len`.
+    pub fn index(&self, key: &[u8]) -> usize {
+        for (level, (offset, size)) in self.levels.iter().enumerate() {
+            let i = offset + (hash(key, level as u64) % *size as u64) as usize;
+            if self.bits.get(i) {
+                return self.bits.rank(i);
+            }
+        }
+
+        // keys outside of the set still need to map somewhere
+        self.fallback.get(key).copied().unwrap_or(0)
+    }
+
+    /// Approximate number of bytes used on the heap.
+    pub fn heap_size(&self) -> usize {
+        self.bits.heap_size()
+            + self.levels.capacity() * std::mem::size_of::<(usize, usize)>()
+            + self.fallback.keys().map(|k| k.len() + 24).sum::<usize>()
+    }
+}
+
+/// Seeded 64 bit hash (FNV-1a with a murmur3 finalizer)
+pub fn hash(key: &[u8], seed: u64) -> u64 {
+    let mut h = 0xcbf29ce484222325 ^ seed.wrapping_mul(0x9e3779b97f4a7c15);
+    for b in key {
+        h ^= *b as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51afd7ed558ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ceb9fe1a85ec53);
+    h ^= h >> 33;
+    h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("key-{}", i * 7919)).collect()
+    }
+
+    #[test]
+    fn minimal_and_perfect() {
+        for n in [0, 1, 2, 10, 1000, 50_000] {
+            let keys = keys(n);
+            let mphf = Mphf::new(&keys, DEFAULT_GAMMA);
+
+            assert_eq!(mphf.len(), n);
+
+            let mut seen = vec![false; n];
+            for k in &keys {
+                let i = mphf.index(k.as_bytes());
+                assert!(i < n, "index {i} of {k} out of range");
+                assert!(!seen[i], "index {i} of {k} used twice");
+                seen[i] = true;
+            }
+        }
+    }
+
+    #[test]
+    fn fallback_for_unplaceable_keys() {
+        let keys = keys(100);
+        // there are no levels, so every key has to go to the fallback map
+        let mphf = Mphf::with_levels(&keys, DEFAULT_GAMMA, 0);
+        assert_eq!(mphf.fallback.len(), 100);
+
+        let mut indices = keys
+            .iter()
+            .map(|k| mphf.index(k.as_bytes()))
+            .collect::<Vec<_>>();
+        indices.sort_unstable();
+        assert_eq!(indices, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn hash_depends_on_seed() {
+        assert_ne!(hash(b"abc", 0), hash(b"abc", 1));
+        assert_ne!(hash(b"abc", 0), hash(b"abd", 0));
+        assert_eq!(hash(b"abc", 3), hash(b"abc", 3));
+    }
+}
@@ -0,0 +1,75 @@
+use bintable2::*;
+
+/// Rows sorted in memory at once, if not given on the command line
+const RUN_ROWS: usize = 1 << 24;
+
+fn print_help() -> ! {
+    eprintln!("retokenize-bintable <input> <outputname> <tokenizer> [run-rows]");
+    eprintln!("    tokenizer: comma separated steps applied to every key of the input");
+    eprintln!("               nfkc lowercase words whitespace numbers punctuation max-length=<n>");
+    eprintln!("    run-rows:  rows sorted in memory at once, more rows are sorted in runs written");
+    eprintln!(
+        "               beside the output and merged. A row takes about 40 bytes plus its key."
+    );
+    eprintln!("               Defaults to {RUN_ROWS}");
+    std::process::exit(0);
+}
+
+fn get_args() -> (String, String, Tokenizer, usize) {
+    let mut a = std::env::args().skip(1);
+    let input = a.next().unwrap_or_else(|| print_help());
+    let output = a.next().unwrap_or_else(|| print_help());
+    let tokenizer = match a.next().map(|t| t.parse()) {
+        Some(Ok(tokenizer)) => tokenizer,
+        Some(Err(e)) => {
+            eprintln!("{e}");
+            print_help()
+        }
+        None => print_help(),
+    };
+    let run_rows = match a.next().map(|r| r.parse()) {
+        Some(Ok(run_rows)) => run_rows,
+        Some(Err(_)) => print_help(),
+        None => RUN_ROWS,
+    };
+
+    (input, output, tokenizer, run_rows)
+}
+
+fn main() {
+    let (input, output, tokenizer, run_rows) = get_args();
+
+    if input == output {
+        panic!("input must not be output");
+    }
+
+    let table = BinTable::open(&input).expect("open bintable");
+
+    // the new tokenizer is applied on top of the ones the input was created with
+    let mut header = table.header().clone();
+    header.tokenizers.push(tokenizer.clone());
+
+    eprintln!("tokenizing");
+    let mut rows = RunSorter::new(&output, run_rows);
+    for row in table {
+        for tokenized in tokenizer.tokenize(&row.tokenized) {
+            rows.push(TableRow { tokenized, ..row })
+                .expect("write sorted run");
+        }
+    }
+
+    eprintln!("merging {} sorted runs", rows.runs() + 1);
+    let rows = rows.finish().expect("read sorted runs");
+
+    eprintln!("writing");
+    let mut out = BinTableWriter::create(&output)
+        .expect("open output file")
+        .sorted();
+    out.write_header(&header).expect("write header");
+
+    for row in rows {
+        let row = row.expect("read sorted run");
+        out.write(&row).expect("write to output");
+    }
+    out.finish().expect("write to output");
+}
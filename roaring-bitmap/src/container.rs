@@ -0,0 +1,248 @@
+/// Containers with more than this many values are stored as bitmaps.
+pub(crate) const ARRAY_LIMIT: usize = 4096;
+
+const BITMAP_WORDS: usize = 1024;
+
+/// Set of the lower 16 bits of all values that share the same upper 16 bits.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Container {
+    /// sorted list of values, used for sparse containers
+    Array(Vec<u16>),
+    /// 2^16 bits, used for dense containers. Keeps track of it's cardinality.
+    Bitmap(Box<[u64; BITMAP_WORDS]>, u32),
+}
+
+impl Container {
+    pub fn new() -> Self {
+        Container::Array(Vec::new())
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Container::Array(v) => v.len(),
+            Container::Bitmap(_, n) => *n as usize,
+        }
+    }
+
+    pub fn insert(&mut self, value: u16) -> bool {
+        match self {
+            Container::Array(v) => {
+                // values are usually inserted in ascending order
+                let position = match v.last() {
+                    Some(last) if *last < value => v.len(),
+                    _ => match v.binary_search(&value) {
+                        Ok(_) => return false,
+                        Err(position) => position,
+                    },
+                };
+
+                v.insert(position, value);
+
+                if v.len() > ARRAY_LIMIT {
+                    *self = Container::bitmap_from_sorted(v);
+                }
+
+                true
+            }
+            Container::Bitmap(words, n) => {
+                let (word, bit) = split(value);
+                let is_new = words[word] & bit == 0;
+                words[word] |= bit;
+                *n += is_new as u32;
+                is_new
+            }
+        }
+    }
+
+    pub fn contains(&self, value: u16) -> bool {
+        match self {
+            Container::Array(v) => v.binary_search(&value).is_ok(),
+            Container::Bitmap(words, _) => {
+                let (word, bit) = split(value);
+                words[word] & bit != 0
+            }
+        }
+    }
+
+    pub fn and(&self, other: &Container) -> Container {
+        use Container::*;
+
+        match (self, other) {
+            (Array(a), Array(b)) => Array(intersect_sorted(a, b)),
+            (Array(a), Bitmap(..)) | (Bitmap(..), Array(a)) => {
+                let bitmap = if let Bitmap(..) = self { self } else { other };
+                Array(a.iter().copied().filter(|v| bitmap.contains(*v)).collect())
+            }
+            (Bitmap(a, _), Bitmap(b, _)) => {
+                let mut words = Box::new([0u64; BITMAP_WORDS]);
+                let mut n = 0;
+                for i in 0..BITMAP_WORDS {
+                    words[i] = a[i] & b[i];
+                    n += words[i].count_ones();
+                }
+
+                let c = Bitmap(words, n);
+                if n as usize <= ARRAY_LIMIT {
+                    Array(c.iter().collect())
+                } else {
+                    c
+                }
+            }
+        }
+    }
+
+    pub fn iter(&self) -> ContainerIter<'_> {
+        match self {
+            Container::Array(v) => ContainerIter::Array(v.iter()),
+            Container::Bitmap(words, _) => ContainerIter::Bitmap {
+                words: &words[..],
+                index: 0,
+                current: words[0],
+            },
+        }
+    }
+
+    /// Approximate number of bytes this container occupies on the heap.
+    pub fn heap_size(&self) -> usize {
+        match self {
+            Container::Array(v) => v.capacity() * std::mem::size_of::<u16>(),
+            Container::Bitmap(..) => BITMAP_WORDS * std::mem::size_of::<u64>(),
+        }
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        if let Container::Array(v) = self {
+            v.shrink_to_fit();
+        }
+    }
+
+    /*
+        Scheme: <varint>cardinality
+        if cardinality <= ARRAY_LIMIT:
+            for v in values: <varint>delta to previous value
+        else:
+            1024 little endian u64 words
+    */
+    pub fn write(&self, out: &mut Vec<u8>) {
+        use varint_compression::compress;
+
+        out.extend(compress(self.len() as u64));
+
+        match self {
+            Container::Array(v) => {
+                let mut last = 0;
+                for value in v {
+                    out.extend(compress((value - last) as u64));
+                    last = *value;
+                }
+            }
+            Container::Bitmap(words, _) => {
+                for word in words.iter() {
+                    out.extend(word.to_le_bytes());
+                }
+            }
+        }
+    }
+
+    pub fn read(data: &[u8]) -> (Container, &[u8]) {
+        use varint_compression::decompress;
+
+        let (n, mut data) = decompress(data).expect("decompress cardinality of container");
+        let n = n as usize;
+
+        if n <= ARRAY_LIMIT {
+            let mut v = Vec::with_capacity(n);
+            let mut last = 0;
+            for _ in 0..n {
+                let (delta, rest) = decompress(data).expect("decompress container value");
+                last += delta as u16;
+                v.push(last);
+                data = rest;
+            }
+
+            return (Container::Array(v), data);
+        }
+
+        let mut words = Box::new([0u64; BITMAP_WORDS]);
+        for (i, word) in words.iter_mut().enumerate() {
+            let bytes = &data[i * 8..(i + 1) * 8];
+            *word = u64::from_le_bytes(bytes.try_into().unwrap());
+        }
+
+        (
+            Container::Bitmap(words, n as u32),
+            &data[BITMAP_WORDS * 8..],
+        )
+    }
+
+    fn bitmap_from_sorted(values: &[u16]) -> Container {
+        let mut words = Box::new([0u64; BITMAP_WORDS]);
+        for v in values {
+            let (word, bit) = split(*v);
+            words[word] |= bit;
+        }
+
+        Container::Bitmap(words, values.len() as u32)
+    }
+}
+
+fn split(value: u16) -> (usize, u64) {
+    ((value >> 6) as usize, 1 << (value & 63))
+}
+
+fn intersect_sorted(a: &[u16], b: &[u16]) -> Vec<u16> {
+    let mut result = Vec::with_capacity(a.len().min(b.len()));
+
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                result.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+
+    result
+}
+
+pub(crate) enum ContainerIter<'a> {
+    Array(std::slice::Iter<'a, u16>),
+    Bitmap {
+        words: &'a [u64],
+        index: usize,
+        current: u64,
+    },
+}
+
+impl<'a> Iterator for ContainerIter<'a> {
+    type Item = u16;
+
+    fn next(&mut self) -> Option<u16> {
+        match self {
+            ContainerIter::Array(iter) => iter.next().copied(),
+            ContainerIter::Bitmap {
+                words,
+                index,
+                current,
+            } => {
+                while *current == 0 {
+                    if *index + 1 >= words.len() {
+                        return None;
+                    }
+                    *index += 1;
+                    *current = words[*index];
+                }
+
+                let bit = current.trailing_zeros();
+                // clear lowest bit
+                *current &= *current - 1;
+
+                Some((*index as u16) << 6 | bit as u16)
+            }
+        }
+    }
+}
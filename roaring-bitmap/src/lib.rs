@@ -0,0 +1,375 @@
+mod container;
+use container::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_and_iterate() {
+        let values = [0, 1, 7, 65535, 65536, 65537, 1 << 20, u32::MAX];
+
+        let mut bitmap = RoaringBitmap::new();
+        for v in values {
+            assert!(bitmap.insert(v), "expected {v} to be new");
+        }
+        assert!(!bitmap.insert(7), "7 is already in the bitmap");
+
+        assert_eq!(bitmap.len(), values.len() as u64);
+        assert_eq!(bitmap.iter().collect::<Vec<_>>(), values);
+
+        for v in values {
+            assert!(bitmap.contains(v), "expected {v} to be in the bitmap");
+        }
+        assert!(!bitmap.contains(2));
+    }
+
+    #[test]
+    fn dense_container_becomes_bitmap() {
+        let bitmap: RoaringBitmap = (0..10_000).map(|i| i * 3).collect();
+
+        assert!(matches!(bitmap.containers[0], Container::Bitmap(..)));
+        assert_eq!(bitmap.len(), 10_000);
+        assert_eq!(
+            bitmap.iter().collect::<Vec<_>>(),
+            (0..10_000).map(|i| i * 3).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn intersection() {
+        let a: RoaringBitmap = (0..20_000).filter(|i| i % 2 == 0).collect();
+        let b: RoaringBitmap = (0..20_000).filter(|i| i % 3 == 0).chain([70_000]).collect();
+        let c: RoaringBitmap = [6, 12, 70_000, 80_000].into_iter().collect();
+
+        let expected = (0..20_000).filter(|i| i % 6 == 0).collect::<Vec<_>>();
+        assert_eq!(a.and(&b).iter().collect::<Vec<_>>(), expected);
+        assert_eq!(b.and(&a).iter().collect::<Vec<_>>(), expected);
+
+        assert_eq!(b.and(&c).iter().collect::<Vec<_>>(), [6, 12, 70_000]);
+        assert_eq!(c.and(&a).iter().collect::<Vec<_>>(), [6, 12]);
+    }
+
+    #[test]
+    fn grouped_intersection() {
+        let a: GroupedBitmaps = [(1, 1), (1, 2), (2, 5), (4, 1)].into_iter().collect();
+        let b: GroupedBitmaps = [(1, 2), (2, 5), (2, 6), (3, 1), (4, 2)]
+            .into_iter()
+            .collect();
+
+        assert_eq!(a.len(), 4);
+        assert_eq!(a.intersect(&b).iter().collect::<Vec<_>>(), [(1, 2), (2, 5)]);
+        assert_eq!(b.get(2).map(RoaringBitmap::len), Some(2));
+        assert!(b.get(5).is_none());
+    }
+
+    #[test]
+    fn serde() {
+        let dense = (0..10_000).map(|i| (7, i * 2));
+        let sparse = [(1, 1), (1, 2), (2, 5), (4, 1 << 20), (1 << 40, u32::MAX)];
+        let bitmaps: GroupedBitmaps = dense.chain(sparse).collect();
+
+        let mut data = Vec::new();
+        bitmaps.write(&mut data);
+        data.push(42);
+
+        let (retrieved, rest) = GroupedBitmaps::read(&data);
+
+        assert_eq!(rest, [42], "expected only the trailing byte to remain");
+        assert_eq!(retrieved, bitmaps);
+    }
+}
+
+/// Compressed set of u32 values.
+///
+/// Values are partitioned by their upper 16 bits.
+/// Each partition is stored either as a sorted array or as a bitmap, depending on how dense it is.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RoaringBitmap {
+    keys: Vec<u16>,
+    containers: Vec<Container>,
+}
+
+impl RoaringBitmap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of values in this set
+    pub fn len(&self) -> u64 {
+        self.containers.iter().map(|c| c.len() as u64).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Insert a value, returns true if the value wasn't present before.
+    /// Inserting in ascending order is fastest.
+    pub fn insert(&mut self, value: u32) -> bool {
+        let (key, low) = ((value >> 16) as u16, value as u16);
+
+        let index = match self.keys.last() {
+            Some(last) if *last == key => self.keys.len() - 1,
+            Some(last) if *last < key => self.push_container(key),
+            None => self.push_container(key),
+            _ => match self.keys.binary_search(&key) {
+                Ok(index) => index,
+                Err(index) => {
+                    self.keys.insert(index, key);
+                    self.containers.insert(index, Container::new());
+                    index
+                }
+            },
+        };
+
+        self.containers[index].insert(low)
+    }
+
+    pub fn contains(&self, value: u32) -> bool {
+        let (key, low) = ((value >> 16) as u16, value as u16);
+
+        match self.keys.binary_search(&key) {
+            Ok(index) => self.containers[index].contains(low),
+            Err(_) => false,
+        }
+    }
+
+    /// Intersection of two bitmaps
+    pub fn and(&self, other: &RoaringBitmap) -> RoaringBitmap {
+        let mut result = RoaringBitmap::new();
+
+        let (mut i, mut j) = (0, 0);
+        while i < self.keys.len() && j < other.keys.len() {
+            match self.keys[i].cmp(&other.keys[j]) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    let c = self.containers[i].and(&other.containers[j]);
+                    if c.len() != 0 {
+                        result.keys.push(self.keys[i]);
+                        result.containers.push(c);
+                    }
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Iterates all values in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        self.keys
+            .iter()
+            .zip(self.containers.iter())
+            .flat_map(|(key, c)| c.iter().map(move |low| (*key as u32) << 16 | low as u32))
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.keys.shrink_to_fit();
+        self.containers.shrink_to_fit();
+        self.containers
+            .iter_mut()
+            .for_each(Container::shrink_to_fit);
+    }
+
+    /// Approximate number of bytes used by this bitmap on the heap.
+    pub fn heap_size(&self) -> usize {
+        self.keys.capacity() * std::mem::size_of::<u16>()
+            + self.containers.capacity() * std::mem::size_of::<Container>()
+            + self
+                .containers
+                .iter()
+                .map(Container::heap_size)
+                .sum::<usize>()
+    }
+
+    /*
+        Scheme: <varint>container count
+        for c in containers:
+            <varint>key
+            container
+    */
+    pub fn write(&self, out: &mut Vec<u8>) {
+        use varint_compression::compress;
+
+        out.extend(compress(self.keys.len() as u64));
+        for (key, c) in self.keys.iter().zip(self.containers.iter()) {
+            out.extend(compress(*key as u64));
+            c.write(out);
+        }
+    }
+
+    /// Reads a bitmap previously written using `write`, returns the remaining bytes.
+    pub fn read(data: &[u8]) -> (RoaringBitmap, &[u8]) {
+        use varint_compression::decompress;
+
+        let (n, mut data) = decompress(data).expect("decompress container count");
+        let n = n as usize;
+
+        let mut bitmap = RoaringBitmap {
+            keys: Vec::with_capacity(n),
+            containers: Vec::with_capacity(n),
+        };
+
+        for _ in 0..n {
+            let (key, rest) = decompress(data).expect("decompress container key");
+            let (c, rest) = Container::read(rest);
+            bitmap.keys.push(key as u16);
+            bitmap.containers.push(c);
+            data = rest;
+        }
+
+        (bitmap, data)
+    }
+
+    fn push_container(&mut self, key: u16) -> usize {
+        self.keys.push(key);
+        self.containers.push(Container::new());
+        self.keys.len() - 1
+    }
+}
+
+impl FromIterator<u32> for RoaringBitmap {
+    fn from_iter<T: IntoIterator<Item = u32>>(iter: T) -> Self {
+        let mut bitmap = RoaringBitmap::new();
+        for v in iter {
+            bitmap.insert(v);
+        }
+        bitmap.shrink_to_fit();
+        bitmap
+    }
+}
+
+/// Sorted map from a group id to a bitmap of values inside that group.
+///
+/// Useful when values cluster inside few groups, e.g. rowids that belong to a few (tableid, colid) pairs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GroupedBitmaps {
+    groups: Box<[(u64, RoaringBitmap)]>,
+}
+
+impl GroupedBitmaps {
+    /// Total number of values in all groups
+    pub fn len(&self) -> u64 {
+        self.groups.iter().map(|(_, b)| b.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Number of distinct groups
+    pub fn group_count(&self) -> usize {
+        self.groups.len()
+    }
+
+    pub fn get(&self, group: u64) -> Option<&RoaringBitmap> {
+        let index = self.groups.binary_search_by_key(&group, |g| g.0).ok()?;
+        Some(&self.groups[index].1)
+    }
+
+    /// Intersects groups and their bitmaps. Groups present in only one of the maps are skipped entirely.
+    pub fn intersect(&self, other: &GroupedBitmaps) -> GroupedBitmaps {
+        let mut groups = Vec::new();
+
+        let (mut i, mut j) = (0, 0);
+        while i < self.groups.len() && j < other.groups.len() {
+            let (a, b) = (&self.groups[i], &other.groups[j]);
+            match a.0.cmp(&b.0) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    let bitmap = a.1.and(&b.1);
+                    if !bitmap.is_empty() {
+                        groups.push((a.0, bitmap));
+                    }
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+
+        GroupedBitmaps {
+            groups: groups.into_boxed_slice(),
+        }
+    }
+
+    /// Iterates all (group, value) pairs, ordered by group and value.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, u32)> + '_ {
+        self.groups
+            .iter()
+            .flat_map(|(group, bitmap)| bitmap.iter().map(move |v| (*group, v)))
+    }
+
+    /*
+        Scheme: <varint>group count
+        for g in groups:
+            <varint>delta to previous group
+            bitmap
+    */
+    pub fn write(&self, out: &mut Vec<u8>) {
+        use varint_compression::compress;
+
+        out.extend(compress(self.groups.len() as u64));
+
+        let mut last = 0;
+        for (group, bitmap) in self.groups.iter() {
+            out.extend(compress(group - last));
+            last = *group;
+            bitmap.write(out);
+        }
+    }
+
+    /// Reads a map previously written using `write`, returns the remaining bytes.
+    pub fn read(data: &[u8]) -> (GroupedBitmaps, &[u8]) {
+        use varint_compression::decompress;
+
+        let (n, mut data) = decompress(data).expect("decompress group count");
+
+        let mut groups = Vec::with_capacity(n as usize);
+        let mut last = 0;
+        for _ in 0..n {
+            let (delta, rest) = decompress(data).expect("decompress group");
+            let (bitmap, rest) = RoaringBitmap::read(rest);
+            last += delta;
+            groups.push((last, bitmap));
+            data = rest;
+        }
+
+        let groups = groups.into_boxed_slice();
+        (GroupedBitmaps { groups }, data)
+    }
+
+    /// Approximate number of bytes used by this map on the heap.
+    pub fn heap_size(&self) -> usize {
+        self.groups.len() * std::mem::size_of::<(u64, RoaringBitmap)>()
+            + self
+                .groups
+                .iter()
+                .map(|(_, b)| b.heap_size())
+                .sum::<usize>()
+    }
+}
+
+impl FromIterator<(u64, u32)> for GroupedBitmaps {
+    fn from_iter<T: IntoIterator<Item = (u64, u32)>>(iter: T) -> Self {
+        let mut groups = std::collections::BTreeMap::<u64, RoaringBitmap>::new();
+        for (group, value) in iter {
+            groups.entry(group).or_default().insert(value);
+        }
+
+        let groups = groups
+            .into_iter()
+            .map(|(group, mut bitmap)| {
+                bitmap.shrink_to_fit();
+                (group, bitmap)
+            })
+            .collect::<Vec<_>>()
+            .into_boxed_slice();
+
+        GroupedBitmaps { groups }
+    }
+}
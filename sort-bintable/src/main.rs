@@ -1,20 +1,27 @@
 use bintable2::*;
-use std::fs::File;
-use std::io::{BufWriter, Write};
 use std::sync::mpsc::{sync_channel, Receiver};
 use std::thread::spawn;
 
 fn print_help() -> ! {
-    eprintln!("sort-bintable <input> <outputname>");
+    eprintln!("sort-bintable <input> <outputname> [--fsst]");
+    eprintln!("    --fsst: compress keys with a symbol table trained on the input instead of smaz");
     std::process::exit(0);
 }
 
-fn get_args() -> (String, String) {
+/// Number of keys the fsst symbol table gets trained on.
+const TRAINING_SAMPLE: usize = 1 << 14;
+
+fn get_args() -> (String, String, bool) {
     let mut a = std::env::args().skip(1);
     let input = a.next().unwrap_or_else(|| print_help());
     let output = a.next().unwrap_or_else(|| print_help());
+    let fsst = match a.next().as_deref() {
+        None => false,
+        Some("--fsst") => true,
+        Some(_) => print_help(),
+    };
 
-    (input, output)
+    (input, output, fsst)
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord)]
@@ -47,14 +54,14 @@ impl Iterator for Group {
 }
 
 fn main() {
-    let (input, output) = get_args();
+    let (input, output, fsst) = get_args();
 
     if input == output {
         panic!("input must not be output");
     }
 
     eprintln!("streaming");
-    let rows = get_rows(input);
+    let (header, rows) = get_rows(input);
 
     let mut groups = group(rows);
 
@@ -63,20 +70,36 @@ fn main() {
 
     // write back data
 
-    let out = File::create(output).expect("open output file");
-    let mut out = BufWriter::with_capacity(1024 * 1024 /*1Mb*/, out);
-    let mut acc = ParseAcc::default();
+    let mut out = BinTableWriter::create(&output)
+        .expect("open output file")
+        .sorted();
+    if fsst {
+        eprintln!("training symbol table");
+        out = out.with_fsst(train(&groups));
+    }
 
     eprintln!("writing");
+    out.write_header(&header).expect("write header");
     for g in groups {
         for row in g {
-            row.write_bin(&mut out, &mut acc).expect("write to output");
+            out.write(&row).expect("write to output");
 
             drop(row)
         }
     }
+    out.finish().expect("write to output");
+}
 
-    out.flush().expect("flush buffered writer");
+/// Trains on keys evenly spread over the sorted groups.
+fn train(groups: &[Group]) -> SymbolTable {
+    let step = (groups.len() / TRAINING_SAMPLE).max(1);
+    let sample = groups
+        .iter()
+        .step_by(step)
+        .map(|g| g.id.as_bytes())
+        .collect::<Vec<_>>();
+
+    SymbolTable::train(&sample)
 }
 
 fn group(rows: Receiver<TableRow>) -> Vec<Group> {
@@ -114,15 +137,16 @@ fn group(rows: Receiver<TableRow>) -> Vec<Group> {
     v
 }
 
-fn get_rows(path: String) -> Receiver<TableRow> {
+fn get_rows(path: String) -> (Header, Receiver<TableRow>) {
+    let table = BinTable::open(&path).expect("open bintable");
+    let header = table.header().clone();
+
     let (s, r) = sync_channel(1024);
     spawn(move || {
-        let table = BinTable::open(&path).expect("open bintable");
-
         for row in table {
             s.send(row).expect("send to channel");
         }
     });
 
-    r
+    (header, r)
 }
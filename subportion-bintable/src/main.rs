@@ -2,19 +2,19 @@
 
 use bintable2::*;
 use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::io::BufWriter;
 use std::sync::mpsc::{sync_channel, Receiver};
 use std::thread::spawn;
 
 fn print_help() -> ! {
-    eprintln!("subportion-bintable <input> <factor>");
+    eprintln!("subportion-bintable <input> <factor> [--mode row|key|table] [--seed <n>]");
+    eprintln!("    writes the sample to <input>-<mode>-<seed>-<factor>, row sampling with seed 0 is default");
     std::process::exit(0);
 }
 
 struct BintableName {
     path: String,
     name: String,
-    _factor: f32,
 }
 
 fn parse_bintable_name(path: String) -> BintableName {
@@ -24,28 +24,19 @@ fn parse_bintable_name(path: String) -> BintableName {
         (".", &path as &str)
     };
 
-    let path = path.to_string();
-    let name = name.to_string();
-
-    let (name, _factor) = if let Some((name, factor)) = name.rsplit_once('-') {
-        (
-            name.to_string(),
-            factor
-                .parse::<f32>()
-                .expect("factor to be a floating point number"),
-        )
-    } else {
-        (name, 1.0f32)
+    // samples are always drawn from the whole corpus (or the best sample beside it)
+    let name = match Sampling::parse_subset_name(name) {
+        Some((corpus, _)) => corpus,
+        None => name,
     };
 
     BintableName {
-        path,
-        name,
-        _factor,
+        path: path.to_string(),
+        name: name.to_string(),
     }
 }
 
-fn args() -> (BintableName, f32) {
+fn args() -> (BintableName, Sampling) {
     let mut a = std::env::args().skip(1);
     let input = a.next().unwrap_or_else(|| print_help());
     let factor = a
@@ -58,45 +49,58 @@ fn args() -> (BintableName, f32) {
         panic!("factor must be smaller than 1 and greater than 0");
     }
 
-    (parse_bintable_name(input), factor)
+    let mut mode = SampleMode::Row;
+    let mut seed = 0;
+    while let Some(arg) = a.next() {
+        let value = a.next().unwrap_or_else(|| print_help());
+        match arg.as_str() {
+            "--mode" => mode = value.parse().expect("valid sample mode"),
+            "--seed" => seed = value.parse().expect("seed to be a number"),
+            _ => print_help(),
+        }
+    }
+
+    (
+        parse_bintable_name(input),
+        Sampling::new(mode, factor, seed),
+    )
 }
 
 fn main() {
-    let (input, factor) = args();
+    let (input, sampling) = args();
 
-    let output = format!("{}/{}-{}", input.path, input.name, factor);
+    let output = format!("{}/{}", input.path, sampling.subset_name(&input.name));
 
     let inputfile = format!("{}/{}", input.path, input.name);
 
-    let rows = get_rows(&inputfile, factor);
+    let (header, rows) = get_rows(&inputfile, sampling);
 
     // write back data
 
     // This is done so that the file will be created well after the sampler has picked an appropriate one
     let firstrow = rows.recv().expect("read first row");
     let out = File::create_new(output).expect("open output file");
-    let mut out = BufWriter::with_capacity(1024 * 1024 /* 1MB */, out);
-    let mut acc = ParseAcc::default();
-    firstrow
-        .write_bin(&mut out, &mut acc)
-        .expect("write to output");
+    let out = BufWriter::with_capacity(1024 * 1024 /* 1MB */, out);
+    let mut out = BinTableWriter::new(out);
+    out.write_header(&header).expect("write header");
+    out.write(&firstrow).expect("write to output");
 
     for row in rows {
-        row.write_bin(&mut out, &mut acc).expect("write to output");
+        out.write(&row).expect("write to output");
     }
-
-    out.flush().expect("flush output");
+    out.finish().expect("write to output");
 }
 
-fn get_rows(path: &str, factor: f32) -> Receiver<TableRow> {
-    let path = path.to_string();
+fn get_rows(path: &str, sampling: Sampling) -> (Header, Receiver<TableRow>) {
+    let table = BinTableSampler::open(path, sampling).expect("open bintable");
+    let header = table.header().clone();
+
     let (s, r) = sync_channel(1024);
     spawn(move || {
-        let table = BinTableSampler::open(&path, factor).expect("open bintable");
         for row in table {
             s.send(row).expect("send to channel");
         }
     });
 
-    r
+    (header, r)
 }
@@ -1,8 +1,6 @@
 mod db;
 
-use std::io::Write;
-
-use bintable2::TableRow;
+use bintable2::{BinTableWriter, TableRow};
 use sqlx::{postgres::PgPoolOptions, FromRow, Postgres};
 use sqlx::{postgres::PgRow, Row};
 use tokio_stream::StreamExt;
@@ -32,8 +30,7 @@ async fn main() -> Result<(), sqlx::Error> {
         .connect(&db::client_str())
         .await?;
 
-    let output = std::fs::File::create(&config.outfile).expect("to create outfile");
-    let mut output = std::io::BufWriter::new(output);
+    let mut output = BinTableWriter::create(&config.outfile).expect("to create outfile");
 
     let query = format!(
         "SELECT tokenized, tableid, colid, rowid
@@ -47,18 +44,15 @@ async fn main() -> Result<(), sqlx::Error> {
     let mut stream = query.fetch(&pool);
 
     let mut count = 0;
-    let mut acc = bintable2::ParseAcc::default();
     while let Some(row) = stream.try_next().await? {
-        row.write_bin(&mut output, &mut acc)
-            .expect("write to outfile");
+        output.write(&row).expect("write to outfile");
 
         if count & 0xffff == 0 {
             println!("{} rows", count + 1);
         }
         count += 1;
     }
-
-    output.flush().expect("flush file");
+    output.finish().expect("write to outfile");
 
     Ok(())
 }